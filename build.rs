@@ -23,17 +23,21 @@ fn main() {
         .unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
-    download_cyw43_firmware();
-
     // By default, Cargo will re-run a build script whenever
     // any file in the project changes. By specifying `memory.x`
     // here, we ensure the build script is only re-run when
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
 
-    println!("cargo:rustc-link-arg=--nmagic");
-    println!("cargo:rustc-link-arg=-Tlink.x");
-    println!("cargo:rustc-link-arg=-Tdefmt.x");
+    // Only for the robot: a host build (`sim`) links like any other program,
+    // and has no radio to load firmware onto.
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("none") {
+        download_cyw43_firmware();
+
+        println!("cargo:rustc-link-arg=--nmagic");
+        println!("cargo:rustc-link-arg=-Tlink.x");
+        println!("cargo:rustc-link-arg=-Tdefmt.x");
+    }
 }
 
 fn download_cyw43_firmware() {
@@ -0,0 +1,116 @@
+use {
+    crate::{battery, brownout, current, pressure, thermal},
+    embassy_rp::adc::{Adc, Async, Channel},
+    embassy_time::{Duration, Ticker},
+};
+
+// The chip has one ADC, and `Adc` can't be shared, so one task (`run`) owns it and reads every
+// analog input on that input's own schedule: each module's `SAMPLE_PERIOD`, rounded to a whole
+// number of `PERIOD`s. Readings go through each module's `Monitor`, which publishes them
+// (`battery::volts`, `brownout::minimum_volts`, ...) for the motion loop's guards to act on.
+// Inputs that aren't fitted are left out of `Inputs`, and never read.
+//
+// The pack's divider is read once per tick for both `brownout` and (less often) `battery`,
+// rather than once for each. A thermal reading that crosses the warning threshold is logged
+// to flash, which is fine here, on core 0 (see `multicore`), and only holds up the readings
+// after it.
+
+/// ADC full scale, in volts at the pin.
+pub const REFERENCE_VOLTS: f32 = 3.3;
+/// ADC full scale, in counts (12 bits).
+pub const FULL_SCALE: f32 = 4095.0;
+
+/// How often `run` comes round. Every input's period is a whole number of these.
+pub const PERIOD: Duration = Duration::from_millis(1);
+
+/// Volts at the pin for one raw reading.
+#[inline]
+pub fn volts(counts: u16) -> f32 {
+    (counts as f32) * const { REFERENCE_VOLTS / FULL_SCALE }
+}
+
+/// The pack's voltage divider, read quickly for `brownout`, and every so often for `battery`.
+pub struct Supply {
+    pub channel: Channel<'static>,
+    pub battery: battery::Monitor,
+    pub brownout: brownout::Monitor,
+}
+
+/// The servo rail's current-sense output.
+pub struct Rail {
+    pub channel: Channel<'static>,
+    pub monitor: current::Monitor,
+}
+
+/// The force-sensing resistors under the first `N` feet, in leg order.
+pub struct Pressure<const N: usize> {
+    pub channels: [Channel<'static>; N],
+    pub feet: pressure::Feet<N>,
+}
+
+/// The chip's own temperature sensor (`Channel::new_temp_sensor`).
+pub struct Temperature {
+    pub channel: Channel<'static>,
+    pub monitor: thermal::Monitor,
+}
+
+/// Every analog input `run` reads, each with whatever turns its readings into something useful.
+/// `None` for anything not fitted. `FEET` is how many feet have a pressure sensor.
+#[derive(Default)]
+pub struct Inputs<const FEET: usize> {
+    pub supply: Option<Supply>,
+    pub rail: Option<Rail>,
+    pub pressure: Option<Pressure<FEET>>,
+    pub temperature: Option<Temperature>,
+}
+
+/// Read every input in `inputs` on its own schedule, forever. Spawn once, with the only `Adc`.
+#[inline]
+pub async fn run<const FEET: usize>(mut adc: Adc<'static, Async>, mut inputs: Inputs<FEET>) -> ! {
+    let mut ticker = Ticker::every(PERIOD);
+    let mut ticks: u64 = 0;
+    loop {
+        if let Some(supply) = &mut inputs.supply
+            && due(ticks, brownout::SAMPLE_PERIOD)
+        {
+            match adc.read(&mut supply.channel).await {
+                Ok(counts) => {
+                    let _: Option<f32> = supply.brownout.update(counts);
+                    if due(ticks, battery::SAMPLE_PERIOD) {
+                        let _: f32 = supply.battery.update(counts);
+                    }
+                }
+                Err(e) => crate::warn!("Couldn't read the supply: {e:?}"),
+            }
+        }
+        if let Some(rail) = &mut inputs.rail
+            && due(ticks, current::SAMPLE_PERIOD)
+            && let Err(e) = rail.monitor.sample(&mut adc, &mut rail.channel).await
+        {
+            let () = crate::warn!("Couldn't read servo current: {e:?}");
+        }
+        if let Some(pressure) = &mut inputs.pressure
+            && due(ticks, pressure::SAMPLE_PERIOD)
+            && let Err(e) = pressure.feet.sample(&mut adc, &mut pressure.channels).await
+        {
+            let () = crate::warn!("Couldn't read foot pressure: {e:?}");
+        }
+        if let Some(temperature) = &mut inputs.temperature
+            && due(ticks, thermal::SAMPLE_PERIOD)
+            && let Err(e) = temperature
+                .monitor
+                .sample(&mut adc, &mut temperature.channel)
+                .await
+        {
+            let () = crate::warn!("Couldn't read the chip temperature: {e:?}");
+        }
+        ticks += 1;
+        let () = ticker.next().await;
+    }
+}
+
+/// Whether an input read every `period` is due on this tick.
+#[inline]
+fn due(ticks: u64, period: Duration) -> bool {
+    ticks.is_multiple_of((period.as_ticks() / PERIOD.as_ticks()).max(1))
+}
@@ -0,0 +1,124 @@
+use {
+    crate::{
+        body::{self, Body, LegError, Pose},
+        ik, pwm,
+    },
+    embassy_time::{Duration, Instant, Ticker},
+};
+
+/// A snapshot of the whole body at one moment in an animation.
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe<const N: usize> {
+    /// When this keyframe should be reached, measured from the start of the animation.
+    pub at: Duration,
+    pub pose: Pose,
+    /// Foot positions in the ground frame, or `None` to leave the feet planted
+    /// and only move the body.
+    pub feet: Option<[ik::CartesianDisplacementFromEyeCenterLookingForward; N]>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    Once,
+    Times(u32),
+    Forever,
+}
+
+/// A choreographed behavior (stretch, shake, dance, ...) described as data:
+/// keyframes in increasing order of `at`, linearly interpolated in between.
+#[derive(Clone, Copy, Debug)]
+pub struct Animation<'a, const N: usize> {
+    pub keyframes: &'a [Keyframe<N>],
+    pub repeat: Repeat,
+}
+
+impl<const N: usize> Animation<'_, N> {
+    /// Time from the start to the last keyframe.
+    #[inline]
+    pub fn duration(&self) -> Duration {
+        self.keyframes
+            .last()
+            .map_or(Duration::from_ticks(0), |last| last.at)
+    }
+
+    /// Interpolated pose and feet `t` into one pass through the keyframes.
+    /// Before the first keyframe or after the last, holds that keyframe.
+    /// `None` if there are no keyframes at all.
+    #[inline]
+    pub fn sample(
+        &self,
+        t: Duration,
+    ) -> Option<(
+        Pose,
+        Option<[ik::CartesianDisplacementFromEyeCenterLookingForward; N]>,
+    )> {
+        let first = self.keyframes.first()?;
+        if t <= first.at {
+            return Some((first.pose, first.feet));
+        }
+        for (a, b) in self.keyframes.iter().zip(&self.keyframes[1..]) {
+            if t < b.at {
+                let span = (b.at - a.at).as_micros() as f32;
+                let s = ((t - a.at).as_micros() as f32) / span;
+                let feet = match (a.feet, b.feet) {
+                    (Some(fa), Some(fb)) => Some(core::array::from_fn(|i| fa[i].lerp(fb[i], s))),
+                    // Only one end specifies the feet, so hold them until that keyframe:
+                    (fa, fb) => fa.or(fb),
+                };
+                return Some((a.pose.lerp(b.pose, s), feet));
+            }
+        }
+        let last = self.keyframes.last()?;
+        Some((last.pose, last.feet))
+    }
+}
+
+/// Play an animation on a body, updating once per PWM period.
+/// Respects `body::freeze`: the animation clock pauses while frozen.
+#[inline]
+pub async fn play<const N: usize>(
+    body: &mut Body<'_, N>,
+    animation: &Animation<'_, N>,
+) -> Result<(), LegError> {
+    let duration = animation.duration();
+    if duration.as_ticks() == 0 {
+        // Zero or one keyframe(s), so there's nothing to interpolate:
+        return match animation.sample(duration) {
+            Some((pose, feet)) => body.set_pose_and_foot_positions(pose, feet),
+            None => Ok(()),
+        };
+    }
+    let passes = match animation.repeat {
+        Repeat::Once => Some(1),
+        Repeat::Times(n) => Some(n),
+        Repeat::Forever => None,
+    };
+
+    let mut elapsed = Duration::from_ticks(0);
+    let mut last = Instant::now();
+    let mut ticker = Ticker::every(Duration::from_millis(pwm::PULSE_PERIOD_MS as _));
+    loop {
+        let now = Instant::now();
+        if !body::is_frozen() {
+            elapsed += now - last;
+        }
+        last = now;
+
+        let t = match passes {
+            Some(passes) if elapsed >= duration * passes => {
+                // Finished: land exactly on the last keyframe.
+                let Some((pose, feet)) = animation.sample(duration) else {
+                    return Ok(());
+                };
+                return body.set_pose_and_foot_positions(pose, feet);
+            }
+            _ => Duration::from_ticks(elapsed.as_ticks() % duration.as_ticks()),
+        };
+
+        let Some((pose, feet)) = animation.sample(t) else {
+            return Ok(());
+        };
+        let () = body.set_pose_and_foot_positions(pose, feet)?;
+        let () = ticker.next().await;
+    }
+}
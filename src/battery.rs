@@ -0,0 +1,192 @@
+use {
+    crate::{
+        analog,
+        body::Body,
+        gait::{Gait, Limiter},
+        power,
+    },
+    core::sync::atomic::{AtomicU32, Ordering},
+    embassy_time::Duration,
+};
+
+/// Time between ADC readings (see `analog::run`).
+pub const SAMPLE_PERIOD: Duration = Duration::from_millis(100);
+
+/// Fraction of the gait's commanded speed allowed once the pack reaches `Thresholds::slow`.
+pub const SLOW_SPEED_SCALE: f32 = 0.5;
+
+/// Latest filtered pack voltage, as `f32` bits; NaN until the first reading.
+static VOLTS: AtomicU32 = AtomicU32::new(f32::NAN.to_bits());
+
+/// Latest filtered pack voltage, if anything's measuring it.
+#[inline]
+pub fn volts() -> Option<f32> {
+    Some(f32::from_bits(VOLTS.load(Ordering::Relaxed))).filter(|volts| !volts.is_nan())
+}
+
+/// Pack voltages at which `Guard` steps in, from mildest to most drastic.
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    /// Log a warning.
+    pub warn: f32,
+    /// Cap the gait at `SLOW_SPEED_SCALE` of its commanded speed.
+    pub slow: f32,
+    /// Stop walking, sit down and relax, before the pack's damaged or the board browns out.
+    pub shutdown: f32,
+    /// How far above a threshold the voltage has to recover before that level clears,
+    /// so a sagging pack doesn't flap between levels under load.
+    pub hysteresis: f32,
+}
+
+impl Thresholds {
+    /// A 2S LiPo.
+    pub const DEFAULT: Self = Self {
+        warn: 7.2,
+        slow: 7.0,
+        shutdown: 6.6,
+        hysteresis: 0.2,
+    };
+}
+
+/// How worried to be about the pack, mildest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Ok,
+    Low,
+    Slow,
+    Shutdown,
+}
+
+/// Turns raw ADC counts into a filtered pack voltage, published through `volts`.
+#[derive(Clone, Copy, Debug)]
+pub struct Monitor {
+    /// Pack volts per volt at the pin, e.g. 3.0 for a 20k/10k divider.
+    divider: f32,
+    /// Weight of each new reading in the exponential moving average, on (0, 1].
+    smoothing: f32,
+    filtered: Option<f32>,
+}
+
+impl Monitor {
+    #[inline]
+    pub const fn new(divider: f32, smoothing: f32) -> Self {
+        Self {
+            divider,
+            smoothing,
+            filtered: None,
+        }
+    }
+
+    /// Fold in one raw reading, returning (and publishing) the new filtered voltage.
+    #[inline]
+    pub fn update(&mut self, counts: u16) -> f32 {
+        let volts = analog::volts(counts) * self.divider;
+        let filtered = match self.filtered {
+            None => volts,
+            Some(filtered) => filtered + self.smoothing * (volts - filtered),
+        };
+        self.filtered = Some(filtered);
+        let () = VOLTS.store(filtered.to_bits(), Ordering::Relaxed);
+        filtered
+    }
+}
+
+/// Acts on the pack voltage from the motion loop, stepping through `Level`s as it sags.
+/// Levels only ever clear by `Thresholds::hysteresis`; `Level::Shutdown` never does,
+/// since by then the robot's sitting down with its servos off.
+#[derive(Clone, Copy, Debug)]
+pub struct Guard {
+    thresholds: Thresholds,
+    level: Level,
+    /// Cut the servo rail once the body's finished sitting down.
+    cut_pending: bool,
+}
+
+impl Guard {
+    #[inline]
+    pub const fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            level: Level::Ok,
+            cut_pending: false,
+        }
+    }
+
+    #[inline]
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) {
+        self.thresholds = thresholds
+    }
+
+    #[inline]
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Call once per motion loop iteration, along with `Body::advance`, which carries out
+    /// the sit-down `Level::Shutdown` starts.
+    #[inline]
+    pub fn check<const N: usize>(&mut self, body: &mut Body<'_, N>, gait: &mut Gait) {
+        if self.cut_pending && !body.is_sitting_down() {
+            self.cut_pending = false;
+            let () = power::cut();
+        }
+        let Some(volts) = volts() else {
+            return;
+        };
+        let Thresholds {
+            warn,
+            slow,
+            shutdown,
+            hysteresis,
+        } = self.thresholds;
+        let level = if volts < shutdown {
+            Level::Shutdown
+        } else if volts < slow {
+            Level::Slow
+        } else if volts < warn {
+            Level::Low
+        } else {
+            Level::Ok
+        };
+        let cleared = |threshold: f32| volts >= threshold + hysteresis;
+        let level = match self.level {
+            Level::Shutdown => return,
+            current if level >= current => level,
+            Level::Slow if !cleared(slow) => Level::Slow,
+            Level::Low if !cleared(warn) => Level::Low,
+            _ => level,
+        };
+        if level == self.level {
+            return;
+        }
+        let previous = core::mem::replace(&mut self.level, level);
+
+        let () = gait.set_speed_scale(
+            Limiter::Battery,
+            if level >= Level::Slow {
+                SLOW_SPEED_SCALE
+            } else {
+                1.0
+            },
+        );
+        match level {
+            Level::Ok => crate::info!("Battery recovered: {volts} V"),
+            Level::Low if previous < Level::Low => crate::warn!("Battery low: {volts} V"),
+            Level::Low => crate::info!("Battery back above {slow} V: full speed"),
+            Level::Slow => crate::warn!("Battery at {volts} V: slowing down"),
+            Level::Shutdown => {
+                let () = crate::error!("Battery at {volts} V: sitting down");
+                let () = gait.set_velocity(0.0, 0.0, 0.0);
+                let () = body.start_sitting_down();
+                self.cut_pending = true;
+            }
+        }
+    }
+}
+
+impl Default for Guard {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Thresholds::DEFAULT)
+    }
+}
@@ -0,0 +1,164 @@
+#![no_std]
+#![no_main]
+#![feature(async_trait_bounds, impl_trait_in_assoc_type)]
+
+use {
+    core::hint::black_box,
+    cortex_m::peripheral::DWT,
+    defmt_rtt as _,
+    embassy_executor::Spawner,
+    embassy_time::{Duration, Ticker},
+    eye_bot_inverse_kinematics::{ik, info, leg::Leg, pwm, warn},
+};
+
+// Counts CPU cycles (with the Cortex-M33's DWT cycle counter) for `ik::hip_to_foot_2d` alone
+// and for a whole `Leg::ik_to` (mount transform, yaw, 2D IK, and all three PWM writes),
+// across a grid of targets covering the leg's workspace and some past it, then reports
+// min/mean/max to the log. Interrupts are off while each call is timed, so the executor
+// and time driver don't show up in the numbers, and the cost of reading the counter itself
+// is measured first and subtracted. Rerun before and after anything meant to be faster
+// (lookup-table trig, fixed point) and compare like for like: same clock, same build profile.
+//
+// Results repeat every few seconds so they're easy to catch after flashing.
+
+const GRID: usize = 16;
+const REACH: f32 = ik::REACH_FROM_HIP;
+
+/// Cycle counts from a series of calls.
+struct Stats {
+    min: u32,
+    max: u32,
+    total: u64,
+    count: u32,
+    /// How many of those calls returned an error (still timed, since that's a path too).
+    errors: u32,
+}
+
+impl Stats {
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            min: u32::MAX,
+            max: 0,
+            total: 0,
+            count: 0,
+            errors: 0,
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, cycles: u32, ok: bool) {
+        self.min = self.min.min(cycles);
+        self.max = self.max.max(cycles);
+        self.total += cycles as u64;
+        self.count += 1;
+        if !ok {
+            self.errors += 1;
+        }
+    }
+
+    #[inline]
+    fn report(&self, name: &str) {
+        let mean = if self.count == 0 {
+            0
+        } else {
+            self.total / self.count as u64
+        };
+        info!(
+            "{name}: min {} / mean {mean} / max {} cycles over {} calls ({} errors)",
+            self.min, self.max, self.count, self.errors
+        );
+    }
+}
+
+/// Cycles `f` takes, less `overhead`, with interrupts off.
+#[inline]
+fn time<T>(overhead: u32, f: impl FnOnce() -> T) -> (u32, T) {
+    cortex_m::interrupt::free(|_| {
+        let start = DWT::cycle_count();
+        let out = f();
+        let end = DWT::cycle_count();
+        (end.wrapping_sub(start).saturating_sub(overhead), out)
+    })
+}
+
+/// `i` of `GRID` evenly spaced points from `from` to `to`, inclusive.
+#[inline]
+fn grid(i: usize, from: f32, to: f32) -> f32 {
+    from + (to - from) * (i as f32) / ((GRID - 1) as f32)
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let Some(mut core) = cortex_m::Peripherals::take() else {
+        panic!("Core peripherals already taken")
+    };
+    let () = core.DCB.enable_trace();
+    let () = core.DWT.enable_cycle_counter();
+    if !DWT::has_cycle_counter() {
+        panic!("No cycle counter on this core");
+    }
+
+    let (pwm0, pwm1) = pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await;
+    let (pwm2, _pwm3) = pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await;
+    let mut leg = match Leg::with_home_yaw(0.0, pwm0, pwm1, pwm2) {
+        Ok(ok) => ok,
+        Err(e) => panic!("Couldn't initialize a leg: {e:?}"),
+    };
+
+    info!("Clock: {} Hz", embassy_rp::clocks::clk_sys_freq());
+
+    let mut ticker = Ticker::every(Duration::from_secs(5));
+    loop {
+        // Reading the counter twice in a row, with nothing in between:
+        let overhead = (0..GRID)
+            .map(|_| time(0, || ()).0)
+            .min()
+            .unwrap_or_default();
+
+        let mut stats = Stats::new();
+        for i in 0..GRID {
+            for j in 0..GRID {
+                // A square around the hip, from folded up to a bit past full reach:
+                let displacement = ik::HipToFootDisplacementIn2dPlane {
+                    x: grid(i, 0.0, 1.1 * REACH),
+                    y: grid(j, -1.1 * REACH, 0.5 * REACH),
+                };
+                let (cycles, result) =
+                    time(overhead, || ik::hip_to_foot_2d(black_box(displacement)));
+                let () = stats.add(cycles, result.is_ok());
+                let _ = black_box(result);
+            }
+        }
+        let () = stats.report("ik::hip_to_foot_2d");
+
+        let mut stats = Stats::new();
+        for i in 0..GRID {
+            for j in 0..GRID {
+                for k in 0..GRID {
+                    let target = ik::CartesianDisplacementFromEyeCenterLookingForward {
+                        x: grid(
+                            i,
+                            ik::LENGTH_CENTER_TO_YAW,
+                            ik::LENGTH_CENTER_TO_YAW + REACH,
+                        ),
+                        y: grid(j, -0.5 * REACH, 0.5 * REACH),
+                        z: grid(k, -REACH, 0.5 * REACH),
+                    };
+                    let (cycles, result) = time(overhead, || leg.ik_to(black_box(target)));
+                    let () = stats.add(cycles, result.is_ok());
+                }
+            }
+        }
+        let () = stats.report("Leg::ik_to");
+
+        // Don't leave the leg wherever the last target happened to be:
+        if let Err(e) = leg.relax() {
+            warn!("Couldn't relax the leg: {e:?}");
+        }
+
+        let () = ticker.next().await;
+    }
+}
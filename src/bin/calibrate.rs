@@ -0,0 +1,436 @@
+#![no_std]
+#![no_main]
+#![feature(async_trait_bounds, impl_trait_in_assoc_type)]
+
+use {
+    core::fmt::Write as _,
+    defmt_rtt as _,
+    embassy_executor::Spawner,
+    embassy_rp::{
+        bind_interrupts,
+        peripherals::{PIO0, USB},
+        pio::{self, Pio},
+        usb::{self, Driver},
+    },
+    embassy_usb::{
+        Builder, Config,
+        class::cdc_acm::{CdcAcmClass, State},
+        driver::EndpointError,
+    },
+    eye_bot_inverse_kinematics::{
+        info,
+        leg::{self, Joint, Leg},
+        pwm, status, storage, warn,
+        ws2812::Ws2812,
+    },
+    static_cell::StaticCell,
+};
+
+// Walks through calibrating one leg over USB serial, one joint at a time, then saves the result
+// to flash with everything else in `storage::Config` left as it was. Open the serial port in any
+// terminal and follow along; single keypresses, no Enter needed:
+//
+//     + / -   jog by `FINE_STEP`         ] / [   jog by `COARSE_STEP`
+//     c       mark center (the trim)     n / x   mark min / max
+//     Enter   accept this joint          s       skip it, keeping what's saved
+//     q       stop without saving
+//
+// Jogging ignores the saved trim and limits, so a joint can be driven anywhere the servo can
+// go: mind the leg's own hard stops. Center becomes the trim, and min and max are stored
+// relative to it, just as `param set` takes them.
+//
+// The leg is on the same pins as the other test binaries (yaw, hip, knee on 10, 11, 12), and
+// since it can be any of the body's legs, the first question is which one to save it as. The
+// status LED, on GPIO 22 as on the robot, breathes magenta throughout.
+
+bind_interrupts!(struct Irqs {
+    PIO0_IRQ_0 => pio::InterruptHandler<PIO0>;
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
+});
+
+/// Largest USB full-speed bulk packet.
+const MAX_PACKET_SIZE: u16 = 64;
+
+const FINE_STEP: f32 = 0.5 * core::f32::consts::PI / 180.0;
+const COARSE_STEP: f32 = 5.0 * core::f32::consts::PI / 180.0;
+
+/// Furthest any servo goes from center, in radians.
+const WIDEST: f32 = 1.0 / pwm::RADIANS_TO_SERVO;
+
+#[derive(Debug)]
+enum Stopped {
+    Disconnected,
+    Quit,
+}
+
+impl From<EndpointError> for Stopped {
+    #[inline]
+    fn from(_: EndpointError) -> Self {
+        Self::Disconnected
+    }
+}
+
+/// One serial port, read a keypress at a time.
+struct Terminal {
+    class: CdcAcmClass<'static, Driver<'static, USB>>,
+    packet: [u8; MAX_PACKET_SIZE as usize],
+    /// Unread bytes are `packet[read..length]`.
+    read: usize,
+    length: usize,
+}
+
+impl Terminal {
+    #[inline]
+    async fn key(&mut self) -> Result<u8, EndpointError> {
+        while self.read >= self.length {
+            self.length = self.class.read_packet(&mut self.packet).await?;
+            self.read = 0;
+        }
+        let key = self.packet[self.read];
+        self.read += 1;
+        Ok(key)
+    }
+
+    /// Split into packets, ending with a short one so the host doesn't wait for more.
+    #[inline]
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), EndpointError> {
+        for chunk in bytes.chunks(MAX_PACKET_SIZE as usize) {
+            let () = self.class.write_packet(chunk).await?;
+        }
+        if bytes.len() % (MAX_PACKET_SIZE as usize) == 0 {
+            let () = self.class.write_packet(&[]).await?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    async fn print(&mut self, args: core::fmt::Arguments<'_>) -> Result<(), EndpointError> {
+        let mut line = heapless::String::<128>::new();
+        let _: core::fmt::Result = line.write_fmt(args);
+        self.write(line.as_bytes()).await
+    }
+}
+
+/// What's been marked so far for one joint, in radians from the servo's own center.
+#[derive(Clone, Copy, Debug, Default)]
+struct Marks {
+    center: Option<f32>,
+    min: Option<f32>,
+    max: Option<f32>,
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    {
+        // Status LED task:
+        #[embassy_executor::task]
+        pub async fn task(leds: Ws2812<'static, PIO0, 0, 1>) {
+            status::run(leds).await
+        }
+        let () = status::set_calibrating(true);
+        let Pio {
+            mut common, sm0, ..
+        } = Pio::new(p.PIO0, Irqs);
+        let leds = Ws2812::new(&mut common, sm0, p.DMA_CH0, p.PIN_22);
+        if let Err(e) = spawner.spawn(task(leds)) {
+            warn!("Couldn't spawn the status LED task: {e:?}");
+        }
+    }
+
+    let () = storage::init(p.FLASH).await;
+    let mut config = match storage::load_or_init().await {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Couldn't load the saved config ({e:?}); starting from defaults");
+            storage::Config::default()
+        }
+    };
+
+    let (pwm0, pwm1) = pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await;
+    let (pwm2, _pwm3) = pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await;
+    let mut leg = match Leg::with_home_yaw(0.0, pwm0, pwm1, pwm2) {
+        Ok(ok) => ok,
+        Err(e) => panic!("Couldn't initialize a leg: {e:?}"),
+    };
+
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUFFER: StaticCell<[u8; 64]> = StaticCell::new();
+    static STATE: StaticCell<State> = StaticCell::new();
+
+    let mut usb_config = Config::new(0xc0de, 0xcafe);
+    usb_config.manufacturer = Some("wrsturgeon");
+    usb_config.product = Some("eye-bot calibration");
+    usb_config.max_power = 100;
+    usb_config.max_packet_size_0 = 64;
+
+    let mut builder = Builder::new(
+        Driver::new(p.USB, Irqs),
+        usb_config,
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [],
+        CONTROL_BUFFER.init([0; 64]),
+    );
+    let class = CdcAcmClass::new(&mut builder, STATE.init(State::new()), MAX_PACKET_SIZE);
+    let mut usb = builder.build();
+
+    let mut terminal = Terminal {
+        class,
+        packet: [0; MAX_PACKET_SIZE as usize],
+        read: 0,
+        length: 0,
+    };
+    let session = async {
+        loop {
+            let () = terminal.class.wait_connection().await;
+            info!("Connected");
+            match wizard(&mut terminal, &mut leg, &mut config).await {
+                Ok(()) | Err(Stopped::Quit) => {}
+                Err(Stopped::Disconnected) => info!("Disconnected"),
+            }
+            // Whatever happened, don't leave the leg holding a half-calibrated pose:
+            if let Err(e) = leg.relax() {
+                warn!("Couldn't relax the leg: {e:?}");
+            }
+        }
+    };
+    let (never, _) = embassy_futures::join::join(usb.run(), session).await;
+    never
+}
+
+/// From picking a leg to saving it.
+#[inline]
+async fn wizard(
+    terminal: &mut Terminal,
+    leg: &mut Leg<'_>,
+    config: &mut storage::Config,
+) -> Result<(), Stopped> {
+    let () = terminal
+        .write(b"\r\neye-bot leg calibration\r\nWhich leg is plugged in? ")
+        .await?;
+    let index = loop {
+        match terminal.key().await? {
+            b'q' => return Err(Stopped::Quit),
+            key @ b'0'..=b'9' if ((key - b'0') as usize) < storage::MAX_LEGS => {
+                break (key - b'0') as usize;
+            }
+            _ => {}
+        }
+    };
+    let () = terminal.print(format_args!("{index}\r\n")).await?;
+
+    let mut parameters = config.legs[index].parameters;
+    for joint in Joint::ALL {
+        let () = calibrate_joint(terminal, leg, joint, &mut parameters).await?;
+    }
+
+    let () = terminal.print(format_args!("\r\nLeg {index}:\r\n")).await?;
+    for (name, (&old, &new)) in leg::PARAMETER_NAMES
+        .iter()
+        .zip(config.legs[index].parameters.iter().zip(&parameters))
+    {
+        let () = terminal
+            .print(format_args!(
+                "  {name:9} {:7.2} deg (was {:7.2})\r\n",
+                new.to_degrees(),
+                old.to_degrees(),
+            ))
+            .await?;
+    }
+    let () = terminal.write(b"Save? (y/n) ").await?;
+    loop {
+        match terminal.key().await? {
+            b'y' | b'Y' => break,
+            b'n' | b'N' | b'q' => {
+                let () = terminal.write(b"n\r\nNothing saved.\r\n").await?;
+                return Err(Stopped::Quit);
+            }
+            _ => {}
+        }
+    }
+    let () = terminal.write(b"y\r\n").await?;
+
+    config.legs[index].parameters = parameters;
+    match storage::save(config).await {
+        Ok(()) => terminal.write(b"Saved.\r\n").await?,
+        Err(e) => {
+            let () = terminal
+                .print(format_args!("error: couldn't save: {e:?}\r\n"))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Jog one joint until its center, min and max are all marked (or it's skipped),
+/// then write them into `parameters` as a trim and limits around it.
+#[inline]
+async fn calibrate_joint(
+    terminal: &mut Terminal,
+    leg: &mut Leg<'_>,
+    joint: Joint,
+    parameters: &mut [f32; leg::PARAMETER_NAMES.len()],
+) -> Result<(), Stopped> {
+    let name = match joint {
+        Joint::Yaw => "yaw",
+        Joint::Hip => "hip",
+        Joint::Knee => "knee",
+    };
+    // Indices into `leg::PARAMETER_NAMES`:
+    let j = joint as usize;
+    let (trim, min, max) = (j, 3 + 2 * j, 4 + 2 * j);
+
+    // Free the servo to go anywhere, starting from wherever its saved trim says center is:
+    let () = set(leg, name, "trim", 0.0);
+    let () = set(leg, name, "min", -WIDEST);
+    let () = set(leg, name, "max", WIDEST);
+    let mut position = parameters[trim];
+    let mut marks = Marks::default();
+
+    let () = terminal
+        .print(format_args!(
+            "\r\n{name}: +/- jog, ]/[ jog more, c center, n min, x max, Enter accept, s skip, q quit\r\n"
+        ))
+        .await?;
+    loop {
+        if let Err(e) = leg.set_joint(joint, position) {
+            let () = terminal.print(format_args!("\r\nerror: {e:?}\r\n")).await?;
+        }
+        let () = terminal
+            .print(format_args!(
+                "\r{name} at {:7.2} deg  center {}  min {}  max {}  ",
+                position.to_degrees(),
+                Degrees(marks.center),
+                Degrees(marks.min),
+                Degrees(marks.max),
+            ))
+            .await?;
+
+        let step = match terminal.key().await? {
+            b'+' | b'=' => FINE_STEP,
+            b'-' | b'_' => -FINE_STEP,
+            b']' => COARSE_STEP,
+            b'[' => -COARSE_STEP,
+            b'c' => {
+                marks.center = Some(position);
+                0.0
+            }
+            b'n' => {
+                marks.min = Some(position);
+                0.0
+            }
+            b'x' => {
+                marks.max = Some(position);
+                0.0
+            }
+            b's' => {
+                if let Err(e) = apply(
+                    leg,
+                    name,
+                    parameters[trim],
+                    parameters[min],
+                    parameters[max],
+                ) {
+                    warn!("Couldn't restore {name}'s saved settings: {e:?}");
+                }
+                let () = terminal.write(b"\r\nskipped\r\n").await?;
+                return Ok(());
+            }
+            b'q' => {
+                let () = terminal.write(b"\r\nNothing saved.\r\n").await?;
+                return Err(Stopped::Quit);
+            }
+            b'\r' | b'\n' => {
+                let Marks {
+                    center: Some(center),
+                    min: Some(lowest),
+                    max: Some(highest),
+                } = marks
+                else {
+                    let () = terminal
+                        .write(b"\r\nmark center, min and max first\r\n")
+                        .await?;
+                    continue;
+                };
+                if !(lowest < center && center < highest) {
+                    let () = terminal
+                        .write(b"\r\nmin must be below center, and max above it\r\n")
+                        .await?;
+                    continue;
+                }
+                let accepted = [
+                    (trim, center),
+                    (min, lowest - center),
+                    (max, highest - center),
+                ];
+                // Check them against what the servo can take by setting them for real:
+                if let Err(e) = apply(leg, name, center, lowest - center, highest - center) {
+                    let () = terminal.print(format_args!("\r\nerror: {e:?}\r\n")).await?;
+                    let () = set(leg, name, "trim", 0.0);
+                    let () = set(leg, name, "min", -WIDEST);
+                    let () = set(leg, name, "max", WIDEST);
+                    continue;
+                }
+                for (i, value) in accepted {
+                    parameters[i] = value;
+                }
+                let () = terminal.write(b"\r\naccepted\r\n").await?;
+                // Back to (the new) center before moving on:
+                if let Err(e) = leg.set_joint(joint, 0.0) {
+                    let () = terminal.print(format_args!("error: {e:?}\r\n")).await?;
+                }
+                return Ok(());
+            }
+            _ => 0.0,
+        };
+        position = (position + step).clamp(-WIDEST, WIDEST);
+    }
+}
+
+/// Trim, then limits, so each limit is checked against the new center.
+#[inline]
+fn apply(
+    leg: &mut Leg<'_>,
+    name: &str,
+    trim: f32,
+    min: f32,
+    max: f32,
+) -> Result<(), leg::ParameterError> {
+    let () = leg.set_parameter(field(name, "trim").as_str(), trim)?;
+    // Widest first, so the new min always fits under the old max:
+    let () = leg.set_parameter(field(name, "max").as_str(), WIDEST)?;
+    let () = leg.set_parameter(field(name, "min").as_str(), min)?;
+    leg.set_parameter(field(name, "max").as_str(), max)
+}
+
+/// Set one parameter, only logging if it doesn't take (the wizard's own values always should).
+#[inline]
+fn set(leg: &mut Leg<'_>, joint: &str, suffix: &str, radians: f32) {
+    if let Err(e) = leg.set_parameter(field(joint, suffix).as_str(), radians) {
+        warn!("Couldn't set {joint}_{suffix}: {e:?}");
+    }
+}
+
+/// E.g. `hip_trim`, as `leg::PARAMETER_NAMES` spells it.
+#[inline]
+fn field(joint: &str, suffix: &str) -> heapless::String<16> {
+    let mut name = heapless::String::new();
+    let _: core::fmt::Result = write!(name, "{joint}_{suffix}");
+    name
+}
+
+/// An optional angle in radians, shown in degrees or as `-`.
+struct Degrees(Option<f32>);
+
+impl core::fmt::Display for Degrees {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            Some(radians) => write!(f, "{:.2}", radians.to_degrees()),
+            None => f.write_str("-"),
+        }
+    }
+}
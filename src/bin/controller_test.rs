@@ -3,25 +3,102 @@
 #![feature(async_trait_bounds, impl_trait_in_assoc_type)]
 
 use {
+    core::{f32::consts::PI, fmt::Write as _},
     defmt_rtt as _,
     embassy_executor::Spawner,
     embassy_rp::{
         bind_interrupts,
-        peripherals::{UART1, USB},
-        pwm::SetDutyCycle as _,
-        uart, usb,
+        peripherals::USB,
+        usb::{self, Driver},
     },
-    embassy_time::{Duration, Ticker, Timer},
-    eye_bot_inverse_kinematics::pwm,
-    panic_probe as _,
+    embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal},
+    embassy_time::{Duration, Instant, Ticker, Timer},
+    embassy_usb::{
+        Builder, Config,
+        class::cdc_acm::{CdcAcmClass, State},
+        driver::EndpointError,
+    },
+    eye_bot_inverse_kinematics::{info, pwm, servo::Servo, warn},
+    static_cell::StaticCell,
 };
 
+// Sweeps one servo at a time through a waveform, for exercising servos during bring-up.
+// Everything is set at runtime over USB serial, one command per line:
+//
+//     channel <0-5>               which output (pins 10 through 15); the last one goes limp
+//     wave <sine|triangle|step|off>
+//     amplitude <0-1>             either side of center, in servo units (1 is a full half-range)
+//     frequency <hertz>
+//     center <-1-1>               what the waveform swings around, in servo units
+//     status                      print all of the above
+//
+// Positions outside what a servo can take are refused (and reported once) rather than clipped,
+// so an amplitude that's too big shows up as the servo stalling at the edges.
+
 bind_interrupts!(struct Irqs {
-    UART1_IRQ => uart::InterruptHandler<UART1>;
     USBCTRL_IRQ => usb::InterruptHandler<USB>;
 });
 
-const MAIN_LOOP_PERIOD_MS: u16 = 1; // pwm::PULSE_PERIOD_MS;
+/// Largest USB full-speed bulk packet.
+const MAX_PACKET_SIZE: u16 = 64;
+const MAX_LINE_LENGTH: usize = 64;
+const CHANNELS: usize = 6;
+
+/// Highest frequency accepted, well past what any hobby servo can follow.
+const MAX_FREQUENCY_HZ: f32 = 10.0;
+
+/// Latest settings from the console, picked up by the sweep loop once per period.
+static SETTINGS: Signal<CriticalSectionRawMutex, Settings> = Signal::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Waveform {
+    Off,
+    Sine,
+    Triangle,
+    Step,
+}
+
+impl Waveform {
+    /// On [-1, 1], starting at 0 and rising, for `phase` on [0, 1).
+    #[inline]
+    fn at(self, phase: f32) -> f32 {
+        match self {
+            Self::Off => 0.0,
+            Self::Sine => libm::sinf(2.0 * PI * phase),
+            Self::Triangle => {
+                // Shifted a quarter period so it starts at 0 like the others:
+                let phase = (phase + 0.75) % 1.0;
+                4.0 * (phase - 0.5).abs() - 1.0
+            }
+            Self::Step => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Settings {
+    channel: usize,
+    waveform: Waveform,
+    amplitude: f32,
+    frequency: f32,
+    center: f32,
+}
+
+impl Settings {
+    const DEFAULT: Self = Self {
+        channel: 0,
+        waveform: Waveform::Off,
+        amplitude: 0.25,
+        frequency: 0.5,
+        center: 0.0,
+    };
+}
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
@@ -30,36 +107,236 @@ async fn main(spawner: Spawner) {
     {
         // USB background task:
         #[embassy_executor::task]
-        pub async fn task(driver: usb::Driver<'static, USB>) {
-            embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
+        pub async fn task(driver: Driver<'static, USB>) {
+            console(driver).await
         }
-        let () = match spawner.spawn(task(usb::Driver::new(p.USB, Irqs))) {
-            Ok(()) => defmt::info!("Spawned USB task"),
+        let () = match spawner.spawn(task(Driver::new(p.USB, Irqs))) {
+            Ok(()) => info!("Spawned USB task"),
             Err(e) => {
-                log::error!("Error spawning USB task");
                 Timer::after(Duration::from_secs(1)).await;
-                defmt::panic!("Error spawning USB task: {}", e);
+                panic!("Error spawning USB task: {e:?}");
             }
         };
     }
 
-    let (_pwm0, _pwm1) = pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await;
-    let (_pwm2, mut pwm3) = pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await;
-
-    let pulse_center = pwm::pulse_center().await;
-    let pulse_range_plus_minus = pwm::pulse_range_plus_minus().await;
+    let (pwm0, pwm1) = pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await;
+    let (pwm2, pwm3) = pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await;
+    let (pwm4, pwm5) = pwm::init_slice(p.PWM_SLICE7, p.PIN_14, p.PIN_15).await;
+    let mut servos = heapless::Vec::<Servo<'static>, CHANNELS>::new();
+    for output in [pwm0, pwm1, pwm2, pwm3, pwm4, pwm5] {
+        match Servo::with_center_and_ranges(output, 0.0, -1.0, 1.0) {
+            Ok(servo) => {
+                let Ok(()) = servos.push(servo) else {
+                    unreachable!()
+                };
+            }
+            Err(e) => panic!("Couldn't set up a servo: {e:?}"),
+        }
+    }
 
-    let mut counter: u8 = 0;
-    let mut ticker = Ticker::every(Duration::from_millis(MAIN_LOOP_PERIOD_MS as _));
+    let mut settings = Settings::DEFAULT;
+    // Only report a refused position once, not every period until it's fixed:
+    let mut refused = false;
+    let start = Instant::now();
+    let mut ticker = Ticker::every(Duration::from_millis(pwm::PULSE_PERIOD_MS as _));
     loop {
-        let theta = if counter > 192 { 0.1 } else { 0.0 };
+        if let Some(new) = SETTINGS.try_take() {
+            if new.channel != settings.channel
+                && let Err(e) = servos[settings.channel].relax()
+            {
+                warn!("Couldn't relax channel {}: {e:?}", settings.channel);
+            }
+            settings = new;
+            refused = false;
+        }
 
-        match pwm3.set_duty_cycle((pulse_center + pulse_range_plus_minus * theta) as _) {
-            Ok(()) => {}
-            Err(e) => log::error!("Couldn't set duty cycle: {e:?}"),
+        let seconds = start.elapsed().as_micros() as f32 * 1e-6;
+        let phase = (seconds * settings.frequency) % 1.0;
+        let position = settings.center + settings.amplitude * settings.waveform.at(phase);
+        match servos[settings.channel].go_to(position) {
+            Ok(()) => refused = false,
+            Err(e) => {
+                if !refused {
+                    warn!("Couldn't go to {position}: {e:?}");
+                }
+                refused = true;
+            }
         }
 
         let () = ticker.next().await;
-        counter = counter.wrapping_add(1);
     }
 }
+
+/// One serial port taking the commands above, reconnecting forever.
+#[inline]
+async fn console(driver: Driver<'static, USB>) -> ! {
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUFFER: StaticCell<[u8; 64]> = StaticCell::new();
+    static STATE: StaticCell<State> = StaticCell::new();
+
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("wrsturgeon");
+    config.product = Some("eye-bot servo sweep");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [],
+        CONTROL_BUFFER.init([0; 64]),
+    );
+    let mut class = CdcAcmClass::new(&mut builder, STATE.init(State::new()), MAX_PACKET_SIZE);
+    let mut usb = builder.build();
+
+    let serve = async {
+        // Kept across reconnections, so `status` always matches what's running:
+        let mut settings = Settings::DEFAULT;
+        loop {
+            let () = class.wait_connection().await;
+            if let Err(EndpointError::Disabled) = session(&mut class, &mut settings).await {
+                info!("Console disconnected");
+            }
+        }
+    };
+    let (never, _) = embassy_futures::join::join(usb.run(), serve).await;
+    never
+}
+
+#[inline]
+async fn session(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    settings: &mut Settings,
+) -> Result<(), EndpointError> {
+    let mut line = heapless::Vec::<u8, MAX_LINE_LENGTH>::new();
+    let mut overflowed = false;
+    let mut packet = [0; MAX_PACKET_SIZE as usize];
+    let () = write(class, b"> ").await?;
+    loop {
+        let n = class.read_packet(&mut packet).await?;
+        for &byte in &packet[..n] {
+            match byte {
+                b'\r' | b'\n' => {
+                    let () = write(class, b"\r\n").await?;
+                    let mut reply = heapless::String::<128>::new();
+                    let _: core::fmt::Result = if overflowed {
+                        write!(reply, "error: line too long\r\n")
+                    } else if line.is_empty() {
+                        Ok(())
+                    } else {
+                        match apply(&line, settings) {
+                            Ok(()) => {
+                                let () = SETTINGS.signal(*settings);
+                                write!(reply, "{settings:?}\r\n")
+                            }
+                            Err(e) => write!(reply, "error: {e}\r\n"),
+                        }
+                    };
+                    let () = write(class, reply.as_bytes()).await?;
+                    let () = line.clear();
+                    overflowed = false;
+                    let () = write(class, b"> ").await?;
+                }
+                // Backspace or delete:
+                0x08 | 0x7f => {
+                    if line.pop().is_some() {
+                        let () = write(class, b"\x08 \x08").await?;
+                    }
+                }
+                b => {
+                    if line.push(b).is_err() {
+                        overflowed = true;
+                    }
+                    // Echo, since most terminals don't:
+                    let () = write(class, &[b]).await?;
+                }
+            }
+        }
+    }
+}
+
+/// Parse one command line into `settings`, leaving them alone if it doesn't parse.
+#[inline]
+fn apply(line: &[u8], settings: &mut Settings) -> Result<(), &'static str> {
+    let line = core::str::from_utf8(line).map_err(|_| "not UTF-8")?;
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let argument = words.next();
+    if words.next().is_some() {
+        return Err("too many arguments");
+    }
+    let number = || -> Result<f32, &'static str> {
+        let value: f32 = argument
+            .ok_or("missing a number")?
+            .parse()
+            .map_err(|_| "not a number")?;
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err("not a number")
+        }
+    };
+    match command {
+        "status" => {}
+        "channel" => {
+            let channel = argument
+                .ok_or("missing a channel")?
+                .parse()
+                .map_err(|_| "not a channel")?;
+            if channel >= CHANNELS {
+                return Err("no such channel");
+            }
+            settings.channel = channel;
+        }
+        "wave" => {
+            settings.waveform = match argument {
+                Some("sine") => Waveform::Sine,
+                Some("triangle") => Waveform::Triangle,
+                Some("step") => Waveform::Step,
+                Some("off") => Waveform::Off,
+                _ => return Err("expected sine, triangle, step or off"),
+            }
+        }
+        "amplitude" => {
+            let amplitude = number()?;
+            if !(0.0..=1.0).contains(&amplitude) {
+                return Err("amplitude must be between 0 and 1");
+            }
+            settings.amplitude = amplitude;
+        }
+        "frequency" => {
+            let frequency = number()?;
+            if !(0.0..=MAX_FREQUENCY_HZ).contains(&frequency) {
+                return Err("frequency must be between 0 and 10 Hz");
+            }
+            settings.frequency = frequency;
+        }
+        "center" => {
+            let center = number()?;
+            if !(-1.0..=1.0).contains(&center) {
+                return Err("center must be between -1 and 1");
+            }
+            settings.center = center;
+        }
+        _ => return Err("unknown command"),
+    }
+    Ok(())
+}
+
+/// Split into packets, ending with a short one so the host doesn't wait for more.
+#[inline]
+async fn write(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    bytes: &[u8],
+) -> Result<(), EndpointError> {
+    for chunk in bytes.chunks(MAX_PACKET_SIZE as usize) {
+        let () = class.write_packet(chunk).await?;
+    }
+    if bytes.len() % (MAX_PACKET_SIZE as usize) == 0 {
+        let () = class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
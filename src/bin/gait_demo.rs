@@ -0,0 +1,236 @@
+#![no_std]
+#![no_main]
+#![feature(async_trait_bounds, impl_trait_in_assoc_type)]
+
+use {
+    defmt_rtt as _,
+    embassy_executor::Spawner,
+    embassy_rp::{
+        gpio::{Input, Pull},
+        watchdog::Watchdog,
+    },
+    embassy_time::{Duration, Instant},
+    eye_bot_inverse_kinematics::{
+        body::{self, Body},
+        gait::{self, Gait, Pattern},
+        ik, info,
+        leg::Leg,
+        pwm, scheduler, storage, warn, watchdog,
+    },
+};
+
+// Stands up, then walks through every gait pattern and a few parameter sets in turn, each for
+// `STAGE_DURATION` or until the button on GPIO 18 (to ground) is pressed, forever. Patterns
+// cross-fade into each other as they would in normal use, so this doubles as a soak test of the
+// body and gait together: after every stage, it logs how many ticks had a leg fail its IK
+// and how many the gait held back a swing to stay stable. Both should be zero on flat ground.
+//
+// Wired like `teleop`: legs in order, yaw-hip-knee each, from GPIO 0 through 17.
+
+const STAGE_DURATION: Duration = Duration::from_secs(8);
+/// How fast the body rises or sinks to each stage's height, in length units per second.
+const HEIGHT_SPEED: f32 = 0.5;
+
+struct Stage {
+    name: &'static str,
+    pattern: Pattern,
+    /// Forward, leftward, and counterclockwise, as in `Gait::set_velocity`.
+    velocity: (f32, f32, f32),
+    parameters: gait::Parameters,
+    /// Body height above the stance's own.
+    height: f32,
+}
+
+const STAGES: [Stage; 8] = [
+    Stage {
+        name: "tripod, forward",
+        pattern: Pattern::Tripod,
+        velocity: (3.0, 0.0, 0.0),
+        parameters: gait::Parameters::DEFAULT,
+        height: 0.0,
+    },
+    Stage {
+        name: "tripod, crabbing left",
+        pattern: Pattern::Tripod,
+        velocity: (0.0, 2.0, 0.0),
+        parameters: gait::Parameters::DEFAULT,
+        height: 0.0,
+    },
+    Stage {
+        name: "ripple, turning in place",
+        pattern: Pattern::Ripple,
+        velocity: (0.0, 0.0, 0.3),
+        parameters: gait::Parameters::DEFAULT,
+        height: 0.0,
+    },
+    Stage {
+        name: "ripple, arcing right",
+        pattern: Pattern::Ripple,
+        velocity: (2.0, 0.0, -0.2),
+        parameters: gait::Parameters::DEFAULT,
+        height: 0.0,
+    },
+    Stage {
+        name: "wave, forward",
+        pattern: Pattern::Wave,
+        velocity: (1.5, 0.0, 0.0),
+        parameters: gait::Parameters::DEFAULT,
+        height: 0.0,
+    },
+    Stage {
+        name: "tripod, high slow steps",
+        pattern: Pattern::Tripod,
+        velocity: (2.0, 0.0, 0.0),
+        parameters: gait::Parameters {
+            step_height: 1.5,
+            cycle_period: Duration::from_millis(1_500),
+            ..gait::Parameters::DEFAULT
+        },
+        height: 0.5,
+    },
+    Stage {
+        name: "tripod, short quick steps",
+        pattern: Pattern::Tripod,
+        velocity: (2.0, 0.0, 0.0),
+        parameters: gait::Parameters {
+            max_stride_length: 1.0,
+            cycle_period: Duration::from_millis(600),
+            ..gait::Parameters::DEFAULT
+        },
+        height: 0.0,
+    },
+    Stage {
+        name: "standing, body low",
+        pattern: Pattern::Tripod,
+        velocity: (0.0, 0.0, 0.0),
+        parameters: gait::Parameters::DEFAULT,
+        height: -1.0,
+    },
+];
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let () = storage::init(p.FLASH).await;
+    let config = match storage::load().await {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("No saved config ({e:?}); using defaults");
+            storage::Config::default()
+        }
+    };
+
+    let button = Input::new(p.PIN_18, Pull::Up);
+
+    let slices = [
+        pwm::init_slice(p.PWM_SLICE0, p.PIN_0, p.PIN_1).await,
+        pwm::init_slice(p.PWM_SLICE1, p.PIN_2, p.PIN_3).await,
+        pwm::init_slice(p.PWM_SLICE2, p.PIN_4, p.PIN_5).await,
+        pwm::init_slice(p.PWM_SLICE3, p.PIN_6, p.PIN_7).await,
+        pwm::init_slice(p.PWM_SLICE4, p.PIN_8, p.PIN_9).await,
+        pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await,
+        pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await,
+        pwm::init_slice(p.PWM_SLICE7, p.PIN_14, p.PIN_15).await,
+        pwm::init_slice(p.PWM_SLICE8, p.PIN_16, p.PIN_17).await,
+    ];
+    let mut outputs = slices.into_iter().flat_map(|(a, b)| [a, b]);
+    let mut legs = heapless::Vec::<Leg<'static>, { gait::N_LEGS }>::new();
+    for (i, leg) in config.legs.iter().enumerate() {
+        let (Some(yaw), Some(hip), Some(knee)) = (outputs.next(), outputs.next(), outputs.next())
+        else {
+            unreachable!()
+        };
+        match Leg::with_mount(leg.mount, yaw, hip, knee) {
+            Ok(leg) => {
+                let Ok(()) = legs.push(leg) else {
+                    unreachable!()
+                };
+            }
+            Err(e) => panic!("Couldn't initialize leg {i}: {e:?}"),
+        }
+    }
+    let Ok(legs) = legs.into_array() else {
+        unreachable!()
+    };
+    let mut body = Body::new(legs);
+    let mut gait = Gait::new(
+        STAGES[0].pattern,
+        body.standing_foot_positions(),
+        gait::Parameters::DEFAULT,
+    );
+    // Trims and limits; the stages set their own gait parameters over whatever's saved:
+    if let Err(e) = config.apply(&mut body, &mut gait) {
+        warn!("Couldn't apply the saved config: {e:?}");
+    }
+
+    if let Err(e) = body.stand_up().await {
+        warn!("Couldn't stand all the way up: {e:?}");
+    }
+    let () = gait.set_neutral(body.standing_foot_positions());
+
+    // From here on, anything that stops the loop below resets the chip instead of leaving
+    // the robot walking blind:
+    let mut watchdog =
+        watchdog::Feeder::start(Watchdog::new(p.WATCHDOG), watchdog::DEFAULT_TIMEOUT).await;
+
+    let mut stage = 0;
+    let () = begin(&STAGES[stage], &mut gait);
+    let mut stage_ends = Instant::now() + STAGE_DURATION;
+    let mut height = 0.0;
+    let mut was_pressed = false;
+    let (mut ik_errors, mut stalls) = (0_u32, 0_u32);
+    let mut elapsed = scheduler::PERIOD;
+    let mut clock = scheduler::Clock::every(scheduler::PERIOD);
+    loop {
+        // Polled once a period, which also debounces it:
+        let pressed = button.is_low();
+        let skipped = pressed && !was_pressed;
+        was_pressed = pressed;
+        if skipped || Instant::now() >= stage_ends {
+            info!(
+                "Done with {}{}: {ik_errors} ticks with IK errors, {stalls} stalled",
+                STAGES[stage].name,
+                if skipped { " (skipped)" } else { "" }
+            );
+            (ik_errors, stalls) = (0, 0);
+            stage = (stage + 1) % STAGES.len();
+            let () = begin(&STAGES[stage], &mut gait);
+            stage_ends = Instant::now() + STAGE_DURATION;
+        }
+
+        let max_change = HEIGHT_SPEED * (elapsed.as_micros() as f32) * 1e-6;
+        height += (STAGES[stage].height - height).clamp(-max_change, max_change);
+        let pose = body::Pose {
+            translation: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                z: height,
+                ..body::Pose::NEUTRAL.translation
+            },
+            ..body::Pose::NEUTRAL
+        };
+        if let Err(e) = body.set_pose_and_foot_positions(pose, Some(gait.advance(elapsed))) {
+            if ik_errors == 0 {
+                warn!("{e:?}");
+            }
+            ik_errors += 1;
+        }
+        if gait.is_stalled() {
+            stalls += 1;
+        }
+
+        let () = watchdog.feed();
+        elapsed = clock.next().await.elapsed;
+    }
+}
+
+/// Switch the gait over to `stage`, cross-fading if the pattern changes.
+#[inline]
+fn begin(stage: &Stage, gait: &mut Gait) {
+    info!("Now: {}", stage.name);
+    if gait.pattern() != stage.pattern {
+        let () = gait.set_pattern(stage.pattern);
+    }
+    *gait.parameters_mut() = stage.parameters;
+    let (vx, vy, yaw_rate) = stage.velocity;
+    gait.set_velocity(vx, vy, yaw_rate)
+}
@@ -0,0 +1,268 @@
+#![no_std]
+#![no_main]
+#![feature(async_trait_bounds, impl_trait_in_assoc_type)]
+
+use {
+    core::{f32::consts::PI, fmt::Write as _},
+    defmt_rtt as _,
+    embassy_executor::Spawner,
+    embassy_rp::{
+        bind_interrupts,
+        peripherals::USB,
+        usb::{self, Driver},
+    },
+    embassy_usb::{
+        Builder, Config,
+        class::cdc_acm::{CdcAcmClass, State},
+        driver::EndpointError,
+    },
+    eye_bot_inverse_kinematics::{
+        ik::{self, HipToFootDisplacementIn2dPlane},
+        info,
+        leg::Leg,
+        pwm, warn,
+    },
+    static_cell::StaticCell,
+};
+
+// Characterizes the f32 solver's numeric accuracy on the chip itself: sweeps the reachable
+// workspace, runs each point through IK and back through forward kinematics, and streams the
+// worst and RMS distance between where it asked for and where it'd end up, over USB serial.
+//
+// Two sweeps, each at a few resolutions, finest last:
+//
+//     2d    `ik::hip_to_foot_2d` alone, over the annulus between the leg's least and most reach
+//     leg   a whole `Leg::ik_to` (yaw included) over a box in front of its mount
+//
+// Leg points its servo limits refuse are counted as skipped rather than errors. The leg is on
+// the same pins as the other test binaries (10, 11, 12), but better left unplugged: its servos
+// get sent every point in the box, as fast as the chip can solve them.
+// Press any key to run it all again.
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
+});
+
+/// Largest USB full-speed bulk packet.
+const MAX_PACKET_SIZE: u16 = 64;
+
+/// Points along each axis of the grid, one sweep per entry.
+const RESOLUTIONS: [usize; 4] = [8, 16, 32, 64];
+
+const REACH: f32 = ik::REACH_FROM_HIP;
+const MIN_REACH: f32 = ik::MIN_REACH_FROM_HIP;
+
+/// Round-trip errors over one sweep.
+struct Errors {
+    count: u32,
+    skipped: u32,
+    /// In double precision, so thousands of tiny errors don't round away.
+    sum_squared: f64,
+    worst: f32,
+    worst_at: [f32; 3],
+}
+
+impl Errors {
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            count: 0,
+            skipped: 0,
+            sum_squared: 0.0,
+            worst: 0.0,
+            worst_at: [0.0; 3],
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, at: [f32; 3], error: f32) {
+        self.count += 1;
+        self.sum_squared += (error as f64) * (error as f64);
+        // NaN should never happen, but would be worth seeing:
+        if error > self.worst || error.is_nan() {
+            self.worst = error;
+            self.worst_at = at;
+        }
+    }
+
+    #[inline]
+    fn rms(&self) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            libm::sqrt(self.sum_squared / self.count as f64) as f32
+        }
+    }
+}
+
+/// `i` of `n` evenly spaced points from `from` to `to`, inclusive.
+#[inline]
+fn grid(i: usize, n: usize, from: f32, to: f32) -> f32 {
+    from + (to - from) * (i as f32) / ((n - 1) as f32)
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let (pwm0, pwm1) = pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await;
+    let (pwm2, _pwm3) = pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await;
+    let mut leg = match Leg::with_home_yaw(0.0, pwm0, pwm1, pwm2) {
+        Ok(ok) => ok,
+        Err(e) => panic!("Couldn't initialize a leg: {e:?}"),
+    };
+
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUFFER: StaticCell<[u8; 64]> = StaticCell::new();
+    static STATE: StaticCell<State> = StaticCell::new();
+
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("wrsturgeon");
+    config.product = Some("eye-bot IK accuracy");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    let mut builder = Builder::new(
+        Driver::new(p.USB, Irqs),
+        config,
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [],
+        CONTROL_BUFFER.init([0; 64]),
+    );
+    let mut class = CdcAcmClass::new(&mut builder, STATE.init(State::new()), MAX_PACKET_SIZE);
+    let mut usb = builder.build();
+
+    let serve = async {
+        loop {
+            let () = class.wait_connection().await;
+            if let Err(EndpointError::Disabled) = session(&mut class, &mut leg).await {
+                info!("Disconnected");
+            }
+            if let Err(e) = leg.relax() {
+                warn!("Couldn't relax the leg: {e:?}");
+            }
+        }
+    };
+    let (never, _) = embassy_futures::join::join(usb.run(), serve).await;
+    never
+}
+
+#[inline]
+async fn session(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    leg: &mut Leg<'_>,
+) -> Result<(), EndpointError> {
+    let mut packet = [0; MAX_PACKET_SIZE as usize];
+    loop {
+        let () = write(
+            class,
+            b"\r\nsweep     n    points  skipped  rms        worst      at\r\n",
+        )
+        .await?;
+        for n in RESOLUTIONS {
+            let errors = sweep_2d(n).await;
+            let () = report(class, "2d", n, &errors).await?;
+        }
+        for n in RESOLUTIONS {
+            let errors = sweep_leg(n, leg).await;
+            let () = report(class, "leg", n, &errors).await?;
+        }
+        let () = write(class, b"done; press any key to run again\r\n").await?;
+        let _: usize = class.read_packet(&mut packet).await?;
+    }
+}
+
+/// `n` distances from just inside the least reach to just inside the most,
+/// times `4 * n` directions all the way around.
+#[inline]
+async fn sweep_2d(n: usize) -> Errors {
+    let mut errors = Errors::new();
+    for i in 0..n {
+        // Just inside either edge, so rounding doesn't push a point out of reach:
+        let distance = grid(i, n, 1.001 * MIN_REACH, 0.999 * REACH);
+        for j in 0..(4 * n) {
+            let angle = 2.0 * PI * (j as f32) / ((4 * n) as f32);
+            let (x, y) = (distance * libm::cosf(angle), distance * libm::sinf(angle));
+            match ik::hip_to_foot_2d(HipToFootDisplacementIn2dPlane { x, y }) {
+                Ok(angles) => {
+                    let foot = ik::hip_and_knee_to_foot_2d(angles);
+                    let error = libm::hypotf(foot.x - x, foot.y - y);
+                    let () = errors.add([x, y, 0.0], error);
+                }
+                Err(_) => errors.skipped += 1,
+            }
+        }
+        // Let USB keep up between rows:
+        let () = embassy_futures::yield_now().await;
+    }
+    errors
+}
+
+/// An `n`-cubed box from the yaw servo out to full reach, a full reach wide,
+/// and from a full reach down to half a reach up.
+#[inline]
+async fn sweep_leg(n: usize, leg: &mut Leg<'_>) -> Errors {
+    let mut errors = Errors::new();
+    let yaw = ik::LENGTH_CENTER_TO_YAW;
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                let target = ik::CartesianDisplacementFromEyeCenterLookingForward {
+                    x: grid(i, n, yaw, yaw + ik::LENGTH_YAW_TO_HIP + REACH),
+                    y: grid(j, n, -0.5 * REACH, 0.5 * REACH),
+                    z: grid(k, n, -REACH, 0.5 * REACH),
+                };
+                match leg.ik_to(target) {
+                    Ok(()) => {
+                        let foot = leg.forward_kinematics();
+                        let (dx, dy, dz) =
+                            (foot.x - target.x, foot.y - target.y, foot.z - target.z);
+                        let error = libm::sqrtf(dx * dx + dy * dy + dz * dz);
+                        let () = errors.add([target.x, target.y, target.z], error);
+                    }
+                    Err(_) => errors.skipped += 1,
+                }
+            }
+            let () = embassy_futures::yield_now().await;
+        }
+    }
+    errors
+}
+
+#[inline]
+async fn report(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    name: &str,
+    n: usize,
+    errors: &Errors,
+) -> Result<(), EndpointError> {
+    let [x, y, z] = errors.worst_at;
+    let mut line = heapless::String::<128>::new();
+    let _: core::fmt::Result = write!(
+        line,
+        "{name:5} {n:5} {:9} {:8}  {:9.3e}  {:9.3e}  ({x:.3}, {y:.3}, {z:.3})\r\n",
+        errors.count,
+        errors.skipped,
+        errors.rms(),
+        errors.worst,
+    );
+    info!("{}", line.as_str());
+    write(class, line.as_bytes()).await
+}
+
+/// Split into packets, ending with a short one so the host doesn't wait for more.
+#[inline]
+async fn write(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    bytes: &[u8],
+) -> Result<(), EndpointError> {
+    for chunk in bytes.chunks(MAX_PACKET_SIZE as usize) {
+        let () = class.write_packet(chunk).await?;
+    }
+    if bytes.len() % (MAX_PACKET_SIZE as usize) == 0 {
+        let () = class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
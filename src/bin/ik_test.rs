@@ -8,11 +8,17 @@ use {
     embassy_rp::{
         bind_interrupts,
         peripherals::{UART1, USB},
-        uart, usb,
+        uart::{self, UartRx},
+        usb,
+        watchdog::Watchdog,
     },
     embassy_time::{Duration, Ticker, Timer},
-    eye_bot_inverse_kinematics::{ik, leg::Leg, pwm},
-    panic_probe as _,
+    eye_bot_inverse_kinematics::{
+        command::{self, Command},
+        console, error, ik, info,
+        leg::Leg,
+        pwm, scheduler, warn, watchdog,
+    },
 };
 
 bind_interrupts!(struct Irqs {
@@ -20,8 +26,6 @@ bind_interrupts!(struct Irqs {
     USBCTRL_IRQ => usb::InterruptHandler<USB>;
 });
 
-const MAIN_LOOP_PERIOD_MS: u16 = pwm::PULSE_PERIOD_MS;
-
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
@@ -30,51 +34,83 @@ async fn main(spawner: Spawner) {
         // USB background task:
         #[embassy_executor::task]
         pub async fn task(driver: usb::Driver<'static, USB>) {
-            embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
+            console::run(driver).await
         }
         let () = match spawner.spawn(task(usb::Driver::new(p.USB, Irqs))) {
-            Ok(()) => defmt::info!("Spawned USB task"),
+            Ok(()) => info!("Spawned USB task"),
             Err(e) => {
-                log::error!("Error spawning USB task");
+                error!("Error spawning USB task");
                 Timer::after(Duration::from_secs(1)).await;
-                defmt::panic!("Error spawning USB task: {}", e);
+                panic!("Error spawning USB task: {e:?}");
             }
         };
     }
 
+    {
+        // UART command task:
+        #[embassy_executor::task]
+        pub async fn task(rx: UartRx<'static, UART1, uart::Async>) {
+            command::read_uart(rx).await
+        }
+        let rx = UartRx::new(p.UART1, p.PIN_5, Irqs, p.DMA_CH0, uart::Config::default());
+        let () = match spawner.spawn(task(rx)) {
+            Ok(()) => info!("Spawned UART task"),
+            Err(e) => {
+                error!("Error spawning UART task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning UART task: {e:?}");
+            }
+        };
+    }
+
+    // From here on, anything that stops the loop below (including the error loop just after)
+    // resets the chip instead of leaving the leg powered where it was:
+    let mut watchdog =
+        watchdog::Feeder::start(Watchdog::new(p.WATCHDOG), watchdog::DEFAULT_TIMEOUT).await;
+
     let (pwm0, pwm1) = pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await;
     let (pwm2, _pwm3) = pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await;
 
-    let mut leg = match Leg::with_home_yaw(0.0, pwm0, pwm1, pwm2).await {
+    let mut leg = match Leg::with_home_yaw(0.0, pwm0, pwm1, pwm2) {
         Ok(ok) => ok,
         Err(e) => {
             let mut ticker = Ticker::every(Duration::from_secs(1));
             loop {
-                log::error!("Couldn't initialize a leg: {e:?}");
+                error!("Couldn't initialize a leg: {e:?}");
                 let () = ticker.next().await;
             }
         }
     };
 
-    let mut counter: u16 = 0;
-    let mut ticker = Ticker::every(Duration::from_millis(MAIN_LOOP_PERIOD_MS as _));
+    // Once a foot position arrives over UART, hold it instead of sweeping:
+    let mut commanded = None;
+
+    let mut ms = 0.0;
+    let mut clock = scheduler::Clock::every(scheduler::PERIOD);
     loop {
-        let foot_pos = ik::CartesianDisplacementFromEyeCenterLookingForward {
-            x: 2.0 * libm::sinf(counter as f32 / 100.0)
+        while let Some(command) = command::try_receive() {
+            match command {
+                Command::FootPosition { leg: 0, position } => commanded = Some(position),
+                other => warn!("This binary only drives leg 0; ignoring {other:?}"),
+            }
+        }
+
+        let foot_pos = commanded.unwrap_or(ik::CartesianDisplacementFromEyeCenterLookingForward {
+            x: 2.0 * libm::sinf(ms / 100.0)
                 + 2.0
                 + ik::LENGTH_CENTER_TO_YAW
                 + ik::LENGTH_YAW_TO_HIP
                 + ik::LENGTH_HIP_TO_KNEE,
-            y: 2.0 * libm::cosf(counter as f32 / 100.0),
-            z: 1.0 * libm::sinf(counter as f32 / 1_000.0) + 2.0 - ik::LENGTH_KNEE_TO_FOOT,
-        };
+            y: 2.0 * libm::cosf(ms / 100.0),
+            z: 1.0 * libm::sinf(ms / 1_000.0) + 2.0 - ik::LENGTH_KNEE_TO_FOOT,
+        });
 
         match leg.ik_to(foot_pos) {
             Ok(()) => {}
-            Err(e) => log::error!("Leg inverse kinematics error: {e:?}"),
+            Err(e) => error!("Leg inverse kinematics error: {e:?}"),
         }
 
-        counter += MAIN_LOOP_PERIOD_MS;
-        let () = ticker.next().await;
+        let () = watchdog.feed();
+        ms = clock.next().await.since_start.as_millis() as f32;
     }
 }
@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+#![feature(async_trait_bounds, impl_trait_in_assoc_type)]
+
+use {
+    defmt_rtt as _,
+    embassy_executor::Spawner,
+    embassy_time::{Duration, Ticker},
+    eye_bot_inverse_kinematics::{error, ik, info, leg::Leg, pwm, scheduler},
+};
+#[cfg(feature = "log-usb")]
+use {
+    embassy_rp::{bind_interrupts, peripherals::USB, usb},
+    embassy_time::Timer,
+};
+
+// Traces a circle with one foot, in the vertical plane straight out from its yaw servo,
+// logging each target and the angles IK found for it over USB.
+// The leg is on the same pins as the other single-leg binaries (10, 11, 12: yaw, hip, knee).
+// Without `log-usb`, USB isn't brought up at all, and everything goes out over defmt only.
+
+#[cfg(feature = "log-usb")]
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
+});
+
+const WAVE_PERIOD_MS: u64 = 1000;
+
+/// Center of the circle, out from the hip and down from it.
+const CENTER_X: f32 = 2.75;
+const CENTER_Z: f32 = -5.85;
+const RADIUS: f32 = 1.0;
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+    #[cfg(not(feature = "log-usb"))]
+    let _: Spawner = spawner;
+
+    #[cfg(feature = "log-usb")]
+    {
+        // USB background task:
+        #[embassy_executor::task]
+        pub async fn task(driver: usb::Driver<'static, USB>) {
+            embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
+        }
+        let () = match spawner.spawn(task(usb::Driver::new(p.USB, Irqs))) {
+            Ok(()) => info!("Spawned USB task"),
+            Err(e) => {
+                error!("Error spawning USB task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning USB task: {e:?}");
+            }
+        };
+    }
+
+    let (pwm0, pwm1) = pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await;
+    let (pwm2, _pwm3) = pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await;
+
+    let mut leg = match Leg::with_home_yaw(0.0, pwm0, pwm1, pwm2) {
+        Ok(ok) => ok,
+        Err(e) => {
+            let mut ticker = Ticker::every(Duration::from_secs(1));
+            loop {
+                error!("Couldn't initialize a leg: {e:?}");
+                let () = ticker.next().await;
+            }
+        }
+    };
+
+    let mut ms = 0;
+    let mut clock = scheduler::Clock::every(scheduler::PERIOD);
+    loop {
+        let theta = ((ms % WAVE_PERIOD_MS) as f32)
+            * const { 2.0 * core::f32::consts::PI / (WAVE_PERIOD_MS as f32) };
+
+        let foot = ik::CartesianDisplacementFromYawServo {
+            x: ik::LENGTH_YAW_TO_HIP + CENTER_X + RADIUS * libm::cosf(theta),
+            y: 0.0,
+            z: CENTER_Z - RADIUS * libm::sinf(theta),
+        };
+
+        info!("");
+        info!("Cartesian: {foot:01.2?}");
+        match leg.ik_to_leg_local(foot) {
+            Ok(()) => info!("Angles(IK): {:01.2?}", leg.angles()),
+            Err(e) => error!("Leg inverse kinematics error: {e:?}"),
+        }
+
+        ms = clock.next().await.since_start.as_millis();
+    }
+}
@@ -0,0 +1,363 @@
+#![no_std]
+#![no_main]
+#![feature(async_trait_bounds, impl_trait_in_assoc_type)]
+
+use {
+    defmt_rtt as _,
+    embassy_executor::Spawner,
+    embassy_rp::{
+        adc::{self, Adc},
+        bind_interrupts,
+        gpio::{Input, Pull},
+        peripherals::{PIO0, UART1, USB},
+        pio::{self, Pio},
+        uart::{self, UartRx},
+        usb,
+        watchdog::Watchdog,
+    },
+    embassy_time::{Duration, Timer},
+    eye_bot_inverse_kinematics::{
+        analog, battery,
+        body::Body,
+        brownout,
+        buzzer::{self, Buzzer},
+        command, console, current, error, estop, fault,
+        gait::{self, Gait, Pattern},
+        info,
+        leg::Leg,
+        multicore, output, power, pressure, pwm, scheduler, sequence, status, storage, telemetry,
+        thermal, warn, watchdog,
+        ws2812::Ws2812,
+    },
+    static_cell::StaticCell,
+};
+
+// The whole robot, split across both cores (see `multicore`):
+//
+//     core 0   USB (log, console, telemetry, MAVLink), UART commands, telemetry encoding,
+//              analog sampling, the emergency stop, fault logging, stored sequences, and
+//              everything that touches flash
+//     core 1   `scheduler::run`: commands in, gait and servos out, every `pwm::PULSE_PERIOD_MS`,
+//              with the pulse widths themselves written from `output`'s interrupt executor
+//
+// Every command source feeds the same queue (`command::submit`), and RC sticks (`rc::publish`)
+// their own signal; the motion task alone drains both, so no two producers ever hold a leg.
+//
+// Setup (config, legs, standing up) happens on core 0 before core 1 starts, since it reads
+// flash; the finished body and gait are then moved over whole.
+//
+// Every pin, all on the board's header (GPIO 23 through 25 and 29 belong to the board itself:
+// power, VBUS and VSYS sensing, and the radio):
+//
+//     GPIO 0-17   servos, yaw-hip-knee for each leg in order (PWM slices 0 through 8)
+//     GPIO 18     piezo buzzer (PWM slice 9, see `buzzer`)
+//     GPIO 19     emergency stop button, to ground (see `estop`)
+//     GPIO 20     servo rail enable, pulled down off the board (see `power`)
+//     GPIO 21     UART1 receive, for commands (GPIO 5 is a servo here)
+//     GPIO 22     WS2812 status LED (see `status`)
+//     GPIO 26     ADC 0: the pack, through a divider (see `battery` and `brownout`)
+//     GPIO 27     ADC 1: the servo rail's current-sense amplifier (see `current`)
+//     GPIO 28     ADC 2: a force-sensing resistor under leg 0's foot (see `pressure`)
+//
+// The ADC pins, and the chip's own temperature sensor, are all read by `analog`'s task on
+// core 0. That's the whole header, so legs 1 through 5 have no foot sensor at all, which the
+// gait takes as landing right where it planned, and nothing runs `contact::watch`.
+//
+// The servo rail stays off until every leg has a pulse to hold (`power::Rail::power_up`), and
+// goes off again for a flat pack (see `battery`) or a panic (see `fault`).
+
+/// Pack volts per volt at GPIO 26: a 20k/10k divider, so a full 2S pack reads 2.8 V.
+const SUPPLY_DIVIDER: f32 = 3.0;
+/// Weight of each reading in `battery`'s average: about a second's worth, at its rate.
+const BATTERY_SMOOTHING: f32 = 0.1;
+/// The servo rail's sense amplifier on GPIO 27: a 10 mOhm shunt at a gain of 20.
+const RAIL_SENSOR: current::Sensor = current::Sensor {
+    amps_per_volt: 5.0,
+    zero_volts: 0.0,
+};
+/// Readings per published rail current: one servo period's worth, at its rate.
+const RAIL_WINDOW: u16 = 10;
+/// Legs with a force-sensing resistor under the foot, from leg 0.
+const FSR_FEET: usize = 1;
+/// A nominal FSR 402 over a 10k resistor: about a newton per thousand counts over its tare.
+const FSR_CALIBRATION: pressure::Calibration = pressure::Calibration {
+    tare: 40.0,
+    newtons_per_count: 0.001,
+};
+/// Weight of each reading in `thermal`'s average: about ten seconds' worth, at its rate.
+const THERMAL_SMOOTHING: f32 = 0.1;
+
+bind_interrupts!(struct Irqs {
+    ADC_IRQ_FIFO => adc::InterruptHandler;
+    PIO0_IRQ_0 => pio::InterruptHandler<PIO0>;
+    UART1_IRQ => uart::InterruptHandler<UART1>;
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
+});
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    // Off until `power_up`, and kept for good, so the pin goes on driving the gate:
+    static RAIL: StaticCell<power::Rail<'static>> = StaticCell::new();
+    let rail = RAIL.init(power::Rail::new(p.PIN_20));
+
+    {
+        // USB background task:
+        #[embassy_executor::task]
+        pub async fn task(driver: usb::Driver<'static, USB>) {
+            console::run(driver).await
+        }
+        let () = match spawner.spawn(task(usb::Driver::new(p.USB, Irqs))) {
+            Ok(()) => info!("Spawned USB task"),
+            Err(e) => {
+                error!("Error spawning USB task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning USB task: {e:?}");
+            }
+        };
+    }
+
+    {
+        // UART command task:
+        #[embassy_executor::task]
+        pub async fn task(rx: UartRx<'static, UART1, uart::Async>) {
+            command::read_uart(rx).await
+        }
+        let rx = UartRx::new(p.UART1, p.PIN_21, Irqs, p.DMA_CH0, uart::Config::default());
+        let () = match spawner.spawn(task(rx)) {
+            Ok(()) => info!("Spawned UART task"),
+            Err(e) => {
+                error!("Error spawning UART task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning UART task: {e:?}");
+            }
+        };
+    }
+
+    {
+        // Status LED task, early so it shows booting the rest of the way:
+        #[embassy_executor::task]
+        pub async fn task(leds: Ws2812<'static, PIO0, 0, 1>) {
+            status::run(leds).await
+        }
+        let Pio {
+            mut common, sm0, ..
+        } = Pio::new(p.PIO0, Irqs);
+        let leds = Ws2812::new(&mut common, sm0, p.DMA_CH1, p.PIN_22);
+        let () = match spawner.spawn(task(leds)) {
+            Ok(()) => info!("Spawned status LED task"),
+            Err(e) => {
+                error!("Error spawning status LED task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning status LED task: {e:?}");
+            }
+        };
+    }
+
+    {
+        // Buzzer task, chirping once it's up:
+        #[embassy_executor::task]
+        pub async fn task(buzzer: Buzzer<'static>) {
+            buzzer::run(buzzer).await
+        }
+        let () = match spawner.spawn(task(Buzzer::new(p.PWM_SLICE9, p.PIN_18))) {
+            Ok(()) => info!("Spawned buzzer task"),
+            Err(e) => {
+                error!("Error spawning buzzer task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning buzzer task: {e:?}");
+            }
+        };
+    }
+
+    {
+        // Telemetry encoding task:
+        #[embassy_executor::task]
+        pub async fn task() {
+            telemetry::stream().await
+        }
+        let () = match spawner.spawn(task()) {
+            Ok(()) => info!("Spawned telemetry task"),
+            Err(e) => {
+                error!("Error spawning telemetry task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning telemetry task: {e:?}");
+            }
+        };
+    }
+
+    let () = storage::init(p.FLASH).await;
+    let () = fault::recover().await;
+    let config = match storage::load().await {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("No saved config ({e:?}); using defaults");
+            storage::Config::default()
+        }
+    };
+
+    {
+        // Fault logging task, on this core since it writes flash:
+        #[embassy_executor::task]
+        pub async fn task() {
+            fault::watch().await
+        }
+        let () = match spawner.spawn(task()) {
+            Ok(()) => info!("Spawned fault task"),
+            Err(e) => {
+                error!("Error spawning fault task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning fault task: {e:?}");
+            }
+        };
+    }
+
+    {
+        // Stored sequence task, submitting commands like any other source:
+        #[embassy_executor::task]
+        pub async fn task() {
+            sequence::run().await
+        }
+        let () = match spawner.spawn(task()) {
+            Ok(()) => info!("Spawned sequence task"),
+            Err(e) => {
+                error!("Error spawning sequence task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning sequence task: {e:?}");
+            }
+        };
+    }
+
+    {
+        // Analog sampling task, the only one with the ADC:
+        #[embassy_executor::task]
+        pub async fn task(adc: Adc<'static, adc::Async>, inputs: analog::Inputs<FSR_FEET>) {
+            analog::run(adc, inputs).await
+        }
+        let inputs = analog::Inputs {
+            supply: Some(analog::Supply {
+                channel: adc::Channel::new_pin(p.PIN_26, Pull::None),
+                battery: battery::Monitor::new(SUPPLY_DIVIDER, BATTERY_SMOOTHING),
+                brownout: brownout::Monitor::new(SUPPLY_DIVIDER),
+            }),
+            rail: Some(analog::Rail {
+                channel: adc::Channel::new_pin(p.PIN_27, Pull::None),
+                monitor: current::Monitor::new(RAIL_SENSOR, RAIL_WINDOW),
+            }),
+            pressure: Some(analog::Pressure {
+                channels: [adc::Channel::new_pin(p.PIN_28, Pull::None)],
+                feet: pressure::Feet::new(
+                    [FSR_CALIBRATION; FSR_FEET],
+                    pressure::Thresholds::DEFAULT,
+                ),
+            }),
+            temperature: Some(analog::Temperature {
+                channel: adc::Channel::new_temp_sensor(p.ADC_TEMP_SENSOR),
+                monitor: thermal::Monitor::new(thermal::DEFAULT_WARNING_CELSIUS, THERMAL_SMOOTHING),
+            }),
+        };
+        let adc = Adc::new(p.ADC, Irqs, adc::Config::default());
+        let () = match spawner.spawn(task(adc, inputs)) {
+            Ok(()) => info!("Spawned analog sampling task"),
+            Err(e) => {
+                error!("Error spawning analog sampling task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning analog sampling task: {e:?}");
+            }
+        };
+    }
+
+    {
+        // Emergency stop task, needing nothing from the motion core to freeze it:
+        #[embassy_executor::task]
+        pub async fn task(pin: Input<'static>) {
+            estop::watch(pin, estop::DEFAULT_DEBOUNCE).await
+        }
+        let () = match spawner.spawn(task(Input::new(p.PIN_19, Pull::Up))) {
+            Ok(()) => info!("Spawned emergency stop task"),
+            Err(e) => {
+                error!("Error spawning emergency stop task");
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning emergency stop task: {e:?}");
+            }
+        };
+    }
+
+    let slices = [
+        pwm::init_slice(p.PWM_SLICE0, p.PIN_0, p.PIN_1).await,
+        pwm::init_slice(p.PWM_SLICE1, p.PIN_2, p.PIN_3).await,
+        pwm::init_slice(p.PWM_SLICE2, p.PIN_4, p.PIN_5).await,
+        pwm::init_slice(p.PWM_SLICE3, p.PIN_6, p.PIN_7).await,
+        pwm::init_slice(p.PWM_SLICE4, p.PIN_8, p.PIN_9).await,
+        pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await,
+        pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await,
+        pwm::init_slice(p.PWM_SLICE7, p.PIN_14, p.PIN_15).await,
+        pwm::init_slice(p.PWM_SLICE8, p.PIN_16, p.PIN_17).await,
+    ];
+    let mut outputs = slices.into_iter().flat_map(|(a, b)| [a, b]);
+    let mut legs = heapless::Vec::<Leg<'static>, { gait::N_LEGS }>::new();
+    for (i, leg) in config.legs.iter().enumerate() {
+        let (Some(yaw), Some(hip), Some(knee)) = (outputs.next(), outputs.next(), outputs.next())
+        else {
+            unreachable!()
+        };
+        match Leg::with_mount(leg.mount, yaw, hip, knee) {
+            Ok(leg) => {
+                let Ok(()) = legs.push(leg) else {
+                    unreachable!()
+                };
+            }
+            Err(e) => panic!("Couldn't initialize leg {i}: {e:?}"),
+        }
+    }
+    let Ok(legs) = legs.into_array() else {
+        unreachable!()
+    };
+    let mut body = Body::new(legs);
+    let mut gait = Gait::new(
+        Pattern::Tripod,
+        body.standing_foot_positions(),
+        gait::Parameters::DEFAULT,
+    );
+    if let Err(e) = config.apply(&mut body, &mut gait) {
+        warn!("Couldn't apply the saved config: {e:?}");
+    }
+
+    if let Err(e) = rail.power_up(&mut body).await {
+        panic!("Couldn't power up the servos: {e:?}");
+    }
+
+    if let Err(e) = body.stand_up().await {
+        warn!("Couldn't stand all the way up: {e:?}");
+    }
+    let () = gait.set_neutral(body.standing_foot_positions());
+
+    // Started here rather than on core 1, since a watchdog reset last boot gets logged to flash.
+    // From here on, anything that stops the motion loop resets the chip instead of leaving
+    // the robot walking blind:
+    let watchdog =
+        watchdog::Feeder::start(Watchdog::new(p.WATCHDOG), watchdog::DEFAULT_TIMEOUT).await;
+
+    // Also spawns the flash task, doing the motion task's saves and loads on this core:
+    let () = match multicore::spawn_motion(spawner, p.CORE1, move |spawner| {
+        let () = output::start();
+        let () = match spawner.spawn(motion(body, gait, watchdog)) {
+            Ok(()) => info!("Spawned motion task on core 1"),
+            Err(e) => panic!("Error spawning motion task: {e:?}"),
+        };
+    }) {
+        Ok(()) => info!("Spawned flash task"),
+        Err(e) => {
+            error!("Error spawning flash task");
+            Timer::after(Duration::from_secs(1)).await;
+            panic!("Error spawning flash task: {e:?}");
+        }
+    };
+}
+
+/// Everything that moves, once per PWM period, on core 1.
+#[embassy_executor::task]
+async fn motion(body: Body<'static, { gait::N_LEGS }>, gait: Gait, watchdog: watchdog::Feeder) {
+    scheduler::run(body, gait, watchdog).await
+}
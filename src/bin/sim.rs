@@ -0,0 +1,104 @@
+use {
+    core::f32::consts::PI,
+    embassy_time::Duration,
+    eye_bot_inverse_kinematics::{
+        body::Body,
+        gait::{self, Gait, Pattern},
+        leg::Leg,
+        pwm, sim,
+    },
+    std::{
+        env,
+        fmt::Write as _,
+        io::{self, Write as _},
+        process::ExitCode,
+    },
+};
+
+// Walks a six-legged body on stand-in servos with the real gait and IK, one servo period at a
+// time but without waiting for it, and prints every foot's trajectory as CSV: `t` in seconds,
+// then `leg<i>.x|y|z` from forward kinematics in the body frame (named as in `plot`).
+// IK and servo errors go to stderr, so they don't break the CSV. See `sim` for how to run it.
+
+const USAGE: &str = "usage: sim [seconds] [vx] [vy] [yaw rate] [tripod|ripple|wave]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let number = |i: usize, default: f32| args.get(i).map_or(Ok(default), |arg| arg.parse());
+    let (Ok(seconds), Ok(vx), Ok(vy), Ok(yaw_rate)) = (
+        number(0, 10.0),
+        number(1, 5.0),
+        number(2, 0.0),
+        number(3, 0.0),
+    ) else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let pattern = match args.get(4).map(String::as_str) {
+        None | Some("tripod") => Pattern::Tripod,
+        Some("ripple") => Pattern::Ripple,
+        Some("wave") => Pattern::Wave,
+        Some(_) => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut legs = Vec::with_capacity(gait::N_LEGS);
+    for i in 0..gait::N_LEGS {
+        let home_yaw = 2.0 * PI * (i as f32) / (gait::N_LEGS as f32);
+        match Leg::with_home_yaw(
+            home_yaw,
+            sim::Output::new(),
+            sim::Output::new(),
+            sim::Output::new(),
+        ) {
+            Ok(leg) => legs.push(leg),
+            Err(e) => {
+                eprintln!("Couldn't initialize leg {i}: {e:?}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Ok(legs) = <[Leg<'_>; gait::N_LEGS]>::try_from(legs) else {
+        unreachable!()
+    };
+    let mut body = Body::new(legs);
+
+    let neutral = body.standing_foot_positions();
+    if let Err(e) = body.set_foot_positions(neutral) {
+        eprintln!("Couldn't stand: {e:?}");
+        return ExitCode::FAILURE;
+    }
+    let mut gait = Gait::new(pattern, neutral, gait::Parameters::DEFAULT);
+    let () = gait.set_velocity(vx, vy, yaw_rate);
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let mut line = String::from("t");
+    for i in 0..gait::N_LEGS {
+        let _: std::fmt::Result = write!(line, ",leg{i}.x,leg{i}.y,leg{i}.z");
+    }
+    if writeln!(out, "{line}").is_err() {
+        return ExitCode::SUCCESS;
+    }
+
+    let period = Duration::from_millis(pwm::PULSE_PERIOD_MS as _);
+    let steps = (seconds * 1_000.0 / (pwm::PULSE_PERIOD_MS as f32)) as u32;
+    for step in 1..=steps {
+        let t = (step as f32) * (pwm::PULSE_PERIOD_MS as f32) * 1e-3;
+        if let Err(e) = body.set_foot_positions(gait.advance(period)) {
+            eprintln!("t = {t:.3}: {e:?}");
+        }
+
+        line.clear();
+        let _: std::fmt::Result = write!(line, "{t:.3}");
+        for foot in body.forward_kinematics() {
+            let _: std::fmt::Result = write!(line, ",{},{},{}", foot.x, foot.y, foot.z);
+        }
+        // Stop quietly if whatever's reading (e.g. `head`) has had enough:
+        if writeln!(out, "{line}").is_err() {
+            return ExitCode::SUCCESS;
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,302 @@
+#![no_std]
+#![no_main]
+#![feature(async_trait_bounds, impl_trait_in_assoc_type)]
+
+use {
+    core::fmt::Write as _,
+    defmt_rtt as _,
+    embassy_executor::Spawner,
+    embassy_rp::{
+        bind_interrupts,
+        peripherals::USB,
+        usb::{self, Driver},
+        watchdog::Watchdog,
+    },
+    embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal},
+    embassy_time::{Duration, Timer},
+    embassy_usb::{
+        Builder, Config,
+        class::cdc_acm::{CdcAcmClass, State},
+        driver::EndpointError,
+    },
+    eye_bot_inverse_kinematics::{
+        body::{self, Body},
+        gait::{self, Gait, Pattern},
+        ik, info,
+        leg::Leg,
+        pwm, scheduler, storage, warn, watchdog,
+    },
+    static_cell::StaticCell,
+};
+
+// Drive the whole robot from a keyboard, with nothing on the PC but a serial terminal.
+// Each key takes effect as soon as it's pressed, no Enter needed:
+//
+//     w / s   faster forward / backward      a / d   faster left / right
+//     q / e   turn faster left / right       r / f   raise / lower the body
+//     space   stop walking (height stays)    ?       print the keys again
+//
+// Speeds build up a step per press, up to `MAX_SPEED` and `MAX_YAW_RATE`, and the gait eases
+// into each change on its own. Dropping the connection stops the robot where it stands.
+//
+// Legs are wired in order, yaw-hip-knee each, from GPIO 0 through 17 (PWM slices 0 through 8).
+// Mounts, trims and limits come from the flash config store if anything's saved there.
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
+});
+
+/// Largest USB full-speed bulk packet.
+const MAX_PACKET_SIZE: u16 = 64;
+
+/// Per keypress, in length units per second.
+const SPEED_STEP: f32 = 1.0;
+const MAX_SPEED: f32 = 5.0;
+/// Per keypress, in radians per second.
+const YAW_RATE_STEP: f32 = 0.1;
+const MAX_YAW_RATE: f32 = 0.5;
+/// Per keypress, in length units.
+const HEIGHT_STEP: f32 = 0.25;
+const MAX_HEIGHT: f32 = 2.0;
+
+/// Short enough for one packet.
+const HELP: &[u8] = b"\r\nwasd walk, q/e turn, r/f height, space stop, ? help\r\n";
+
+/// Latest from the keyboard, picked up by the motion loop once per period.
+static DRIVE: Signal<CriticalSectionRawMutex, Drive> = Signal::new();
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Drive {
+    forward: f32,
+    left: f32,
+    yaw_rate: f32,
+    /// Above the stance's own height.
+    height: f32,
+}
+
+impl Drive {
+    const STOPPED: Self = Self {
+        forward: 0.0,
+        left: 0.0,
+        yaw_rate: 0.0,
+        height: 0.0,
+    };
+
+    /// What one keypress changes, or `None` for a key that isn't bound.
+    #[inline]
+    fn after(self, key: u8) -> Option<Self> {
+        let step = |value: f32, by: f32, max: f32| (value + by).clamp(-max, max);
+        Some(match key {
+            b'w' => Self {
+                forward: step(self.forward, SPEED_STEP, MAX_SPEED),
+                ..self
+            },
+            b's' => Self {
+                forward: step(self.forward, -SPEED_STEP, MAX_SPEED),
+                ..self
+            },
+            b'a' => Self {
+                left: step(self.left, SPEED_STEP, MAX_SPEED),
+                ..self
+            },
+            b'd' => Self {
+                left: step(self.left, -SPEED_STEP, MAX_SPEED),
+                ..self
+            },
+            b'q' => Self {
+                yaw_rate: step(self.yaw_rate, YAW_RATE_STEP, MAX_YAW_RATE),
+                ..self
+            },
+            b'e' => Self {
+                yaw_rate: step(self.yaw_rate, -YAW_RATE_STEP, MAX_YAW_RATE),
+                ..self
+            },
+            b'r' => Self {
+                height: step(self.height, HEIGHT_STEP, MAX_HEIGHT),
+                ..self
+            },
+            b'f' => Self {
+                height: step(self.height, -HEIGHT_STEP, MAX_HEIGHT),
+                ..self
+            },
+            b' ' => Self {
+                height: self.height,
+                ..Self::STOPPED
+            },
+            _ => return None,
+        })
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let () = storage::init(p.FLASH).await;
+    let config = match storage::load().await {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("No saved config ({e:?}); using defaults");
+            storage::Config::default()
+        }
+    };
+
+    {
+        // USB background task:
+        #[embassy_executor::task]
+        pub async fn task(driver: Driver<'static, USB>) {
+            keyboard(driver).await
+        }
+        let () = match spawner.spawn(task(Driver::new(p.USB, Irqs))) {
+            Ok(()) => info!("Spawned USB task"),
+            Err(e) => {
+                Timer::after(Duration::from_secs(1)).await;
+                panic!("Error spawning USB task: {e:?}");
+            }
+        };
+    }
+
+    let slices = [
+        pwm::init_slice(p.PWM_SLICE0, p.PIN_0, p.PIN_1).await,
+        pwm::init_slice(p.PWM_SLICE1, p.PIN_2, p.PIN_3).await,
+        pwm::init_slice(p.PWM_SLICE2, p.PIN_4, p.PIN_5).await,
+        pwm::init_slice(p.PWM_SLICE3, p.PIN_6, p.PIN_7).await,
+        pwm::init_slice(p.PWM_SLICE4, p.PIN_8, p.PIN_9).await,
+        pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11).await,
+        pwm::init_slice(p.PWM_SLICE6, p.PIN_12, p.PIN_13).await,
+        pwm::init_slice(p.PWM_SLICE7, p.PIN_14, p.PIN_15).await,
+        pwm::init_slice(p.PWM_SLICE8, p.PIN_16, p.PIN_17).await,
+    ];
+    let mut outputs = slices.into_iter().flat_map(|(a, b)| [a, b]);
+    let mut legs = heapless::Vec::<Leg<'static>, { gait::N_LEGS }>::new();
+    for (i, leg) in config.legs.iter().enumerate() {
+        let (Some(yaw), Some(hip), Some(knee)) = (outputs.next(), outputs.next(), outputs.next())
+        else {
+            unreachable!()
+        };
+        match Leg::with_mount(leg.mount, yaw, hip, knee) {
+            Ok(leg) => {
+                let Ok(()) = legs.push(leg) else {
+                    unreachable!()
+                };
+            }
+            Err(e) => panic!("Couldn't initialize leg {i}: {e:?}"),
+        }
+    }
+    let Ok(legs) = legs.into_array() else {
+        unreachable!()
+    };
+    let mut body = Body::new(legs);
+    let mut gait = Gait::new(
+        Pattern::Tripod,
+        body.standing_foot_positions(),
+        gait::Parameters::DEFAULT,
+    );
+    if let Err(e) = config.apply(&mut body, &mut gait) {
+        warn!("Couldn't apply the saved config: {e:?}");
+    }
+
+    if let Err(e) = body.stand_up().await {
+        warn!("Couldn't stand all the way up: {e:?}");
+    }
+    let () = gait.set_neutral(body.standing_foot_positions());
+
+    // From here on, anything that stops the loop below resets the chip instead of leaving
+    // the robot walking blind:
+    let mut watchdog =
+        watchdog::Feeder::start(Watchdog::new(p.WATCHDOG), watchdog::DEFAULT_TIMEOUT).await;
+
+    let mut drive = Drive::STOPPED;
+    let mut elapsed = scheduler::PERIOD;
+    let mut clock = scheduler::Clock::every(scheduler::PERIOD);
+    loop {
+        if let Some(new) = DRIVE.try_take() {
+            drive = new;
+            let () = gait.set_velocity(drive.forward, drive.left, drive.yaw_rate);
+        }
+
+        let pose = body::Pose {
+            translation: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                z: drive.height,
+                ..body::Pose::NEUTRAL.translation
+            },
+            ..body::Pose::NEUTRAL
+        };
+        if let Err(e) = body.set_pose_and_foot_positions(pose, Some(gait.advance(elapsed))) {
+            warn!("{e:?}");
+        }
+
+        let () = watchdog.feed();
+        elapsed = clock.next().await.elapsed;
+    }
+}
+
+/// One serial port, one keypress at a time, reconnecting forever.
+#[inline]
+async fn keyboard(driver: Driver<'static, USB>) -> ! {
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUFFER: StaticCell<[u8; 64]> = StaticCell::new();
+    static STATE: StaticCell<State> = StaticCell::new();
+
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("wrsturgeon");
+    config.product = Some("eye-bot teleop");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [],
+        CONTROL_BUFFER.init([0; 64]),
+    );
+    let mut class = CdcAcmClass::new(&mut builder, STATE.init(State::new()), MAX_PACKET_SIZE);
+    let mut usb = builder.build();
+
+    let serve = async {
+        loop {
+            let () = class.wait_connection().await;
+            info!("Keyboard connected");
+            let mut drive = Drive::STOPPED;
+            let _: Result<(), EndpointError> = session(&mut class, &mut drive).await;
+            info!("Keyboard disconnected; stopping");
+            let () = DRIVE.signal(Drive::STOPPED);
+        }
+    };
+    let (never, _) = embassy_futures::join::join(usb.run(), serve).await;
+    never
+}
+
+#[inline]
+async fn session(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    drive: &mut Drive,
+) -> Result<(), EndpointError> {
+    let mut packet = [0; MAX_PACKET_SIZE as usize];
+    let () = class.write_packet(HELP).await?;
+    loop {
+        let n = class.read_packet(&mut packet).await?;
+        for &key in &packet[..n] {
+            if key == b'?' {
+                let () = class.write_packet(HELP).await?;
+                continue;
+            }
+            let Some(new) = drive.after(key) else {
+                continue;
+            };
+            *drive = new;
+            let () = DRIVE.signal(new);
+
+            let mut line = heapless::String::<{ MAX_PACKET_SIZE as usize - 1 }>::new();
+            let _: core::fmt::Result = write!(
+                line,
+                "\rforward {:5.1}  left {:5.1}  turn {:5.2}  height {:5.2}  ",
+                drive.forward, drive.left, drive.yaw_rate, drive.height,
+            );
+            let () = class.write_packet(line.as_bytes()).await?;
+        }
+    }
+}
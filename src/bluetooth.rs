@@ -0,0 +1,107 @@
+use {
+    crate::{
+        command::{self, LineTooLong, Lines},
+        sanitize::Source,
+        telemetry,
+    },
+    embassy_rp::uart::{self, Async, Uart, UartTx},
+    embassy_time::{Duration, Instant},
+};
+
+// An HC-05 (or similar) module is a transparent serial bridge: whatever a paired phone sends
+// comes out of its TX pin, and the module doesn't tell us when a phone connects or leaves.
+// Anyone in radio range can pair with the default PIN, so the link starts locked:
+// the first line has to be `auth <passphrase>`, and the session locks itself again
+// after `IDLE_TIMEOUT` without a command. Everything else is the same text as the other ports.
+
+/// How long an authenticated session survives without a line.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 9600 baud, 8N1: the HC-05's factory setting in data mode.
+#[inline]
+pub fn config() -> uart::Config {
+    let mut config = uart::Config::default();
+    config.baudrate = 9_600;
+    config
+}
+
+/// Serve commands from a Bluetooth serial module on `uart` forever, answering each line
+/// with `ok` or `error: ...` so a phone terminal shows what happened.
+#[inline]
+pub async fn run<T: uart::Instance>(uart: Uart<'static, T, Async>, passphrase: &'static str) -> ! {
+    let (mut tx, mut rx) = uart.split();
+    let mut lines = Lines::default();
+    let mut authenticated_until: Option<Instant> = None;
+    loop {
+        let mut byte = [0];
+        if let Err(e) = rx.read(&mut byte).await {
+            let () = crate::warn!("Bluetooth read error: {e:?}");
+            lines = Lines::default();
+            continue;
+        }
+        let line = match lines.push(byte[0]) {
+            None => continue,
+            Some(Err(LineTooLong)) => {
+                let () = reply(&mut tx, b"error: line too long\r\n").await;
+                continue;
+            }
+            Some(Ok(line)) => line,
+        };
+
+        let now = Instant::now();
+        if authenticated_until.is_some_and(|until| now < until) {
+            authenticated_until = Some(now + IDLE_TIMEOUT);
+        } else {
+            authenticated_until = None;
+            let answer: &[u8] = match line.strip_prefix(b"auth ") {
+                Some(attempt) if constant_time_eq(attempt, passphrase.as_bytes()) => {
+                    let () = crate::info!("Bluetooth session authenticated");
+                    authenticated_until = Some(now + IDLE_TIMEOUT);
+                    b"ok\r\n"
+                }
+                Some(_) => {
+                    let () = crate::warn!("Bluetooth authentication failed");
+                    b"error: wrong passphrase\r\n"
+                }
+                None => b"error: send `auth <passphrase>` first\r\n",
+            };
+            let () = reply(&mut tx, answer).await;
+            continue;
+        }
+
+        let answer: &[u8] = match command::parse_scheduled_bytes(line) {
+            Ok(scheduled) => match command::submit_scheduled(Source::Bluetooth, scheduled).await {
+                Ok(()) => b"ok\r\n",
+                Err(e) => {
+                    let () = crate::warn!("Refused a Bluetooth command: {e:?}");
+                    b"error: refused\r\n"
+                }
+            },
+            Err(e) => {
+                let () = telemetry::count(telemetry::ErrorKind::Command);
+                let () = crate::warn!("Couldn't parse a Bluetooth command: {e:?}");
+                b"error: couldn't parse that\r\n"
+            }
+        };
+        let () = reply(&mut tx, answer).await;
+    }
+}
+
+#[inline]
+async fn reply<T: uart::Instance>(tx: &mut UartTx<'static, T, Async>, bytes: &[u8]) {
+    if let Err(e) = tx.write(bytes).await {
+        let () = crate::warn!("Bluetooth write error: {e:?}");
+    }
+}
+
+/// Compare without returning early, so response timing doesn't leak how much of a guess was right.
+#[inline]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b)
+        .fold(0, |difference, (x, y)| difference | (x ^ y))
+        == 0
+}
@@ -0,0 +1,700 @@
+use {
+    crate::{
+        ik,
+        interp::{Easing, Interpolation},
+        leg::{self, Leg},
+        math::{self, Quaternion, Rotation2, Transform3, Vec2, Vec3},
+        pwm, servo,
+    },
+    core::sync::atomic::{AtomicBool, Ordering},
+    embassy_time::{Duration, Instant, Ticker},
+};
+
+/// Global rather than per-`Body`, so that an e-stop input or a serial task can freeze motion
+/// without needing access to whatever `Body` the motion loop is holding.
+static FROZEN: AtomicBool = AtomicBool::new(false);
+
+/// Stop every interpolator and hold every leg at its last commanded position
+/// until `resume` is called. New targets are ignored in the meantime.
+#[inline]
+pub fn freeze() {
+    FROZEN.store(true, Ordering::SeqCst)
+}
+
+/// Pick up where `freeze` left off: interpolators continue from where they paused.
+#[inline]
+pub fn resume() {
+    FROZEN.store(false, Ordering::SeqCst)
+}
+
+#[inline]
+pub fn is_frozen() -> bool {
+    FROZEN.load(Ordering::SeqCst)
+}
+
+/// How the body stands when it isn't walking.
+#[derive(Clone, Copy, Debug)]
+pub struct Stance {
+    /// Horizontal distance from the eye center to each foot.
+    pub foot_radius: f32,
+    /// Distance from the eye center down to the ground.
+    pub height: f32,
+}
+
+impl Stance {
+    /// Thighs level and shins vertical.
+    pub const DEFAULT: Self = Self {
+        foot_radius: ik::LENGTH_CENTER_TO_YAW + ik::LENGTH_YAW_TO_HIP + ik::LENGTH_HIP_TO_KNEE,
+        height: ik::LENGTH_KNEE_TO_FOOT,
+    };
+
+    /// Transport pose: knees up and feet tucked in under the hips,
+    /// with the body resting on its belly.
+    pub const FOLDED: Self = Self {
+        foot_radius: ik::LENGTH_CENTER_TO_YAW
+            + ik::LENGTH_YAW_TO_HIP
+            + 0.5 * ik::LENGTH_HIP_TO_KNEE,
+        height: ik::LENGTH_KNEE_TO_FOOT - ik::LENGTH_HIP_TO_KNEE,
+    };
+}
+
+/// Time for each stage of `Body::stand_up`.
+pub const STAND_UP_STAGE_DURATION: Duration = Duration::from_millis(1_500);
+
+/// Time for each stage of `Body::sit_down`.
+pub const SIT_DOWN_STAGE_DURATION: Duration = Duration::from_millis(1_500);
+
+/// Body orientation as Euler angles, applied yaw-then-pitch-then-roll (intrinsic Z-Y-X), for
+/// reading and typing in. Converts to and from the `Quaternion` a `Pose` keeps.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rotation {
+    pub roll: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+/// Where the body sits relative to the ground frame,
+/// i.e. the body frame it would have in its neutral pose.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pose {
+    pub translation: ik::CartesianDisplacementFromEyeCenterLookingForward,
+    /// Unit length; `Body` normalizes whatever it's given.
+    pub rotation: Quaternion,
+}
+
+impl From<Rotation> for Quaternion {
+    #[inline]
+    fn from(Rotation { roll, pitch, yaw }: Rotation) -> Self {
+        Self::from_roll_pitch_yaw(roll, pitch, yaw)
+    }
+}
+
+impl From<Quaternion> for Rotation {
+    /// Pitch is clamped at +/-90 degrees; see `Quaternion::roll_pitch_yaw`.
+    #[inline]
+    fn from(quaternion: Quaternion) -> Self {
+        let (roll, pitch, yaw) = quaternion.roll_pitch_yaw();
+        Self { roll, pitch, yaw }
+    }
+}
+
+impl Rotation {
+    /// Component-wise linear interpolation: `t = 0` is `self`, `t = 1` is `other`.
+    #[inline]
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            roll: self.roll + (other.roll - self.roll) * t,
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+            yaw: self.yaw + (other.yaw - self.yaw) * t,
+        }
+    }
+}
+
+impl Pose {
+    /// Linear interpolation of the translation and spherical of the rotation:
+    /// `t = 0` is `self`, `t = 1` is `other`.
+    #[inline]
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+        }
+    }
+
+    pub const NEUTRAL: Self = Self {
+        translation: ik::CartesianDisplacementFromEyeCenterLookingForward {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        rotation: Quaternion::IDENTITY,
+    };
+
+    /// From the body frame into the ground frame.
+    #[inline]
+    pub fn transform(&self) -> Transform3 {
+        Transform3 {
+            rotation: self.rotation.matrix(),
+            translation: self.translation.into(),
+        }
+    }
+
+    /// Re-express a ground-frame point in the (translated, rotated) body frame.
+    #[inline]
+    pub fn ground_to_body(
+        &self,
+        point: ik::CartesianDisplacementFromEyeCenterLookingForward,
+    ) -> ik::CartesianDisplacementFromEyeCenterLookingForward {
+        self.transform().inverse_apply(point.into()).into()
+    }
+}
+
+/// Static stability margin: how far the center of mass (projected onto the ground)
+/// sits inside the support polygon formed by the planted feet.
+/// Negative if it's outside, and `NEG_INFINITY` with fewer than three feet down.
+#[inline]
+pub fn stability_margin(
+    feet: &[ik::CartesianDisplacementFromEyeCenterLookingForward],
+    planted: &[bool],
+    (com_x, com_y): (f32, f32),
+) -> f32 {
+    let planted_feet = || {
+        feet.iter()
+            .zip(planted)
+            .filter_map(|(&foot, &planted)| planted.then_some(Vec3::from(foot).xy()))
+    };
+    if planted_feet().count() < 3 {
+        return f32::NEG_INFINITY;
+    }
+
+    // An edge from `a` to `b` is on the (counterclockwise) convex hull
+    // iff no planted foot lies strictly to its right:
+    let cross = |a: Vec2, b: Vec2, p: Vec2| (b - a).cross(p - a);
+    let com = Vec2::new(com_x, com_y);
+    let mut margin = f32::INFINITY;
+    for a in planted_feet() {
+        for b in planted_feet() {
+            let length = (b - a).norm();
+            if length <= f32::EPSILON || planted_feet().any(|p| cross(a, b, p) < -f32::EPSILON) {
+                continue;
+            }
+            margin = margin.min(cross(a, b, com) / length);
+        }
+    }
+    margin
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub struct LegError {
+    pub index: usize,
+    pub error: leg::IkError,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub struct RelaxError {
+    pub index: usize,
+    pub error: leg::CouldntRelax,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub struct AttachError {
+    pub index: usize,
+    pub error: servo::CouldntMove,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum SitDownError {
+    Lowering(LegError),
+    Relaxing(RelaxError),
+}
+
+/// What to do when a target would bring two neighboring legs too close together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collisions {
+    /// Refuse the whole command: no foot moves.
+    Reject,
+    /// Push both legs apart (equally, about their yaw servos) until they clear the margin.
+    Clamp,
+}
+
+/// A set of `N` legs sharing one body frame (the eye-center frame in `ik`).
+/// Each leg carries its own `leg::Mount`, so targets can be given to all of them at once.
+pub struct Body<'d, const N: usize> {
+    legs: [Leg<'d>; N],
+    pose: Pose,
+    stance: Stance,
+    /// Last commanded foot positions, in the ground frame.
+    feet: Option<[ik::CartesianDisplacementFromEyeCenterLookingForward; N]>,
+    /// Smallest allowed angle between leg `i` and leg `i + 1` (wrapping around),
+    /// measured counterclockwise between the directions they point.
+    yaw_separation_margins: [f32; N],
+    collisions: Collisions,
+    /// A `start_sitting_down` that `advance` hasn't finished yet.
+    sitting: Option<SittingDown<N>>,
+    /// Set by `relax` and `start_sitting_down`, and cleared only by `stand_up` and `attach`.
+    resting: bool,
+}
+
+/// `Body::sit_down`'s two stages, for `Body::advance` to work through a tick at a time.
+#[derive(Clone, Copy, Debug)]
+struct SittingDown<const N: usize> {
+    lowering: Interpolation<[ik::CartesianDisplacementFromEyeCenterLookingForward; N]>,
+    tucking: Interpolation<[ik::CartesianDisplacementFromEyeCenterLookingForward; N]>,
+}
+
+impl<'d, const N: usize> Body<'d, N> {
+    #[inline]
+    pub fn new(legs: [Leg<'d>; N]) -> Self {
+        Self {
+            legs,
+            pose: Pose::NEUTRAL,
+            stance: Stance::DEFAULT,
+            feet: None,
+            yaw_separation_margins: [0.0; N],
+            collisions: Collisions::Clamp,
+            sitting: None,
+            resting: false,
+        }
+    }
+
+    /// Set the smallest allowed angle (radians) between leg `i` and leg `i + 1` (wrapping around),
+    /// and what to do with targets that violate it. Legs are assumed numbered counterclockwise.
+    #[inline]
+    pub fn set_collision_avoidance(
+        &mut self,
+        yaw_separation_margins: [f32; N],
+        collisions: Collisions,
+    ) {
+        self.yaw_separation_margins = yaw_separation_margins;
+        self.collisions = collisions;
+    }
+
+    #[inline]
+    pub fn legs(&self) -> &[Leg<'d>; N] {
+        &self.legs
+    }
+
+    #[inline]
+    pub fn legs_mut(&mut self) -> &mut [Leg<'d>; N] {
+        &mut self.legs
+    }
+
+    /// Last commanded joint angles of every leg, in radians.
+    #[inline]
+    pub fn joint_angles(&self) -> [ik::Angles; N] {
+        core::array::from_fn(|i| self.legs[i].angles())
+    }
+
+    /// Where every foot is according to its last commanded joint angles, in the body frame.
+    #[inline]
+    pub fn forward_kinematics(&self) -> [ik::CartesianDisplacementFromEyeCenterLookingForward; N] {
+        core::array::from_fn(|i| self.legs[i].forward_kinematics())
+    }
+
+    #[inline]
+    pub fn mounts(&self) -> [leg::Mount; N] {
+        core::array::from_fn(|i| *self.legs[i].mount())
+    }
+
+    #[inline]
+    pub fn pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    /// Last commanded foot positions, in the ground frame.
+    #[inline]
+    pub fn foot_positions(
+        &self,
+    ) -> Option<&[ik::CartesianDisplacementFromEyeCenterLookingForward; N]> {
+        self.feet.as_ref()
+    }
+
+    #[inline]
+    pub fn stance(&self) -> &Stance {
+        &self.stance
+    }
+
+    /// Where each foot rests under the current stance, in the ground frame:
+    /// straight out from the eye center along its leg's home direction.
+    /// Also the natural neutral positions for a gait.
+    #[inline]
+    pub fn standing_foot_positions(
+        &self,
+    ) -> [ik::CartesianDisplacementFromEyeCenterLookingForward; N] {
+        self.foot_positions_for(self.stance)
+    }
+
+    /// Where each foot would rest under `stance`, in the ground frame.
+    #[inline]
+    pub fn foot_positions_for(
+        &self,
+        Stance {
+            foot_radius,
+            height,
+        }: Stance,
+    ) -> [ik::CartesianDisplacementFromEyeCenterLookingForward; N] {
+        core::array::from_fn(|i| {
+            let yaw = self.legs[i].mount().home_yaw_radians;
+            ik::CartesianDisplacementFromEyeCenterLookingForward::from_heading(
+                yaw,
+                foot_radius,
+                -height,
+            )
+        })
+    }
+
+    /// Widen/narrow the stance or raise/lower the body, moving every foot
+    /// in a straight line from wherever it was to its new standing position.
+    #[inline]
+    pub async fn set_stance(&mut self, stance: Stance, duration: Duration) -> Result<(), LegError> {
+        self.stance = stance;
+        let to = self.standing_foot_positions();
+        self.move_feet_linearly(to, duration).await
+    }
+
+    /// Unfold from `Stance::FOLDED` and rise to the current stance over a few seconds.
+    ///
+    /// Every foot only ever moves straight out along its own leg's home direction,
+    /// so no leg sweeps sideways into its neighbor on the way up.
+    #[inline]
+    pub async fn stand_up(&mut self) -> Result<(), LegError> {
+        let standing = self.stance;
+        self.resting = false;
+
+        if self.feet.is_none() {
+            // Nothing commanded yet, so assume we're starting from the transport pose:
+            let () = self.set_foot_positions(self.foot_positions_for(Stance::FOLDED))?;
+        }
+
+        // Slide the feet out to their standing radius with the belly still on the ground:
+        let unfolded = self.foot_positions_for(Stance {
+            foot_radius: standing.foot_radius,
+            height: Stance::FOLDED.height,
+        });
+        let () = self
+            .move_feet_linearly(unfolded, STAND_UP_STAGE_DURATION)
+            .await?;
+
+        // Then push the body straight up:
+        let stood = self.foot_positions_for(standing);
+        self.move_feet_linearly(stood, STAND_UP_STAGE_DURATION)
+            .await
+    }
+
+    /// The reverse of `stand_up`: lower the body onto its belly, tuck the feet back in
+    /// to `Stance::FOLDED`, then let every servo go limp.
+    /// Meant for shutdown commands and low-battery handling.
+    #[inline]
+    pub async fn sit_down(&mut self) -> Result<(), SitDownError> {
+        let [lowered, folded] = self.sitting_stages();
+        let () = self
+            .move_feet_linearly(lowered, SIT_DOWN_STAGE_DURATION)
+            .await
+            .map_err(SitDownError::Lowering)?;
+        let () = self
+            .move_feet_linearly(folded, SIT_DOWN_STAGE_DURATION)
+            .await
+            .map_err(SitDownError::Lowering)?;
+        self.relax().map_err(SitDownError::Relaxing)
+    }
+
+    /// `sit_down`, for a loop that can't stop for seconds at a time: only starts lowering
+    /// the body, leaving the rest to `advance`. Replaces a sit-down already under way.
+    #[inline]
+    pub fn start_sitting_down(&mut self) {
+        let [lowered, folded] = self.sitting_stages();
+        // With nothing commanded yet, jump straight there, as `move_feet_linearly` would:
+        let (from, duration) = match self.feet {
+            Some(feet) => (feet, SIT_DOWN_STAGE_DURATION),
+            None => (lowered, Duration::from_ticks(0)),
+        };
+        self.sitting = Some(SittingDown {
+            lowering: Interpolation::new(from, lowered, duration, Easing::Linear),
+            tucking: Interpolation::new(lowered, folded, SIT_DOWN_STAGE_DURATION, Easing::Linear),
+        });
+        self.resting = true;
+    }
+
+    /// Whether a `start_sitting_down` is still going. The feet are its until it's done.
+    #[inline]
+    pub fn is_sitting_down(&self) -> bool {
+        self.sitting.is_some()
+    }
+
+    /// Carry a `start_sitting_down` on by `elapsed` (nothing, while frozen), letting every
+    /// servo go limp at the end. Call once per motion loop iteration. `Some` once it's over,
+    /// whether it got all the way down or a leg failed (which stops it where it is).
+    #[inline]
+    pub fn advance(&mut self, elapsed: Duration) -> Option<Result<(), SitDownError>> {
+        let sitting = self.sitting.as_mut()?;
+        let elapsed = if is_frozen() {
+            Duration::from_ticks(0)
+        } else {
+            elapsed
+        };
+        let feet = if sitting.lowering.is_done() {
+            sitting.tucking.advance(elapsed)
+        } else {
+            sitting.lowering.advance(elapsed)
+        };
+        let done = sitting.tucking.is_done();
+        if let Err(e) = self.set_foot_positions(feet) {
+            self.sitting = None;
+            return Some(Err(SitDownError::Lowering(e)));
+        }
+        if !done {
+            return None;
+        }
+        self.sitting = None;
+        Some(self.relax().map_err(SitDownError::Relaxing))
+    }
+
+    /// Where `sit_down` takes the feet: first lowering the body straight down with the feet
+    /// where they are, then sliding them in along each leg's own home direction.
+    #[inline]
+    fn sitting_stages(&self) -> [[ik::CartesianDisplacementFromEyeCenterLookingForward; N]; 2] {
+        [
+            self.foot_positions_for(Stance {
+                foot_radius: self.stance.foot_radius,
+                height: Stance::FOLDED.height,
+            }),
+            self.foot_positions_for(Stance::FOLDED),
+        ]
+    }
+
+    /// Whether the body's been relaxed or sat down (see `relax` and `start_sitting_down`)
+    /// since it last stood up or was attached, or has never had its feet commanded at all.
+    /// Nothing should walk it in the meantime: see `Gait::step`.
+    #[inline]
+    pub fn is_resting(&self) -> bool {
+        self.resting || self.feet.is_none()
+    }
+
+    /// Let every servo go limp, even if an earlier leg fails. Returns the first error, if any.
+    /// Foot positions are forgotten, since nothing holds them anymore.
+    #[inline]
+    pub fn relax(&mut self) -> Result<(), RelaxError> {
+        self.feet = None;
+        self.resting = true;
+        let mut result = Ok(());
+        for (index, leg) in self.legs.iter_mut().enumerate() {
+            if let Err(error) = leg.relax()
+                && result.is_ok()
+            {
+                result = Err(RelaxError { index, error });
+            }
+        }
+        result
+    }
+
+    /// Power every servo back up at its last commanded angle (see `Leg::attach`), stopping at the
+    /// first leg that fails. The feet are taken to be wherever those angles put them,
+    /// and a gait may take them from there.
+    #[inline]
+    pub fn attach(&mut self) -> Result<(), AttachError> {
+        for (index, leg) in self.legs.iter_mut().enumerate() {
+            let () = leg.attach().map_err(|error| AttachError { index, error })?;
+        }
+        let transform = self.pose.transform();
+        let feet = self.forward_kinematics();
+        self.feet = Some(feet.map(|foot| transform.apply(foot.into()).into()));
+        self.resting = false;
+        Ok(())
+    }
+
+    /// Move every foot in a straight line from its last commanded position to `to`
+    /// over `duration`, updating the servos once per PWM period.
+    /// If no position has been commanded yet, jumps straight there.
+    #[inline]
+    pub async fn move_feet_linearly(
+        &mut self,
+        to: [ik::CartesianDisplacementFromEyeCenterLookingForward; N],
+        duration: Duration,
+    ) -> Result<(), LegError> {
+        let Some(from) = self.feet else {
+            return self.set_foot_positions(to);
+        };
+        let mut motion = Interpolation::new(from, to, duration, Easing::Linear);
+        let mut last = Instant::now();
+        let mut ticker = Ticker::every(Duration::from_millis(pwm::PULSE_PERIOD_MS as _));
+        loop {
+            let now = Instant::now();
+            // Only count time spent unfrozen, so a freeze pauses rather than skips ahead:
+            let feet = motion.advance(if is_frozen() {
+                Duration::from_ticks(0)
+            } else {
+                now - last
+            });
+            last = now;
+            if motion.is_done() {
+                return self.set_foot_positions(feet);
+            }
+            let () = self.set_foot_positions(feet)?;
+            let () = ticker.next().await;
+        }
+    }
+
+    /// Same as the free function `freeze`, which also freezes every other body.
+    #[inline]
+    pub fn freeze(&self) {
+        freeze()
+    }
+
+    /// Same as the free function `resume`, which also resumes every other body.
+    #[inline]
+    pub fn resume(&self) {
+        resume()
+    }
+
+    /// Stability margin (see `stability_margin`) if only the `planted` feet stayed down,
+    /// taking the center of mass to be the eye center. `None` before any feet are commanded.
+    #[inline]
+    pub fn stability_margin(&self, planted: [bool; N]) -> Option<f32> {
+        let feet = self.feet.as_ref()?;
+        let center = (self.pose.translation.x, self.pose.translation.y);
+        Some(stability_margin(feet, &planted, center))
+    }
+
+    /// Move the body while keeping every foot where it is on the ground.
+    #[inline]
+    pub fn set_pose(
+        &mut self,
+        translation: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        rotation: Quaternion,
+    ) -> Result<(), LegError> {
+        self.set_pose_and_foot_positions(
+            Pose {
+                translation,
+                rotation,
+            },
+            None,
+        )
+    }
+
+    /// Move the body and (if given) the feet together in one update.
+    /// Without new feet, every foot stays where it is on the ground.
+    #[inline]
+    pub fn set_pose_and_foot_positions(
+        &mut self,
+        pose: Pose,
+        feet: Option<[ik::CartesianDisplacementFromEyeCenterLookingForward; N]>,
+    ) -> Result<(), LegError> {
+        if is_frozen() {
+            return Ok(());
+        }
+        self.pose = Pose {
+            rotation: pose.rotation.normalized(),
+            ..pose
+        };
+        match feet.or(self.feet) {
+            Some(feet) => self.set_foot_positions(feet),
+            None => Ok(()),
+        }
+    }
+
+    /// Move every foot, even if an earlier leg fails, so one unreachable target
+    /// doesn't freeze the rest of the body. Returns the first error, if any.
+    ///
+    /// Positions are in the ground frame; the current pose is applied on top.
+    #[inline]
+    pub fn set_foot_positions(
+        &mut self,
+        feet: [ik::CartesianDisplacementFromEyeCenterLookingForward; N],
+    ) -> Result<(), LegError> {
+        if is_frozen() {
+            return Ok(());
+        }
+        let mut targets = feet.map(|foot| self.pose.ground_to_body(foot));
+        let () = self.avoid_collisions(&mut targets)?;
+
+        self.feet = Some(feet);
+        let mut result = Ok(());
+        for (index, (leg, foot)) in self.legs.iter_mut().zip(targets).enumerate() {
+            if let Err(error) = leg.ik_to(foot) {
+                #[cfg(not(feature = "sim"))]
+                let () = crate::stats::count_leg_error(&error);
+                #[cfg(not(feature = "sim"))]
+                let () = crate::telemetry::count(match error {
+                    leg::IkError::Ik2dError(_) | leg::IkError::WouldCollide { .. } => {
+                        crate::telemetry::ErrorKind::Ik
+                    }
+                    _ => crate::telemetry::ErrorKind::Servo,
+                });
+                if result.is_ok() {
+                    result = Err(LegError { index, error });
+                }
+            }
+        }
+        result
+    }
+
+    /// Check every neighboring pair of body-frame targets against its yaw separation margin,
+    /// and either reject the lot or swing offending pairs apart.
+    #[inline]
+    fn avoid_collisions(
+        &self,
+        targets: &mut [ik::CartesianDisplacementFromEyeCenterLookingForward; N],
+    ) -> Result<(), LegError> {
+        if N < 2 {
+            return Ok(());
+        }
+        for i in 0..N {
+            let j = (i + 1) % N;
+            let margin = self.yaw_separation_margins[i];
+            if margin <= 0.0 {
+                continue;
+            }
+            let (mount_i, mount_j) = (self.legs[i].mount(), self.legs[j].mount());
+            let yaw_i = (Vec3::from(targets[i]).xy() - mount_i.yaw_servo()).angle();
+            let yaw_j = (Vec3::from(targets[j]).xy() - mount_j.yaw_servo()).angle();
+
+            // Counterclockwise from leg i to leg j, on [0, 2 pi):
+            let mut gap = math::wrap_angle(yaw_j - yaw_i);
+            if gap < 0.0 {
+                gap += const { 2.0 * core::f32::consts::PI };
+            }
+            // A gap near a full turn means j has crossed over behind i, which is just as bad:
+            if gap >= margin && gap <= const { 2.0 * core::f32::consts::PI } - margin {
+                continue;
+            }
+
+            match self.collisions {
+                Collisions::Reject => {
+                    return Err(LegError {
+                        index: i,
+                        error: leg::IkError::WouldCollide { neighbor: j },
+                    });
+                }
+                Collisions::Clamp => {
+                    let gap = if gap > core::f32::consts::PI {
+                        gap - const { 2.0 * core::f32::consts::PI }
+                    } else {
+                        gap
+                    };
+                    let correction = 0.5 * (margin - gap);
+                    rotate_about(&mut targets[i], mount_i, -correction);
+                    rotate_about(&mut targets[j], mount_j, correction);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Swing a body-frame point about a leg's yaw servo, counterclockwise by `radians`.
+#[inline]
+fn rotate_about(
+    point: &mut ik::CartesianDisplacementFromEyeCenterLookingForward,
+    mount: &leg::Mount,
+    radians: f32,
+) {
+    let pivot = mount.yaw_servo();
+    let swung = pivot + Rotation2::from_radians(radians).rotate(Vec3::from(*point).xy() - pivot);
+    (point.x, point.y) = (swung.x, swung.y);
+}
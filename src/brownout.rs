@@ -0,0 +1,181 @@
+use {
+    crate::{
+        analog, fault,
+        gait::{Gait, Limiter, Pattern},
+    },
+    core::sync::atomic::{AtomicU32, Ordering},
+    embassy_time::{Duration, Instant},
+};
+
+// Fast supply dips, as opposed to `battery`'s slow sag: a tripod of servos all starting
+// at once can pull a tired pack (or thin wires) down far enough, for a few milliseconds,
+// to reset the board mid-step. `battery`'s filter smooths those right out, so `analog::run`
+// hands this every reading of the same divider, taken quickly, and it keeps the lowest of
+// each window. When the dips get deep, `Guard` asks less of the servos until they stop:
+//
+//     Dip     walk slower, one leg at a time (`Pattern::Wave`), so fewer servos start together
+//     Severe  also pause the gait cycle entirely, so nothing's swinging
+//
+// It steps back down only after `Thresholds::recovery` without a dip. A boot that follows a
+// brownout reset (see `fault::recover`) starts at `Dip`, since whatever caused it is likely
+// still there.
+
+/// Time between ADC readings: fast enough to catch a dip a few milliseconds long.
+pub const SAMPLE_PERIOD: Duration = Duration::from_millis(1);
+/// Readings per published minimum.
+pub const WINDOW: u16 = 20;
+
+/// Fraction of the gait's commanded speed allowed during a dip.
+pub const DIP_SPEED_SCALE: f32 = 0.5;
+
+/// Lowest supply voltage in the latest window, as `f32` bits; NaN until the first window.
+static MINIMUM_VOLTS: AtomicU32 = AtomicU32::new(f32::NAN.to_bits());
+
+/// Lowest supply voltage in the latest window, if anything's measuring it.
+#[inline]
+pub fn minimum_volts() -> Option<f32> {
+    Some(f32::from_bits(MINIMUM_VOLTS.load(Ordering::Relaxed))).filter(|volts| !volts.is_nan())
+}
+
+/// Supply voltages at which `Guard` steps in.
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    pub dip: f32,
+    pub severe: f32,
+    /// Time without a dip below a level's threshold before stepping back from it.
+    pub recovery: Duration,
+}
+
+impl Thresholds {
+    /// A 2S LiPo feeding the board through a 5 V regulator with a volt or so of dropout.
+    pub const DEFAULT: Self = Self {
+        dip: 6.4,
+        severe: 6.0,
+        recovery: Duration::from_secs(2),
+    };
+}
+
+/// How much to hold back, mildest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Ok,
+    Dip,
+    Severe,
+}
+
+/// Keeps the lowest of every `WINDOW` raw readings, published through `minimum_volts`.
+#[derive(Clone, Copy, Debug)]
+pub struct Monitor {
+    /// Supply volts per volt at the pin, e.g. 3.0 for a 20k/10k divider.
+    divider: f32,
+    lowest: u16,
+    count: u16,
+}
+
+impl Monitor {
+    #[inline]
+    pub const fn new(divider: f32) -> Self {
+        Self {
+            divider,
+            lowest: u16::MAX,
+            count: 0,
+        }
+    }
+
+    /// Fold in one raw reading, returning (and publishing) the window's minimum once it fills.
+    #[inline]
+    pub fn update(&mut self, counts: u16) -> Option<f32> {
+        self.lowest = self.lowest.min(counts);
+        self.count += 1;
+        if self.count < WINDOW {
+            return None;
+        }
+        let volts = analog::volts(self.lowest) * self.divider;
+        self.lowest = u16::MAX;
+        self.count = 0;
+        let () = MINIMUM_VOLTS.store(volts.to_bits(), Ordering::Relaxed);
+        Some(volts)
+    }
+}
+
+/// Acts on supply dips from the motion loop, stepping through `Level`s.
+#[derive(Clone, Copy, Debug)]
+pub struct Guard {
+    thresholds: Thresholds,
+    level: Level,
+    /// When the supply last dipped below the current level's threshold.
+    last_dip: Instant,
+    /// The pattern to go back to once the dips stop.
+    pattern_before: Option<Pattern>,
+}
+
+impl Guard {
+    /// Starts at `Level::Dip` if the last reset was a brownout; the first `check` acts on it.
+    #[inline]
+    pub fn new(thresholds: Thresholds) -> Self {
+        let browned_out = fault::last_reset_was_brownout();
+        if browned_out {
+            let () = crate::warn!("Starting cautiously after a brownout");
+        }
+        Self {
+            thresholds,
+            level: if browned_out { Level::Dip } else { Level::Ok },
+            last_dip: Instant::now(),
+            pattern_before: None,
+        }
+    }
+
+    #[inline]
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Call once per motion loop iteration.
+    #[inline]
+    pub fn check(&mut self, gait: &mut Gait) {
+        let now = Instant::now();
+        let measured = match minimum_volts() {
+            Some(volts) if volts < self.thresholds.severe => Level::Severe,
+            Some(volts) if volts < self.thresholds.dip => Level::Dip,
+            _ => Level::Ok,
+        };
+        let level = if measured >= self.level {
+            if measured > Level::Ok {
+                self.last_dip = now;
+            }
+            measured
+        } else if now - self.last_dip >= self.thresholds.recovery {
+            // One step at a time, restarting the clock for the next:
+            self.last_dip = now;
+            match self.level {
+                Level::Severe => Level::Dip,
+                _ => Level::Ok,
+            }
+        } else {
+            self.level
+        };
+        if level == self.level && self.pattern_before.is_some() == (level > Level::Ok) {
+            return;
+        }
+        self.level = level;
+
+        let () = gait.set_paused(level >= Level::Severe);
+        if level > Level::Ok {
+            let () = gait.set_speed_scale(Limiter::Supply, DIP_SPEED_SCALE);
+            if self.pattern_before.is_none() {
+                self.pattern_before = Some(gait.pattern());
+                let () = gait.set_pattern(Pattern::Wave);
+            }
+        } else {
+            let () = gait.set_speed_scale(Limiter::Supply, 1.0);
+            if let Some(pattern) = self.pattern_before.take() {
+                let () = gait.set_pattern(pattern);
+            }
+        }
+        match level {
+            Level::Ok => crate::info!("Supply steady again"),
+            Level::Dip => crate::warn!("Supply dipping: easing off"),
+            Level::Severe => crate::warn!("Supply dipping hard: pausing the gait"),
+        }
+    }
+}
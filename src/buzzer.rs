@@ -0,0 +1,224 @@
+use embassy_time::Duration;
+
+#[cfg(not(feature = "sim"))]
+use {
+    crate::{
+        battery, estop,
+        pwm::clock_frequency,
+        telemetry::{self, ErrorKind},
+    },
+    embassy_futures::select::{Either, select},
+    embassy_rp::{
+        Peripheral,
+        pwm::{self, Config, Pwm},
+    },
+    embassy_sync::{
+        blocking_mutex::raw::CriticalSectionRawMutex,
+        channel::{Channel, TrySendError},
+    },
+    embassy_time::{Instant, Timer},
+    fixed::{FixedU16, types::extra::U4},
+};
+
+// A piezo buzzer on one PWM channel, so a failure in the field can be heard without a serial
+// connection. Anything can queue a `Melody` with `play`; `run` plays them one after another,
+// and listens for trouble on its own:
+//
+//     startup       rising chirp             once, when `run` starts
+//     IK errors     one short beep           at most once a `FAULT_REPEAT` per kind,
+//     servo errors  two short beeps          whenever `telemetry` counts more of them
+//     bad commands  three short beeps
+//     estop         one long low tone        when the emergency stop latches
+//     low battery   falling two-tone alarm   every `ALARM_PERIOD` while the pack's below
+//                                            `battery::Thresholds::DEFAULT.slow`
+//
+// Dropped telemetry frames stay quiet: they pile up whenever nothing's listening over USB.
+//
+// Each note sets the slice's divider and top for its pitch (`settings_for`) with the compare
+// at half of top, a square wave, which is as loud as a piezo gets.
+
+/// One pitch (or silence) held for a while.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Note {
+    /// Zero for a rest.
+    pub hz: u16,
+    pub duration: Duration,
+}
+
+impl Note {
+    #[inline]
+    pub const fn new(hz: u16, ms: u64) -> Self {
+        Self {
+            hz,
+            duration: Duration::from_millis(ms),
+        }
+    }
+
+    #[inline]
+    pub const fn rest(ms: u64) -> Self {
+        Self::new(0, ms)
+    }
+}
+
+pub type Melody = &'static [Note];
+
+const BEEP: Note = Note::new(2_700, 60);
+const GAP: Note = Note::rest(90);
+
+/// C, E, G, rising.
+pub const STARTUP: Melody = &[
+    Note::new(1_047, 70),
+    Note::new(1_319, 70),
+    Note::new(1_568, 140),
+];
+pub const IK_FAULT: Melody = &[BEEP];
+pub const SERVO_FAULT: Melody = &[BEEP, GAP, BEEP];
+pub const COMMAND_FAULT: Melody = &[BEEP, GAP, BEEP, GAP, BEEP];
+pub const ESTOP: Melody = &[Note::new(440, 1_000)];
+pub const LOW_BATTERY: Melody = &[Note::new(880, 300), Note::rest(50), Note::new(587, 500)];
+
+/// Melodies that may wait behind the one playing before `play` drops new ones.
+pub const QUEUE_DEPTH: usize = 4;
+
+/// How often `run` looks for trouble when nothing's playing.
+pub const CHECK_PERIOD: Duration = Duration::from_millis(250);
+
+/// Shortest time between beeps for the same kind of error.
+pub const FAULT_REPEAT: Duration = Duration::from_secs(5);
+
+/// Time between low-battery alarms.
+pub const ALARM_PERIOD: Duration = Duration::from_secs(15);
+
+#[cfg(not(feature = "sim"))]
+static MELODIES: Channel<CriticalSectionRawMutex, Melody, QUEUE_DEPTH> = Channel::new();
+
+/// Integer clock divider and counter top for a square wave at `hz` from a `clock_hz` system
+/// clock (counting up, not phase-correct: one period is `(top + 1) * divider` cycles),
+/// using the smallest divider that fits, for the finest pitch. `None` if `hz` is zero or too
+/// low to reach even with the largest divider.
+#[inline]
+pub fn settings_for(clock_hz: u32, hz: u16) -> Option<(u8, u16)> {
+    if hz == 0 {
+        return None;
+    }
+    let cycles = clock_hz / u32::from(hz);
+    let divider = cycles.div_ceil(1 << 16).max(1);
+    let top = (cycles / divider).checked_sub(1)?;
+    Some((u8::try_from(divider).ok()?, u16::try_from(top).ok()?))
+}
+
+/// Queue `melody` for `run`, dropping it if `QUEUE_DEPTH` are already waiting.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn play(melody: Melody) {
+    if let Err(TrySendError::Full(_)) = MELODIES.try_send(melody) {
+        let () = crate::warn!("Buzzer queue full; dropped a melody");
+    }
+}
+
+/// The beeps for `kind`, if it's worth any.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub const fn fault(kind: ErrorKind) -> Option<Melody> {
+    match kind {
+        ErrorKind::Ik => Some(IK_FAULT),
+        ErrorKind::Servo => Some(SERVO_FAULT),
+        ErrorKind::Command => Some(COMMAND_FAULT),
+        ErrorKind::DroppedFrame => None,
+    }
+}
+
+#[cfg(not(feature = "sim"))]
+pub struct Buzzer<'d> {
+    pwm: Pwm<'d>,
+    config: Config,
+}
+
+#[cfg(not(feature = "sim"))]
+impl<'d> Buzzer<'d> {
+    /// Silent until the first `tone`.
+    #[inline]
+    pub fn new<Slice: pwm::Slice>(
+        slice: impl Peripheral<P = Slice> + 'd,
+        pin: impl Peripheral<P = impl pwm::ChannelAPin<Slice>> + 'd,
+    ) -> Self {
+        let mut config = Config::default();
+        config.enable = false;
+        Self {
+            pwm: Pwm::new_output_a(slice, pin, config.clone()),
+            config,
+        }
+    }
+
+    /// Sound `hz` until told otherwise. Zero (or a pitch out of reach) is silence.
+    #[inline]
+    pub async fn tone(&mut self, hz: u16) {
+        let Some((divider, top)) = settings_for(clock_frequency().await, hz) else {
+            return self.silence();
+        };
+        self.config.divider = FixedU16::<U4>::from_num(divider);
+        self.config.top = top;
+        self.config.compare_a = top / 2;
+        self.config.enable = true;
+        self.pwm.set_config(&self.config)
+    }
+
+    #[inline]
+    pub fn silence(&mut self) {
+        self.config.enable = false;
+        self.pwm.set_config(&self.config)
+    }
+
+    /// Play every note in turn, then fall silent.
+    #[inline]
+    pub async fn play(&mut self, melody: &[Note]) {
+        for note in melody {
+            let () = self.tone(note.hz).await;
+            let () = Timer::after(note.duration).await;
+        }
+        self.silence()
+    }
+}
+
+/// Play the startup chirp, then whatever's queued and whatever trouble turns up, forever.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub async fn run(mut buzzer: Buzzer<'static>) -> ! {
+    let () = buzzer.play(STARTUP).await;
+    let mut counts = telemetry::error_counts();
+    let mut beeped: [Option<Instant>; telemetry::ERROR_KINDS] = [None; telemetry::ERROR_KINDS];
+    let mut latched = estop::is_latched();
+    let mut alarmed: Option<Instant> = None;
+    loop {
+        match select(MELODIES.receive(), Timer::after(CHECK_PERIOD)).await {
+            Either::First(melody) => buzzer.play(melody).await,
+            Either::Second(()) => {
+                let now = Instant::now();
+                let latest = telemetry::error_counts();
+                for kind in [ErrorKind::Ik, ErrorKind::Servo, ErrorKind::Command] {
+                    let i = kind as usize;
+                    if latest[i] != counts[i]
+                        && beeped[i].is_none_or(|at| now - at >= FAULT_REPEAT)
+                        && let Some(melody) = fault(kind)
+                    {
+                        beeped[i] = Some(now);
+                        let () = play(melody);
+                    }
+                }
+                counts = latest;
+
+                if estop::is_latched() && !latched {
+                    let () = play(ESTOP);
+                }
+                latched = estop::is_latched();
+
+                if battery::volts().is_some_and(|volts| volts < battery::Thresholds::DEFAULT.slow)
+                    && alarmed.is_none_or(|at| now - at >= ALARM_PERIOD)
+                {
+                    alarmed = Some(now);
+                    let () = play(LOW_BATTERY);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1082 @@
+use {
+    crate::{
+        body::{self, Body},
+        estop, failsafe,
+        gait::{self, Gait},
+        gcode, ik,
+        leg::{self, Joint, MotionLimits},
+        param::{self, Parameter},
+        plot,
+        sanitize::{self, Source},
+        sequence, servo, storage, teach, telemetry,
+    },
+    core::{cell::RefCell, str::FromStr},
+    embassy_rp::uart::{self, Async, UartRx},
+    embassy_sync::{
+        blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+        channel::{Channel, TrySendError},
+        signal::Signal,
+    },
+    embassy_time::{Duration, Instant, with_timeout},
+};
+
+/// Longest line the UART reader will buffer before giving up on it.
+pub const MAX_LINE_LENGTH: usize = 64;
+/// How many parsed commands may wait for the motion layer before readers start blocking.
+pub const QUEUE_DEPTH: usize = 8;
+
+/// Everything a command source (UART, USB, ...) has parsed, waiting for the motion layer.
+/// However many producers there are, only the one task with the `Executor` ever touches a `Leg`.
+static COMMANDS: Channel<CriticalSectionRawMutex, Command, QUEUE_DEPTH> = Channel::new();
+
+/// How many commands may wait for a time in the future (see `submit_at`).
+pub const SCHEDULE_DEPTH: usize = 32;
+
+/// Commands held back until their time comes, in no particular order.
+/// `try_receive` hands out the earliest due one before anything in `COMMANDS`.
+static SCHEDULED: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<heapless::Vec<(Instant, Command), SCHEDULE_DEPTH>>,
+> = Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// How long `query_status` waits for the motion task to answer.
+pub const STATUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Filled in by `Executor` whenever it sees `Command::Status`.
+static STATUS: Signal<CriticalSectionRawMutex, Status> = Signal::new();
+
+/// Filled in by `Executor` whenever it sees `Command::GetParameter` or `Command::SetParameter`.
+static PARAMETER: Signal<CriticalSectionRawMutex, Result<f32, param::CouldntAccess>> =
+    Signal::new();
+
+/// How long `query_config` waits, since erasing flash takes a while.
+pub const CONFIG_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Filled in by `Executor` once it's done a `Command::SaveConfig`, `Command::LoadConfig`
+/// or `Command::ResetConfig`.
+static CONFIG: Signal<CriticalSectionRawMutex, Result<(), CouldntExecute>> = Signal::new();
+
+/// Flash work for `run_flash_jobs`, handed over by `Executor`. At most one at a time, since the
+/// executor takes no other command until it hears back through `FLASH_DONE`.
+static FLASH_JOB: Signal<CriticalSectionRawMutex, FlashJob> = Signal::new();
+
+/// What came of the last `FLASH_JOB`.
+static FLASH_DONE: Signal<CriticalSectionRawMutex, FlashDone> = Signal::new();
+
+/// A snapshot of the motion layer, taken by whichever task owns it.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Status {
+    pub frozen: bool,
+    /// Whether the feet are holding positions (false after `relax`, before anything's commanded).
+    pub holding: bool,
+    pub pose: body::Pose,
+    pub velocity: gait::Velocity,
+    pub phase: f32,
+    pub stalled: bool,
+    pub legs: usize,
+    /// Whether any parameter has changed since the last `config save` or `config load`.
+    pub unsaved: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Command {
+    /// `leg <index> ik <x> <y> <z>`: move one foot to a point in the body frame.
+    FootPosition {
+        leg: usize,
+        position: ik::CartesianDisplacementFromEyeCenterLookingForward,
+    },
+    /// `leg <index> joint <yaw|hip|knee> <radians>`: drive one joint directly.
+    JointAngle {
+        leg: usize,
+        joint: Joint,
+        radians: f32,
+    },
+    /// `leg <index> angles <yaw> <hip> <knee>`: drive every joint of one leg directly, in radians.
+    JointAngles { leg: usize, angles: ik::Angles },
+    /// `walk <vx> <vy> <yaw rate>`: set the gait's velocity.
+    GaitVelocity { x: f32, y: f32, yaw_rate: f32 },
+    /// `pose <x> <y> <z> <roll> <pitch> <yaw>`: shift and tilt the body over its feet.
+    BodyPose(body::Pose),
+    /// `servo <index> us <microseconds>`: raw pulse width to one servo,
+    /// numbered yaw, hip, knee for leg 0, then the same for leg 1, and so on.
+    ServoPulse { servo: usize, microseconds: u16 },
+    /// `relax`: let every servo go limp.
+    Relax,
+    /// `freeze`: stop walking and hold every leg where it is, ignoring new targets until `Resume`.
+    /// Also drops anything scheduled (see `submit_at`) and stops any running `sequence`.
+    Freeze,
+    /// `resume`: undo a `Freeze` (but not an emergency stop, which needs `ResetEstop`).
+    Resume,
+    /// `status`: report a `Status` through `query_status`. The console adds `stats::snapshot`.
+    Status,
+    /// `G0`/`G1`: move one foot in a straight line, in the body frame,
+    /// at up to `speed` (units per second) or as fast as the leg's `MotionLimits` allow if `None`.
+    /// Axes left out keep their last commanded value.
+    MoveFoot {
+        leg: usize,
+        x: Option<f32>,
+        y: Option<f32>,
+        z: Option<f32>,
+        speed: Option<f32>,
+    },
+    /// `G4`: wait before carrying out the next command.
+    Dwell(#[cfg_attr(feature = "serde", serde(with = "crate::message::milliseconds"))] Duration),
+    /// `M17`: power every servo back up at its last commanded angle, and let the gait walk again
+    /// after a `Relax` or a sit-down.
+    Attach,
+    /// `param get <name>`: report a parameter through `query_parameter`.
+    GetParameter(Parameter),
+    /// `param set <name> <value>`: change a parameter, reporting its new value
+    /// (or why not) through `query_parameter`.
+    SetParameter(Parameter, f32),
+    /// `param list`: answered by the transport, from `param::all` and `Status::legs`.
+    ListParameters,
+    /// `config save`: write every parameter to flash, reporting through `query_config`.
+    /// Until then, `SetParameter` only lasts until the next reboot.
+    SaveConfig,
+    /// `config load`: undo every parameter change since the last save, reporting
+    /// through `query_config`.
+    LoadConfig,
+    /// `config reset`: erase every saved config and go back to the compiled-in defaults,
+    /// reporting through `query_config`.
+    ResetConfig,
+    /// `config show`: answered by the transport, comparing `storage::load` with live values.
+    ShowConfig,
+    /// `faults`: answered by the transport, listing `fault`'s log oldest first.
+    DumpFaults,
+    /// `dump`: answered by the transport, listing `journal`'s recent log lines oldest first.
+    DumpLog,
+    /// `plot <variable> ...`: answered by the transport, streaming CSV until interrupted.
+    Plot(plot::Selection),
+    /// `heartbeat`: does nothing but keep the `failsafe::Watchdog` from tripping.
+    Heartbeat,
+    /// `estop reset`: clear a latched emergency stop, once the button's released.
+    ResetEstop,
+    /// `teach start`: let every servo go limp to be posed by hand, starting a new recording.
+    TeachStart,
+    /// `teach capture [<milliseconds>]`: capture the legs as the next keyframe, reached that
+    /// long after the one before (`teach::DEFAULT_TRANSITION` if left out).
+    TeachCapture(
+        #[cfg_attr(feature = "serde", serde(with = "crate::message::milliseconds"))] Duration,
+    ),
+    /// `teach save`: write the recording to flash, replacing the one saved before.
+    TeachSave,
+    /// `teach play`: play the recording back, or the saved one if nothing's been captured.
+    TeachPlay,
+    /// `seq clear`: forget the uploaded program, to upload a new one.
+    SequenceClear,
+    /// `seq append <hex>`: add bytes to the end of the uploaded program (see `sequence`).
+    SequenceAppend(sequence::Chunk),
+    /// `seq save`: write the uploaded program to flash, replacing the one saved before.
+    SequenceSave,
+    /// `seq run`: start the uploaded program, or the saved one if nothing's been uploaded.
+    SequenceRun,
+    /// `seq stop`: stop the running program, wherever it's got to.
+    SequenceStop,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum ParseError {
+    Empty,
+    UnknownCommand,
+    MissingArgument,
+    TooManyArguments,
+    NotANumber,
+    UnknownJoint,
+    NotUtf8,
+    UnknownParameter,
+    Plot(plot::CouldntSelect),
+    /// `at` or `after` in front of a command that asks for an answer, which has to come now.
+    NotSchedulable,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntExecute {
+    NoSuchLeg {
+        index: usize,
+    },
+    /// A `MoveFoot` left out an axis, but that leg's never been given a position to keep.
+    UnknownPosition {
+        leg: usize,
+    },
+    Ik(leg::IkError),
+    Joint(servo::CouldntMove),
+    Relax(body::RelaxError),
+    Attach(body::AttachError),
+    Pose(body::LegError),
+    Save(storage::CouldntSave),
+    Load(storage::CouldntRevert),
+    Reset(storage::CouldntReset),
+    Estop(estop::CouldntReset),
+    /// `Resume` during an emergency stop, which only `ResetEstop` may clear.
+    EstopLatched,
+    Teach(teach::CouldntTeach),
+    Sequence(sequence::CouldntSequence),
+}
+
+/// Flash work the motion core can't do itself.
+#[derive(Clone, Copy, Debug)]
+enum FlashJob {
+    /// `storage::commit` these live parameters.
+    SaveConfig(storage::Config),
+    LoadConfig,
+    ResetConfig,
+    SaveTeach,
+    LoadTeach,
+    SaveSequence,
+    StartSequence,
+}
+
+/// What came of a `FlashJob`, for `Executor::advance` to finish off.
+#[derive(Debug)]
+enum FlashDone {
+    /// `SaveConfig`.
+    Saved(Result<(), storage::CouldntSave>),
+    /// `LoadConfig`, still to be applied.
+    Config(Result<storage::Config, storage::CouldntLoad>),
+    /// `ResetConfig`, with the defaults still to be applied.
+    Reset(Result<(), storage::CouldntReset>),
+    /// `LoadTeach`, still to be played.
+    Recording(Result<teach::Recording, teach::CouldntTeach>),
+    /// Anything else: nothing left to do but report it.
+    Done(Result<(), CouldntExecute>),
+}
+
+/// Why `submit` turned a command away.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum Refused {
+    Invalid(sanitize::Invalid),
+    /// This source is sending faster than `sanitize::RATE_HZ`.
+    RateLimited,
+    /// The command queue is full; try again.
+    QueueFull,
+    /// Too many commands are already waiting for their time (see `SCHEDULE_DEPTH`).
+    ScheduleFull,
+}
+
+/// A command, and when to carry it out.
+#[derive(Clone, Copy, Debug)]
+pub struct Scheduled {
+    pub command: Command,
+    /// `None` for as soon as possible.
+    pub at: Option<Instant>,
+}
+
+/// `parse`, for raw bytes off the wire.
+#[inline]
+pub fn parse_bytes(line: &[u8]) -> Result<Command, ParseError> {
+    core::str::from_utf8(line)
+        .map_err(|_| ParseError::NotUtf8)
+        .and_then(parse)
+}
+
+/// `parse_scheduled`, for raw bytes off the wire.
+#[inline]
+pub fn parse_scheduled_bytes(line: &[u8]) -> Result<Scheduled, ParseError> {
+    core::str::from_utf8(line)
+        .map_err(|_| ParseError::NotUtf8)
+        .and_then(parse_scheduled)
+}
+
+/// `parse`, optionally prefixed with when to carry the command out:
+///
+///     at <milliseconds> <command>       that long after boot (the clock telemetry timestamps use)
+///     after <milliseconds> <command>    that long from now
+///
+/// `at` lets a host line up a sequence ahead of time, unaffected by however long each line
+/// takes to arrive. Commands that answer something (`status`, `param ...`, `config ...`,
+/// `faults`, `plot`) can't wait.
+#[inline]
+pub fn parse_scheduled(line: &str) -> Result<Scheduled, ParseError> {
+    let line = line.trim_start();
+    let (when, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if !matches!(when, "at" | "after") {
+        return Ok(Scheduled {
+            command: parse(line)?,
+            at: None,
+        });
+    }
+    let rest = rest.trim_start();
+    let (ms, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if ms.is_empty() {
+        return Err(ParseError::MissingArgument);
+    }
+    let ms = ms.parse().map_err(|_| ParseError::NotANumber)?;
+    let at = match when {
+        "at" => Instant::from_millis(ms),
+        _ => Instant::now() + Duration::from_millis(ms),
+    };
+    match parse(rest)? {
+        Command::Status
+        | Command::GetParameter(_)
+        | Command::SetParameter(..)
+        | Command::ListParameters
+        | Command::SaveConfig
+        | Command::LoadConfig
+        | Command::ResetConfig
+        | Command::ShowConfig
+        | Command::DumpFaults
+        | Command::DumpLog
+        | Command::Plot(_) => Err(ParseError::NotSchedulable),
+        command => Ok(Scheduled {
+            command,
+            at: Some(at),
+        }),
+    }
+}
+
+/// Parse one whitespace-separated text command (without its line ending).
+/// Lines starting with a `G` or `M` word are read as G-code; see `gcode::parse`.
+#[inline]
+pub fn parse(line: &str) -> Result<Command, ParseError> {
+    if gcode::looks_like_gcode(line) {
+        return gcode::parse(line);
+    }
+    let mut words = line.split_ascii_whitespace();
+    let command = match words.next().ok_or(ParseError::Empty)? {
+        "leg" => {
+            let leg = number(&mut words)?;
+            match words.next().ok_or(ParseError::MissingArgument)? {
+                "ik" => Command::FootPosition {
+                    leg,
+                    position: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                        x: number(&mut words)?,
+                        y: number(&mut words)?,
+                        z: number(&mut words)?,
+                    },
+                },
+                "joint" => Command::JointAngle {
+                    leg,
+                    joint: match words.next().ok_or(ParseError::MissingArgument)? {
+                        "yaw" => Joint::Yaw,
+                        "hip" => Joint::Hip,
+                        "knee" => Joint::Knee,
+                        _ => return Err(ParseError::UnknownJoint),
+                    },
+                    radians: number(&mut words)?,
+                },
+                "angles" => Command::JointAngles {
+                    leg,
+                    angles: ik::Angles {
+                        yaw: number(&mut words)?,
+                        hip: number(&mut words)?,
+                        knee: number(&mut words)?,
+                    },
+                },
+                _ => return Err(ParseError::UnknownCommand),
+            }
+        }
+        "walk" => Command::GaitVelocity {
+            x: number(&mut words)?,
+            y: number(&mut words)?,
+            yaw_rate: number(&mut words)?,
+        },
+        "pose" => Command::BodyPose(body::Pose {
+            translation: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                x: number(&mut words)?,
+                y: number(&mut words)?,
+                z: number(&mut words)?,
+            },
+            rotation: body::Rotation {
+                roll: number(&mut words)?,
+                pitch: number(&mut words)?,
+                yaw: number(&mut words)?,
+            }
+            .into(),
+        }),
+        "servo" => {
+            let servo = number(&mut words)?;
+            match words.next().ok_or(ParseError::MissingArgument)? {
+                "us" => Command::ServoPulse {
+                    servo,
+                    microseconds: number(&mut words)?,
+                },
+                _ => return Err(ParseError::UnknownCommand),
+            }
+        }
+        "param" => match words.next().ok_or(ParseError::MissingArgument)? {
+            "get" => Command::GetParameter(parameter(&mut words)?),
+            "set" => Command::SetParameter(parameter(&mut words)?, number(&mut words)?),
+            "list" => Command::ListParameters,
+            _ => return Err(ParseError::UnknownCommand),
+        },
+        "config" => match words.next().ok_or(ParseError::MissingArgument)? {
+            "save" => Command::SaveConfig,
+            "load" => Command::LoadConfig,
+            "show" => Command::ShowConfig,
+            "reset" => Command::ResetConfig,
+            _ => return Err(ParseError::UnknownCommand),
+        },
+        "plot" => Command::Plot(plot::Selection::parse(&mut words).map_err(ParseError::Plot)?),
+        "estop" => match words.next().ok_or(ParseError::MissingArgument)? {
+            "reset" => Command::ResetEstop,
+            _ => return Err(ParseError::UnknownCommand),
+        },
+        "teach" => match words.next().ok_or(ParseError::MissingArgument)? {
+            "start" => Command::TeachStart,
+            "capture" => Command::TeachCapture(match words.next() {
+                None => teach::DEFAULT_TRANSITION,
+                Some(ms) => Duration::from_millis(ms.parse().map_err(|_| ParseError::NotANumber)?),
+            }),
+            "save" => Command::TeachSave,
+            "play" => Command::TeachPlay,
+            _ => return Err(ParseError::UnknownCommand),
+        },
+        "seq" => match words.next().ok_or(ParseError::MissingArgument)? {
+            "clear" => Command::SequenceClear,
+            "append" => Command::SequenceAppend(
+                sequence::Chunk::from_hex(words.next().ok_or(ParseError::MissingArgument)?)
+                    .ok_or(ParseError::NotANumber)?,
+            ),
+            "save" => Command::SequenceSave,
+            "run" => Command::SequenceRun,
+            "stop" => Command::SequenceStop,
+            _ => return Err(ParseError::UnknownCommand),
+        },
+        "faults" => Command::DumpFaults,
+        "dump" => Command::DumpLog,
+        "relax" => Command::Relax,
+        "freeze" => Command::Freeze,
+        "resume" => Command::Resume,
+        "status" => Command::Status,
+        "heartbeat" => Command::Heartbeat,
+        _ => return Err(ParseError::UnknownCommand),
+    };
+    match words.next() {
+        None => Ok(command),
+        Some(_) => Err(ParseError::TooManyArguments),
+    }
+}
+
+#[inline]
+fn number<'a, T: FromStr>(words: &mut impl Iterator<Item = &'a str>) -> Result<T, ParseError> {
+    words
+        .next()
+        .ok_or(ParseError::MissingArgument)?
+        .parse()
+        .map_err(|_| ParseError::NotANumber)
+}
+
+#[inline]
+fn parameter<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<Parameter, ParseError> {
+    Parameter::parse(words.next().ok_or(ParseError::MissingArgument)?)
+        .ok_or(ParseError::UnknownParameter)
+}
+
+/// Queue a command for the motion layer, waiting if the queue is full.
+#[inline]
+pub async fn send(command: Command) {
+    let () = failsafe::heard();
+    COMMANDS.send(command).await
+}
+
+/// Queue a command for the motion layer without waiting, handing it back if the queue is full.
+#[inline]
+pub fn try_send(command: Command) -> Result<(), Command> {
+    let () = failsafe::heard();
+    COMMANDS
+        .try_send(command)
+        .map_err(|TrySendError::Full(command)| command)
+}
+
+/// Queue a command from outside, if it passes `sanitize::check` and `source` isn't sending
+/// too fast, waiting if the queue is full. Every external command source goes through this
+/// (or `try_submit`) rather than `send`.
+#[inline]
+pub async fn submit(source: Source, command: Command) -> Result<(), Refused> {
+    let () = admit(source, &command)?;
+    let () = send(command).await;
+    Ok(())
+}
+
+/// `submit`, without waiting: a full queue hands back `Refused::QueueFull`.
+#[inline]
+pub fn try_submit(source: Source, command: Command) -> Result<(), Refused> {
+    let () = admit(source, &command)?;
+    try_send(command).map_err(|_| Refused::QueueFull)
+}
+
+/// `submit`, but held back until `at`. A time already past goes straight into the queue;
+/// one more than `sanitize::MAX_DELAY` away is refused as out of bounds.
+#[inline]
+pub async fn submit_at(source: Source, command: Command, at: Instant) -> Result<(), Refused> {
+    let now = Instant::now();
+    if at <= now {
+        return submit(source, command).await;
+    }
+    let () = admit(source, &command)?;
+    if at - now > sanitize::MAX_DELAY {
+        let () = telemetry::count(telemetry::ErrorKind::Command);
+        return Err(Refused::Invalid(sanitize::Invalid::OutOfBounds));
+    }
+    let () = failsafe::heard();
+    SCHEDULED.lock(|scheduled| {
+        scheduled
+            .borrow_mut()
+            .push((at, command))
+            .map_err(|_| Refused::ScheduleFull)
+    })
+}
+
+/// `submit` or `submit_at`, whichever `scheduled` calls for.
+#[inline]
+pub async fn submit_scheduled(source: Source, scheduled: Scheduled) -> Result<(), Refused> {
+    match scheduled.at {
+        None => submit(source, scheduled.command).await,
+        Some(at) => submit_at(source, scheduled.command, at).await,
+    }
+}
+
+/// Drop every command still waiting for its time.
+#[inline]
+pub fn cancel_scheduled() {
+    SCHEDULED.lock(|scheduled| scheduled.borrow_mut().clear())
+}
+
+#[inline]
+fn admit(source: Source, command: &Command) -> Result<(), Refused> {
+    let result = sanitize::check(command)
+        .map_err(Refused::Invalid)
+        .and_then(|()| {
+            if sanitize::take_token(source) {
+                Ok(())
+            } else {
+                Err(Refused::RateLimited)
+            }
+        });
+    if result.is_err() {
+        let () = telemetry::count(telemetry::ErrorKind::Command);
+    }
+    result
+}
+
+/// Take the next queued command, if there is one, without waiting.
+/// Scheduled commands that have come due go first, earliest first.
+#[inline]
+pub fn try_receive() -> Option<Command> {
+    let now = Instant::now();
+    let due = SCHEDULED.lock(|scheduled| {
+        let mut scheduled = scheduled.borrow_mut();
+        let (index, _) = scheduled
+            .iter()
+            .enumerate()
+            .filter(|(_, (at, _))| *at <= now)
+            .min_by_key(|(_, (at, _))| *at)?;
+        // Not `swap_remove`, so commands due at the same time keep their order:
+        Some(scheduled.remove(index).1)
+    });
+    due.or_else(|| COMMANDS.try_receive().ok())
+}
+
+/// Carries out commands on the body and gait without ever making the motion loop wait.
+/// Anything that takes longer than a tick (a move eased out under `MotionLimits`, a `Dwell`,
+/// a `TeachPlay`, anything that touches flash) is started by `execute` and carried on by
+/// `advance`, one tick at a time. Meant for the motion task, which owns the body and gait.
+#[derive(Debug)]
+pub struct Executor<const N: usize> {
+    /// The command still going, and how far it's got.
+    running: Option<(Command, Running<N>)>,
+}
+
+/// What an `Executor` carries on with between ticks.
+#[derive(Debug)]
+enum Running<const N: usize> {
+    /// A `FootPosition` or `MoveFoot` too far away to reach in one step.
+    Move { leg: usize, motion: leg::Move },
+    /// A `Dwell`, with this long left.
+    Dwell(Duration),
+    /// A `TeachPlay`.
+    Play(teach::Playback<N>),
+    /// Waiting on `run_flash_jobs` to answer through `FLASH_DONE`.
+    Flash,
+}
+
+impl<const N: usize> Default for Executor<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Executor<N> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { running: None }
+    }
+
+    /// Whether a command is still going. Hold every other command back until it's done,
+    /// so that commands (G-code especially) take effect in order.
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Whether a command has hold of the legs, which the gait should leave alone until it's done.
+    #[inline]
+    pub fn is_moving(&self) -> bool {
+        matches!(
+            self.running,
+            Some((_, Running::Move { .. } | Running::Play(_)))
+        )
+    }
+
+    /// Carry out a command, or start it. `None` if it's still going, in which case `advance`
+    /// reports how it went once it's done.
+    #[inline]
+    pub fn execute(
+        &mut self,
+        command: Command,
+        body: &mut Body<'_, N>,
+        gait: &mut Gait,
+    ) -> Option<Result<(), CouldntExecute>> {
+        match start(command, body, gait) {
+            Ok(Some(running)) => {
+                self.running = Some((command, running));
+                None
+            }
+            Ok(None) => Some(Ok(())),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Carry whatever `execute` started on by `elapsed`, handing back the command and how it
+    /// went once it's done. Call once per motion loop iteration. Moves and playback pause
+    /// while frozen, and while the body's sitting down.
+    #[inline]
+    pub fn advance(
+        &mut self,
+        body: &mut Body<'_, N>,
+        gait: &mut Gait,
+        elapsed: Duration,
+    ) -> Option<(Command, Result<(), CouldntExecute>)> {
+        let (_, running) = self.running.as_mut()?;
+        let result = match running {
+            Running::Move { .. } | Running::Play(_) if body.is_sitting_down() => return None,
+            Running::Move { leg, motion } => {
+                match body.legs_mut()[*leg].continue_move(motion, elapsed) {
+                    Ok(false) => return None,
+                    Ok(true) => Ok(()),
+                    Err(e) => Err(CouldntExecute::Ik(e)),
+                }
+            }
+            Running::Dwell(left) => match left.checked_sub(elapsed) {
+                Some(rest) if rest > Duration::from_ticks(0) => {
+                    *left = rest;
+                    return None;
+                }
+                _ => Ok(()),
+            },
+            Running::Play(playback) => {
+                let elapsed = if body::is_frozen() {
+                    Duration::from_ticks(0)
+                } else {
+                    elapsed
+                };
+                match playback.advance(elapsed) {
+                    None => Ok(()),
+                    Some(frame) => {
+                        let played = body
+                            .legs_mut()
+                            .iter_mut()
+                            .zip(frame.angles)
+                            .enumerate()
+                            .try_for_each(|(leg, (servos, angles))| {
+                                servos
+                                    .set_angles(angles)
+                                    .map_err(|error| teach::CouldntPlay { leg, error })
+                            });
+                        match played {
+                            Ok(()) => return None,
+                            Err(e) => Err(CouldntExecute::Teach(teach::CouldntTeach::Play(e))),
+                        }
+                    }
+                }
+            }
+            Running::Flash => match FLASH_DONE.try_take()? {
+                FlashDone::Saved(saved) => {
+                    let () = CONFIG.signal(saved.map_err(CouldntExecute::Save));
+                    Ok(())
+                }
+                FlashDone::Config(loaded) => {
+                    let () = CONFIG.signal(
+                        loaded
+                            .map_err(storage::CouldntRevert::Load)
+                            .and_then(|stored| storage::revert(&stored, body, gait))
+                            .map_err(CouldntExecute::Load),
+                    );
+                    Ok(())
+                }
+                FlashDone::Reset(reset) => {
+                    let () = CONFIG.signal(
+                        reset
+                            .and_then(|()| storage::apply_defaults(body, gait))
+                            .map_err(CouldntExecute::Reset),
+                    );
+                    Ok(())
+                }
+                FlashDone::Recording(Ok(recording)) => {
+                    *running = Running::Play(teach::Playback::new(recording, body.joint_angles()));
+                    return None;
+                }
+                FlashDone::Recording(Err(e)) => Err(CouldntExecute::Teach(e)),
+                FlashDone::Done(result) => result,
+            },
+        };
+        let (command, _) = self.running.take()?;
+        Some((command, result))
+    }
+}
+
+/// Carry out a command, or start it and return what's left for `Executor::advance`.
+#[inline]
+fn start<const N: usize>(
+    command: Command,
+    body: &mut Body<'_, N>,
+    gait: &mut Gait,
+) -> Result<Option<Running<N>>, CouldntExecute> {
+    Ok(match command {
+        Command::FootPosition { leg, position } => {
+            let leg_index = leg;
+            let leg = body
+                .legs_mut()
+                .get_mut(leg_index)
+                .ok_or(CouldntExecute::NoSuchLeg { index: leg_index })?;
+            let limits = leg.motion_limits();
+            leg.start_move(position, limits)
+                .map_err(CouldntExecute::Ik)?
+                .map(|motion| Running::Move {
+                    leg: leg_index,
+                    motion,
+                })
+        }
+        Command::JointAngle {
+            leg,
+            joint,
+            radians,
+        } => {
+            let () = body
+                .legs_mut()
+                .get_mut(leg)
+                .ok_or(CouldntExecute::NoSuchLeg { index: leg })?
+                .set_joint(joint, radians)
+                .map_err(CouldntExecute::Joint)?;
+            None
+        }
+        Command::JointAngles { leg, angles } => {
+            let () = body
+                .legs_mut()
+                .get_mut(leg)
+                .ok_or(CouldntExecute::NoSuchLeg { index: leg })?
+                .set_angles(angles)
+                .map_err(CouldntExecute::Joint)?;
+            None
+        }
+        Command::GaitVelocity { x, y, yaw_rate } => {
+            let () = gait.set_velocity(x, y, yaw_rate);
+            None
+        }
+        Command::BodyPose(body::Pose {
+            translation,
+            rotation,
+        }) => {
+            let () = body
+                .set_pose(translation, rotation)
+                .map_err(CouldntExecute::Pose)?;
+            None
+        }
+        Command::ServoPulse {
+            servo,
+            microseconds,
+        } => {
+            let joints = leg::Joint::ALL;
+            let () = body
+                .legs_mut()
+                .get_mut(servo / joints.len())
+                .ok_or(CouldntExecute::NoSuchLeg {
+                    index: servo / joints.len(),
+                })?
+                .set_pulse_width(joints[servo % joints.len()], microseconds)
+                .map_err(CouldntExecute::Joint)?;
+            None
+        }
+        Command::Relax => {
+            let () = body.relax().map_err(CouldntExecute::Relax)?;
+            None
+        }
+        Command::Freeze => {
+            let () = cancel_scheduled();
+            let () = sequence::stop();
+            let () = gait.set_velocity(0.0, 0.0, 0.0);
+            let () = body.freeze();
+            None
+        }
+        Command::Resume => {
+            if estop::is_latched() {
+                return Err(CouldntExecute::EstopLatched);
+            }
+            let () = body.resume();
+            None
+        }
+        Command::MoveFoot {
+            leg,
+            x,
+            y,
+            z,
+            speed,
+        } => {
+            let leg_index = leg;
+            let leg = body
+                .legs_mut()
+                .get_mut(leg_index)
+                .ok_or(CouldntExecute::NoSuchLeg { index: leg_index })?;
+            let from = leg.target();
+            let to = match (from, x, y, z) {
+                (_, Some(x), Some(y), Some(z)) => {
+                    ik::CartesianDisplacementFromEyeCenterLookingForward { x, y, z }
+                }
+                (Some(from), x, y, z) => ik::CartesianDisplacementFromEyeCenterLookingForward {
+                    x: x.unwrap_or(from.x),
+                    y: y.unwrap_or(from.y),
+                    z: z.unwrap_or(from.z),
+                },
+                (None, ..) => return Err(CouldntExecute::UnknownPosition { leg: leg_index }),
+            };
+            // A requested speed can only slow the move down, never push it past the leg's limits:
+            let limits = leg.motion_limits();
+            let limits = match speed {
+                Some(speed) if speed > 0.0 => MotionLimits {
+                    max_speed: speed.min(limits.max_speed),
+                    ..limits
+                },
+                _ => limits,
+            };
+            leg.start_move(to, limits)
+                .map_err(CouldntExecute::Ik)?
+                .map(|motion| Running::Move {
+                    leg: leg_index,
+                    motion,
+                })
+        }
+        Command::Dwell(duration) => Some(Running::Dwell(duration)),
+        Command::Attach => {
+            let () = body.attach().map_err(CouldntExecute::Attach)?;
+            None
+        }
+        Command::Status => {
+            let () = STATUS.signal(Status {
+                frozen: body::is_frozen(),
+                holding: body.foot_positions().is_some(),
+                pose: *body.pose(),
+                velocity: gait.velocity(),
+                phase: gait.phase(),
+                stalled: gait.is_stalled(),
+                legs: N,
+                unsaved: storage::has_unsaved_changes(),
+            });
+            None
+        }
+        Command::GetParameter(parameter) => {
+            let () = PARAMETER.signal(parameter.get(body, gait));
+            None
+        }
+        Command::SetParameter(parameter, value) => {
+            let result = parameter.set(value, body, gait);
+            if result.is_ok() {
+                let () = storage::mark_unsaved();
+            }
+            let () = PARAMETER.signal(result.and_then(|()| parameter.get(body, gait)));
+            None
+        }
+        Command::SaveConfig => {
+            let mut captured = storage::Config::default();
+            let () = captured.capture(body, gait);
+            hand_over(FlashJob::SaveConfig(captured))
+        }
+        Command::LoadConfig => hand_over(FlashJob::LoadConfig),
+        Command::ResetConfig => hand_over(FlashJob::ResetConfig),
+        Command::ResetEstop => {
+            let () = estop::reset().map_err(CouldntExecute::Estop)?;
+            None
+        }
+        Command::TeachStart => {
+            let () = teach::start(body).map_err(CouldntExecute::Teach)?;
+            None
+        }
+        Command::TeachCapture(after) => {
+            let () = teach::capture(body, after).map_err(CouldntExecute::Teach)?;
+            None
+        }
+        Command::TeachSave => hand_over(FlashJob::SaveTeach),
+        Command::TeachPlay => {
+            // The recording in RAM, or the one saved if nothing's been captured:
+            let recording = teach::taught();
+            if recording.is_empty() {
+                hand_over(FlashJob::LoadTeach)
+            } else {
+                Some(Running::Play(teach::Playback::new(
+                    recording,
+                    body.joint_angles(),
+                )))
+            }
+        }
+        Command::SequenceClear => {
+            let () = sequence::clear();
+            None
+        }
+        Command::SequenceAppend(chunk) => {
+            let () = sequence::append(&chunk).map_err(CouldntExecute::Sequence)?;
+            None
+        }
+        Command::SequenceSave => hand_over(FlashJob::SaveSequence),
+        Command::SequenceRun => hand_over(FlashJob::StartSequence),
+        Command::SequenceStop => {
+            let () = sequence::stop();
+            None
+        }
+        Command::ListParameters
+        | Command::ShowConfig
+        | Command::DumpFaults
+        | Command::DumpLog
+        | Command::Plot(_)
+        | Command::Heartbeat => None,
+    })
+}
+
+/// Give `run_flash_jobs` something to do, for the motion task to wait on without waiting.
+#[inline]
+fn hand_over<const N: usize>(job: FlashJob) -> Option<Running<N>> {
+    let () = FLASH_DONE.reset();
+    let () = FLASH_JOB.signal(job);
+    Some(Running::Flash)
+}
+
+/// Do whatever flash work `Executor` hands over, forever. Run on core 0, since flash can't be
+/// written from the motion core, by `multicore::spawn_motion`, so that it's never left out.
+#[inline]
+pub(crate) async fn run_flash_jobs() -> ! {
+    loop {
+        let done = match FLASH_JOB.wait().await {
+            FlashJob::SaveConfig(captured) => FlashDone::Saved(storage::commit(&captured).await),
+            FlashJob::LoadConfig => FlashDone::Config(storage::load().await),
+            FlashJob::ResetConfig => FlashDone::Reset(storage::reset().await),
+            FlashJob::SaveTeach => {
+                FlashDone::Done(teach::save().await.map_err(CouldntExecute::Teach))
+            }
+            FlashJob::LoadTeach => FlashDone::Recording(teach::load().await),
+            FlashJob::SaveSequence => {
+                FlashDone::Done(sequence::save().await.map_err(CouldntExecute::Sequence))
+            }
+            FlashJob::StartSequence => {
+                FlashDone::Done(sequence::start().await.map_err(CouldntExecute::Sequence))
+            }
+        };
+        let () = FLASH_DONE.signal(done);
+    }
+}
+
+/// Send a `GetParameter` or `SetParameter` to the motion task and wait for the value
+/// it ends up with. `None` if it doesn't answer within `STATUS_TIMEOUT`.
+#[inline]
+pub async fn query_parameter(command: Command) -> Option<Result<f32, param::CouldntAccess>> {
+    let () = PARAMETER.reset();
+    let () = send(command).await;
+    with_timeout(STATUS_TIMEOUT, PARAMETER.wait()).await.ok()
+}
+
+/// Send a `SaveConfig`, `LoadConfig` or `ResetConfig` to the motion task and wait for it to finish.
+/// `None` if it doesn't answer within `CONFIG_TIMEOUT`.
+#[inline]
+pub async fn query_config(command: Command) -> Option<Result<(), CouldntExecute>> {
+    let () = CONFIG.reset();
+    let () = send(command).await;
+    with_timeout(CONFIG_TIMEOUT, CONFIG.wait()).await.ok()
+}
+
+/// Ask the motion task for a `Status` and wait for it.
+/// `None` if it doesn't answer within `STATUS_TIMEOUT` (e.g. nothing is running an `Executor`).
+#[inline]
+pub async fn query_status() -> Option<Status> {
+    let () = STATUS.reset();
+    let () = send(Command::Status).await;
+    with_timeout(STATUS_TIMEOUT, STATUS.wait()).await.ok()
+}
+
+/// Read newline-terminated commands from UART1 forever, queueing each one that parses.
+/// Lines that don't parse (or don't fit in `MAX_LINE_LENGTH`) are logged and dropped.
+#[inline]
+pub async fn read_uart<T: uart::Instance>(mut rx: UartRx<'static, T, Async>) -> ! {
+    let mut lines = Lines::default();
+    loop {
+        let mut byte = [0];
+        if let Err(e) = rx.read(&mut byte).await {
+            let () = crate::warn!("UART read error: {e:?}");
+            lines = Lines::default();
+            continue;
+        }
+        match lines.push(byte[0]) {
+            None => {}
+            Some(Err(LineTooLong)) => {
+                let () = crate::warn!("Dropped a command longer than {MAX_LINE_LENGTH} bytes");
+            }
+            Some(Ok(line)) => match parse_scheduled_bytes(line) {
+                Ok(scheduled) => {
+                    if let Err(e) = submit_scheduled(Source::Uart, scheduled).await {
+                        let () = crate::warn!("Refused a UART command: {e:?}");
+                    }
+                }
+                Err(e) => {
+                    let () = telemetry::count(telemetry::ErrorKind::Command);
+                    let () = crate::warn!("Couldn't parse a UART command: {e:?}");
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub struct LineTooLong;
+
+/// Splits a byte stream into non-empty lines, ending at either `\r` or `\n`.
+#[derive(Debug, Default)]
+pub struct Lines {
+    line: heapless::Vec<u8, MAX_LINE_LENGTH>,
+    overflowed: bool,
+    /// The last `push` handed out `line`, so the next one starts a new line.
+    complete: bool,
+}
+
+impl Lines {
+    /// Feed one byte, getting a line back (without its ending) whenever one completes.
+    /// A line that overflows `MAX_LINE_LENGTH` is reported once, at its end, then dropped.
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Option<Result<&[u8], LineTooLong>> {
+        if core::mem::take(&mut self.complete) {
+            let () = self.line.clear();
+        }
+        match byte {
+            b'\r' | b'\n' => {
+                if core::mem::take(&mut self.overflowed) {
+                    let () = self.line.clear();
+                    return Some(Err(LineTooLong));
+                }
+                if self.line.is_empty() {
+                    return None;
+                }
+                self.complete = true;
+                Some(Ok(&self.line))
+            }
+            b => {
+                if self.line.push(b).is_err() {
+                    self.overflowed = true;
+                }
+                None
+            }
+        }
+    }
+}
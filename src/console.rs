@@ -0,0 +1,505 @@
+use {
+    crate::{
+        command::{self, Command, LineTooLong, Scheduled},
+        failsafe, fault, hil, journal, mavlink, param, plot, rc,
+        sanitize::Source,
+        stats, storage, telemetry,
+    },
+    core::fmt::Write as _,
+    embassy_futures::{
+        join::join5,
+        select::{Either, select},
+    },
+    embassy_rp::{peripherals::USB, usb::Driver},
+    embassy_time::{Instant, Ticker, Timer},
+    embassy_usb::{
+        Builder, Config,
+        class::cdc_acm::{CdcAcmClass, State},
+        driver::EndpointError,
+    },
+    static_cell::StaticCell,
+};
+
+/// Largest USB full-speed bulk packet.
+const MAX_PACKET_SIZE: u16 = 64;
+
+/// Bring up USB as four serial ports: the first carries `log` output as before,
+/// the second is an interactive console taking `command::parse_scheduled` syntax
+/// (and `hil` scripts), the third streams binary `telemetry` frames, and the fourth
+/// speaks `mavlink` to a ground station.
+/// Without the `log-usb` feature, the log port (and its 1024-byte buffer) is left out,
+/// so the console is the first of three.
+/// Replaces `embassy_usb_logger::run!`, so call it from the USB task instead.
+/// Only call this once: it claims static buffers.
+#[inline]
+pub async fn run(driver: Driver<'static, USB>) -> ! {
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 512]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUFFER: StaticCell<[u8; 64]> = StaticCell::new();
+    #[cfg(feature = "log-usb")]
+    static LOGGER_STATE: StaticCell<State> = StaticCell::new();
+    static CONSOLE_STATE: StaticCell<State> = StaticCell::new();
+    static TELEMETRY_STATE: StaticCell<State> = StaticCell::new();
+    static GROUND_STATION_STATE: StaticCell<State> = StaticCell::new();
+
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("wrsturgeon");
+    config.product = Some("eye-bot");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        CONFIG_DESCRIPTOR.init([0; 512]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [],
+        CONTROL_BUFFER.init([0; 64]),
+    );
+    #[cfg(feature = "log-usb")]
+    let logger = CdcAcmClass::new(
+        &mut builder,
+        LOGGER_STATE.init(State::new()),
+        MAX_PACKET_SIZE,
+    );
+    let console = CdcAcmClass::new(
+        &mut builder,
+        CONSOLE_STATE.init(State::new()),
+        MAX_PACKET_SIZE,
+    );
+    let telemetry = CdcAcmClass::new(
+        &mut builder,
+        TELEMETRY_STATE.init(State::new()),
+        MAX_PACKET_SIZE,
+    );
+    let ground_station = CdcAcmClass::new(
+        &mut builder,
+        GROUND_STATION_STATE.init(State::new()),
+        MAX_PACKET_SIZE,
+    );
+    let mut usb = builder.build();
+
+    #[cfg(feature = "log-usb")]
+    let log = embassy_usb_logger::with_class!(1024, log::LevelFilter::Info, logger);
+    #[cfg(not(feature = "log-usb"))]
+    let log = core::future::pending::<()>();
+
+    let (never, _, _, _, _) = join5(
+        usb.run(),
+        log,
+        serve(console),
+        stream(telemetry),
+        ground_station_link(ground_station),
+    )
+    .await;
+    never
+}
+
+/// Reconnect forever, answering one line at a time.
+#[inline]
+async fn serve(mut class: CdcAcmClass<'static, Driver<'static, USB>>) -> ! {
+    loop {
+        let () = class.wait_connection().await;
+        let () = crate::info!("Console connected");
+        if let Err(EndpointError::Disabled) = session(&mut class).await {
+            let () = crate::info!("Console disconnected");
+        }
+    }
+}
+
+/// Forward telemetry frames whenever a host is listening.
+/// Frames queued while nobody's connected pile up until they start getting dropped.
+#[inline]
+async fn stream(mut class: CdcAcmClass<'static, Driver<'static, USB>>) -> ! {
+    let mut buffer = [0; MAX_PACKET_SIZE as usize];
+    loop {
+        let () = class.wait_connection().await;
+        loop {
+            let n = telemetry::read(&mut buffer).await;
+            if let Err(EndpointError::Disabled) = class.write_packet(&buffer[..n]).await {
+                break;
+            }
+        }
+    }
+}
+
+/// Send a heartbeat (and every `mavlink::report`ed value) each `HEARTBEAT_PERIOD`,
+/// and drive the robot from MANUAL_CONTROL through the default RC mapping.
+#[inline]
+async fn ground_station_link(mut class: CdcAcmClass<'static, Driver<'static, USB>>) -> ! {
+    let mut encoder = mavlink::Encoder::default();
+    let mut burst = heapless::Vec::<u8, { mavlink::PERIODIC_CAPACITY }>::new();
+    let mut packet = [0; MAX_PACKET_SIZE as usize];
+    loop {
+        let () = class.wait_connection().await;
+        let () = crate::info!("Ground station connected");
+        let mut decoder = mavlink::Decoder::default();
+        let mut next_heartbeat = Instant::now();
+        loop {
+            match select(class.read_packet(&mut packet), Timer::at(next_heartbeat)).await {
+                Either::First(Err(EndpointError::Disabled)) => break,
+                Either::First(Err(EndpointError::BufferOverflow)) => {}
+                Either::First(Ok(n)) => {
+                    for &byte in &packet[..n] {
+                        match decoder.push(byte) {
+                            None => {}
+                            Some(mavlink::Message::Heartbeat { .. }) => failsafe::heard(),
+                            Some(message) => {
+                                if let Some(channels) = message.channels() {
+                                    let () = rc::publish(&rc::Mapping::DEFAULT, &channels);
+                                }
+                            }
+                        }
+                    }
+                }
+                Either::Second(()) => {
+                    next_heartbeat += mavlink::HEARTBEAT_PERIOD;
+                    let () = encoder.periodic(&mut burst);
+                    if let Err(EndpointError::Disabled) = write(&mut class, &burst).await {
+                        break;
+                    }
+                }
+            }
+        }
+        let () = crate::info!("Ground station disconnected");
+    }
+}
+
+#[inline]
+async fn session(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+) -> Result<(), EndpointError> {
+    let mut line = heapless::Vec::<u8, { command::MAX_LINE_LENGTH }>::new();
+    let mut overflowed = false;
+    // Between `test begin` and `test end`, lines are recorded here instead of answered:
+    let mut script: Option<hil::Script> = None;
+    let mut packet = [0; MAX_PACKET_SIZE as usize];
+    let () = write(class, b"> ").await?;
+    loop {
+        let n = class.read_packet(&mut packet).await?;
+        for &byte in &packet[..n] {
+            match byte {
+                b'\r' | b'\n' => {
+                    let () = write(class, b"\r\n").await?;
+                    if let Some(ref mut recording) = script {
+                        match line.as_slice() {
+                            b"test end" => {
+                                let () = run_script(class, recording).await?;
+                                script = None;
+                            }
+                            b"test abort" => {
+                                let () = write(class, b"discarded\r\n").await?;
+                                script = None;
+                            }
+                            _ => {
+                                let line = if overflowed {
+                                    Err(LineTooLong)
+                                } else {
+                                    Ok(&*line)
+                                };
+                                if let Err(e) = recording.push(line) {
+                                    let mut reply = heapless::String::<128>::new();
+                                    let _: core::fmt::Result = write!(reply, "error: {e:?}\r\n");
+                                    let () = write(class, reply.as_bytes()).await?;
+                                }
+                            }
+                        }
+                    } else if overflowed {
+                        let () = write(class, b"error: line too long\r\n").await?;
+                    } else if line.as_slice() == b"test begin" {
+                        let () = write(class, b"recording; `test end` runs it\r\n").await?;
+                        script = Some(hil::Script::new());
+                    } else if !line.is_empty() {
+                        let () = respond(class, &line).await?;
+                    }
+                    let () = line.clear();
+                    overflowed = false;
+                    let prompt: &[u8] = if script.is_some() { b"test> " } else { b"> " };
+                    let () = write(class, prompt).await?;
+                }
+                // Backspace or delete:
+                0x08 | 0x7f => {
+                    if line.pop().is_some() {
+                        let () = write(class, b"\x08 \x08").await?;
+                    }
+                }
+                b => {
+                    if line.push(b).is_err() {
+                        overflowed = true;
+                    }
+                    // Echo, since most terminals don't:
+                    let () = write(class, &[b]).await?;
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+async fn respond(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    line: &[u8],
+) -> Result<(), EndpointError> {
+    let mut reply = heapless::String::<512>::new();
+    let parsed = match command::parse_scheduled_bytes(line) {
+        Ok(Scheduled {
+            command,
+            at: Some(at),
+        }) => {
+            let _: core::fmt::Result = match command::submit_at(Source::Usb, command, at).await {
+                Ok(()) => write!(reply, "ok\r\n"),
+                Err(e) => write!(reply, "error: {e:?}\r\n"),
+            };
+            return write(class, reply.as_bytes()).await;
+        }
+        parsed => parsed.map(|scheduled| scheduled.command),
+    };
+    let _: core::fmt::Result = match parsed {
+        Err(e) => {
+            let () = telemetry::count(telemetry::ErrorKind::Command);
+            write!(reply, "error: {e:?}\r\n")
+        }
+        Ok(Command::Status) => match command::query_status().await {
+            None => write!(reply, "error: no answer from the motion task\r\n"),
+            Some(status) => {
+                // Two lines, since both together won't always fit in `reply`:
+                let _: core::fmt::Result = write!(reply, "{status:?}\r\n");
+                let () = write(class, reply.as_bytes()).await?;
+                let () = reply.clear();
+                write!(reply, "{}\r\n", stats::snapshot())
+            }
+        },
+        Ok(command @ (Command::GetParameter(_) | Command::SetParameter(..))) => {
+            match command::query_parameter(command).await {
+                None => write!(reply, "error: no answer from the motion task\r\n"),
+                Some(Err(e)) => write!(reply, "error: {e:?}\r\n"),
+                Some(Ok(value)) => write!(reply, "{value}\r\n"),
+            }
+        }
+        Ok(Command::Plot(selection)) => {
+            let () = plot(class, &selection).await?;
+            Ok(())
+        }
+        Ok(Command::ListParameters) => match command::query_status().await {
+            None => write!(reply, "error: no answer from the motion task\r\n"),
+            Some(status) => {
+                // One line each, since every leg's parameters together won't fit in `reply`:
+                for parameter in param::all(status.legs) {
+                    let mut line = heapless::String::<32>::new();
+                    let _: core::fmt::Result = write!(line, "{parameter}\r\n");
+                    let () = write(class, line.as_bytes()).await?;
+                }
+                Ok(())
+            }
+        },
+        Ok(command @ (Command::SaveConfig | Command::LoadConfig | Command::ResetConfig)) => {
+            match command::query_config(command).await {
+                None => write!(reply, "error: no answer from the motion task\r\n"),
+                Some(Err(e)) => write!(reply, "error: {e:?}\r\n"),
+                Some(Ok(())) => write!(reply, "ok\r\n"),
+            }
+        }
+        Ok(Command::DumpFaults) => {
+            let () = dump_faults(class).await?;
+            Ok(())
+        }
+        Ok(Command::DumpLog) => {
+            let () = dump_log(class).await?;
+            Ok(())
+        }
+        Ok(Command::ShowConfig) => {
+            let () = show_config(class).await?;
+            Ok(())
+        }
+        Ok(command) => match command::submit(Source::Usb, command).await {
+            Ok(()) => write!(reply, "ok\r\n"),
+            Err(e) => write!(reply, "error: {e:?}\r\n"),
+        },
+    };
+    write(class, reply.as_bytes()).await
+}
+
+/// Run a recorded `hil` script step by step, printing how each expectation went and then
+/// the overall result.
+#[inline]
+async fn run_script(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    script: &hil::Script,
+) -> Result<(), EndpointError> {
+    let mut line = heapless::String::<160>::new();
+    let steps = match script.steps() {
+        Ok(steps) => steps,
+        Err(number) => {
+            let _: core::fmt::Result = write!(line, "result: error (line {number})\r\n");
+            return write(class, line.as_bytes()).await;
+        }
+    };
+    let (mut expected, mut passed) = (0_usize, 0_usize);
+    for &(number, ref step) in steps {
+        let () = line.clear();
+        let _: core::fmt::Result = match (hil::run(step).await, step) {
+            (hil::Outcome::Done, _) => continue,
+            (hil::Outcome::Passed { actual }, hil::Step::Expect(expectation)) => {
+                expected += 1;
+                passed += 1;
+                write!(line, "pass line {number}: {expectation} (got {actual})\r\n")
+            }
+            (hil::Outcome::Failed { actual }, hil::Step::Expect(expectation)) => {
+                expected += 1;
+                write!(line, "FAIL line {number}: {expectation} (got {actual})\r\n")
+            }
+            (hil::Outcome::Refused(e), _) => {
+                let _: core::fmt::Result =
+                    write!(line, "result: fail (line {number} refused: {e:?})\r\n");
+                return write(class, line.as_bytes()).await;
+            }
+            // Only expectations pass or fail:
+            (hil::Outcome::Passed { .. } | hil::Outcome::Failed { .. }, _) => continue,
+        };
+        let () = write(class, line.as_bytes()).await?;
+    }
+    let () = line.clear();
+    let verdict = if passed == expected { "pass" } else { "fail" };
+    let _: core::fmt::Result = write!(
+        line,
+        "result: {verdict} ({passed} of {expected} expectations held)\r\n"
+    );
+    write(class, line.as_bytes()).await
+}
+
+/// Print every stored parameter next to its live value, starring those that differ
+/// (i.e. that `config save` would change), then the eye calibration.
+#[inline]
+async fn show_config(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+) -> Result<(), EndpointError> {
+    let mut line = heapless::String::<160>::new();
+    let config = match storage::load().await {
+        Ok(config) => config,
+        Err(e) => {
+            let _: core::fmt::Result = write!(line, "nothing stored ({e:?}); defaults:\r\n");
+            let () = write(class, line.as_bytes()).await?;
+            storage::Config::default()
+        }
+    };
+    let Some(status) = command::query_status().await else {
+        return write(class, b"error: no answer from the motion task\r\n").await;
+    };
+    for parameter in param::all(status.legs) {
+        let stored = config.parameter(parameter).unwrap_or(f32::NAN);
+        let live = command::query_parameter(Command::GetParameter(parameter)).await;
+        let () = line.clear();
+        let _: core::fmt::Result = match live {
+            Some(Ok(live)) if live != stored && !(live.is_nan() && stored.is_nan()) => {
+                write!(line, "* {parameter} {stored} (live {live})\r\n")
+            }
+            Some(Ok(_)) => write!(line, "  {parameter} {stored}\r\n"),
+            Some(Err(e)) => write!(line, "  {parameter} {stored} (live: {e:?})\r\n"),
+            None => write!(line, "  {parameter} {stored} (live: no answer)\r\n"),
+        };
+        let () = write(class, line.as_bytes()).await?;
+    }
+    for (name, axis) in [("pan", &config.pan), ("tilt", &config.tilt)] {
+        let () = line.clear();
+        let _: core::fmt::Result = write!(line, "  {name} {axis:?}\r\n");
+        let () = write(class, line.as_bytes()).await?;
+    }
+    let () = line.clear();
+    let _: core::fmt::Result = write!(line, "  iris {:?}\r\n", config.iris);
+    let () = write(class, line.as_bytes()).await?;
+    let () = line.clear();
+    let _: core::fmt::Result = write!(line, "  eyelid {:?}\r\n", config.eyelid);
+    write(class, line.as_bytes()).await
+}
+
+/// Print every fault in the log, oldest first, one per line.
+#[inline]
+async fn dump_faults(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+) -> Result<(), EndpointError> {
+    let mut after = None;
+    let mut line = heapless::String::<{ 64 + fault::MAX_MESSAGE_LENGTH }>::new();
+    loop {
+        let () = line.clear();
+        let _: core::fmt::Result = match fault::read_after(after).await {
+            Err(e) => {
+                let _: core::fmt::Result = write!(line, "error: {e:?}\r\n");
+                return write(class, line.as_bytes()).await;
+            }
+            Ok(None) => {
+                if after.is_none() {
+                    let () = write(class, b"no faults logged\r\n").await?;
+                }
+                return Ok(());
+            }
+            Ok(Some(record)) => {
+                after = Some(record.sequence);
+                write!(line, "{record}\r\n")
+            }
+        };
+        let () = write(class, line.as_bytes()).await?;
+    }
+}
+
+/// Print every line in `journal`'s ring, oldest first, noting any gap where the ring
+/// overwrote lines (or new ones came in faster than they went out).
+#[inline]
+async fn dump_log(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+) -> Result<(), EndpointError> {
+    let mut after = None;
+    let mut line = heapless::String::<{ 64 + journal::MAX_LINE_LENGTH }>::new();
+    while let Some(logged) = journal::read_after(after) {
+        let () = line.clear();
+        let expected = after.map_or(0, |after: u32| after.wrapping_add(1));
+        if logged.sequence != expected {
+            let lost = logged.sequence.wrapping_sub(expected);
+            let _: core::fmt::Result = write!(line, "({lost} lines overwritten)\r\n");
+            let () = write(class, line.as_bytes()).await?;
+            let () = line.clear();
+        }
+        after = Some(logged.sequence);
+        let _: core::fmt::Result = write!(line, "{logged}\r\n");
+        let () = write(class, line.as_bytes()).await?;
+    }
+    if after.is_none() {
+        let () = write(class, b"nothing logged\r\n").await?;
+    }
+    Ok(())
+}
+
+/// Stream CSV lines every `plot::PERIOD` until the host sends anything at all.
+#[inline]
+async fn plot(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    selection: &plot::Selection,
+) -> Result<(), EndpointError> {
+    let () = write(class, selection.header().as_bytes()).await?;
+    let mut ticker = Ticker::every(plot::PERIOD);
+    let mut packet = [0; MAX_PACKET_SIZE as usize];
+    loop {
+        match select(class.read_packet(&mut packet), ticker.next()).await {
+            Either::First(read) => return read.map(|_| ()),
+            Either::Second(()) => {
+                if let Some(sample) = telemetry::latest() {
+                    let () = write(class, selection.line(&sample).as_bytes()).await?;
+                }
+            }
+        }
+    }
+}
+
+/// Split into packets, ending with a short one so the host doesn't wait for more.
+#[inline]
+async fn write(
+    class: &mut CdcAcmClass<'static, Driver<'static, USB>>,
+    bytes: &[u8],
+) -> Result<(), EndpointError> {
+    for chunk in bytes.chunks(MAX_PACKET_SIZE as usize) {
+        let () = class.write_packet(chunk).await?;
+    }
+    if bytes.len() % (MAX_PACKET_SIZE as usize) == 0 {
+        let () = class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
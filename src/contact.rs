@@ -0,0 +1,115 @@
+use {
+    crate::gait::{Gait, N_LEGS},
+    core::sync::atomic::{AtomicU32, Ordering},
+    embassy_rp::gpio::Input,
+    embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel},
+    embassy_time::{Duration, Instant, Timer},
+};
+
+// One switch per foot, closing to ground when the foot's on something (so pull the pin up).
+// Each switch's task waits for an edge, then for the switch to settle, and only reports a
+// change if the settled level differs from the last one it reported. Events carry the time
+// of the first edge, not of the report, so bounce doesn't skew them.
+
+/// Default time a switch must hold a new level before it counts.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(5);
+
+/// Events not yet handed to the gait. Older ones are dropped if `apply` falls behind.
+const QUEUE_DEPTH: usize = 16;
+
+static EVENTS: Channel<CriticalSectionRawMutex, Event, QUEUE_DEPTH> = Channel::new();
+
+/// Liftoffs seen while the gait had that foot planted, i.e. probable slips.
+static SLIPS: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Touchdown,
+    Liftoff,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Event {
+    pub leg: usize,
+    pub edge: Edge,
+    /// When the switch first changed, before debouncing.
+    pub timestamp: Instant,
+}
+
+/// Turns raw edges into debounced touchdowns and liftoffs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Debouncer {
+    /// The last level reported, if any.
+    stable: Option<bool>,
+}
+
+impl Debouncer {
+    /// Report the level a switch settled at after an edge at `edge_at`,
+    /// getting an event back if it's actually changed.
+    #[inline]
+    pub fn settle(&mut self, leg: usize, in_contact: bool, edge_at: Instant) -> Option<Event> {
+        if self.stable.replace(in_contact) == Some(in_contact) {
+            return None;
+        }
+        Some(Event {
+            leg,
+            edge: if in_contact {
+                Edge::Touchdown
+            } else {
+                Edge::Liftoff
+            },
+            timestamp: edge_at,
+        })
+    }
+}
+
+/// Watch one foot's switch forever (active low), queueing an event for every debounced change.
+/// Run one of these per leg.
+#[inline]
+pub async fn watch(leg: usize, mut pin: Input<'static>, debounce: Duration) -> ! {
+    let mut debouncer = Debouncer::default();
+    // Report the starting level, so the gait knows this leg has a sensor at all:
+    let _: Option<()> = debouncer
+        .settle(leg, pin.is_low(), Instant::now())
+        .map(queue);
+    loop {
+        let () = pin.wait_for_any_edge().await;
+        let edge_at = Instant::now();
+        let () = Timer::after(debounce).await;
+        if let Some(event) = debouncer.settle(leg, pin.is_low(), edge_at) {
+            let () = queue(event);
+        }
+    }
+}
+
+#[inline]
+fn queue(event: Event) {
+    if EVENTS.try_send(event).is_err() {
+        // Full: drop the oldest, since the newest says more about where the foot is now.
+        let _: Option<Event> = EVENTS.try_receive().ok();
+        let _: Result<(), _> = EVENTS.try_send(event);
+    }
+}
+
+/// Hand every queued event to the gait. Call once per motion loop iteration, before
+/// `Gait::advance`. Legs past `gait::N_LEGS` are ignored.
+#[inline]
+pub fn apply(gait: &mut Gait) {
+    while let Ok(event) = EVENTS.try_receive() {
+        if event.leg >= N_LEGS {
+            continue;
+        }
+        let touchdown = event.edge == Edge::Touchdown;
+        if !touchdown && !gait.is_swinging(event.leg) {
+            let _: u32 = SLIPS.fetch_add(1, Ordering::Relaxed);
+            let () = crate::warn!("Leg {} lost contact mid-stance: slipping?", event.leg);
+        }
+        let () = gait.report_contact(event.leg, touchdown);
+    }
+}
+
+/// How many times a planted foot has lost contact since boot.
+#[inline]
+pub fn slips() -> u32 {
+    SLIPS.load(Ordering::Relaxed)
+}
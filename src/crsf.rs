@@ -0,0 +1,294 @@
+use {
+    crate::rc,
+    core::cell::Cell,
+    embassy_futures::join::join,
+    embassy_rp::uart::{self, Async, Uart, UartRx, UartTx},
+    embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    embassy_time::{Duration, Ticker},
+};
+
+// CRSF frames are:
+//
+//     address | length | type | payload ... | crc8
+//
+// where `length` counts `type`, `payload`, and `crc8`, and `crc8` (polynomial 0xD5)
+// covers `type` and `payload`. Multi-byte payload fields are big-endian.
+
+/// Address of the flight controller, i.e. us; also the sync byte receivers use toward us.
+const ADDRESS_FLIGHT_CONTROLLER: u8 = 0xC8;
+/// Longest frame the protocol allows, address and length included.
+const MAX_FRAME_LENGTH: usize = 64;
+
+const TYPE_BATTERY_SENSOR: u8 = 0x08;
+const TYPE_LINK_STATISTICS: u8 = 0x14;
+const TYPE_RC_CHANNELS_PACKED: u8 = 0x16;
+const TYPE_FLIGHT_MODE: u8 = 0x21;
+
+/// How often to send battery and mode telemetry back toward the transmitter.
+pub const TELEMETRY_PERIOD: Duration = Duration::from_millis(200);
+
+/// Longest mode name the transmitter will display.
+const MAX_MODE_LENGTH: usize = 15;
+
+/// What to report back to the transmitter, set by whoever knows.
+static TELEMETRY: Mutex<CriticalSectionRawMutex, Cell<Telemetry>> =
+    Mutex::new(Cell::new(Telemetry {
+        battery_volts: None,
+        mode: "",
+    }));
+
+/// Latest link statistics from the receiver.
+static LINK_STATISTICS: Mutex<CriticalSectionRawMutex, Cell<Option<LinkStatistics>>> =
+    Mutex::new(Cell::new(None));
+
+/// 420 000 baud, 8N1, not inverted: ExpressLRS's default.
+#[inline]
+pub fn config() -> uart::Config {
+    let mut config = uart::Config::default();
+    config.baudrate = 420_000;
+    config
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Telemetry {
+    battery_volts: Option<f32>,
+    mode: &'static str,
+}
+
+/// Report battery voltage to the transmitter from now on.
+#[inline]
+pub fn set_battery_volts(volts: f32) {
+    TELEMETRY.lock(|telemetry| {
+        telemetry.set(Telemetry {
+            battery_volts: Some(volts),
+            ..telemetry.get()
+        })
+    })
+}
+
+/// Report a mode name (e.g. "WALK" or "SIT") to the transmitter from now on.
+/// Only the first 15 bytes fit.
+#[inline]
+pub fn set_mode(mode: &'static str) {
+    TELEMETRY.lock(|telemetry| {
+        telemetry.set(Telemetry {
+            mode,
+            ..telemetry.get()
+        })
+    })
+}
+
+/// Radio link quality, as the receiver reports it.
+#[derive(Clone, Copy, Debug)]
+pub struct LinkStatistics {
+    /// dBm, from each of the receiver's antennas. Less negative is better.
+    pub uplink_rssi: [i16; 2],
+    /// Percentage of packets received from the transmitter.
+    pub uplink_link_quality: u8,
+    /// dB.
+    pub uplink_snr: i8,
+    pub active_antenna: u8,
+    pub rf_mode: u8,
+    pub uplink_tx_power: u8,
+    /// dBm, at the transmitter.
+    pub downlink_rssi: i16,
+    pub downlink_link_quality: u8,
+    pub downlink_snr: i8,
+}
+
+#[inline]
+pub fn link_statistics() -> Option<LinkStatistics> {
+    LINK_STATISTICS.lock(Cell::get)
+}
+
+/// One decoded CRSF frame we care about.
+#[derive(Clone, Copy, Debug)]
+pub enum Frame {
+    /// Raw 11-bit values, as in SBUS.
+    Channels([u16; 16]),
+    LinkStatistics(LinkStatistics),
+}
+
+/// Reassembles frames from a byte stream, resynchronizing on its own after garbage.
+#[derive(Debug)]
+pub struct Decoder {
+    buffer: [u8; MAX_FRAME_LENGTH],
+    length: usize,
+}
+
+impl Default for Decoder {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            buffer: [0; MAX_FRAME_LENGTH],
+            length: 0,
+        }
+    }
+}
+
+impl Decoder {
+    /// Feed one byte, getting a frame back whenever one we understand completes.
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Option<Frame> {
+        if self.length == 0 && byte != ADDRESS_FLIGHT_CONTROLLER {
+            return None;
+        }
+        if self.length == 1 && !(2..=(MAX_FRAME_LENGTH as u8 - 2)).contains(&byte) {
+            self.length = 0;
+            return None;
+        }
+        self.buffer[self.length] = byte;
+        self.length += 1;
+        if self.length < 2 || self.length < 2 + self.buffer[1] as usize {
+            return None;
+        }
+
+        let frame = &self.buffer[2..self.length];
+        self.length = 0;
+        let (body, &[crc]) = frame.split_at(frame.len() - 1) else {
+            return None;
+        };
+        if crc8(body) != crc {
+            return None;
+        }
+        let (&frame_type, payload) = body.split_first()?;
+        match frame_type {
+            TYPE_RC_CHANNELS_PACKED => {
+                let payload: &[u8; 22] = payload.try_into().ok()?;
+                Some(Frame::Channels(rc::unpack_11_bit(payload)))
+            }
+            TYPE_LINK_STATISTICS => {
+                let &[
+                    rssi_1,
+                    rssi_2,
+                    lq,
+                    snr,
+                    antenna,
+                    rf_mode,
+                    power,
+                    down_rssi,
+                    down_lq,
+                    down_snr,
+                ] = payload
+                else {
+                    return None;
+                };
+                // RSSI goes over the air as a positive number of negative dBm:
+                Some(Frame::LinkStatistics(LinkStatistics {
+                    uplink_rssi: [-(rssi_1 as i16), -(rssi_2 as i16)],
+                    uplink_link_quality: lq,
+                    uplink_snr: snr as i8,
+                    active_antenna: antenna,
+                    rf_mode,
+                    uplink_tx_power: power,
+                    downlink_rssi: -(down_rssi as i16),
+                    downlink_link_quality: down_lq,
+                    downlink_snr: down_snr as i8,
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// CRC-8 with polynomial 0xD5 (DVB-S2), as CRSF uses.
+#[inline]
+pub fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for &byte in bytes {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0xD5
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Wrap `payload` into a complete frame of type `frame_type`, returning its length.
+#[inline]
+fn encode(frame_type: u8, payload: &[u8], out: &mut [u8; MAX_FRAME_LENGTH]) -> usize {
+    let length = payload.len() + 2;
+    out[0] = ADDRESS_FLIGHT_CONTROLLER;
+    out[1] = length as u8;
+    out[2] = frame_type;
+    let () = out[3..3 + payload.len()].copy_from_slice(payload);
+    out[3 + payload.len()] = crc8(&out[2..3 + payload.len()]);
+    2 + length
+}
+
+/// Decode CRSF from `uart` (configured with `config`) forever, publishing channels through `rc`
+/// under `mapping` and sending battery and mode telemetry back every `TELEMETRY_PERIOD`.
+#[inline]
+pub async fn run<T: uart::Instance>(uart: Uart<'static, T, Async>, mapping: rc::Mapping) -> ! {
+    let (tx, rx) = uart.split();
+    let (never, _) = join(read(rx, mapping), write(tx)).await;
+    never
+}
+
+#[inline]
+async fn read<T: uart::Instance>(mut rx: UartRx<'static, T, Async>, mapping: rc::Mapping) -> ! {
+    let mut decoder = Decoder::default();
+    loop {
+        let mut byte = [0];
+        if let Err(e) = rx.read(&mut byte).await {
+            let () = crate::warn!("CRSF read error: {e:?}");
+            decoder = Decoder::default();
+            continue;
+        }
+        match decoder.push(byte[0]) {
+            None => {}
+            Some(Frame::Channels(raw)) => {
+                // ExpressLRS keeps sending the last channels for a while after losing the link,
+                // so trust link quality over the mere arrival of channels:
+                let failsafe =
+                    link_statistics().is_some_and(|statistics| statistics.uplink_link_quality == 0);
+                let () = rc::publish(&mapping, &rc::normalize_11_bit(&raw, failsafe));
+            }
+            Some(Frame::LinkStatistics(statistics)) => {
+                let () = LINK_STATISTICS.lock(|cell| cell.set(Some(statistics)));
+            }
+        }
+    }
+}
+
+#[inline]
+async fn write<T: uart::Instance>(mut tx: UartTx<'static, T, Async>) -> ! {
+    let mut frame = [0; MAX_FRAME_LENGTH];
+    let mut ticker = Ticker::every(TELEMETRY_PERIOD);
+    loop {
+        let () = ticker.next().await;
+        let Telemetry {
+            battery_volts,
+            mode,
+        } = TELEMETRY.lock(Cell::get);
+
+        if let Some(volts) = battery_volts {
+            // Voltage and current in tenths, a 24-bit capacity used, and percent remaining;
+            // only voltage is known here.
+            let decivolts = ((volts * 10.0) as u16).to_be_bytes();
+            let n = encode(
+                TYPE_BATTERY_SENSOR,
+                &[decivolts[0], decivolts[1], 0, 0, 0, 0, 0, 0],
+                &mut frame,
+            );
+            if let Err(e) = tx.write(&frame[..n]).await {
+                let () = crate::warn!("CRSF write error: {e:?}");
+            }
+        }
+
+        if !mode.is_empty() {
+            let mut payload = [0; MAX_MODE_LENGTH + 1];
+            let name = &mode.as_bytes()[..mode.len().min(MAX_MODE_LENGTH)];
+            let () = payload[..name.len()].copy_from_slice(name);
+            // Null-terminated:
+            let n = encode(TYPE_FLIGHT_MODE, &payload[..=name.len()], &mut frame);
+            if let Err(e) = tx.write(&frame[..n]).await {
+                let () = crate::warn!("CRSF write error: {e:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use {
+    crate::{analog, body::Body},
+    core::sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    embassy_rp::adc::{self, Adc, Async, Channel},
+    embassy_time::{Duration, Instant},
+};
+
+/// Time between ADC readings (see `analog::run`).
+pub const SAMPLE_PERIOD: Duration = Duration::from_millis(2);
+
+/// Latest windowed average servo-rail current, as `f32` bits; NaN until the first window.
+static AMPS: AtomicU32 = AtomicU32::new(f32::NAN.to_bits());
+
+/// Whether a `StallDetector` thinks a joint is stalled right now.
+static STALLED: AtomicBool = AtomicBool::new(false);
+
+/// Latest average servo-rail current, if anything's measuring it.
+#[inline]
+pub fn amps() -> Option<f32> {
+    Some(f32::from_bits(AMPS.load(Ordering::Relaxed))).filter(|amps| !amps.is_nan())
+}
+
+/// Whether a joint looks stalled: current stuck high with nothing left to move toward.
+#[inline]
+pub fn is_stalled() -> bool {
+    STALLED.load(Ordering::Relaxed)
+}
+
+/// How a current-sense output maps to amps: a shunt amplifier or an INA-style analog out.
+#[derive(Clone, Copy, Debug)]
+pub struct Sensor {
+    /// Amps per volt at the pin, e.g. 1 / (shunt ohms * amplifier gain).
+    pub amps_per_volt: f32,
+    /// Volts at the pin with no current flowing (nonzero for bidirectional sensors).
+    pub zero_volts: f32,
+}
+
+/// Averages raw readings over fixed windows, publishing each window's mean through `amps`.
+/// Servo current is spiky (every PWM pulse), so single readings are nearly meaningless.
+#[derive(Clone, Copy, Debug)]
+pub struct Monitor {
+    sensor: Sensor,
+    /// Readings per window.
+    window: u16,
+    sum: u32,
+    count: u16,
+}
+
+impl Monitor {
+    /// `window` readings per published average (zero counts as one).
+    #[inline]
+    pub const fn new(sensor: Sensor, window: u16) -> Self {
+        Self {
+            sensor,
+            window: if window == 0 { 1 } else { window },
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    /// Fold in one raw reading, returning (and publishing) a new average whenever a window fills.
+    #[inline]
+    pub fn update(&mut self, counts: u16) -> Option<f32> {
+        self.sum += counts as u32;
+        self.count += 1;
+        if self.count < self.window {
+            return None;
+        }
+        let mean = (self.sum as f32) / (self.count as f32);
+        self.sum = 0;
+        self.count = 0;
+        // `analog::volts` takes a whole reading; scale the mean by the volts in one count:
+        let volts = mean * analog::volts(1);
+        let amps = (volts - self.sensor.zero_volts) * self.sensor.amps_per_volt;
+        let () = AMPS.store(amps.to_bits(), Ordering::Relaxed);
+        Some(amps)
+    }
+
+    /// Take one reading from `channel` and `update` with it.
+    #[inline]
+    pub async fn sample(
+        &mut self,
+        adc: &mut Adc<'_, Async>,
+        channel: &mut Channel<'_>,
+    ) -> Result<Option<f32>, adc::Error> {
+        adc.read(channel).await.map(|counts| self.update(counts))
+    }
+}
+
+/// Flags a probable stall: current staying above `threshold_amps` for `hold` although every
+/// joint's command has been steady for `settle`, i.e. long enough that a free servo would have
+/// arrived and stopped drawing much. Without position feedback, that's the best sign a joint
+/// is pushing against something instead of reaching its target.
+#[derive(Clone, Copy, Debug)]
+pub struct StallDetector {
+    threshold_amps: f32,
+    settle: Duration,
+    hold: Duration,
+    /// When any joint's commanded angle last changed.
+    last_moved: Option<Instant>,
+    /// When the current last went over the threshold (and has stayed there since).
+    high_since: Option<Instant>,
+    last_angles: Option<[f32; 3]>,
+}
+
+impl StallDetector {
+    /// Over 4 A for a second, with every joint a half second past its last change.
+    pub const DEFAULT: Self = Self::new(4.0, Duration::from_millis(500), Duration::from_secs(1));
+
+    #[inline]
+    pub const fn new(threshold_amps: f32, settle: Duration, hold: Duration) -> Self {
+        Self {
+            threshold_amps,
+            settle,
+            hold,
+            last_moved: None,
+            high_since: None,
+            last_angles: None,
+        }
+    }
+
+    /// Call once per motion loop iteration. Returns (and publishes through `is_stalled`)
+    /// whether a joint looks stalled.
+    #[inline]
+    pub fn check<const N: usize>(&mut self, body: &Body<'_, N>) -> bool {
+        let now = Instant::now();
+
+        // A cheap fingerprint of every commanded angle, to notice when anything moves:
+        let angles = body
+            .joint_angles()
+            .iter()
+            .fold([0.0; 3], |[a, b, c], angles| {
+                [a + angles.yaw, b + angles.hip, c + angles.knee]
+            });
+        if self.last_angles != Some(angles) {
+            self.last_angles = Some(angles);
+            self.last_moved = Some(now);
+        }
+
+        let high = amps().is_some_and(|amps| amps > self.threshold_amps);
+        if !high {
+            self.high_since = None;
+        } else if self.high_since.is_none() {
+            self.high_since = Some(now);
+        }
+
+        let settled = self
+            .last_moved
+            .is_some_and(|moved| now - moved >= self.settle);
+        let stalled = settled
+            && self
+                .high_since
+                .is_some_and(|since| now - since >= self.hold);
+        if stalled != STALLED.swap(stalled, Ordering::Relaxed) {
+            if stalled {
+                let () = crate::warn!(
+                    "Probable servo stall: {} A with every joint settled",
+                    amps().unwrap_or(f32::NAN)
+                );
+            } else {
+                let () = crate::info!("Servo current back to normal");
+            }
+        }
+        stalled
+    }
+}
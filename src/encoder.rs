@@ -0,0 +1,148 @@
+use {
+    crate::{
+        gait::N_LEGS,
+        leg::{Joint, Leg},
+        servo,
+    },
+    core::{
+        f32::consts::TAU,
+        sync::atomic::{AtomicBool, AtomicI32, Ordering},
+    },
+    embassy_rp::{
+        pio,
+        pio_programs::rotary_encoder::{Direction, PioEncoder},
+    },
+    embassy_time::{Duration, Timer},
+};
+
+// Some yaw joints carry an incremental quadrature encoder, decoded by a PIO state machine
+// (`pio_programs::rotary_encoder`) so no steps are lost however busy the cores are. One `count`
+// task per encoder keeps a running total in `COUNTS`; `Feedback` turns that into an angle.
+//
+// An incremental encoder only knows how far it's moved since power-up, so it has to be homed:
+// `Feedback::rehome` drives the yaw servo to its center (the servo's own pot is absolute) and
+// takes that as zero. If only the servo rail lost power, the count is still good and the
+// joint may have been pushed around meanwhile; `Feedback::resync` picks up where it actually is.
+
+/// Time a yaw servo gets to reach center before `rehome` zeroes its encoder there.
+pub const DEFAULT_SETTLE: Duration = Duration::from_millis(500);
+
+/// Running total of each leg's yaw encoder.
+static COUNTS: [AtomicI32; N_LEGS] = [const { AtomicI32::new(0) }; N_LEGS];
+/// Whether each leg's yaw encoder has a `count` task running.
+static CONNECTED: [AtomicBool; N_LEGS] = [const { AtomicBool::new(false) }; N_LEGS];
+
+/// Raw running total of this leg's yaw encoder, if it has one.
+#[inline]
+pub fn counts(leg: usize) -> Option<i32> {
+    CONNECTED
+        .get(leg)
+        .filter(|connected| connected.load(Ordering::Relaxed))
+        .map(|_| COUNTS[leg].load(Ordering::Relaxed))
+}
+
+/// Count this leg's yaw encoder steps forever. `reversed` flips the count for an encoder
+/// mounted so counterclockwise (looking down) reads as `Direction::Clockwise`.
+#[inline]
+pub async fn count<PIO: pio::Instance, const SM: usize>(
+    leg: usize,
+    mut encoder: PioEncoder<'static, PIO, SM>,
+    reversed: bool,
+) -> ! {
+    let (Some(counts), Some(connected)) = (COUNTS.get(leg), CONNECTED.get(leg)) else {
+        let () = crate::error!("No leg {leg} to count an encoder for");
+        loop {
+            let () = Timer::after_secs(3600).await;
+        }
+    };
+    let () = connected.store(true, Ordering::Relaxed);
+    loop {
+        let step = match encoder.read().await {
+            Direction::CounterClockwise => 1,
+            Direction::Clockwise => -1,
+        };
+        let _: i32 = counts.fetch_add(if reversed { -step } else { step }, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntHome {
+    NoEncoder { leg: usize },
+    Move(servo::CouldntMove),
+}
+
+/// Turns encoder counts into measured yaw angles, radians from center like `ik::Angles::yaw`.
+#[derive(Clone, Copy, Debug)]
+pub struct Feedback<const N: usize> {
+    /// Encoder counts per radian of yaw, including any gearing.
+    counts_per_radian: [f32; N],
+    /// Each encoder's count with the joint centered, once homed.
+    zero: [Option<i32>; N],
+}
+
+impl<const N: usize> Feedback<N> {
+    /// Every encoder with the same resolution: `counts_per_revolution` steps (as `count` sees
+    /// them) per turn of the encoder, and `gear_ratio` encoder turns per turn of the joint.
+    #[inline]
+    pub fn new(counts_per_revolution: f32, gear_ratio: f32) -> Self {
+        Self {
+            counts_per_radian: [counts_per_revolution * gear_ratio / TAU; N],
+            zero: [None; N],
+        }
+    }
+
+    #[inline]
+    pub fn set_counts_per_radian(&mut self, leg: usize, counts_per_radian: f32) {
+        self.counts_per_radian[leg] = counts_per_radian
+    }
+
+    #[inline]
+    pub fn is_homed(&self, leg: usize) -> bool {
+        self.zero.get(leg).is_some_and(Option::is_some)
+    }
+
+    /// This leg's measured yaw, if it has an encoder and it's been homed.
+    #[inline]
+    pub fn measured(&self, leg: usize) -> Option<f32> {
+        let zero = (*self.zero.get(leg)?)?;
+        Some(((counts(leg)? - zero) as f32) / self.counts_per_radian[leg])
+    }
+
+    /// How far this leg's yaw is from where it was told to be (measured minus commanded),
+    /// e.g. to catch a stalled or slipping joint.
+    #[inline]
+    pub fn error(&self, index: usize, leg: &Leg<'_>) -> Option<f32> {
+        Some(self.measured(index)? - leg.angles().yaw)
+    }
+
+    /// Drive this leg's yaw to center, wait `settle` for it to get there, and call that zero.
+    /// Needed once after every power-up before `measured` means anything.
+    #[inline]
+    pub async fn rehome(
+        &mut self,
+        index: usize,
+        leg: &mut Leg<'_>,
+        settle: Duration,
+    ) -> Result<(), CouldntHome> {
+        if counts(index).is_none() || index >= N {
+            return Err(CouldntHome::NoEncoder { leg: index });
+        }
+        let () = leg.set_joint(Joint::Yaw, 0.0).map_err(CouldntHome::Move)?;
+        let () = Timer::after(settle).await;
+        self.zero[index] = counts(index);
+        let () = crate::info!("Homed leg {index}'s yaw encoder");
+        Ok(())
+    }
+
+    /// After the servo rail comes back (but not the encoder's), command the yaw to where the
+    /// encoder says it is, so the joint doesn't snap back to wherever it was last told to be.
+    #[inline]
+    pub fn resync(&self, index: usize, leg: &mut Leg<'_>) -> Result<(), CouldntHome> {
+        let measured = self
+            .measured(index)
+            .ok_or(CouldntHome::NoEncoder { leg: index })?;
+        leg.set_joint(Joint::Yaw, measured)
+            .map_err(CouldntHome::Move)
+    }
+}
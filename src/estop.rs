@@ -0,0 +1,125 @@
+use {
+    crate::{
+        body::{self, Body},
+        fault,
+        gait::Gait,
+        power,
+    },
+    core::sync::atomic::{AtomicBool, Ordering},
+    embassy_rp::gpio::Input,
+    embassy_time::{Duration, Timer},
+};
+
+// A dedicated emergency-stop button, normally open and closing to ground (so pull the pin up).
+// `watch` runs as its own task, waiting on the pin's interrupt, and freezes every leg the
+// moment a press is confirmed: that needs nothing from the motion task or any link, so a
+// hung USB stack or a runaway command stream can't hold it up. The stop then latches until
+// the button's released *and* something sends `estop reset` (`Command::ResetEstop`).
+
+/// Default time the pin must stay low before a press counts. Short, since this is a stop
+/// button: it only has to reject contact bounce and noise spikes on a long wire.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(2);
+
+/// Whether a stop is in force.
+static LATCHED: AtomicBool = AtomicBool::new(false);
+/// Whether the button's held down right now.
+static PRESSED: AtomicBool = AtomicBool::new(false);
+
+#[inline]
+pub fn is_latched() -> bool {
+    LATCHED.load(Ordering::SeqCst)
+}
+
+/// What to do to the servos on a stop. Walking always stops and every leg freezes first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Hold every joint where it is.
+    Freeze,
+    /// Also let every servo go limp (through `Guard`), e.g. if a leg's caught on something,
+    /// and cut their power rail (see `power`) if there's a switch on it.
+    Detach,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntReset {
+    /// The button's still down.
+    StillPressed,
+}
+
+/// Watch the button forever (active low). Run as its own task.
+#[inline]
+pub async fn watch(mut pin: Input<'static>, debounce: Duration) -> ! {
+    loop {
+        let () = pin.wait_for_low().await;
+        let () = Timer::after(debounce).await;
+        if pin.is_high() {
+            continue;
+        }
+        let () = PRESSED.store(true, Ordering::SeqCst);
+        if !LATCHED.swap(true, Ordering::SeqCst) {
+            let () = body::freeze();
+            let () = crate::error!("Emergency stop");
+            let () = fault::record(fault::Kind::EmergencyStop, 0, "").await;
+        }
+        let () = pin.wait_for_high().await;
+        let () = Timer::after(debounce).await;
+        if pin.is_high() {
+            let () = PRESSED.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Clear a stop, if the button's been released. Legs unfreeze, but after `Action::Detach`
+/// the servos stay limp until `attach` (M17), and a cut rail stays off until `power::Rail::power_up`.
+/// Walking stays stopped until the next command.
+#[inline]
+pub fn reset() -> Result<(), CouldntReset> {
+    if PRESSED.load(Ordering::SeqCst) {
+        return Err(CouldntReset::StillPressed);
+    }
+    if LATCHED.swap(false, Ordering::SeqCst) {
+        let () = body::resume();
+        let () = crate::info!("Emergency stop reset");
+    }
+    Ok(())
+}
+
+/// The motion task's half of a stop: everything that needs the body or the gait.
+#[derive(Clone, Copy, Debug)]
+pub struct Guard {
+    action: Action,
+    handled: bool,
+}
+
+impl Guard {
+    #[inline]
+    pub const fn new(action: Action) -> Self {
+        Self {
+            action,
+            handled: false,
+        }
+    }
+
+    /// Call once per motion loop iteration. Returns whether a stop is in force,
+    /// in which case the loop should skip anything that moves.
+    #[inline]
+    pub fn check<const N: usize>(&mut self, body: &mut Body<'_, N>, gait: &mut Gait) -> bool {
+        let latched = is_latched();
+        if !latched {
+            self.handled = false;
+            return false;
+        }
+        if !self.handled {
+            self.handled = true;
+            let () = gait.set_velocity(0.0, 0.0, 0.0);
+            if self.action == Action::Detach {
+                let () = power::cut();
+                if let Err(e) = body.relax() {
+                    let () = crate::error!("Emergency stop couldn't detach every servo: {e:?}");
+                }
+            }
+        }
+        true
+    }
+}
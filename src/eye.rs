@@ -0,0 +1,382 @@
+use {
+    crate::{
+        gait::{self, Gait},
+        ik,
+        math::{Quaternion, Vec3},
+        pwm,
+        servo::{self, Servo},
+        trig,
+    },
+    core::f32::consts::TAU,
+    embassy_time::Duration,
+};
+
+/// Calibration for one gaze axis, in radians, kept separate from the legs'.
+#[derive(Clone, Copy, Debug)]
+pub struct Axis {
+    /// Servo angle (from its electrical center) at which the eye looks straight ahead.
+    pub trim_radians: f32,
+    /// Furthest the eye may turn in the negative direction. Must be at most zero.
+    pub min_radians: f32,
+    /// Furthest the eye may turn in the positive direction. Must be at least zero.
+    pub max_radians: f32,
+    /// Whether a positive servo angle turns the eye in the negative direction.
+    pub reversed: bool,
+}
+
+impl Axis {
+    /// No trim, not reversed, and the same limit either way.
+    #[inline]
+    pub const fn symmetric(limit_radians: f32) -> Self {
+        Self {
+            trim_radians: 0.0,
+            min_radians: -limit_radians,
+            max_radians: limit_radians,
+            reversed: false,
+        }
+    }
+
+    /// Servo angle (radians) that points the eye at `radians` along this axis.
+    #[inline]
+    fn to_servo(&self, radians: f32) -> f32 {
+        self.trim_radians + if self.reversed { -radians } else { radians }
+    }
+}
+
+/// How hard `Eye::look_at` turns the body per radian the target lies beyond the eye's pan travel.
+pub const LOOK_AT_YAW_GAIN: f32 = 2.0;
+/// Fastest `Eye::look_at` will turn the body, in radians per second.
+pub const LOOK_AT_MAX_YAW_RATE: f32 = 1.0;
+
+/// Fraction of its full travel the pupil may cover per second, so size changes ramp smoothly.
+pub const PUPIL_SLEW_PER_SECOND: f32 = 2.0;
+/// Anything the rangefinder sees closer than this, in millimetres, catches the eye's attention.
+pub const NOTICE_DISTANCE_MM: u16 = 300;
+/// One full constrict-dilate cycle of `Expression::Pulse`.
+pub const PUPIL_PULSE_PERIOD: Duration = Duration::from_millis(1_500);
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntInit {
+    PanServo(servo::CouldntInitialize),
+    TiltServo(servo::CouldntInitialize),
+    IrisServo(servo::CouldntInitialize),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntLook {
+    Pan(servo::CouldntMove),
+    Tilt(servo::CouldntMove),
+    Iris(servo::CouldntMove),
+}
+
+/// Servo angles (radians from its electrical center) for the smallest and largest pupil.
+#[derive(Clone, Copy, Debug)]
+pub struct IrisCalibration {
+    pub constricted_radians: f32,
+    pub dilated_radians: f32,
+}
+
+/// Preset pupil sizes. Sizes are fractions of full travel: 0 is fully constricted, 1 fully dilated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Expression {
+    Neutral,
+    Dilate,
+    Constrict,
+    /// Breathe back and forth between constricted and dilated every `PUPIL_PULSE_PERIOD`.
+    Pulse,
+    /// Any other size, clamped to [0, 1].
+    Size(f32),
+}
+
+/// A small servo (or anything else driven like one, e.g. an LED ring's dimmer)
+/// setting the apparent size of the pupil.
+pub struct Iris<'d> {
+    servo: Servo<'d>,
+    calibration: IrisCalibration,
+    expression: Expression,
+    /// Current size, on [0, 1], which ramps toward the expression's.
+    size: f32,
+    /// Time into the current `Expression::Pulse` cycle.
+    pulse_phase: f32,
+}
+
+impl<'d> Iris<'d> {
+    #[inline]
+    pub fn new(pwm: pwm::Output<'d>, calibration: IrisCalibration) -> Result<Self, CouldntInit> {
+        let IrisCalibration {
+            constricted_radians,
+            dilated_radians,
+        } = calibration;
+        let lower = constricted_radians.min(dilated_radians).min(0.0);
+        let higher = constricted_radians.max(dilated_radians).max(0.0);
+        let servo = Servo::with_center_and_ranges(
+            pwm,
+            0.0,
+            pwm::RADIANS_TO_SERVO * lower,
+            pwm::RADIANS_TO_SERVO * higher,
+        )
+        .map_err(CouldntInit::IrisServo)?;
+        Ok(Self {
+            servo,
+            calibration,
+            expression: Expression::Neutral,
+            size: 0.5,
+            pulse_phase: 0.0,
+        })
+    }
+
+    #[inline]
+    pub fn expression(&self) -> Expression {
+        self.expression
+    }
+
+    /// Change expression. The pupil ramps there over the next few `update`s rather than jumping.
+    #[inline]
+    pub fn set_expression(&mut self, expression: Expression) {
+        if expression == Expression::Pulse && self.expression != Expression::Pulse {
+            // Start the cycle wherever the pupil is now, so the pulse doesn't jump:
+            self.pulse_phase = trig::acosf(1.0 - 2.0 * self.size) / TAU;
+        }
+        self.expression = expression
+    }
+
+    /// Current size, from 0 (fully constricted) to 1 (fully dilated).
+    #[inline]
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    /// Ramp toward the current expression by `elapsed`'s worth of travel and move the servo.
+    #[inline]
+    pub fn update(&mut self, elapsed: Duration) -> Result<(), servo::CouldntMove> {
+        let seconds = (elapsed.as_micros() as f32) * 1e-6;
+        let target = match self.expression {
+            Expression::Neutral => 0.5,
+            Expression::Dilate => 1.0,
+            Expression::Constrict => 0.0,
+            Expression::Pulse => {
+                let period = (PUPIL_PULSE_PERIOD.as_micros() as f32) * 1e-6;
+                let phase = self.pulse_phase + seconds / period;
+                self.pulse_phase = phase - libm::floorf(phase);
+                // Raised cosine: starts constricted, peaks dilated halfway through.
+                0.5 * (1.0 - trig::cosf(TAU * self.pulse_phase))
+            }
+            Expression::Size(size) => size.clamp(0.0, 1.0),
+        };
+
+        let max_step = PUPIL_SLEW_PER_SECOND * seconds;
+        self.size += (target - self.size).clamp(-max_step, max_step);
+
+        let IrisCalibration {
+            constricted_radians,
+            dilated_radians,
+        } = self.calibration;
+        self.servo.go_to(
+            pwm::RADIANS_TO_SERVO
+                * (constricted_radians + (dilated_radians - constricted_radians) * self.size),
+        )
+    }
+}
+
+pub struct Eye<'d> {
+    pan: Servo<'d>,
+    tilt: Servo<'d>,
+    pan_axis: Axis,
+    tilt_axis: Axis,
+    azimuth: f32,
+    elevation: f32,
+    /// World-frame `(azimuth, elevation)` to keep looking at despite body motion, if any.
+    held_heading: Option<(f32, f32)>,
+    /// Whether something's within `NOTICE_DISTANCE_MM`, as of the last `notice`.
+    noticing: bool,
+    iris: Option<Iris<'d>>,
+}
+
+impl<'d> Eye<'d> {
+    #[inline]
+    pub fn new(
+        pan_pwm: pwm::Output<'d>,
+        tilt_pwm: pwm::Output<'d>,
+        pan_axis: Axis,
+        tilt_axis: Axis,
+    ) -> Result<Self, CouldntInit> {
+        Ok(Self {
+            pan: servo_for(pan_pwm, &pan_axis).map_err(CouldntInit::PanServo)?,
+            tilt: servo_for(tilt_pwm, &tilt_axis).map_err(CouldntInit::TiltServo)?,
+            pan_axis,
+            tilt_axis,
+            azimuth: 0.0,
+            elevation: 0.0,
+            held_heading: None,
+            noticing: false,
+            iris: None,
+        })
+    }
+
+    /// Give the eye a pupil to drive from `update`.
+    #[inline]
+    pub fn with_iris(self, iris: Iris<'d>) -> Self {
+        Self {
+            iris: Some(iris),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn iris_mut(&mut self) -> Option<&mut Iris<'d>> {
+        self.iris.as_mut()
+    }
+
+    /// Everything the eye does on its own, once per control tick:
+    /// stabilize against the body's `attitude` (if a heading is held) and ramp the pupil.
+    #[inline]
+    pub fn update(&mut self, elapsed: Duration, attitude: Quaternion) -> Result<(), CouldntLook> {
+        let () = self.stabilize(attitude)?;
+        if let Some(ref mut iris) = self.iris {
+            let () = iris.update(elapsed).map_err(CouldntLook::Iris)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn pan_axis(&self) -> &Axis {
+        &self.pan_axis
+    }
+
+    #[inline]
+    pub fn tilt_axis(&self) -> &Axis {
+        &self.tilt_axis
+    }
+
+    /// Last commanded gaze, as `(azimuth, elevation)` in radians.
+    #[inline]
+    pub fn gaze(&self) -> (f32, f32) {
+        (self.azimuth, self.elevation)
+    }
+
+    /// Point the eye `azimuth` radians to the left (counterclockwise when looking down)
+    /// and `elevation` radians up, relative to straight ahead.
+    #[inline]
+    pub fn look_toward(&mut self, azimuth: f32, elevation: f32) -> Result<(), CouldntLook> {
+        let () = self
+            .pan
+            .go_to(pwm::RADIANS_TO_SERVO * self.pan_axis.to_servo(azimuth))
+            .map_err(CouldntLook::Pan)?;
+        self.azimuth = azimuth;
+        let () = self
+            .tilt
+            .go_to(pwm::RADIANS_TO_SERVO * self.tilt_axis.to_servo(elevation))
+            .map_err(CouldntLook::Tilt)?;
+        self.elevation = elevation;
+        Ok(())
+    }
+
+    /// Look at a point in the body frame (the eye center is the origin).
+    /// Within the eye's own travel, only the eye moves; beyond it, the eye waits at its limit
+    /// while the gait turns the body toward the point. Call every tick until it's centered.
+    ///
+    /// Takes over the gait's yaw rate while in use, but leaves its linear velocity alone.
+    #[inline]
+    pub fn look_at(
+        &mut self,
+        point: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        gait: &mut Gait,
+    ) -> Result<(), CouldntLook> {
+        let (azimuth, elevation) = Vec3::from(point).heading();
+
+        let pan = azimuth.clamp(self.pan_axis.min_radians, self.pan_axis.max_radians);
+        let tilt = elevation.clamp(self.tilt_axis.min_radians, self.tilt_axis.max_radians);
+
+        // Whatever the eye can't cover, the body has to:
+        let excess = azimuth - pan;
+        let yaw_rate =
+            (LOOK_AT_YAW_GAIN * excess).clamp(-LOOK_AT_MAX_YAW_RATE, LOOK_AT_MAX_YAW_RATE);
+        let gait::Velocity { x, y, .. } = gait.commanded_velocity();
+        let () = gait.set_velocity(x, y, yaw_rate);
+
+        self.look_toward(pan, tilt)
+    }
+
+    /// React to the rangefinder (`tof::obstacle`): when something comes within
+    /// `NOTICE_DISTANCE_MM` straight ahead, look at it and narrow the pupil;
+    /// when it goes, relax the pupil again. Leaves the gaze alone while a heading is held.
+    #[inline]
+    pub fn notice(&mut self, obstacle_mm: Option<u16>) -> Result<(), CouldntLook> {
+        let near = obstacle_mm.is_some_and(|mm| mm < NOTICE_DISTANCE_MM);
+        if near == self.noticing {
+            return Ok(());
+        }
+        self.noticing = near;
+        if let Some(ref mut iris) = self.iris {
+            let () = iris.set_expression(if near {
+                Expression::Constrict
+            } else {
+                Expression::Neutral
+            });
+        }
+        if near && self.held_heading.is_none() {
+            return self.look_toward(0.0, 0.0);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_noticing(&self) -> bool {
+        self.noticing
+    }
+
+    /// Stabilization: keep looking toward a fixed world-frame heading
+    /// (azimuth counterclockwise from the body's forward at the time, elevation up)
+    /// by counter-rotating against body motion. Call `stabilize` every tick to apply it.
+    #[inline]
+    pub fn hold_heading(&mut self, azimuth: f32, elevation: f32) {
+        self.held_heading = Some((azimuth, elevation))
+    }
+
+    /// Stop stabilizing; the eye stays where it last pointed.
+    #[inline]
+    pub fn release_heading(&mut self) {
+        self.held_heading = None
+    }
+
+    #[inline]
+    pub fn held_heading(&self) -> Option<(f32, f32)> {
+        self.held_heading
+    }
+
+    /// Given the body's measured attitude (e.g. `imu::Orientation::attitude`), re-aim so the
+    /// gaze stays on the held heading. Pan and tilt can keep the eye pointed the right way under
+    /// any roll, pitch, or yaw, but can't undo the image spinning under roll.
+    /// Aims as close as the axis limits allow. Does nothing if no heading is held.
+    #[inline]
+    pub fn stabilize(&mut self, attitude: Quaternion) -> Result<(), CouldntLook> {
+        let Some((azimuth, elevation)) = self.held_heading else {
+            return Ok(());
+        };
+
+        // Unit vector toward the heading in the world frame, then into the body frame:
+        let local = attitude.inverse_rotate(Vec3::from_heading(azimuth, elevation));
+
+        let (azimuth, elevation) = local.heading();
+        let azimuth = azimuth.clamp(self.pan_axis.min_radians, self.pan_axis.max_radians);
+        let elevation = elevation.clamp(self.tilt_axis.min_radians, self.tilt_axis.max_radians);
+        self.look_toward(azimuth, elevation)
+    }
+}
+
+/// The servo's own range is the axis's limits, shifted by its trim and flipped if reversed.
+#[inline]
+fn servo_for<'d>(pwm: pwm::Output<'d>, axis: &Axis) -> Result<Servo<'d>, servo::CouldntInitialize> {
+    let (a, b) = (
+        axis.to_servo(axis.min_radians),
+        axis.to_servo(axis.max_radians),
+    );
+    Servo::with_center_and_ranges(
+        pwm,
+        0.0,
+        pwm::RADIANS_TO_SERVO * a.min(b),
+        pwm::RADIANS_TO_SERVO * a.max(b),
+    )
+}
@@ -0,0 +1,148 @@
+use {
+    crate::{
+        pwm,
+        servo::{self, Servo},
+    },
+    embassy_futures::select::{Either, select},
+    embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal},
+    embassy_time::{Duration, Timer},
+    rand_core::RngCore,
+};
+
+/// How long the lid stays shut during a `blink`.
+pub const BLINK_HOLD: Duration = Duration::from_millis(80);
+/// How long the lid stays shut during a `wink`.
+pub const WINK_HOLD: Duration = Duration::from_millis(400);
+
+/// Lets the rest of the application ask for a blink while `idle_blink` owns the eyelid.
+static REQUESTS: Signal<CriticalSectionRawMutex, Gesture> = Signal::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gesture {
+    Blink,
+    Wink,
+}
+
+/// Ask whatever task is running `idle_blink` to blink or wink as soon as it can.
+#[inline]
+pub fn request(gesture: Gesture) {
+    REQUESTS.signal(gesture)
+}
+
+/// Servo angles (radians from its electrical center) for a fully open and fully shut lid.
+#[derive(Clone, Copy, Debug)]
+pub struct Calibration {
+    pub open_radians: f32,
+    pub closed_radians: f32,
+}
+
+/// Timing for `idle_blink`.
+#[derive(Clone, Copy, Debug)]
+pub struct IdleBlink {
+    /// Average time between spontaneous blinks.
+    pub mean_interval: Duration,
+    /// Each interval is drawn uniformly from `mean_interval` plus or minus this.
+    pub jitter: Duration,
+}
+
+impl IdleBlink {
+    /// Roughly human: every four seconds or so.
+    pub const DEFAULT: Self = Self {
+        mean_interval: Duration::from_millis(4_000),
+        jitter: Duration::from_millis(2_000),
+    };
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntInit {
+    Servo(servo::CouldntInitialize),
+}
+
+pub struct Eyelid<'d> {
+    servo: Servo<'d>,
+    calibration: Calibration,
+}
+
+impl<'d> Eyelid<'d> {
+    #[inline]
+    pub fn new(pwm: pwm::Output<'d>, calibration: Calibration) -> Result<Self, CouldntInit> {
+        let Calibration {
+            open_radians,
+            closed_radians,
+        } = calibration;
+        let lower = open_radians.min(closed_radians).min(0.0);
+        let higher = open_radians.max(closed_radians).max(0.0);
+        let servo = Servo::with_center_and_ranges(
+            pwm,
+            0.0,
+            pwm::RADIANS_TO_SERVO * lower,
+            pwm::RADIANS_TO_SERVO * higher,
+        )
+        .map_err(CouldntInit::Servo)?;
+        Ok(Self { servo, calibration })
+    }
+
+    #[inline]
+    pub fn open(&mut self) -> Result<(), servo::CouldntMove> {
+        self.servo
+            .go_to(pwm::RADIANS_TO_SERVO * self.calibration.open_radians)
+    }
+
+    #[inline]
+    pub fn close(&mut self) -> Result<(), servo::CouldntMove> {
+        self.servo
+            .go_to(pwm::RADIANS_TO_SERVO * self.calibration.closed_radians)
+    }
+
+    /// Shut and reopen quickly.
+    #[inline]
+    pub async fn blink(&mut self) -> Result<(), servo::CouldntMove> {
+        self.shut_for(BLINK_HOLD).await
+    }
+
+    /// Shut, hold a beat, and reopen: a deliberate, knowing blink.
+    #[inline]
+    pub async fn wink(&mut self) -> Result<(), servo::CouldntMove> {
+        self.shut_for(WINK_HOLD).await
+    }
+
+    #[inline]
+    async fn shut_for(&mut self, hold: Duration) -> Result<(), servo::CouldntMove> {
+        let () = self.close()?;
+        let () = Timer::after(hold).await;
+        self.open()
+    }
+
+    #[inline]
+    pub async fn perform(&mut self, gesture: Gesture) -> Result<(), servo::CouldntMove> {
+        match gesture {
+            Gesture::Blink => self.blink().await,
+            Gesture::Wink => self.wink().await,
+        }
+    }
+}
+
+/// Blink every so often on its own, and whenever `request` is called.
+/// Meant to be the body of a dedicated task that owns the eyelid.
+#[inline]
+pub async fn idle_blink(eyelid: &mut Eyelid<'_>, timing: IdleBlink, rng: &mut impl RngCore) -> ! {
+    loop {
+        let gesture = match select(Timer::after(next_interval(&timing, rng)), REQUESTS.wait()).await
+        {
+            Either::First(()) => Gesture::Blink,
+            Either::Second(gesture) => gesture,
+        };
+        if let Err(e) = eyelid.perform(gesture).await {
+            let () = crate::error!("Couldn't move the eyelid: {e:?}");
+        }
+    }
+}
+
+/// Uniformly distributed on `mean_interval` plus or minus `jitter`, never negative.
+#[inline]
+fn next_interval(timing: &IdleBlink, rng: &mut impl RngCore) -> Duration {
+    let jitter_us = timing.jitter.as_micros();
+    let offset_us = (rng.next_u64() % (2 * jitter_us + 1)) as i64 - jitter_us as i64;
+    Duration::from_micros((timing.mean_interval.as_micros() as i64 + offset_us).max(0) as u64)
+}
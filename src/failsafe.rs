@@ -0,0 +1,105 @@
+use {
+    crate::{
+        body::{self, Body},
+        estop,
+        gait::Gait,
+    },
+    core::sync::atomic::{AtomicU32, Ordering},
+    embassy_time::{Duration, Instant},
+};
+
+/// When anything last heard from a controller, in milliseconds since boot (wrapping),
+/// or 0 if nothing has yet. Milliseconds in a `u32` since there are no 64-bit atomics here.
+static LAST_HEARD: AtomicU32 = AtomicU32::new(0);
+
+/// Default time without a command or heartbeat before `Watchdog` trips.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1_000);
+
+/// Note that a controller is still there. Called for every queued command,
+/// every live RC frame, and every ground-station heartbeat.
+#[inline]
+pub fn heard() {
+    // Skip 0, which means "never":
+    LAST_HEARD.store(
+        (Instant::now().as_millis() as u32).max(1),
+        Ordering::Relaxed,
+    )
+}
+
+/// What to do when the command link goes quiet. Walking always stops first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Just stop walking, holding the current pose.
+    Stop,
+    /// Stop walking and `body::freeze` until the link comes back.
+    Freeze,
+    /// Stop walking and `Body::start_sitting_down`, which the motion loop then carries through.
+    /// Standing back up is left to whoever comes back.
+    SitDown,
+}
+
+/// Trips when nothing has been heard from a controller for too long,
+/// so a dropped link doesn't leave the robot walking at its last velocity forever.
+/// Stays quiet until the first command, so a robot booted without a link isn't "lost".
+#[derive(Clone, Copy, Debug)]
+pub struct Watchdog {
+    timeout: Duration,
+    action: Action,
+    tripped: bool,
+}
+
+impl Watchdog {
+    #[inline]
+    pub const fn new(timeout: Duration, action: Action) -> Self {
+        Self {
+            timeout,
+            action,
+            tripped: false,
+        }
+    }
+
+    #[inline]
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout
+    }
+
+    #[inline]
+    pub fn set_action(&mut self, action: Action) {
+        self.action = action
+    }
+
+    #[inline]
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+
+    /// Call once per motion loop iteration. Carries out the action when the link first goes
+    /// quiet and, for `Action::Freeze`, resumes once it's back.
+    #[inline]
+    pub fn check<const N: usize>(&mut self, body: &mut Body<'_, N>, gait: &mut Gait) {
+        let last = LAST_HEARD.load(Ordering::Relaxed);
+        if last == 0 {
+            return;
+        }
+        let silent = (Instant::now().as_millis() as u32).wrapping_sub(last);
+        let quiet = silent as u64 > self.timeout.as_millis();
+
+        if quiet && !self.tripped {
+            self.tripped = true;
+            let () = crate::warn!("No command for {silent} ms: failsafe {:?}", self.action);
+            let () = gait.set_velocity(0.0, 0.0, 0.0);
+            match self.action {
+                Action::Stop => {}
+                Action::Freeze => body::freeze(),
+                Action::SitDown => body.start_sitting_down(),
+            }
+        } else if !quiet && self.tripped {
+            self.tripped = false;
+            let () = crate::info!("Command link is back");
+            // An emergency stop froze things too, and only its own reset may undo that:
+            if self.action == Action::Freeze && !estop::is_latched() {
+                let () = body::resume();
+            }
+        }
+    }
+}
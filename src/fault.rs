@@ -0,0 +1,318 @@
+use {
+    crate::{
+        power,
+        storage::{self, CouldntSave, FAULT_RING, MAX_PAYLOAD_LENGTH, Slot},
+        telemetry,
+    },
+    core::{
+        fmt::{self, Write as _},
+        mem::MaybeUninit,
+        panic::PanicInfo,
+        sync::atomic::{AtomicBool, Ordering, compiler_fence},
+    },
+    embassy_rp::flash,
+    embassy_time::{Duration, Instant, Ticker},
+};
+
+// A ring of fault records in flash (`storage::FAULT_RING`), oldest overwritten first,
+// so whatever went wrong before a crash or brownout can still be read (`faults`) after
+// USB comes back. Each record's payload is:
+//
+//     kind: u8 | uptime_ms: u32 | count: u32 | message (UTF-8) ...
+//
+// A panic can't wait for the flash, so `record_panic` only stashes its message in RAM that
+// survives a reset; `recover`, early on the next boot, moves it (and any brownout the chip
+// noticed) into flash.
+//
+// This is also where every firmware binary's `#[panic_handler]` lives (in place of
+// `panic_probe`), so none can forget to cut the servo rail first.
+
+/// Longest message a record keeps; anything longer is cut off.
+pub const MAX_MESSAGE_LENGTH: usize = FAULT_RING.capacity() - (1 + 4 + 4);
+
+/// How often `watch` checks the error counts.
+pub const WATCH_PERIOD: Duration = Duration::from_secs(10);
+/// IK failures within one `WATCH_PERIOD` worth recording. A few are normal (e.g. a target
+/// just out of reach); a steady stream means something's wrong.
+pub const IK_BURST: u32 = 20;
+
+/// Marks a stash holding a panic not yet in flash.
+const STASH_MAGIC: u32 = u32::from_le_bytes(*b"PANC");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Kind {
+    Panic = 0,
+    /// At least `IK_BURST` IK failures in one `WATCH_PERIOD`.
+    IkBurst = 1,
+    /// Servo or PWM errors in one `WATCH_PERIOD`.
+    Servo = 2,
+    /// The last reset was the brownout detector's.
+    Brownout = 3,
+    /// The chip crossed `thermal`'s warning temperature; the message says how hot.
+    Overheat = 4,
+    /// The last reset was the watchdog's: the motion loop stopped feeding it.
+    Watchdog = 5,
+    /// The emergency-stop button was pressed.
+    EmergencyStop = 6,
+}
+
+/// One fault, as read back from flash.
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub sequence: u32,
+    pub kind: Kind,
+    /// Time since the boot it happened in.
+    pub uptime_ms: u32,
+    /// How many errors a burst counted; 0 for anything else.
+    pub count: u32,
+    pub message: heapless::String<MAX_MESSAGE_LENGTH>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntDump {
+    NotInitialized,
+    Flash(flash::Error),
+}
+
+/// A panic waiting to be moved into flash, in RAM the reset doesn't clear.
+#[repr(C)]
+struct Stash {
+    magic: u32,
+    uptime_ms: u32,
+    length: u32,
+    message: [u8; MAX_MESSAGE_LENGTH],
+}
+
+/// Whether `recover` found the last reset was a brownout.
+static BROWNED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// Whether `recover` found a panic stashed by the last boot.
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+/// Whether this boot has panicked already, so a panic while handling one only halts.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+#[unsafe(link_section = ".uninit.fault")]
+static mut STASH: MaybeUninit<Stash> = MaybeUninit::uninit();
+
+impl Kind {
+    #[inline]
+    fn from_u8(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0 => Self::Panic,
+            1 => Self::IkBurst,
+            2 => Self::Servo,
+            3 => Self::Brownout,
+            4 => Self::Overheat,
+            5 => Self::Watchdog,
+            6 => Self::EmergencyStop,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Record {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} at {} ms: {:?}",
+            self.sequence, self.uptime_ms, self.kind
+        )?;
+        if self.count > 0 {
+            write!(f, " x{}", self.count)?;
+        }
+        if !self.message.is_empty() {
+            write!(f, " ({})", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps as much as fits, rather than failing.
+struct Truncating<'a>(&'a mut heapless::String<MAX_MESSAGE_LENGTH>);
+
+impl fmt::Write for Truncating<'_> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stash a panic for `recover` to log on the next boot. Meant for `panic`, just after
+/// `power::cut`: it touches nothing but RAM.
+#[inline]
+pub fn record_panic(info: &PanicInfo) {
+    let mut message = heapless::String::new();
+    if let Some(location) = info.location() {
+        let _: fmt::Result = write!(
+            Truncating(&mut message),
+            "{}:{}: ",
+            location.file(),
+            location.line()
+        );
+    }
+    let _: fmt::Result = write!(Truncating(&mut message), "{}", info.message());
+    let mut bytes = [0; MAX_MESSAGE_LENGTH];
+    let () = bytes[..message.len()].copy_from_slice(message.as_bytes());
+    // SAFETY: Nothing else touches the stash until `recover`, after the next reset.
+    let () = unsafe {
+        (&raw mut STASH).write_volatile(MaybeUninit::new(Stash {
+            magic: STASH_MAGIC,
+            uptime_ms: Instant::now().as_millis() as u32,
+            length: message.len() as u32,
+            message: bytes,
+        }))
+    };
+    compiler_fence(Ordering::SeqCst)
+}
+
+/// Cut the servo rail, stash the message, log it, and halt, leaving the watchdog (if it's
+/// been started) to reset the chip. Halting the way `panic_probe` does means a debug probe
+/// still sees the panic as one.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let () = power::cut();
+    if !PANICKING.swap(true, Ordering::SeqCst) {
+        let () = record_panic(info);
+        let () = crate::error!("{info}");
+    }
+    cortex_m::asm::udf()
+}
+
+/// Log whatever the last boot left behind: a stashed panic, or a brownout reset.
+/// Call once, right after `storage::init`.
+#[inline]
+pub async fn recover() {
+    // SAFETY: Only ever read here, once per boot. After a power-on reset it's garbage,
+    // which the magic number rules out (bar a one-in-four-billion coincidence).
+    let stash = unsafe { (&raw const STASH).read_volatile().assume_init() };
+    if stash.magic == STASH_MAGIC {
+        // SAFETY: As above.
+        let () = unsafe { (&raw mut STASH).cast::<u32>().write_volatile(0) };
+        let () = PANICKED.store(true, Ordering::Relaxed);
+        let length = (stash.length as usize).min(MAX_MESSAGE_LENGTH);
+        let message = core::str::from_utf8(&stash.message[..length]).unwrap_or("");
+        let () = crate::error!("Recovered a panic from the last boot: {message}");
+        let () = record_with_uptime(Kind::Panic, stash.uptime_ms, 0, message).await;
+    }
+    if embassy_rp::pac::POWMAN.chip_reset().read().had_bor() {
+        let () = BROWNED_OUT.store(true, Ordering::Relaxed);
+        let () = crate::error!("Last reset was a brownout");
+        let () = record_with_uptime(Kind::Brownout, 0, 0, "").await;
+    }
+}
+
+/// Whether `recover` found the last reset was the brownout detector's.
+#[inline]
+pub fn last_reset_was_brownout() -> bool {
+    BROWNED_OUT.load(Ordering::Relaxed)
+}
+
+/// Whether `recover` found the last boot ended in a panic.
+#[inline]
+pub fn last_boot_panicked() -> bool {
+    PANICKED.load(Ordering::Relaxed)
+}
+
+/// Append a fault, timestamped now. Errors are logged, not returned: there's nowhere
+/// better to report a failure to report a failure.
+#[inline]
+pub async fn record(kind: Kind, count: u32, message: &str) {
+    record_with_uptime(kind, Instant::now().as_millis() as u32, count, message).await
+}
+
+#[inline]
+async fn record_with_uptime(kind: Kind, uptime_ms: u32, count: u32, message: &str) {
+    let length = message.len().min(MAX_MESSAGE_LENGTH);
+    let mut payload = [0; FAULT_RING.capacity()];
+    payload[0] = kind as u8;
+    let () = payload[1..5].copy_from_slice(&uptime_ms.to_le_bytes());
+    let () = payload[5..9].copy_from_slice(&count.to_le_bytes());
+    let () = payload[9..9 + length].copy_from_slice(&message.as_bytes()[..length]);
+
+    let mut storage = storage::lock().await;
+    let result = match storage.as_mut() {
+        None => Err(CouldntSave::NotInitialized),
+        Some(flash) => FAULT_RING.append(flash, &payload[..9 + length]),
+    };
+    if let Err(e) = result {
+        let () = crate::warn!("Couldn't log a {kind:?} fault: {e:?}");
+    }
+}
+
+/// Every `WATCH_PERIOD`, record bursts of IK failures and any servo errors from
+/// `telemetry`'s counts. Writes to flash, so a burst costs a few milliseconds' stall.
+#[inline]
+pub async fn watch() -> ! {
+    let mut ticker = Ticker::every(WATCH_PERIOD);
+    let mut last = telemetry::error_counts();
+    loop {
+        let () = ticker.next().await;
+        let counts = telemetry::error_counts();
+        let delta =
+            |kind: telemetry::ErrorKind| counts[kind as usize].wrapping_sub(last[kind as usize]);
+        let ik = delta(telemetry::ErrorKind::Ik);
+        if ik >= IK_BURST {
+            let () = record(Kind::IkBurst, ik, "").await;
+        }
+        let servo = delta(telemetry::ErrorKind::Servo);
+        if servo > 0 {
+            let () = record(Kind::Servo, servo, "").await;
+        }
+        last = counts;
+    }
+}
+
+/// The oldest intact record newer than `after` (or the oldest of all, for `None`).
+/// Call again with its `sequence` for the next one.
+#[inline]
+pub async fn read_after(after: Option<u32>) -> Result<Option<Record>, CouldntDump> {
+    let mut storage = storage::lock().await;
+    let flash = storage.as_mut().ok_or(CouldntDump::NotInitialized)?;
+    let mut payload = [0; MAX_PAYLOAD_LENGTH];
+    let mut oldest: Option<Record> = None;
+    for slot in 0..FAULT_RING.slots() {
+        let Slot::Intact { sequence, length } = FAULT_RING
+            .read(flash, slot, &mut payload)
+            .map_err(CouldntDump::Flash)?
+        else {
+            continue;
+        };
+        if after.is_some_and(|after| sequence <= after)
+            || oldest
+                .as_ref()
+                .is_some_and(|oldest| sequence >= oldest.sequence)
+        {
+            continue;
+        }
+        let Some(record) = decode(sequence, &payload[..length]) else {
+            continue;
+        };
+        oldest = Some(record);
+    }
+    Ok(oldest)
+}
+
+#[inline]
+fn decode(sequence: u32, payload: &[u8]) -> Option<Record> {
+    let &[kind, u0, u1, u2, u3, c0, c1, c2, c3, ref message @ ..] = payload else {
+        return None;
+    };
+    let mut string = heapless::String::new();
+    let _: fmt::Result = Truncating(&mut string).write_str(core::str::from_utf8(message).ok()?);
+    Some(Record {
+        sequence,
+        kind: Kind::from_u8(kind)?,
+        uptime_ms: u32::from_le_bytes([u0, u1, u2, u3]),
+        count: u32::from_le_bytes([c0, c1, c2, c3]),
+        message: string,
+    })
+}
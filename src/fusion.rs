@@ -0,0 +1,125 @@
+use crate::{math::Quaternion, trig};
+
+// Madgwick's gradient-descent orientation filter, IMU-only (no magnetometer), so yaw drifts
+// with the gyro's bias but roll and pitch stay anchored to gravity. Every `update` takes its
+// own time step, so it works at whatever rate the sensor's read.
+//
+// The attitude is a `math::Quaternion` rotating body-frame vectors into the world frame
+// (x forward, y left, z up, at yaw zero), the same way round as a `body::Pose`.
+
+/// How hard each `update` pulls toward the accelerometer's "down", in radians per second.
+/// Madgwick suggests sqrt(3/4) times the gyro's noise; larger corrects drift faster
+/// but lets more vibration through.
+pub const DEFAULT_BETA: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Madgwick {
+    beta: f32,
+    attitude: Quaternion,
+    /// Whether the first reading has set the initial attitude yet.
+    aligned: bool,
+}
+
+impl Madgwick {
+    #[inline]
+    pub const fn new(beta: f32) -> Self {
+        Self {
+            beta,
+            attitude: Quaternion::IDENTITY,
+            aligned: false,
+        }
+    }
+
+    #[inline]
+    pub fn set_beta(&mut self, beta: f32) {
+        self.beta = beta
+    }
+
+    #[inline]
+    pub fn attitude(&self) -> Quaternion {
+        self.attitude
+    }
+
+    /// Start over from whatever the next reading's gravity says, e.g. after being picked up.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.attitude = Quaternion::IDENTITY;
+        self.aligned = false;
+    }
+
+    /// Fold in one reading taken `dt` seconds after the last: `gyro` in radians per second,
+    /// `accel` in any units (only its direction matters). The first reading snaps roll and
+    /// pitch straight to gravity instead of converging slowly from level.
+    #[inline]
+    pub fn update(&mut self, gyro: [f32; 3], accel: [f32; 3], dt: f32) -> Quaternion {
+        let [ax, ay, az] = accel;
+        let accel_norm = libm::sqrtf(ax * ax + ay * ay + az * az);
+        if !self.aligned {
+            if accel_norm <= f32::EPSILON {
+                return self.attitude;
+            }
+            self.aligned = true;
+            self.attitude = Quaternion::from_roll_pitch_yaw(
+                trig::atan2f(ay, az),
+                trig::atan2f(-ax, libm::sqrtf(ay * ay + az * az)),
+                0.0,
+            );
+            return self.attitude;
+        }
+
+        let Quaternion {
+            w: q0,
+            x: q1,
+            y: q2,
+            z: q3,
+        } = self.attitude;
+        let [gx, gy, gz] = gyro;
+
+        // Rate of change from the gyro alone:
+        let mut dq0 = 0.5 * (-q1 * gx - q2 * gy - q3 * gz);
+        let mut dq1 = 0.5 * (q0 * gx + q2 * gz - q3 * gy);
+        let mut dq2 = 0.5 * (q0 * gy - q1 * gz + q3 * gx);
+        let mut dq3 = 0.5 * (q0 * gz + q1 * gy - q2 * gx);
+
+        // Gradient step toward where gravity says we are, skipped in free fall:
+        if accel_norm > f32::EPSILON {
+            let (ax, ay, az) = (ax / accel_norm, ay / accel_norm, az / accel_norm);
+            let s0 = 4.0 * q0 * q2 * q2 + 2.0 * q2 * ax + 4.0 * q0 * q1 * q1 - 2.0 * q1 * ay;
+            let s1 =
+                4.0 * q1 * q3 * q3 - 2.0 * q3 * ax + 4.0 * q0 * q0 * q1 - 2.0 * q0 * ay - 4.0 * q1
+                    + 8.0 * q1 * q1 * q1
+                    + 8.0 * q1 * q2 * q2
+                    + 4.0 * q1 * az;
+            let s2 =
+                4.0 * q0 * q0 * q2 + 2.0 * q0 * ax + 4.0 * q2 * q3 * q3 - 2.0 * q3 * ay - 4.0 * q2
+                    + 8.0 * q2 * q1 * q1
+                    + 8.0 * q2 * q2 * q2
+                    + 4.0 * q2 * az;
+            let s3 = 4.0 * q1 * q1 * q3 - 2.0 * q1 * ax + 4.0 * q2 * q2 * q3 - 2.0 * q2 * ay;
+            let norm = libm::sqrtf(s0 * s0 + s1 * s1 + s2 * s2 + s3 * s3);
+            if norm > f32::EPSILON {
+                let step = self.beta / norm;
+                dq0 -= step * s0;
+                dq1 -= step * s1;
+                dq2 -= step * s2;
+                dq3 -= step * s3;
+            }
+        }
+
+        self.attitude = Quaternion {
+            w: q0 + dq0 * dt,
+            x: q1 + dq1 * dt,
+            y: q2 + dq2 * dt,
+            z: q3 + dq3 * dt,
+        }
+        .normalized();
+        self.attitude
+    }
+}
+
+impl Default for Madgwick {
+    #[inline]
+    fn default() -> Self {
+        Self::new(DEFAULT_BETA)
+    }
+}
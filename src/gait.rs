@@ -0,0 +1,896 @@
+use {
+    crate::{
+        body, ik,
+        math::{Rotation2, Vec2, Vec3},
+        servo::OutOfRange,
+        trig,
+    },
+    core::f32::consts::FRAC_PI_2,
+    embassy_time::Duration,
+};
+
+/// Legs are numbered in order around the body,
+/// so that even and odd legs form the two tripods
+/// and legs 0-2 and 3-5 form the two halves for a ripple, each stepping in a wave of its own.
+pub const N_LEGS: usize = 6;
+
+/// Which legs swing when. Slower patterns keep more feet on the ground.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    /// Alternating tripods: three legs up, three down. Fastest.
+    Tripod,
+    /// Two legs up at a time, staggered so their swings overlap by half.
+    Ripple,
+    /// One leg up at a time, working around the body. Slowest, but most stable.
+    Wave,
+}
+
+impl Pattern {
+    /// Fraction of each leg's cycle spent planted on the ground.
+    #[inline]
+    pub fn duty_factor(self) -> f32 {
+        match self {
+            Self::Tripod => const { 1.0 / 2.0 },
+            Self::Ripple => const { 2.0 / 3.0 },
+            Self::Wave => const { 5.0 / 6.0 },
+        }
+    }
+
+    /// Point in the body's cycle at which this leg lifts off, on [0, 1).
+    #[inline]
+    pub fn phase_offset(self, leg: usize) -> f32 {
+        match self {
+            Self::Tripod => {
+                if leg.is_multiple_of(2) {
+                    0.0
+                } else {
+                    0.5
+                }
+            }
+            Self::Ripple => {
+                // Each half a third of a cycle apart, and the second half a sixth behind the first,
+                // ordered so that no two neighbours (5 and 0 included) are ever up together:
+                const OFFSETS: [f32; N_LEGS] =
+                    [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0 / 6.0, 5.0 / 6.0, 1.0 / 2.0];
+                OFFSETS[leg % N_LEGS]
+            }
+            Self::Wave => ((leg % N_LEGS) as f32) * const { 1.0 / (N_LEGS as f32) },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Parameters {
+    /// Longest distance any foot may travel while planted.
+    /// Faster commands are scaled down (uniformly across legs) to fit.
+    pub max_stride_length: f32,
+    /// How far each foot lifts at the top of its swing.
+    pub step_height: f32,
+    /// Time for every leg to complete one swing and one stance.
+    pub cycle_period: Duration,
+    /// Fraction of each leg's cycle spent planted, overriding the pattern's own.
+    pub duty_factor: Option<f32>,
+    /// The path each foot takes through the air.
+    pub swing: Swing,
+}
+
+impl Parameters {
+    /// A relaxed walk: one cycle a second, with a stride about a quarter of a leg's reach.
+    pub const DEFAULT: Self = Self {
+        max_stride_length: 2.0,
+        step_height: 1.0,
+        cycle_period: Duration::from_millis(1_000),
+        duty_factor: None,
+        swing: Swing::DEFAULT,
+    };
+}
+
+/// A swing foot's path, as two cubic Bézier curves meeting at the top of the step: one up from
+/// liftoff, one down to touchdown, each taking half the swing. Both live in the swing's own
+/// frame, where the stride runs from 0 (liftoff) to 1 (touchdown) and height from 0 (the
+/// ground) to 1 (`Parameters::step_height`), so one shape fits every stride and step.
+///
+/// A plain arc comes down still moving forward and scuffs the toe on touchdown; this one can
+/// lift the foot clear before carrying it, and set it down as steeply as it likes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Swing {
+    /// How hard the foot's pulled straight up off the ground before it heads forward, on
+    /// [0, 1]: the rising curve's first control point sits this much of the step over liftoff.
+    pub lift: f32,
+    /// How far along the stride the foot is highest, on [`MIN_SWING_APEX`, 1 - `MIN_SWING_APEX`].
+    pub apex: f32,
+    /// Radians from straight down at which the foot comes in to land, on [0, `FRAC_PI_2`):
+    /// zero sets it straight down; larger angles sweep it forward into the ground.
+    pub touchdown_angle: f32,
+}
+
+/// Closest the apex of a `Swing` may come to either end of the stride.
+pub const MIN_SWING_APEX: f32 = 0.05;
+
+/// Length, in the swing's own frame, of the last control leg of a `Swing`, along which the foot
+/// comes in to land.
+const SWING_APPROACH: f32 = 0.5;
+
+impl Swing {
+    /// Up off the ground, highest halfway, and straight down to land.
+    pub const DEFAULT: Self = Self {
+        lift: 0.5,
+        apex: 0.5,
+        touchdown_angle: 0.0,
+    };
+
+    /// `(progress, height)` at `s` of the way through the swing, in the swing's own frame.
+    #[inline]
+    pub fn at(&self, s: f32) -> (f32, f32) {
+        let s = s.clamp(0.0, 1.0);
+        let apex = Vec2::new(self.apex, 1.0);
+        // Level through the apex, so the two curves meet without a kink:
+        let level = Vec2::new(0.5 * self.apex.min(1.0 - self.apex), 0.0);
+        let Vec2 { x, y } = if s < 0.5 {
+            cubic_bezier(
+                [
+                    Vec2::new(0.0, 0.0),
+                    Vec2::new(0.0, self.lift),
+                    apex - level,
+                    apex,
+                ],
+                2.0 * s,
+            )
+        } else {
+            let touchdown = Vec2::new(1.0, 0.0);
+            let approach = Vec2::new(
+                trig::sinf(self.touchdown_angle),
+                -trig::cosf(self.touchdown_angle),
+            ) * SWING_APPROACH;
+            cubic_bezier(
+                [apex, apex + level, touchdown - approach, touchdown],
+                2.0 * s - 1.0,
+            )
+        };
+        (x, y)
+    }
+}
+
+/// How fast a foot that hasn't found the ground by the end of its swing keeps reaching down.
+pub const TERRAIN_PROBE_SPEED: f32 = 2.0;
+/// Furthest any foot will be raised or lowered from neutral to follow the terrain.
+pub const MAX_TERRAIN_OFFSET: f32 = 2.0;
+
+/// How quickly the actual body velocity may chase a new `set_velocity` command.
+pub const MAX_LINEAR_ACCELERATION: f32 = 10.0;
+/// How quickly the actual yaw rate may chase a new `set_velocity` command, in radians per second squared.
+pub const MAX_YAW_ACCELERATION: f32 = 2.0;
+
+/// Names accepted by `Gait::set_parameter` and `Gait::parameter`.
+pub const PARAMETER_NAMES: [&str; 8] = [
+    "step_height",
+    "max_stride_length",
+    "cycle_period_ms",
+    "duty_factor",
+    "min_stability_margin",
+    "swing_lift",
+    "swing_apex",
+    "swing_touchdown_angle",
+];
+
+/// Default for `Gait::set_parameter("min_stability_margin", ...)`.
+pub const DEFAULT_MIN_STABILITY_MARGIN: f32 = 0.5;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum ParameterError {
+    UnknownName,
+    OutOfRange(OutOfRange),
+}
+
+/// Body velocity, as if the robot were a holonomic vehicle.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Velocity {
+    /// Forward, in length units per second.
+    pub x: f32,
+    /// Leftward, in length units per second.
+    pub y: f32,
+    /// Counterclockwise when looking down over the robot, in radians per second.
+    pub yaw_rate: f32,
+}
+
+impl Velocity {
+    /// Direction of travel in the body plane, on [-pi, pi). Zero is forward.
+    #[inline]
+    pub fn heading(&self) -> f32 {
+        trig::atan2f(self.y, self.x)
+    }
+
+    /// Linear speed, ignoring rotation.
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        libm::sqrtf((self.x * self.x) + (self.y * self.y))
+    }
+}
+
+/// Something that can hold the gait below its commanded speed.
+/// Each sets its own scale; the gait follows the smallest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limiter {
+    Battery,
+    Obstacle,
+    /// Supply dips, from `brownout`.
+    Supply,
+}
+
+const LIMITERS: usize = 3;
+
+/// The outgoing half of a blend between two patterns (or duty factors).
+#[derive(Clone, Copy, Debug)]
+struct Transition {
+    pattern: Pattern,
+    duty_factor: f32,
+    /// The outgoing pattern keeps its own cycle running until it has faded out.
+    phase: f32,
+    /// How far the blend has progressed, on [0, 1].
+    blend: f32,
+}
+
+pub struct Gait {
+    pattern: Pattern,
+    parameters: Parameters,
+    /// What the body is actually doing, which chases `commanded_velocity`
+    /// at a limited acceleration so feet don't jump when the command changes.
+    velocity: Velocity,
+    commanded_velocity: Velocity,
+    /// Fraction of `commanded_velocity` each `Limiter` allows, on [0, 1].
+    speed_scales: [f32; LIMITERS],
+    transition: Option<Transition>,
+    /// Where each foot rests when standing still.
+    neutral: [ik::CartesianDisplacementFromEyeCenterLookingForward; N_LEGS],
+    /// Fraction of the way through the current cycle, on [0, 1).
+    phase: f32,
+    /// Latest report from each foot's contact sensor, if it has one.
+    contact: [Option<bool>; N_LEGS],
+    /// Latest report from each foot's pressure sensor (whether it's carrying weight), if it has one.
+    loaded: [Option<bool>; N_LEGS],
+    /// How far above (positive) or below (negative) neutral each foot last found the ground.
+    terrain: [f32; N_LEGS],
+    /// Whether each foot touched down before finishing its current swing.
+    landed_early: [bool; N_LEGS],
+    /// Where the center of mass projects onto the ground, relative to the eye center.
+    center_of_mass: (f32, f32),
+    /// Smallest `body::stability_margin` allowed when lifting a leg.
+    min_stability_margin: f32,
+    /// Whether the last tick held back a swing that would have tipped the robot.
+    stalled: bool,
+    /// Whether the cycle's held where it is on purpose (see `set_paused`).
+    paused: bool,
+}
+
+impl Gait {
+    #[inline]
+    pub fn new(
+        pattern: Pattern,
+        neutral: [ik::CartesianDisplacementFromEyeCenterLookingForward; N_LEGS],
+        parameters: Parameters,
+    ) -> Self {
+        Self {
+            pattern,
+            parameters,
+            velocity: Velocity::default(),
+            commanded_velocity: Velocity::default(),
+            speed_scales: [1.0; LIMITERS],
+            transition: None,
+            neutral,
+            phase: 0.0,
+            contact: [None; N_LEGS],
+            loaded: [None; N_LEGS],
+            terrain: [0.0; N_LEGS],
+            landed_early: [false; N_LEGS],
+            center_of_mass: (0.0, 0.0),
+            min_stability_margin: DEFAULT_MIN_STABILITY_MARGIN,
+            stalled: false,
+            paused: false,
+        }
+    }
+
+    /// Where the center of mass projects onto the ground, relative to the eye center,
+    /// e.g. if a payload is mounted off-center.
+    #[inline]
+    pub fn set_center_of_mass(&mut self, x: f32, y: f32) {
+        self.center_of_mass = (x, y)
+    }
+
+    /// Whether the last tick held back a swing that would have tipped the robot.
+    #[inline]
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Hold the cycle where it is, mid-swing or not, until unpaused. Unlike `body::freeze`,
+    /// this only stops the gait: poses and direct commands still go through.
+    #[inline]
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Where each foot rests when standing still, e.g. `Body::standing_foot_positions`.
+    #[inline]
+    pub fn set_neutral(
+        &mut self,
+        neutral: [ik::CartesianDisplacementFromEyeCenterLookingForward; N_LEGS],
+    ) {
+        self.neutral = neutral
+    }
+
+    #[inline]
+    pub fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    /// Switch patterns without teleporting feet: the new pattern's cycle is lined up
+    /// so that as many legs as possible are already in the right swing/stance state,
+    /// then the two are cross-faded over one cycle.
+    #[inline]
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        if pattern == self.pattern {
+            return;
+        }
+        let () = self.begin_transition();
+        let from_pattern = self.pattern;
+        let from_duty = self.duty_factor();
+        self.pattern = pattern;
+        let to_duty = self.duty_factor();
+
+        // Try a handful of evenly spaced phases for the new pattern
+        // and pick the one whose swing/stance states best match the old pattern's right now:
+        const CANDIDATES: usize = 24;
+        let swinging = |pattern: Pattern, duty_factor: f32, phase: f32, leg: usize| {
+            leg_phase(pattern, phase, leg) < 1.0 - duty_factor
+        };
+        let mismatches = |candidate: f32| {
+            (0..N_LEGS)
+                .filter(|&leg| {
+                    swinging(from_pattern, from_duty, self.phase, leg)
+                        != swinging(pattern, to_duty, candidate, leg)
+                })
+                .count()
+        };
+        let mut best = (self.phase, mismatches(self.phase));
+        for i in 0..CANDIDATES {
+            let candidate = (i as f32) * const { 1.0 / (CANDIDATES as f32) };
+            let count = mismatches(candidate);
+            if count < best.1 {
+                best = (candidate, count);
+            }
+        }
+        self.phase = best.0;
+    }
+
+    /// Snapshot the current pattern as the outgoing half of a cross-fade.
+    #[inline]
+    fn begin_transition(&mut self) {
+        self.transition = Some(Transition {
+            pattern: self.pattern,
+            duty_factor: self.duty_factor(),
+            phase: self.phase,
+            blend: 0.0,
+        });
+    }
+
+    #[inline]
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    #[inline]
+    pub fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+
+    /// Fraction of each leg's cycle spent planted, after any override.
+    #[inline]
+    pub fn duty_factor(&self) -> f32 {
+        self.parameters
+            .duty_factor
+            .unwrap_or_else(|| self.pattern.duty_factor())
+    }
+
+    /// Read a parameter by name, for tuning over a serial link.
+    #[inline]
+    pub fn parameter(&self, name: &str) -> Result<f32, ParameterError> {
+        Ok(match name {
+            "step_height" => self.parameters.step_height,
+            "max_stride_length" => self.parameters.max_stride_length,
+            "cycle_period_ms" => self.parameters.cycle_period.as_micros() as f32 * 1e-3,
+            "duty_factor" => self.duty_factor(),
+            "min_stability_margin" => self.min_stability_margin,
+            "swing_lift" => self.parameters.swing.lift,
+            "swing_apex" => self.parameters.swing.apex,
+            "swing_touchdown_angle" => self.parameters.swing.touchdown_angle,
+            _ => return Err(ParameterError::UnknownName),
+        })
+    }
+
+    /// Set a parameter by name, for tuning over a serial link.
+    /// Takes effect on the next tick; out-of-range values are rejected.
+    #[inline]
+    pub fn set_parameter(&mut self, name: &str, value: f32) -> Result<(), ParameterError> {
+        match name {
+            "step_height" => {
+                let () =
+                    OutOfRange::check(0.0, f32::MAX, value).map_err(ParameterError::OutOfRange)?;
+                self.parameters.step_height = value;
+            }
+            "max_stride_length" => {
+                let () =
+                    OutOfRange::check(0.0, f32::MAX, value).map_err(ParameterError::OutOfRange)?;
+                self.parameters.max_stride_length = value;
+            }
+            "cycle_period_ms" => {
+                let () =
+                    OutOfRange::check(1.0, 60_000.0, value).map_err(ParameterError::OutOfRange)?;
+                self.parameters.cycle_period = Duration::from_micros((value * 1e3) as _);
+            }
+            "duty_factor" => {
+                // Strictly between 0 and 1, so both swing and stance take some time:
+                let () =
+                    OutOfRange::check(0.05, 0.95, value).map_err(ParameterError::OutOfRange)?;
+                let () = self.begin_transition();
+                self.parameters.duty_factor = Some(value);
+            }
+            "min_stability_margin" => {
+                // Negative would allow knowingly tipping over:
+                let () =
+                    OutOfRange::check(0.0, f32::MAX, value).map_err(ParameterError::OutOfRange)?;
+                self.min_stability_margin = value;
+            }
+            "swing_lift" => {
+                let () = OutOfRange::check(0.0, 1.0, value).map_err(ParameterError::OutOfRange)?;
+                self.parameters.swing.lift = value;
+            }
+            "swing_apex" => {
+                let () = OutOfRange::check(MIN_SWING_APEX, 1.0 - MIN_SWING_APEX, value)
+                    .map_err(ParameterError::OutOfRange)?;
+                self.parameters.swing.apex = value;
+            }
+            "swing_touchdown_angle" => {
+                // Any flatter and the foot would never come down:
+                let () = OutOfRange::check(0.0, FRAC_PI_2 - 0.01, value)
+                    .map_err(ParameterError::OutOfRange)?;
+                self.parameters.swing.touchdown_angle = value;
+            }
+            _ => return Err(ParameterError::UnknownName),
+        }
+        Ok(())
+    }
+
+    /// What the body is actually doing right now, which may lag behind `set_velocity`.
+    #[inline]
+    pub fn velocity(&self) -> Velocity {
+        self.velocity
+    }
+
+    /// The latest `set_velocity` command, which `velocity` is ramping toward.
+    #[inline]
+    pub fn commanded_velocity(&self) -> Velocity {
+        self.commanded_velocity
+    }
+
+    /// Foot placements are re-planned every tick, with the actual velocity ramping
+    /// toward this command within `MAX_LINEAR_ACCELERATION` and `MAX_YAW_ACCELERATION`.
+    #[inline]
+    pub fn set_velocity(&mut self, vx: f32, vy: f32, yaw_rate: f32) {
+        self.commanded_velocity = Velocity {
+            x: vx,
+            y: vy,
+            yaw_rate,
+        }
+    }
+
+    /// Let `limiter` allow only this fraction of the commanded velocity (clamped to [0, 1]),
+    /// e.g. to save a sagging battery, without forgetting the command itself.
+    /// The actual velocity still ramps to the new target within the acceleration limits.
+    #[inline]
+    pub fn set_speed_scale(&mut self, limiter: Limiter, scale: f32) {
+        self.speed_scales[limiter as usize] = scale.clamp(0.0, 1.0)
+    }
+
+    /// Fraction of the commanded velocity actually pursued: the strictest limiter's.
+    #[inline]
+    pub fn speed_scale(&self) -> f32 {
+        self.speed_scales.iter().copied().fold(1.0, f32::min)
+    }
+
+    /// Walk at `speed` toward `heading_radians` (0 is forward, counterclockwise is positive)
+    /// without turning, e.g. `PI / 2` to sidestep left.
+    #[inline]
+    pub fn crab(&mut self, heading_radians: f32, speed: f32) {
+        self.set_velocity(
+            speed * trig::cosf(heading_radians),
+            speed * trig::sinf(heading_radians),
+            0.0,
+        )
+    }
+
+    /// Spin on the spot, with every foot stepping along an arc about the body center.
+    #[inline]
+    pub fn turn_in_place(&mut self, yaw_rate: f32) {
+        self.set_velocity(0.0, 0.0, yaw_rate)
+    }
+
+    #[inline]
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    /// Move the cycle forward by `elapsed` and return the new foot targets.
+    #[inline]
+    pub fn advance(
+        &mut self,
+        elapsed: Duration,
+    ) -> [ik::CartesianDisplacementFromEyeCenterLookingForward; N_LEGS] {
+        let cycle_us = self.parameters.cycle_period.as_micros();
+        if cycle_us > 0 && !body::is_frozen() && !self.paused {
+            let seconds = (elapsed.as_micros() as f32) * 1e-6;
+            let (velocity_before, transition_before) = (self.velocity, self.transition);
+            let () = self.ramp_velocity(seconds);
+
+            let before: [f32; N_LEGS] = core::array::from_fn(|i| self.leg_phase(i));
+            let phase_before = self.phase;
+            let dphase = (elapsed.as_micros() as f32) / (cycle_us as f32);
+            self.phase += dphase;
+            self.phase -= libm::floorf(self.phase);
+
+            if let Some(transition) = &mut self.transition {
+                transition.phase += dphase;
+                transition.phase -= libm::floorf(transition.phase);
+                transition.blend += dphase;
+                if transition.blend >= 1.0 {
+                    self.transition = None;
+                }
+            }
+
+            let swing_fraction = 1.0 - self.duty_factor();
+
+            // Before letting any leg lift off, make sure the rest can hold the body up.
+            // If not, hold the whole cycle where it is, blend included, and don't speed up.
+            // Slowing down still goes on (a shorter stride may well be stable), as does
+            // anything else that might help (center of mass, parameters, ...):
+            let lifting_off = (0..N_LEGS).any(|i| self.leg_phase(i) < before[i]);
+            if lifting_off {
+                let planted: [bool; N_LEGS] = core::array::from_fn(|i| self.is_planted(i));
+                let margin =
+                    body::stability_margin(&self.foot_targets(), &planted, self.center_of_mass);
+                self.stalled = margin < self.min_stability_margin;
+                if self.stalled {
+                    self.phase = phase_before;
+                    self.velocity = slower(velocity_before, self.velocity);
+                    self.transition = transition_before;
+                    return self.foot_targets();
+                }
+            }
+
+            let probe = TERRAIN_PROBE_SPEED * seconds;
+            for (i, before) in before.into_iter().enumerate() {
+                let phase = self.leg_phase(i);
+                if phase < before {
+                    // Wrapped around: this leg just lifted off for a new swing.
+                    self.landed_early[i] = false;
+                } else if phase >= swing_fraction && self.contact[i] == Some(false) {
+                    // Late touchdown: supposed to be planted but still in the air,
+                    // so keep reaching down until the sensor finds the ground.
+                    self.terrain[i] = (self.terrain[i] - probe).max(-MAX_TERRAIN_OFFSET);
+                }
+            }
+        }
+        self.foot_targets()
+    }
+
+    /// One tick of walking `body`: `advance` by `elapsed`, then move the body to `pose` over the
+    /// new foot targets. A resting body (see `Body::is_resting`) is left alone, cycle and all,
+    /// so a relax or a sit-down lasts until something explicitly stands it up or attaches it.
+    #[inline]
+    pub fn step(
+        &mut self,
+        body: &mut body::Body<'_, N_LEGS>,
+        pose: body::Pose,
+        elapsed: Duration,
+    ) -> Result<(), body::LegError> {
+        if body.is_resting() {
+            return Ok(());
+        }
+        body.set_pose_and_foot_positions(pose, Some(self.advance(elapsed)))
+    }
+
+    /// Move the actual velocity toward the commanded one, within the acceleration limits.
+    #[inline]
+    fn ramp_velocity(&mut self, seconds: f32) {
+        let towards = |actual: f32, commanded: f32, max_step: f32| {
+            actual + (commanded - actual).clamp(-max_step, max_step)
+        };
+        let linear = MAX_LINEAR_ACCELERATION * seconds;
+        let yaw = MAX_YAW_ACCELERATION * seconds;
+        let scale = self.speed_scale();
+        self.velocity = Velocity {
+            x: towards(self.velocity.x, scale * self.commanded_velocity.x, linear),
+            y: towards(self.velocity.y, scale * self.commanded_velocity.y, linear),
+            yaw_rate: towards(
+                self.velocity.yaw_rate,
+                scale * self.commanded_velocity.yaw_rate,
+                yaw,
+            ),
+        };
+    }
+
+    #[inline]
+    pub fn foot_targets(&self) -> [ik::CartesianDisplacementFromEyeCenterLookingForward; N_LEGS] {
+        let incoming = self.foot_targets_with(self.pattern, self.duty_factor(), self.phase);
+        let Some(transition) = self.transition else {
+            return incoming;
+        };
+        let outgoing =
+            self.foot_targets_with(transition.pattern, transition.duty_factor, transition.phase);
+        // Smoothstep, so the blend starts and ends gently:
+        let t = transition.blend * transition.blend * (3.0 - 2.0 * transition.blend);
+        core::array::from_fn(|i| outgoing[i].lerp(incoming[i], t))
+    }
+
+    #[inline]
+    fn foot_targets_with(
+        &self,
+        pattern: Pattern,
+        duty_factor: f32,
+        phase: f32,
+    ) -> [ik::CartesianDisplacementFromEyeCenterLookingForward; N_LEGS] {
+        let stride = self.stride(duty_factor);
+        core::array::from_fn(|i| {
+            self.foot_target(i, leg_phase(pattern, phase, i), duty_factor, stride)
+        })
+    }
+
+    /// Fraction of the way through this leg's own cycle, which starts at liftoff.
+    #[inline]
+    fn leg_phase(&self, leg: usize) -> f32 {
+        leg_phase(self.pattern, self.phase, leg)
+    }
+
+    /// Whether this leg is meant to be in the air right now.
+    #[inline]
+    pub fn is_swinging(&self, leg: usize) -> bool {
+        self.leg_phase(leg) < 1.0 - self.duty_factor()
+    }
+
+    /// Feed in a foot contact sensor (switch, current sense, ...) reading.
+    /// Legs that never report are assumed to land exactly where planned.
+    #[inline]
+    pub fn report_contact(&mut self, leg: usize, in_contact: bool) {
+        self.contact[leg] = Some(in_contact);
+
+        let swing_fraction = 1.0 - self.duty_factor();
+        let s = self.leg_phase(leg) / swing_fraction;
+        if in_contact && (0.5..1.0).contains(&s) && !self.landed_early[leg] {
+            // Early touchdown: hit the ground on the way down,
+            // so remember the ground is higher here and stop descending.
+            let (_, height) = self.parameters.swing.at(s);
+            self.terrain[leg] =
+                (self.terrain[leg] + self.parameters.step_height * height).min(MAX_TERRAIN_OFFSET);
+            self.landed_early[leg] = true;
+        }
+    }
+
+    /// Feed in a foot pressure sensor reading: whether this foot is carrying weight,
+    /// as opposed to merely touching something.
+    #[inline]
+    pub fn report_load(&mut self, leg: usize, loaded: bool) {
+        self.loaded[leg] = Some(loaded)
+    }
+
+    /// Whether this leg can be counted on to hold the body up: in stance and,
+    /// if it has a pressure sensor, actually carrying weight.
+    #[inline]
+    pub fn is_planted(&self, leg: usize) -> bool {
+        !self.is_swinging(leg) && self.loaded[leg] != Some(false)
+    }
+
+    /// How far above (positive) or below (negative) neutral each foot last found the ground.
+    #[inline]
+    pub fn terrain_offsets(&self) -> &[f32; N_LEGS] {
+        &self.terrain
+    }
+
+    /// Forget everything learned about the terrain, e.g. after being picked up.
+    #[inline]
+    pub fn reset_terrain(&mut self) {
+        self.terrain = [0.0; N_LEGS];
+        self.landed_early = [false; N_LEGS];
+    }
+
+    /// Best-fit plane through the terrain under each foot, as a body pose
+    /// (height and tilt) for `Body::set_pose` that keeps the body parallel to the ground.
+    #[inline]
+    pub fn terrain_pose(&self) -> body::Pose {
+        // Least-squares fit of `z = a + b x + c y` via the normal equations:
+        let mut m = [[0.0_f32; 3]; 3];
+        let mut v = [0.0_f32; 3];
+        for (neutral, &z) in self.neutral.iter().zip(&self.terrain) {
+            let row = [1.0, neutral.x, neutral.y];
+            for (r, &ri) in row.iter().enumerate() {
+                for (c, &ci) in row.iter().enumerate() {
+                    m[r][c] += ri * ci;
+                }
+                v[r] += ri * z;
+            }
+        }
+        let Some([a, b, c]) = solve_3x3(m, v) else {
+            return body::Pose::NEUTRAL;
+        };
+        body::Pose {
+            translation: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                x: 0.0,
+                y: 0.0,
+                z: a,
+            },
+            rotation: body::Rotation {
+                // Ground rising to the left means rolling left-side-up:
+                roll: libm::atanf(c),
+                // Ground rising ahead means pitching nose-up (negative about y):
+                pitch: -libm::atanf(b),
+                yaw: 0.0,
+            }
+            .into(),
+        }
+    }
+
+    /// How far the body moves, relative to the ground, while a foot is planted.
+    #[inline]
+    fn stride(&self, duty_factor: f32) -> Stride {
+        let stance_seconds = duty_factor * (self.parameters.cycle_period.as_micros() as f32) * 1e-6;
+        let Velocity { x, y, yaw_rate } = self.velocity;
+        let mut stride = Stride {
+            x: x * stance_seconds,
+            y: y * stance_seconds,
+            yaw: yaw_rate * stance_seconds,
+        };
+
+        // Measure each foot's chord from touchdown to liftoff
+        // and scale the whole stride down if any of them is too long:
+        let longest_squared = self
+            .neutral
+            .iter()
+            .map(|neutral| {
+                let touchdown = stride.offset(neutral, 0.5);
+                let liftoff = stride.offset(neutral, -0.5);
+                let dx = touchdown.0 - liftoff.0;
+                let dy = touchdown.1 - liftoff.1;
+                (dx * dx) + (dy * dy)
+            })
+            .fold(0.0, f32::max);
+        let max = self.parameters.max_stride_length;
+        if longest_squared > max * max {
+            let scale = max / libm::sqrtf(longest_squared);
+            stride.x *= scale;
+            stride.y *= scale;
+            stride.yaw *= scale;
+        }
+
+        stride
+    }
+
+    /// Each leg swings at the start of its own cycle and is planted for the rest,
+    /// with the split set by the duty factor.
+    #[inline]
+    fn foot_target(
+        &self,
+        leg: usize,
+        phase: f32,
+        duty_factor: f32,
+        stride: Stride,
+    ) -> ik::CartesianDisplacementFromEyeCenterLookingForward {
+        let swing_fraction = 1.0 - duty_factor;
+
+        let neutral = &self.neutral[leg];
+        let step_height = self.parameters.step_height;
+
+        let (progress, lift) = if phase < swing_fraction {
+            // Swing: carried through the air toward the body's heading, along `Swing`'s curve.
+            let (along, height) = self.parameters.swing.at(phase / swing_fraction);
+            let lift = if self.landed_early[leg] {
+                0.0
+            } else {
+                step_height * height
+            };
+            (along - 0.5, lift)
+        } else {
+            // Stance: planted, dragged opposite to the body's heading.
+            let s = (phase - swing_fraction) / duty_factor;
+            (0.5 - s, 0.0)
+        };
+
+        let (x, y) = stride.offset(neutral, progress);
+        ik::CartesianDisplacementFromEyeCenterLookingForward {
+            x,
+            y,
+            z: neutral.z + self.terrain[leg] + lift,
+        }
+    }
+}
+
+/// Fraction of the way through a leg's own cycle, which starts at liftoff.
+#[inline]
+fn leg_phase(pattern: Pattern, phase: f32, leg: usize) -> f32 {
+    let phase = phase - pattern.phase_offset(leg);
+    phase - libm::floorf(phase)
+}
+
+/// Each component of `ramped`, unless that's faster than it was `before`.
+#[inline]
+fn slower(before: Velocity, ramped: Velocity) -> Velocity {
+    let slower = |before: f32, ramped: f32| {
+        if ramped.abs() <= before.abs() {
+            ramped
+        } else {
+            before
+        }
+    };
+    Velocity {
+        x: slower(before.x, ramped.x),
+        y: slower(before.y, ramped.y),
+        yaw_rate: slower(before.yaw_rate, ramped.yaw_rate),
+    }
+}
+
+/// The point `t` of the way along the cubic Bézier curve with these control points.
+#[inline]
+fn cubic_bezier([p0, p1, p2, p3]: [Vec2; 4], t: f32) -> Vec2 {
+    let u = 1.0 - t;
+    p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+/// Cramer's rule. `None` if the system is singular (e.g. every foot in a line).
+#[inline]
+fn solve_3x3(m: [[f32; 3]; 3], v: [f32; 3]) -> Option<[f32; 3]> {
+    let det = |m: &[[f32; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(&m);
+    if libm::fabsf(d) < f32::EPSILON {
+        return None;
+    }
+    Some(core::array::from_fn(|column| {
+        let mut replaced = m;
+        for (row, &value) in replaced.iter_mut().zip(&v) {
+            row[column] = value;
+        }
+        det(&replaced) / d
+    }))
+}
+
+/// Body motion over one stance, shared by every leg.
+#[derive(Clone, Copy, Debug)]
+struct Stride {
+    x: f32,
+    y: f32,
+    yaw: f32,
+}
+
+impl Stride {
+    /// Where a foot sits, `progress` of the way through its stride, on [-0.5, 0.5].
+    /// Rotation swings the foot along an arc about the body center rather than a chord,
+    /// so turning doesn't drag feet inward.
+    #[inline]
+    fn offset(
+        &self,
+        neutral: &ik::CartesianDisplacementFromEyeCenterLookingForward,
+        progress: f32,
+    ) -> (f32, f32) {
+        let Vec2 { x, y } = Rotation2::from_radians(self.yaw * progress)
+            .rotate(Vec3::from(*neutral).xy())
+            + Vec2::new(self.x, self.y) * progress;
+        (x, y)
+    }
+}
@@ -0,0 +1,145 @@
+use {
+    crate::command::{Command, ParseError},
+    embassy_time::Duration,
+};
+
+// A small G-code dialect, one block per line:
+//
+//     G0 L<leg> [X<x>] [Y<y>] [Z<z>]          move a foot as fast as possible
+//     G1 L<leg> [X<x>] [Y<y>] [Z<z>] [F<f>]   move a foot in a straight line at F units/minute
+//     G4 P<milliseconds> | S<seconds>         dwell
+//     M17                                     attach (power servos back up)
+//     M18 | M84                               detach (relax)
+//
+// Coordinates are in the body frame, in the same units as `ik`.
+// Letters are case-insensitive, and everything after a `;` is a comment.
+
+/// Feed rate for a `G1` without an `F` word, in units per minute.
+pub const DEFAULT_FEED_RATE: f32 = 600.0;
+
+/// Whether `parse` should handle this line rather than the plain-text commands:
+/// its first word is a `G` or `M` immediately followed by a number.
+#[inline]
+pub fn looks_like_gcode(line: &str) -> bool {
+    let mut chars = line.trim_start().chars();
+    matches!(chars.next(), Some('G' | 'g' | 'M' | 'm')) && matches!(chars.next(), Some('0'..='9'))
+}
+
+/// Parse one block of G-code (without its line ending).
+#[inline]
+pub fn parse(line: &str) -> Result<Command, ParseError> {
+    let line = match line.split_once(';') {
+        Some((code, _comment)) => code,
+        None => line,
+    };
+
+    let mut command = None;
+    let mut words = Words::default();
+    for word in line.split_ascii_whitespace() {
+        let mut chars = word.chars();
+        let letter = chars.next().ok_or(ParseError::Empty)?.to_ascii_uppercase();
+        let value: f32 = chars.as_str().parse().map_err(|_| ParseError::NotANumber)?;
+        let slot = match letter {
+            'G' | 'M' => {
+                if command.is_some() {
+                    // One motion or machine command per line keeps execution order obvious:
+                    return Err(ParseError::TooManyArguments);
+                }
+                command = Some((letter, value));
+                continue;
+            }
+            'L' => &mut words.l,
+            'X' => &mut words.x,
+            'Y' => &mut words.y,
+            'Z' => &mut words.z,
+            'F' => &mut words.f,
+            'P' => &mut words.p,
+            'S' => &mut words.s,
+            _ => return Err(ParseError::UnknownCommand),
+        };
+        if slot.replace(value).is_some() {
+            return Err(ParseError::TooManyArguments);
+        }
+    }
+
+    let (letter, number) = command.ok_or(ParseError::Empty)?;
+    if number < 0.0 || !is_whole(number) {
+        return Err(ParseError::UnknownCommand);
+    }
+    match (letter, number as u32) {
+        ('G', code @ (0 | 1)) => {
+            let leg = words.l.ok_or(ParseError::MissingArgument)?;
+            let () = unused([words.p, words.s])?;
+            if leg < 0.0 || !is_whole(leg) {
+                return Err(ParseError::NotANumber);
+            }
+            Ok(Command::MoveFoot {
+                leg: leg as usize,
+                x: words.x,
+                y: words.y,
+                z: words.z,
+                speed: if code == 0 {
+                    let () = unused([words.f])?;
+                    None
+                } else {
+                    Some(words.f.unwrap_or(DEFAULT_FEED_RATE) / 60.0)
+                },
+            })
+        }
+        ('G', 4) => {
+            let () = unused([words.l, words.x, words.y, words.z, words.f])?;
+            let milliseconds = match (words.p, words.s) {
+                (Some(p), None) => p,
+                (None, Some(s)) => 1_000.0 * s,
+                (None, None) => return Err(ParseError::MissingArgument),
+                (Some(_), Some(_)) => return Err(ParseError::TooManyArguments),
+            };
+            if milliseconds < 0.0 {
+                return Err(ParseError::NotANumber);
+            }
+            Ok(Command::Dwell(Duration::from_micros(
+                (1_000.0 * milliseconds) as u64,
+            )))
+        }
+        ('M', 17) => {
+            let () = unused([
+                words.l, words.x, words.y, words.z, words.f, words.p, words.s,
+            ])?;
+            Ok(Command::Attach)
+        }
+        ('M', 18 | 84) => {
+            let () = unused([
+                words.l, words.x, words.y, words.z, words.f, words.p, words.s,
+            ])?;
+            Ok(Command::Relax)
+        }
+        _ => Err(ParseError::UnknownCommand),
+    }
+}
+
+/// Every parameter word a block might carry.
+#[derive(Default)]
+struct Words {
+    l: Option<f32>,
+    x: Option<f32>,
+    y: Option<f32>,
+    z: Option<f32>,
+    f: Option<f32>,
+    p: Option<f32>,
+    s: Option<f32>,
+}
+
+/// Reject words the command doesn't take, rather than silently ignoring a typo.
+#[inline]
+fn unused<const N: usize>(words: [Option<f32>; N]) -> Result<(), ParseError> {
+    if words.iter().any(Option::is_some) {
+        Err(ParseError::TooManyArguments)
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+fn is_whole(value: f32) -> bool {
+    libm::truncf(value) == value
+}
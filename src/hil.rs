@@ -0,0 +1,286 @@
+use {
+    crate::{
+        command::{self, Command, LineTooLong},
+        param,
+        plot::{self, Variable},
+        sanitize::{self, Source},
+        telemetry,
+    },
+    core::fmt,
+    embassy_time::{Duration, Instant, Timer},
+};
+
+// Hardware-in-the-loop test scripts, for regression runs against the real robot from a bench PC.
+// On the USB console, `test begin` starts recording a script, one statement per line, and
+// `test end` runs it (or `test abort` throws it away):
+//
+//     <command>                                     anything `command::parse` takes
+//     wait <milliseconds>
+//     expect <variable> <value> <tolerance>         checked once, right away
+//     expect <variable> <value> <tolerance> within <milliseconds>
+//                                                   checked every `plot::PERIOD` until it holds
+//     # ...                                         comment (as are blank lines)
+//
+// Variables are `plot`'s, read from the newest `telemetry::Sample`, so a `telemetry::Sampler`
+// has to be running. Commands that only print something (`status`, `param get`, `param list`,
+// `config show`, `faults`, `plot`) have nowhere to print in a script, so they don't parse;
+// `param set` and `config ...` wait for the motion task to answer, like they do on the console.
+//
+// Every expectation reports a line starting `pass` or `FAIL`, and every script ends with
+// exactly one line starting `result: pass`, `result: fail` or `result: error` (didn't parse,
+// so never ran), for tooling to grep. A command the motion layer refuses stops the run there,
+// since nothing after it would mean much.
+
+/// Most statements one script can hold, comments and blank lines aside.
+pub const MAX_STEPS: usize = 64;
+
+/// How long a command may wait on `sanitize`'s rate limit (or a full queue) before it's refused.
+pub const SUBMIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Step {
+    Command(Command),
+    Wait(Duration),
+    Expect(Expectation),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Expectation {
+    pub variable: Variable,
+    pub value: f32,
+    pub tolerance: f32,
+    /// How long to keep checking before giving up. Zero checks just once.
+    pub within: Duration,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntParse {
+    Command(command::ParseError),
+    TooLong,
+    /// Only prints something, which a script has nowhere to show.
+    NotScriptable,
+    UnknownVariable,
+    MissingArgument,
+    TooManyArguments,
+    NotANumber,
+    NegativeTolerance,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntLoad {
+    Parse { line: u16, error: CouldntParse },
+    TooManySteps { line: u16 },
+}
+
+/// How one step went.
+#[derive(Debug)]
+pub enum Outcome {
+    Done,
+    Passed {
+        actual: f32,
+    },
+    /// `actual` is the last value seen, or NaN if there was never a sample to read.
+    Failed {
+        actual: f32,
+    },
+    Refused(Refusal),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum Refusal {
+    Command(command::Refused),
+    Parameter(param::CouldntAccess),
+    Config(command::CouldntExecute),
+    /// The motion task never answered, e.g. because nothing is running a `command::Executor`.
+    NoAnswer,
+}
+
+/// A script as it's recorded, line by line, each step tagged with its line number.
+#[derive(Debug, Default)]
+pub struct Script {
+    steps: heapless::Vec<(u16, Step), MAX_STEPS>,
+    lines: u16,
+    /// The first line with anything wrong, if any.
+    first_error: Option<u16>,
+}
+
+impl Script {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            steps: heapless::Vec::new(),
+            lines: 0,
+            first_error: None,
+        }
+    }
+
+    /// Parse and append one line, as `command::Lines` hands it over. A script that's had an
+    /// error keeps counting lines (so later errors still report the right line), but can't run.
+    #[inline]
+    pub fn push(&mut self, line: Result<&[u8], LineTooLong>) -> Result<(), CouldntLoad> {
+        self.lines = self.lines.saturating_add(1);
+        let parsed = line
+            .map_err(|LineTooLong| CouldntParse::TooLong)
+            .and_then(|line| {
+                core::str::from_utf8(line)
+                    .map_err(|_| CouldntParse::Command(command::ParseError::NotUtf8))
+                    .and_then(parse)
+            });
+        let line = self.lines;
+        let result = match parsed {
+            Ok(None) => Ok(()),
+            Ok(Some(step)) => self
+                .steps
+                .push((line, step))
+                .map_err(|_| CouldntLoad::TooManySteps { line }),
+            Err(error) => Err(CouldntLoad::Parse { line, error }),
+        };
+        if result.is_err() {
+            let _: &mut u16 = self.first_error.get_or_insert(line);
+        }
+        result
+    }
+
+    /// Every step with its line number, or the first line that went wrong.
+    #[inline]
+    pub fn steps(&self) -> Result<&[(u16, Step)], u16> {
+        match self.first_error {
+            Some(line) => Err(line),
+            None => Ok(&self.steps),
+        }
+    }
+}
+
+/// One line of a script, or `None` for a comment or blank line.
+#[inline]
+pub fn parse(line: &str) -> Result<Option<Step>, CouldntParse> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let mut words = line.split_ascii_whitespace();
+    let step = match words.next() {
+        Some("wait") => Step::Wait(Duration::from_millis(number(&mut words)?)),
+        Some("expect") => {
+            let variable = Variable::parse(words.next().ok_or(CouldntParse::MissingArgument)?)
+                .ok_or(CouldntParse::UnknownVariable)?;
+            let value = number(&mut words)?;
+            let tolerance: f32 = number(&mut words)?;
+            if tolerance.is_nan() || tolerance < 0.0 {
+                return Err(CouldntParse::NegativeTolerance);
+            }
+            let within = match words.next() {
+                None => Duration::from_ticks(0),
+                Some("within") => Duration::from_millis(number(&mut words)?),
+                Some(_) => return Err(CouldntParse::TooManyArguments),
+            };
+            Step::Expect(Expectation {
+                variable,
+                value,
+                tolerance,
+                within,
+            })
+        }
+        _ => {
+            return match command::parse(line).map_err(CouldntParse::Command)? {
+                Command::Status
+                | Command::GetParameter(_)
+                | Command::ListParameters
+                | Command::ShowConfig
+                | Command::DumpFaults
+                | Command::DumpLog
+                | Command::Plot(_) => Err(CouldntParse::NotScriptable),
+                command => Ok(Some(Step::Command(command))),
+            };
+        }
+    };
+    match words.next() {
+        None => Ok(Some(step)),
+        Some(_) => Err(CouldntParse::TooManyArguments),
+    }
+}
+
+#[inline]
+fn number<'a, T: core::str::FromStr>(
+    words: &mut impl Iterator<Item = &'a str>,
+) -> Result<T, CouldntParse> {
+    words
+        .next()
+        .ok_or(CouldntParse::MissingArgument)?
+        .parse()
+        .map_err(|_| CouldntParse::NotANumber)
+}
+
+/// Carry out one step, waiting for however long it takes.
+#[inline]
+pub async fn run(step: &Step) -> Outcome {
+    match *step {
+        Step::Command(command @ Command::SetParameter(..)) => {
+            match command::query_parameter(command).await {
+                Some(Ok(_)) => Outcome::Done,
+                Some(Err(e)) => Outcome::Refused(Refusal::Parameter(e)),
+                None => Outcome::Refused(Refusal::NoAnswer),
+            }
+        }
+        Step::Command(
+            command @ (Command::SaveConfig | Command::LoadConfig | Command::ResetConfig),
+        ) => match command::query_config(command).await {
+            Some(Ok(())) => Outcome::Done,
+            Some(Err(e)) => Outcome::Refused(Refusal::Config(e)),
+            None => Outcome::Refused(Refusal::NoAnswer),
+        },
+        Step::Command(command) => {
+            let deadline = Instant::now() + SUBMIT_TIMEOUT;
+            loop {
+                match command::submit(Source::Usb, command).await {
+                    Ok(()) => return Outcome::Done,
+                    Err(command::Refused::RateLimited | command::Refused::QueueFull)
+                        if Instant::now() < deadline =>
+                    {
+                        let () = Timer::after_micros(1_000_000 / sanitize::RATE_HZ as u64).await;
+                    }
+                    Err(e) => return Outcome::Refused(Refusal::Command(e)),
+                }
+            }
+        }
+        Step::Wait(duration) => {
+            let () = Timer::after(duration).await;
+            Outcome::Done
+        }
+        Step::Expect(expectation) => expectation.check().await,
+    }
+}
+
+impl Expectation {
+    #[inline]
+    async fn check(&self) -> Outcome {
+        let deadline = Instant::now() + self.within;
+        let mut actual = f32::NAN;
+        loop {
+            if let Some(sample) = telemetry::latest() {
+                actual = self.variable.value(&sample);
+                if (actual - self.value).abs() <= self.tolerance {
+                    return Outcome::Passed { actual };
+                }
+            }
+            if Instant::now() >= deadline {
+                return Outcome::Failed { actual };
+            }
+            let () = Timer::after(plot::PERIOD).await;
+        }
+    }
+}
+
+impl fmt::Display for Expectation {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} +/- {}", self.variable, self.value, self.tolerance)?;
+        if self.within.as_ticks() > 0 {
+            write!(f, " within {} ms", self.within.as_millis())?;
+        }
+        Ok(())
+    }
+}
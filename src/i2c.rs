@@ -0,0 +1,178 @@
+use {
+    crate::{
+        body::{self, Body},
+        command::{self, Command},
+        gait::Gait,
+        ik,
+        sanitize::Source,
+    },
+    core::cell::RefCell,
+    embassy_rp::{
+        i2c,
+        i2c_slave::{self, I2cSlave},
+    },
+    embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+};
+
+// Register map, one byte per address, multi-byte values little-endian f32:
+//
+//     0x00        status flags (read-only): bit 0 frozen, bit 1 stalled, bit 2 holding
+//     0x01        leg count (read-only)
+//     0x04..0x10  gait velocity: x, y, yaw rate (read/write)
+//     0x10..0x58  target foot positions, body frame: x, y, z for each of six legs (read/write)
+//     0x58..0xA0  joint angles in radians: yaw, hip, knee for each of six legs (read-only)
+//
+// A master writes a register address, then (optionally) data starting there; reads continue
+// from the last address. Writing the last byte of a velocity or foot block sends its command.
+
+pub const REGISTER_STATUS: u8 = 0x00;
+pub const REGISTER_LEG_COUNT: u8 = 0x01;
+pub const REGISTER_VELOCITY: u8 = 0x04;
+pub const REGISTER_FEET: u8 = 0x10;
+pub const REGISTER_JOINT_ANGLES: u8 = 0x58;
+/// Legs the register map has room for.
+pub const MAX_LEGS: usize = 6;
+const REGISTERS: usize = REGISTER_JOINT_ANGLES as usize + MAX_LEGS * BLOCK;
+/// Three f32s.
+const BLOCK: usize = 12;
+
+const STATUS_FROZEN: u8 = 1 << 0;
+const STATUS_STALLED: u8 = 1 << 1;
+const STATUS_HOLDING: u8 = 1 << 2;
+
+/// What a master reads back, refreshed by the motion task through `publish`.
+static REGISTER_IMAGE: Mutex<CriticalSectionRawMutex, RefCell<[u8; REGISTERS]>> =
+    Mutex::new(RefCell::new([0; REGISTERS]));
+
+/// Refresh the read-only registers (and the read-back of the writable ones) from the body
+/// and gait. Meant to be called by the motion task every tick.
+#[inline]
+pub fn publish<const N: usize>(body: &Body<'_, N>, gait: &Gait) {
+    let holding = body.foot_positions().is_some();
+    let angles = body.joint_angles();
+    let velocity = gait.commanded_velocity();
+    REGISTER_IMAGE.lock(|image| {
+        let mut image = image.borrow_mut();
+        image[REGISTER_STATUS as usize] = (if body::is_frozen() { STATUS_FROZEN } else { 0 })
+            | (if gait.is_stalled() { STATUS_STALLED } else { 0 })
+            | (if holding { STATUS_HOLDING } else { 0 });
+        image[REGISTER_LEG_COUNT as usize] = N.min(MAX_LEGS) as u8;
+        let () = put(
+            &mut image[..],
+            REGISTER_VELOCITY as usize,
+            [velocity.x, velocity.y, velocity.yaw_rate],
+        );
+        for i in 0..N.min(MAX_LEGS) {
+            if let Some(foot) = body.legs()[i].target() {
+                let () = put(
+                    &mut image[..],
+                    REGISTER_FEET as usize + i * BLOCK,
+                    [foot.x, foot.y, foot.z],
+                );
+            }
+            let ik::Angles { yaw, hip, knee } = angles[i];
+            let () = put(
+                &mut image[..],
+                REGISTER_JOINT_ANGLES as usize + i * BLOCK,
+                [yaw, hip, knee],
+            );
+        }
+    })
+}
+
+#[inline]
+fn put(image: &mut [u8], address: usize, values: [f32; 3]) {
+    for (i, value) in values.into_iter().enumerate() {
+        let () =
+            image[address + 4 * i..address + 4 * (i + 1)].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+#[inline]
+fn get(image: &[u8], address: usize) -> [f32; 3] {
+    core::array::from_fn(|i| {
+        let mut bytes = [0; 4];
+        let () = bytes.copy_from_slice(&image[address + 4 * i..address + 4 * (i + 1)]);
+        f32::from_le_bytes(bytes)
+    })
+}
+
+/// Serve the register map to an I2C master forever.
+#[inline]
+pub async fn serve<T: i2c::Instance>(mut device: I2cSlave<'static, T>) -> ! {
+    let mut buffer = [0; 1 + REGISTERS];
+    let mut address = 0;
+    loop {
+        let command = match device.listen(&mut buffer).await {
+            Ok(command) => command,
+            Err(e) => {
+                let () = crate::warn!("I2C error: {e:?}");
+                continue;
+            }
+        };
+        let written = match command {
+            i2c_slave::Command::Read => None,
+            i2c_slave::Command::Write(n) | i2c_slave::Command::WriteRead(n) if n > 0 => {
+                Some(&buffer[..n])
+            }
+            i2c_slave::Command::Write(_) | i2c_slave::Command::WriteRead(_) => None,
+            // Nothing on this bus should be broadcasting to us:
+            i2c_slave::Command::GeneralCall(_) => continue,
+        };
+        if let Some((&register, data)) = written.and_then(<[u8]>::split_first) {
+            address = (register as usize).min(REGISTERS);
+            let () = write(address, data).await;
+        }
+        if matches!(
+            command,
+            i2c_slave::Command::Read | i2c_slave::Command::WriteRead(_)
+        ) {
+            let image = REGISTER_IMAGE.lock(|image| *image.borrow());
+            // Past the end of the map, read zeros:
+            if let Err(e) = device.respond_and_fill(&image[address..], 0x00).await {
+                let () = crate::warn!("I2C error: {e:?}");
+            }
+        }
+    }
+}
+
+/// Store written bytes into the writable registers and send a command for every block
+/// whose last byte was written. Read-only registers silently ignore writes.
+#[inline]
+async fn write(address: usize, data: &[u8]) {
+    let image = REGISTER_IMAGE.lock(|image| {
+        let mut image = image.borrow_mut();
+        for (offset, &byte) in data.iter().enumerate() {
+            let register = address + offset;
+            if (REGISTER_VELOCITY as usize..REGISTER_JOINT_ANGLES as usize).contains(&register) {
+                image[register] = byte;
+            }
+        }
+        *image
+    });
+
+    let end = address + data.len();
+    let completes = |block: usize| (address..end).contains(&(block + BLOCK - 1));
+    if completes(REGISTER_VELOCITY as usize) {
+        let [x, y, yaw_rate] = get(&image, REGISTER_VELOCITY as usize);
+        let () = submit(Command::GaitVelocity { x, y, yaw_rate }).await;
+    }
+    for leg in 0..MAX_LEGS {
+        let block = REGISTER_FEET as usize + leg * BLOCK;
+        if completes(block) {
+            let [x, y, z] = get(&image, block);
+            let () = submit(Command::FootPosition {
+                leg,
+                position: ik::CartesianDisplacementFromEyeCenterLookingForward { x, y, z },
+            })
+            .await;
+        }
+    }
+}
+
+#[inline]
+async fn submit(command: Command) {
+    if let Err(e) = command::submit(Source::I2c, command).await {
+        let () = crate::warn!("Refused an I2C command: {e:?}");
+    }
+}
@@ -0,0 +1,89 @@
+use {
+    crate::rc,
+    embassy_rp::uart::{self, Async, UartRx},
+};
+
+// FlySky iBUS frames are 32 bytes, sent every 7 ms:
+//
+//     0x20 | 0x40 | 14 channels x u16 | checksum: u16
+//
+// all little-endian, with channels nominally 1000 to 2000 and the checksum being
+// 0xFFFF minus the sum of every byte before it.
+
+const FRAME_LENGTH: usize = 32;
+const HEADER: [u8; 2] = [0x20, 0x40];
+const CHANNELS: usize = 14;
+
+/// 115 200 baud, 8N1, not inverted.
+#[inline]
+pub fn config() -> uart::Config {
+    let mut config = uart::Config::default();
+    config.baudrate = 115_200;
+    config
+}
+
+/// Reassembles frames from a byte stream, resynchronizing on its own after garbage.
+#[derive(Debug)]
+pub struct Decoder {
+    buffer: [u8; FRAME_LENGTH],
+    length: usize,
+}
+
+impl Default for Decoder {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            buffer: [0; FRAME_LENGTH],
+            length: 0,
+        }
+    }
+}
+
+impl Decoder {
+    /// Feed one byte, getting every channel's value back whenever a valid frame completes.
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Option<[u16; CHANNELS]> {
+        if self.length < HEADER.len() && byte != HEADER[self.length] {
+            // Maybe this byte starts the next header instead:
+            self.length = 0;
+            if byte != HEADER[0] {
+                return None;
+            }
+        }
+        self.buffer[self.length] = byte;
+        self.length += 1;
+        if self.length < FRAME_LENGTH {
+            return None;
+        }
+        self.length = 0;
+
+        let (body, checksum) = self.buffer.split_at(FRAME_LENGTH - 2);
+        let expected = body
+            .iter()
+            .fold(0xFFFF_u16, |sum, &b| sum.wrapping_sub(b as u16));
+        if u16::from_le_bytes([checksum[0], checksum[1]]) != expected {
+            return None;
+        }
+        Some(core::array::from_fn(|i| {
+            u16::from_le_bytes([body[2 + 2 * i], body[3 + 2 * i]])
+        }))
+    }
+}
+
+/// Decode iBUS from a UART configured with `config` forever,
+/// publishing each frame through `rc` under `mapping`.
+#[inline]
+pub async fn read<T: uart::Instance>(mut rx: UartRx<'static, T, Async>, mapping: rc::Mapping) -> ! {
+    let mut decoder = Decoder::default();
+    loop {
+        let mut byte = [0];
+        if let Err(e) = rx.read(&mut byte).await {
+            let () = crate::warn!("iBUS read error: {e:?}");
+            decoder = Decoder::default();
+            continue;
+        }
+        if let Some(channels) = decoder.push(byte[0]) {
+            let () = rc::publish(&mapping, &rc::normalize_microseconds(&channels, false));
+        }
+    }
+}
@@ -1,10 +1,18 @@
-use {crate::pwm, core::f32::consts::PI};
+use {
+    crate::{
+        math::{Vec2, Vec3},
+        trig,
+    },
+    core::f32::consts::PI,
+};
 
 pub const LENGTH_CENTER_TO_YAW: f32 = 0.900;
 pub const LENGTH_YAW_TO_HIP: f32 = 0.574;
 pub const LENGTH_HIP_TO_KNEE: f32 = 2.563;
 pub const LENGTH_KNEE_TO_FOOT: f32 = 5.467;
 
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CartesianDisplacementFromEyeCenterLookingForward {
     /// Along the axis formed if the eye were to shoot a laser out of its pupil,
     /// parallel to the ground.
@@ -15,35 +23,63 @@ pub struct CartesianDisplacementFromEyeCenterLookingForward {
     pub z: f32,
 }
 
+/// Leg-local coordinates, relative to a leg's yaw servo rather than the eye center.
+/// Useful for calibrating or testing a single leg in isolation.
+#[derive(Clone, Copy, Debug)]
+pub struct CartesianDisplacementFromYawServo {
+    /// Along the leg's home direction, i.e. straight out when the yaw servo is centered.
+    pub x: f32,
+    /// Perpendicular to `x`, counterclockwise when looking down over the robot.
+    pub y: f32,
+    /// Up and down, perpendicular to the ground.
+    pub z: f32,
+}
+
 pub struct HipToFootDisplacementIn2dPlane {
     pub x: f32,
     pub y: f32,
 }
 
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Angles {
     pub yaw: f32,
     pub hip: f32,
     pub knee: f32,
 }
 
+/// Radians from each servo's center, turning the way that servo does (see `KNEE_DIRECTION`),
+/// like `Angles`.
 pub struct HipAndKneeAngles {
     pub hip: f32,
     pub knee: f32,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
 pub enum HipToFootError {
     Unreachable(Unreachable),
+    TooClose(TooClose),
     KneeLock(KneeLock),
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
 pub struct Unreachable {
     pub reach_from_hip: f32,
     pub distance: f32,
 }
 
+/// Closer to the hip than the leg can fold, even with the shin doubled back along the thigh.
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub struct TooClose {
+    pub min_reach_from_hip: f32,
+    pub distance: f32,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
 pub enum AngleOutOfRange {
     Yaw { radians: f32 },
     Hip { radians: f32 },
@@ -51,11 +87,97 @@ pub enum AngleOutOfRange {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
 pub enum KneeLock {
     TooClose { hip: f32, knee: f32 },
     TooFar { hip: f32, knee: f32 },
 }
 
+impl CartesianDisplacementFromEyeCenterLookingForward {
+    #[inline]
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// `radius` out from the eye center along `heading_radians` (counterclockwise from `x`,
+    /// looking down), at height `z`.
+    #[inline]
+    pub fn from_heading(heading_radians: f32, radius: f32, z: f32) -> Self {
+        Vec2::from_angle(heading_radians, radius).with_z(z).into()
+    }
+
+    /// `from_heading`, in degrees.
+    #[inline]
+    pub fn from_heading_degrees(heading_degrees: f32, radius: f32, z: f32) -> Self {
+        Self::from_heading(heading_degrees.to_radians(), radius, z)
+    }
+
+    /// Linear interpolation: `t = 0` is `self`, `t = 1` is `other`.
+    #[inline]
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Vec3::from(self).lerp(other.into(), t).into()
+    }
+}
+
+impl From<Vec3> for CartesianDisplacementFromEyeCenterLookingForward {
+    #[inline]
+    fn from(Vec3 { x, y, z }: Vec3) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<CartesianDisplacementFromEyeCenterLookingForward> for Vec3 {
+    #[inline]
+    fn from(
+        CartesianDisplacementFromEyeCenterLookingForward { x, y, z }: CartesianDisplacementFromEyeCenterLookingForward,
+    ) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Vec3> for CartesianDisplacementFromYawServo {
+    #[inline]
+    fn from(Vec3 { x, y, z }: Vec3) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<CartesianDisplacementFromYawServo> for Vec3 {
+    #[inline]
+    fn from(
+        CartesianDisplacementFromYawServo { x, y, z }: CartesianDisplacementFromYawServo,
+    ) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl Angles {
+    #[inline]
+    pub const fn new(yaw: f32, hip: f32, knee: f32) -> Self {
+        Self { yaw, hip, knee }
+    }
+
+    /// Each joint's angle given in degrees, kept (like everywhere else) in radians.
+    #[inline]
+    pub fn from_degrees(yaw: f32, hip: f32, knee: f32) -> Self {
+        Self {
+            yaw: yaw.to_radians(),
+            hip: hip.to_radians(),
+            knee: knee.to_radians(),
+        }
+    }
+
+    /// Each joint's angle in degrees, for display.
+    #[inline]
+    pub fn to_degrees(self) -> Self {
+        Self {
+            yaw: self.yaw.to_degrees(),
+            hip: self.hip.to_degrees(),
+            knee: self.knee.to_degrees(),
+        }
+    }
+}
+
 impl HipToFootDisplacementIn2dPlane {
     #[inline]
     pub fn magnitude_squared(&self) -> f32 {
@@ -63,67 +185,184 @@ impl HipToFootDisplacementIn2dPlane {
     }
 }
 
+/// The two links `hip_to_foot_2d` folds, in the same units as `LENGTH_*`.
+#[derive(Clone, Copy, Debug)]
+pub struct Geometry {
+    pub hip_to_knee: f32,
+    pub knee_to_foot: f32,
+}
+
+/// Everything the planar IK and FK need from a `Geometry`, worked out once by
+/// `Geometry::precompute` (at compile time, for a `const`), so each call does only the
+/// arithmetic that depends on its target.
+#[derive(Clone, Copy, Debug)]
+pub struct Precomputed {
+    geometry: Geometry,
+    reach_from_hip: f32,
+    min_reach_from_hip: f32,
+    /// `hip_to_knee^2 - knee_to_foot^2`, for the hip's law of cosines.
+    squares_difference: f32,
+    /// `hip_to_knee^2 + knee_to_foot^2`, for the knee's.
+    squares_sum: f32,
+    half_over_hip_to_knee: f32,
+    hip_to_knee_times_knee_to_foot: f32,
+}
+
+/// The hip turns the same way as its servo.
+pub const HIP_DIRECTION: f32 = 1.0;
+/// The knee turns against its servo.
+pub const KNEE_DIRECTION: f32 = -1.0;
+
+impl Geometry {
+    /// This robot's legs.
+    pub const DEFAULT: Self = Self {
+        hip_to_knee: LENGTH_HIP_TO_KNEE,
+        knee_to_foot: LENGTH_KNEE_TO_FOOT,
+    };
+
+    #[inline]
+    pub const fn precompute(self) -> Precomputed {
+        let Self {
+            hip_to_knee,
+            knee_to_foot,
+        } = self;
+        Precomputed {
+            geometry: self,
+            reach_from_hip: hip_to_knee + knee_to_foot,
+            min_reach_from_hip: knee_to_foot - hip_to_knee,
+            squares_difference: hip_to_knee * hip_to_knee - knee_to_foot * knee_to_foot,
+            squares_sum: hip_to_knee * hip_to_knee + knee_to_foot * knee_to_foot,
+            half_over_hip_to_knee: 0.5 / hip_to_knee,
+            hip_to_knee_times_knee_to_foot: hip_to_knee * knee_to_foot,
+        }
+    }
+}
+
+/// `Geometry::DEFAULT`, precomputed.
+pub const DEFAULT: Precomputed = Geometry::DEFAULT.precompute();
+
+/// Farthest the foot gets from the hip, with the leg straight.
+pub const REACH_FROM_HIP: f32 = DEFAULT.reach_from_hip;
+
+/// Nearest the foot gets to the hip, with the shin doubled back along the thigh.
+pub const MIN_REACH_FROM_HIP: f32 = DEFAULT.min_reach_from_hip;
+
 #[inline]
 pub fn hip_to_foot_2d(
     displacement: HipToFootDisplacementIn2dPlane,
 ) -> Result<HipAndKneeAngles, HipToFootError> {
-    const LENGTH_HIP_TO_KNEE_SQUARED: f32 = LENGTH_HIP_TO_KNEE * LENGTH_HIP_TO_KNEE;
-    const LENGTH_KNEE_TO_FOOT_SQUARED: f32 = LENGTH_KNEE_TO_FOOT * LENGTH_KNEE_TO_FOOT;
-    const REACH_FROM_HIP: f32 = LENGTH_HIP_TO_KNEE + LENGTH_KNEE_TO_FOOT;
-
-    let distance_squared = displacement.magnitude_squared();
-    let distance = libm::sqrtf(distance_squared);
-    let HipToFootDisplacementIn2dPlane { x, y } = displacement;
-
-    {
-        // Check if this point is even reachable:
-        if distance > REACH_FROM_HIP {
-            return Err(HipToFootError::Unreachable(Unreachable {
-                reach_from_hip: REACH_FROM_HIP,
-                distance,
-            }));
-        }
+    DEFAULT.hip_to_foot_2d(displacement)
+}
+
+/// Forward kinematics for `hip_to_foot_2d`: where the foot ends up for angles it returned.
+#[inline]
+pub fn hip_and_knee_to_foot_2d(angles: HipAndKneeAngles) -> HipToFootDisplacementIn2dPlane {
+    DEFAULT.hip_and_knee_to_foot_2d(angles)
+}
+
+impl Precomputed {
+    #[inline]
+    pub const fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    #[inline]
+    pub const fn reach_from_hip(&self) -> f32 {
+        self.reach_from_hip
     }
 
-    let hip_radians = {
-        // Law of cosines:
-        // L_2^2 = L_1^2 + hypotenuse^2 - 2 L_1 hypotenuse cos(hip_internal_radians)
-        // ==> cos(hip_internal_radians) = L_1^2 + hypotenuse^2 - L_2^2 / 2 L_1 hypotenuse
-        let hip_internal_radians = {
-            let cos_hip_internal_radians = {
-                (const { LENGTH_HIP_TO_KNEE_SQUARED - LENGTH_KNEE_TO_FOOT_SQUARED }
-                    + distance_squared)
-                    * const { 0.5 / LENGTH_HIP_TO_KNEE }
-                    / distance
+    #[inline]
+    pub const fn min_reach_from_hip(&self) -> f32 {
+        self.min_reach_from_hip
+    }
+
+    #[inline]
+    pub fn hip_to_foot_2d(
+        &self,
+        displacement: HipToFootDisplacementIn2dPlane,
+    ) -> Result<HipAndKneeAngles, HipToFootError> {
+        let distance_squared = displacement.magnitude_squared();
+        let distance = libm::sqrtf(distance_squared);
+        let HipToFootDisplacementIn2dPlane { x, y } = displacement;
+
+        {
+            // Check if this point is even reachable:
+            if distance > self.reach_from_hip {
+                return Err(HipToFootError::Unreachable(Unreachable {
+                    reach_from_hip: self.reach_from_hip,
+                    distance,
+                }));
+            }
+            // Otherwise the laws of cosines below would take `acos` of something past 1
+            // (or divide by 0):
+            if distance < self.min_reach_from_hip {
+                return Err(HipToFootError::TooClose(TooClose {
+                    min_reach_from_hip: self.min_reach_from_hip,
+                    distance,
+                }));
+            }
+        }
+
+        let hip_radians = {
+            // Law of cosines:
+            // L_2^2 = L_1^2 + hypotenuse^2 - 2 L_1 hypotenuse cos(hip_internal_radians)
+            // ==> cos(hip_internal_radians) = L_1^2 + hypotenuse^2 - L_2^2 / 2 L_1 hypotenuse
+            let hip_internal_radians = {
+                let cos_hip_internal_radians = {
+                    (self.squares_difference + distance_squared) * self.half_over_hip_to_knee
+                        / distance
+                };
+                // Within [-1, 1] given the checks above,
+                // but rounding can nudge it past at full reach:
+                trig::acosf(cos_hip_internal_radians.clamp(-1.0, 1.0))
             };
-            libm::acosf(cos_hip_internal_radians)
-        };
 
-        // Arctangent of the whole enchilada on [-pi, pi):
-        let sigma_radians = libm::atan2f(y, x);
+            // Arctangent of the whole enchilada on [-pi, pi):
+            let sigma_radians = trig::atan2f(y, x);
 
-        sigma_radians + hip_internal_radians
-    };
+            sigma_radians + hip_internal_radians
+        };
 
-    let knee_radians = {
-        // Law of cosines:
-        // hypotenuse^2 = L_1^2 + L_2^2 - 2 L_1 L_2 cos(knee_internal_radians)
-        // ==> cos(knee_internal_radians) = L_1^2 + L_2^2 - hypotenuse^2 / 2 L_1 L_2
-        let knee_internal_radians = {
-            let cos_knee_internal_radians =
-                (const { LENGTH_HIP_TO_KNEE_SQUARED + LENGTH_KNEE_TO_FOOT_SQUARED }
-                    - distance_squared)
-                    * 0.5
-                    / const { LENGTH_HIP_TO_KNEE * LENGTH_KNEE_TO_FOOT };
-            libm::acosf(cos_knee_internal_radians)
+        let knee_radians = {
+            // Law of cosines:
+            // hypotenuse^2 = L_1^2 + L_2^2 - 2 L_1 L_2 cos(knee_internal_radians)
+            // ==> cos(knee_internal_radians) = L_1^2 + L_2^2 - hypotenuse^2 / 2 L_1 L_2
+            let knee_internal_radians = {
+                let cos_knee_internal_radians = (self.squares_sum - distance_squared) * 0.5
+                    / self.hip_to_knee_times_knee_to_foot;
+                trig::acosf(cos_knee_internal_radians.clamp(-1.0, 1.0))
+            };
+            knee_internal_radians - const { 0.5 * PI } + hip_radians
         };
-        knee_internal_radians - const { 0.5 * PI } + hip_radians
-    };
 
-    let hip = hip_radians * pwm::RADIANS_TO_SERVO;
-    let knee = -knee_radians * pwm::RADIANS_TO_SERVO;
+        let hip = hip_radians * HIP_DIRECTION;
+        let knee = knee_radians * KNEE_DIRECTION;
 
-    // TODO: Knee lock!
+        // TODO: Knee lock!
 
-    Ok(HipAndKneeAngles { hip, knee })
+        Ok(HipAndKneeAngles { hip, knee })
+    }
+
+    /// Forward kinematics for `hip_to_foot_2d`: where the foot ends up with the hip and knee
+    /// at these angles, in radians from center as each servo turns.
+    #[inline]
+    pub fn hip_and_knee_to_foot_2d(
+        &self,
+        HipAndKneeAngles { hip, knee }: HipAndKneeAngles,
+    ) -> HipToFootDisplacementIn2dPlane {
+        let hip_radians = hip * HIP_DIRECTION;
+        let knee_radians = knee * KNEE_DIRECTION;
+        // Undo `knee_radians = knee_internal_radians - pi/2 + hip_radians`:
+        let knee_internal_radians = knee_radians + const { 0.5 * PI } - hip_radians;
+        // The thigh points along `hip_radians`; the shin turns back by the knee's internal angle:
+        let shin_radians = hip_radians - PI + knee_internal_radians;
+        let Geometry {
+            hip_to_knee,
+            knee_to_foot,
+        } = self.geometry;
+        HipToFootDisplacementIn2dPlane {
+            x: hip_to_knee * trig::cosf(hip_radians) + knee_to_foot * trig::cosf(shin_radians),
+            y: hip_to_knee * trig::sinf(hip_radians) + knee_to_foot * trig::sinf(shin_radians),
+        }
+    }
 }
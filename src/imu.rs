@@ -0,0 +1,213 @@
+use {
+    crate::{fusion, math::Quaternion},
+    core::cell::RefCell,
+    embassy_rp::i2c::{self, Async, I2c},
+    embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    embassy_time::{Duration, Instant, Ticker, Timer},
+};
+
+// Both supported chips are set up for the same ranges, so readings convert the same way:
+//
+//     accelerometer  +/-4 g        8192 counts per g
+//     gyroscope      +/-500 deg/s  65.5 counts per deg/s
+//
+// Their axes are taken to line up with the body frame (x forward, y left, z up);
+// mount the board that way, or swap axes in `Reading` before using it.
+
+/// Default I2C address of both chips (AD0 / SA0 low).
+pub const DEFAULT_ADDRESS: u8 = 0x68;
+
+/// Time between readings in `run`.
+pub const SAMPLE_PERIOD: Duration = Duration::from_millis(5);
+
+const COUNTS_PER_G: f32 = 8192.0;
+const COUNTS_PER_RADIAN_PER_SECOND: f32 = 65.5 * 180.0 / core::f32::consts::PI;
+
+const REGISTER_WHO_AM_I: u8 = 0x75;
+
+const MPU6050_WHO_AM_I: u8 = 0x68;
+const MPU6050_CONFIG: u8 = 0x1A;
+const MPU6050_GYRO_CONFIG: u8 = 0x1B;
+const MPU6050_ACCEL_CONFIG: u8 = 0x1C;
+const MPU6050_ACCEL_XOUT_H: u8 = 0x3B;
+const MPU6050_PWR_MGMT_1: u8 = 0x6B;
+
+const ICM42688_WHO_AM_I: u8 = 0x47;
+const ICM42688_ACCEL_DATA_X1: u8 = 0x1F;
+const ICM42688_PWR_MGMT0: u8 = 0x4E;
+const ICM42688_GYRO_CONFIG0: u8 = 0x4F;
+const ICM42688_ACCEL_CONFIG0: u8 = 0x50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Model {
+    Mpu6050,
+    Icm42688,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum ImuError {
+    I2c(i2c::Error),
+    /// Something answered, but not the expected chip.
+    WrongChip {
+        who_am_i: u8,
+    },
+}
+
+/// One reading, in the body frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Reading {
+    /// Specific force in g: about (0, 0, 1) sitting level and still.
+    pub acceleration: [f32; 3],
+    /// Angular velocity in radians per second, counterclockwise about each axis.
+    pub angular_velocity: [f32; 3],
+}
+
+/// The body's attitude, as `run` last estimated it.
+#[derive(Clone, Copy, Debug)]
+pub struct Orientation {
+    /// Body to world, with yaw relative to wherever `run` started (it drifts).
+    pub attitude: Quaternion,
+    /// Left side up is positive, as in `body::Rotation`.
+    pub roll: f32,
+    /// Nose down is positive, as in `body::Rotation`.
+    pub pitch: f32,
+    /// Counterclockwise (looking down) is positive, in radians per second.
+    pub yaw_rate: f32,
+    pub timestamp: Instant,
+}
+
+/// The latest `Orientation`, for the gait, balance and gaze code to read whenever they like.
+static ORIENTATION: Mutex<CriticalSectionRawMutex, RefCell<Option<Orientation>>> =
+    Mutex::new(RefCell::new(None));
+
+/// The latest estimate from `run`, if it's running.
+#[inline]
+pub fn orientation() -> Option<Orientation> {
+    ORIENTATION.lock(|orientation| *orientation.borrow())
+}
+
+pub struct Imu<'d, T: i2c::Instance> {
+    i2c: I2c<'d, T, Async>,
+    model: Model,
+    address: u8,
+}
+
+impl<'d, T: i2c::Instance> Imu<'d, T> {
+    /// Check the chip is there and what it should be, then wake it and set its ranges.
+    #[inline]
+    pub async fn new(i2c: I2c<'d, T, Async>, model: Model, address: u8) -> Result<Self, ImuError> {
+        let mut imu = Self {
+            i2c,
+            model,
+            address,
+        };
+        let who_am_i = imu.read_register(REGISTER_WHO_AM_I).await?;
+        let expected = match model {
+            Model::Mpu6050 => MPU6050_WHO_AM_I,
+            Model::Icm42688 => ICM42688_WHO_AM_I,
+        };
+        if who_am_i != expected {
+            return Err(ImuError::WrongChip { who_am_i });
+        }
+        match model {
+            Model::Mpu6050 => {
+                // Wake up, clocked from the X gyro (steadier than the internal oscillator):
+                let () = imu.write_register(MPU6050_PWR_MGMT_1, 0x01).await?;
+                // 44 Hz low-pass:
+                let () = imu.write_register(MPU6050_CONFIG, 0x03).await?;
+                let () = imu.write_register(MPU6050_GYRO_CONFIG, 0x08).await?;
+                let () = imu.write_register(MPU6050_ACCEL_CONFIG, 0x08).await?;
+            }
+            Model::Icm42688 => {
+                // Both sensors in low-noise mode:
+                let () = imu.write_register(ICM42688_PWR_MGMT0, 0x0F).await?;
+                // Nothing else may be written for 200 us after turning the gyro on:
+                let () = Timer::after_millis(1).await;
+                // Full-scale selection 2 with a 1 kHz output rate, for both:
+                let () = imu.write_register(ICM42688_GYRO_CONFIG0, 0x46).await?;
+                let () = imu.write_register(ICM42688_ACCEL_CONFIG0, 0x46).await?;
+            }
+        }
+        // Let the first samples through the filters:
+        let () = Timer::after_millis(50).await;
+        Ok(imu)
+    }
+
+    #[inline]
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    #[inline]
+    pub async fn read(&mut self) -> Result<Reading, ImuError> {
+        // Both chips are big-endian. The MPU6050 has its temperature between accel and gyro:
+        let mut raw = [0; 14];
+        let (register, gyro_start) = match self.model {
+            Model::Mpu6050 => (MPU6050_ACCEL_XOUT_H, 8),
+            Model::Icm42688 => (ICM42688_ACCEL_DATA_X1, 6),
+        };
+        let () = self
+            .i2c
+            .write_read_async(self.address, [register], &mut raw[..gyro_start + 6])
+            .await
+            .map_err(ImuError::I2c)?;
+        let word = |i: usize| i16::from_be_bytes([raw[i], raw[i + 1]]) as f32;
+        Ok(Reading {
+            acceleration: core::array::from_fn(|axis| word(2 * axis) / COUNTS_PER_G),
+            angular_velocity: core::array::from_fn(|axis| {
+                word(gyro_start + 2 * axis) / COUNTS_PER_RADIAN_PER_SECOND
+            }),
+        })
+    }
+
+    #[inline]
+    async fn read_register(&mut self, register: u8) -> Result<u8, ImuError> {
+        let mut value = [0];
+        let () = self
+            .i2c
+            .write_read_async(self.address, [register], &mut value)
+            .await
+            .map_err(ImuError::I2c)?;
+        Ok(value[0])
+    }
+
+    #[inline]
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), ImuError> {
+        self.i2c
+            .write_async(self.address, [register, value])
+            .await
+            .map_err(ImuError::I2c)
+    }
+}
+
+/// Read the IMU every `SAMPLE_PERIOD` forever, keeping `orientation` up to date
+/// through a `fusion::Madgwick` filter.
+#[inline]
+pub async fn run<T: i2c::Instance>(mut imu: Imu<'static, T>, mut filter: fusion::Madgwick) -> ! {
+    let mut ticker = Ticker::every(SAMPLE_PERIOD);
+    let mut last: Option<Instant> = None;
+    loop {
+        let () = ticker.next().await;
+        let reading = match imu.read().await {
+            Ok(reading) => reading,
+            Err(e) => {
+                let () = crate::warn!("Couldn't read the IMU: {e:?}");
+                continue;
+            }
+        };
+        let now = Instant::now();
+        let dt = last.map_or(0.0, |last| ((now - last).as_micros() as f32) * 1e-6);
+        last = Some(now);
+        let attitude = filter.update(reading.angular_velocity, reading.acceleration, dt);
+        let (roll, pitch, _) = attitude.roll_pitch_yaw();
+        let orientation = Orientation {
+            attitude,
+            roll,
+            pitch,
+            yaw_rate: reading.angular_velocity[2],
+            timestamp: now,
+        };
+        let () = ORIENTATION.lock(|cell| *cell.borrow_mut() = Some(orientation));
+    }
+}
@@ -0,0 +1,405 @@
+use {
+    crate::{
+        body::{self, Pose},
+        gait, ik,
+        math::{Vec2, Vec3},
+        pwm,
+    },
+    core::sync::atomic::{AtomicU32, Ordering},
+    embassy_time::{Duration, Timer},
+};
+
+// Smooth motion from wherever something is to wherever it should be, over a set time, with
+// an easing curve. `Interpolation` is one such move for any value that can `Lerp`; it knows
+// nothing about time except what it's told through `advance`, so it runs the same on the robot
+// as in a host test. `Interpolator` keeps one move going per channel (the body's pose, each
+// foot, the eye's gaze) for the motion task, all at once and independently: starting a new
+// move on a channel replaces whatever was running there.
+//
+// Every move started through an `Interpolator` hands back a `Ticket`, and `finished(ticket)`
+// waits until that move is over (reached its target, or was replaced), so a task can line
+// moves up without owning anything that moves. Tickets are numbered from one counter shared by
+// every `Interpolator`, so keep to one per robot.
+//
+// Time spent frozen (`body::freeze`) doesn't count, so a freeze pauses every move rather than
+// letting it skip ahead.
+
+/// Legs an `Interpolator` has a channel for.
+pub const MAX_LEGS: usize = gait::N_LEGS;
+
+/// The body, each leg, and the eye.
+const CHANNELS: usize = MAX_LEGS + 2;
+
+/// How often `finished` checks.
+pub const POLL_PERIOD: Duration = Duration::from_millis(pwm::PULSE_PERIOD_MS as _);
+
+/// The last ticket number handed out.
+static ISSUED: AtomicU32 = AtomicU32::new(0);
+
+/// Per channel, the newest ticket number known to be over.
+static FINISHED: [AtomicU32; CHANNELS] = [const { AtomicU32::new(0) }; CHANNELS];
+
+/// How progress through a move maps onto progress toward its target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed, starting and stopping abruptly.
+    #[default]
+    Linear,
+    /// Start from rest, arriving at speed.
+    In,
+    /// Start at speed, coming to rest.
+    Out,
+    /// Start from rest and come to rest (smoothstep).
+    InOut,
+}
+
+impl Easing {
+    /// Map `t` (clamped to [0, 1]) to how far along to be, with 0 at 0 and 1 at 1.
+    #[inline]
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::In => t * t,
+            Self::Out => t * (2.0 - t),
+            Self::InOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Anything a move can pass through on its way between two values.
+pub trait Lerp: Copy {
+    /// `t = 0` is `self`, `t = 1` is `other`.
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    #[inline]
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vec2 {
+    #[inline]
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self::lerp(self, other, t)
+    }
+}
+
+impl Lerp for Vec3 {
+    #[inline]
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self::lerp(self, other, t)
+    }
+}
+
+impl Lerp for ik::Angles {
+    #[inline]
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            yaw: self.yaw.lerp(other.yaw, t),
+            hip: self.hip.lerp(other.hip, t),
+            knee: self.knee.lerp(other.knee, t),
+        }
+    }
+}
+
+impl Lerp for ik::CartesianDisplacementFromEyeCenterLookingForward {
+    #[inline]
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self::lerp(self, other, t)
+    }
+}
+
+impl Lerp for Pose {
+    #[inline]
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self::lerp(self, other, t)
+    }
+}
+
+impl<A: Lerp, B: Lerp> Lerp for (A, B) {
+    #[inline]
+    fn lerp(self, other: Self, t: f32) -> Self {
+        (self.0.lerp(other.0, t), self.1.lerp(other.1, t))
+    }
+}
+
+impl<T: Lerp, const N: usize> Lerp for [T; N] {
+    #[inline]
+    fn lerp(self, other: Self, t: f32) -> Self {
+        core::array::from_fn(|i| self[i].lerp(other[i], t))
+    }
+}
+
+/// One move from `from` to `to`.
+#[derive(Clone, Copy, Debug)]
+pub struct Interpolation<T> {
+    from: T,
+    to: T,
+    duration: Duration,
+    easing: Easing,
+    elapsed: Duration,
+}
+
+impl<T: Lerp> Interpolation<T> {
+    #[inline]
+    pub const fn new(from: T, to: T, duration: Duration, easing: Easing) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            easing,
+            elapsed: Duration::from_ticks(0),
+        }
+    }
+
+    /// Move on by `elapsed` and return where that lands.
+    #[inline]
+    pub fn advance(&mut self, elapsed: Duration) -> T {
+        self.elapsed += elapsed;
+        self.value()
+    }
+
+    /// Where the move is now: exactly `to` once it's done.
+    #[inline]
+    pub fn value(&self) -> T {
+        if self.is_done() {
+            return self.to;
+        }
+        let t = (self.elapsed.as_micros() as f32) / (self.duration.as_micros() as f32);
+        self.from.lerp(self.to, self.easing.apply(t))
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    #[inline]
+    pub fn target(&self) -> T {
+        self.to
+    }
+}
+
+/// Something an `Interpolator` moves, one move at a time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Body,
+    Leg(usize),
+    Eye,
+}
+
+impl Channel {
+    #[inline]
+    const fn index(self) -> usize {
+        match self {
+            Self::Body => 0,
+            Self::Leg(leg) => 1 + leg,
+            Self::Eye => 1 + MAX_LEGS,
+        }
+    }
+}
+
+/// Stands for one move started through an `Interpolator`; see `finished`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ticket {
+    channel: Channel,
+    number: u32,
+}
+
+impl Ticket {
+    #[inline]
+    fn issue(channel: Channel) -> Self {
+        Self {
+            channel,
+            number: ISSUED.fetch_add(1, Ordering::SeqCst).wrapping_add(1),
+        }
+    }
+
+    #[inline]
+    fn finish(self) {
+        let _: u32 = FINISHED[self.channel.index()].fetch_max(self.number, Ordering::SeqCst);
+    }
+
+    #[inline]
+    pub fn channel(self) -> Channel {
+        self.channel
+    }
+}
+
+/// Whether a move has reached its target or been replaced.
+#[inline]
+pub fn is_finished(ticket: Ticket) -> bool {
+    FINISHED[ticket.channel.index()].load(Ordering::SeqCst) >= ticket.number
+}
+
+/// Wait until a move has reached its target or been replaced.
+#[inline]
+pub async fn finished(ticket: Ticket) {
+    while !is_finished(ticket) {
+        let () = Timer::after(POLL_PERIOD).await;
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntStart {
+    /// Past the body's legs, or past `MAX_LEGS`.
+    NoSuchLeg { index: usize },
+}
+
+/// Where each channel should be after one `Interpolator::advance`. `None` for channels with
+/// nothing moving, which should be left where they are.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame<const N: usize> {
+    pub pose: Option<Pose>,
+    pub feet: [Option<ik::CartesianDisplacementFromEyeCenterLookingForward>; N],
+    /// `(azimuth, elevation)`, as for `eye::Eye::look_toward`.
+    pub gaze: Option<(f32, f32)>,
+}
+
+type Move<T> = Option<(Ticket, Interpolation<T>)>;
+
+/// One move at a time per channel, for a body with `N` legs and an eye.
+#[derive(Clone, Copy, Debug)]
+pub struct Interpolator<const N: usize> {
+    pose: Move<Pose>,
+    feet: [Move<ik::CartesianDisplacementFromEyeCenterLookingForward>; N],
+    gaze: Move<(f32, f32)>,
+}
+
+impl<const N: usize> Default for Interpolator<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Interpolator<N> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            pose: None,
+            feet: [None; N],
+            gaze: None,
+        }
+    }
+
+    /// Ease the body's pose from `from` (usually `Body::pose`) to `to`.
+    #[inline]
+    pub fn move_body(
+        &mut self,
+        from: Pose,
+        to: Pose,
+        duration: Duration,
+        easing: Easing,
+    ) -> Ticket {
+        start(
+            &mut self.pose,
+            Channel::Body,
+            Interpolation::new(from, to, duration, easing),
+        )
+    }
+
+    /// Ease one foot from `from` (usually `Leg::target`) to `to`, in the body frame.
+    #[inline]
+    pub fn move_foot(
+        &mut self,
+        leg: usize,
+        from: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        to: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        duration: Duration,
+        easing: Easing,
+    ) -> Result<Ticket, CouldntStart> {
+        if leg >= MAX_LEGS {
+            return Err(CouldntStart::NoSuchLeg { index: leg });
+        }
+        let slot = self
+            .feet
+            .get_mut(leg)
+            .ok_or(CouldntStart::NoSuchLeg { index: leg })?;
+        Ok(start(
+            slot,
+            Channel::Leg(leg),
+            Interpolation::new(from, to, duration, easing),
+        ))
+    }
+
+    /// Ease the eye's gaze from `from` (usually `Eye::gaze`) to `to`, both `(azimuth, elevation)`.
+    #[inline]
+    pub fn move_eye(
+        &mut self,
+        from: (f32, f32),
+        to: (f32, f32),
+        duration: Duration,
+        easing: Easing,
+    ) -> Ticket {
+        start(
+            &mut self.gaze,
+            Channel::Eye,
+            Interpolation::new(from, to, duration, easing),
+        )
+    }
+
+    /// Stop whatever's moving on `channel` where it is, which counts as finishing it.
+    #[inline]
+    pub fn cancel(&mut self, channel: Channel) {
+        let ticket = match channel {
+            Channel::Body => self.pose.take().map(|(ticket, _)| ticket),
+            Channel::Leg(leg) => self
+                .feet
+                .get_mut(leg)
+                .and_then(|slot| slot.take())
+                .map(|(ticket, _)| ticket),
+            Channel::Eye => self.gaze.take().map(|(ticket, _)| ticket),
+        };
+        if let Some(ticket) = ticket {
+            let () = ticket.finish();
+        }
+    }
+
+    /// Whether nothing's moving on any channel.
+    #[inline]
+    pub fn is_idle(&self) -> bool {
+        self.pose.is_none() && self.gaze.is_none() && self.feet.iter().all(Option::is_none)
+    }
+
+    /// Move every channel on by `elapsed` (nothing, while frozen). A move that gets to its
+    /// target reports it one last time, then finishes.
+    #[inline]
+    pub fn advance(&mut self, elapsed: Duration) -> Frame<N> {
+        let elapsed = if body::is_frozen() {
+            Duration::from_ticks(0)
+        } else {
+            elapsed
+        };
+        Frame {
+            pose: step(&mut self.pose, elapsed),
+            feet: core::array::from_fn(|i| step(&mut self.feet[i], elapsed)),
+            gaze: step(&mut self.gaze, elapsed),
+        }
+    }
+}
+
+#[inline]
+fn start<T: Lerp>(slot: &mut Move<T>, channel: Channel, interpolation: Interpolation<T>) -> Ticket {
+    if let Some((replaced, _)) = slot.take() {
+        let () = replaced.finish();
+    }
+    let ticket = Ticket::issue(channel);
+    *slot = Some((ticket, interpolation));
+    ticket
+}
+
+#[inline]
+fn step<T: Lerp>(slot: &mut Move<T>, elapsed: Duration) -> Option<T> {
+    let (ticket, interpolation) = slot.as_mut()?;
+    let value = interpolation.advance(elapsed);
+    if interpolation.is_done() {
+        let () = ticket.finish();
+        *slot = None;
+    }
+    Some(value)
+}
@@ -0,0 +1,174 @@
+use {
+    core::{cell::RefCell, fmt},
+    embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    embassy_time::Instant,
+};
+
+// The last `CAPACITY` log lines, kept in RAM whether or not anything's listening, so whatever
+// happened while the robot was untethered can still be read (`dump`) once USB is back. With
+// the `log-ring` feature, `logging`'s macros copy every line at `MIN_LEVEL` or above in here
+// on top of wherever else they send it; without it, the ring stays empty.
+//
+// Lines are numbered from boot, so a reader (like `fault::read_after`'s) walks the ring one
+// `read_after` at a time without holding it locked, and a gap in the numbers shows where older
+// lines were overwritten. Unlike `fault`'s log, nothing here survives a reset.
+
+/// Lines the ring keeps before overwriting the oldest.
+pub const CAPACITY: usize = 32;
+
+/// Longest line kept; anything longer is cut off.
+pub const MAX_LINE_LENGTH: usize = 96;
+
+/// Quietest level worth a place in the ring.
+pub const MIN_LEVEL: Level = Level::Info;
+
+static JOURNAL: Mutex<CriticalSectionRawMutex, RefCell<Ring<CAPACITY>>> =
+    Mutex::new(RefCell::new(Ring::new()));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// From one of `logging`'s macro names.
+    #[inline]
+    pub fn named(name: &str) -> Option<Self> {
+        Some(match name {
+            "trace" => Self::Trace,
+            "debug" => Self::Debug,
+            "info" => Self::Info,
+            "warn" => Self::Warn,
+            "error" => Self::Error,
+            _ => return None,
+        })
+    }
+}
+
+/// One logged line.
+#[derive(Clone, Debug)]
+pub struct Line {
+    /// Counting from zero at boot.
+    pub sequence: u32,
+    pub uptime_ms: u32,
+    pub level: Level,
+    pub text: heapless::String<MAX_LINE_LENGTH>,
+}
+
+impl fmt::Display for Line {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} at {} ms: {:?} {}",
+            self.sequence, self.uptime_ms, self.level, self.text
+        )
+    }
+}
+
+/// The newest `N` lines, oldest overwritten first.
+#[derive(Debug)]
+pub struct Ring<const N: usize> {
+    lines: heapless::Deque<Line, N>,
+    next: u32,
+}
+
+impl<const N: usize> Default for Ring<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Ring<N> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            lines: heapless::Deque::new(),
+            next: 0,
+        }
+    }
+
+    /// Keep `text` (cut to `MAX_LINE_LENGTH`), dropping the oldest line if the ring's full.
+    #[inline]
+    pub fn push(&mut self, uptime_ms: u32, level: Level, text: &str) {
+        let mut line = Line {
+            sequence: self.next,
+            uptime_ms,
+            level,
+            text: heapless::String::new(),
+        };
+        let () = truncate_into(&mut line.text, text);
+        self.next = self.next.wrapping_add(1);
+        if self.lines.is_full() {
+            let _: Option<Line> = self.lines.pop_front();
+        }
+        // Can't fail: there's room now.
+        let _: Result<(), Line> = self.lines.push_back(line);
+    }
+
+    /// The oldest line newer than `after` (or the oldest of all, for `None`).
+    /// Call again with its `sequence` for the next one.
+    #[inline]
+    pub fn read_after(&self, after: Option<u32>) -> Option<Line> {
+        self.lines
+            .iter()
+            .find(|line| after.is_none_or(|after| line.sequence > after))
+            .cloned()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+/// Copy as much of `text` as fits, whole characters only.
+#[inline]
+fn truncate_into<const N: usize>(out: &mut heapless::String<N>, text: &str) {
+    for c in text.chars() {
+        if out.push(c).is_err() {
+            break;
+        }
+    }
+}
+
+/// Keeps as much as fits, rather than failing.
+struct Truncating<'a>(&'a mut heapless::String<MAX_LINE_LENGTH>);
+
+impl fmt::Write for Truncating<'_> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let () = truncate_into(self.0, s);
+        Ok(())
+    }
+}
+
+/// Keep one line, timestamped now, if it's at `MIN_LEVEL` or above. For `logging`'s macros.
+/// Formats outside the lock, so it's no worse for a busy loop than the log line itself.
+#[inline]
+pub fn record(level: Option<Level>, args: fmt::Arguments<'_>) {
+    let Some(level) = level.filter(|&level| level >= MIN_LEVEL) else {
+        return;
+    };
+    let mut text = heapless::String::new();
+    let _: fmt::Result = fmt::write(&mut Truncating(&mut text), args);
+    let uptime_ms = Instant::now().as_millis() as u32;
+    JOURNAL.lock(|journal| journal.borrow_mut().push(uptime_ms, level, &text))
+}
+
+/// The oldest line in the ring newer than `after` (or the oldest of all, for `None`).
+#[inline]
+pub fn read_after(after: Option<u32>) -> Option<Line> {
+    JOURNAL.lock(|journal| journal.borrow().read_after(after))
+}
@@ -1,16 +1,17 @@
 use {
     crate::{
-        ik, pwm,
-        servo::{self, Servo},
+        body, ik,
+        interp::{Easing, Interpolation},
+        math::{self, Rotation2, Transform2, Vec2},
+        pwm,
+        servo::{self, AnyServo},
     },
-    core::f32::consts::PI,
-    embassy_rp::pwm::PwmOutput,
+    embassy_time::{Duration, Instant, Ticker},
+    embedded_hal::pwm::SetDutyCycle,
 };
 
-const TWO_PI: f32 = 2.0 * PI;
-const NEGATIVE_PI: f32 = -PI;
-
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
 pub enum CouldntInit {
     YawServo(servo::CouldntInitialize),
     HipServo(servo::CouldntInitialize),
@@ -18,63 +19,478 @@ pub enum CouldntInit {
 }
 
 #[derive(Debug)]
-pub enum IkError {
-    CouldntMoveYaw(servo::CouldntMove),
-    CouldntMoveHip(servo::CouldntMove),
-    CouldntMoveKnee(servo::CouldntMove),
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum IkError<E = pwm::Error> {
+    CouldntMoveYaw(servo::CouldntMove<E>),
+    CouldntMoveHip(servo::CouldntMove<E>),
+    CouldntMoveKnee(servo::CouldntMove<E>),
     Ik2dError(ik::HipToFootError),
+    /// Rejected by `body::Collisions::Reject`: this foot would come too close to a neighbor's.
+    WouldCollide {
+        neighbor: usize,
+    },
+}
+
+/// One of a leg's three servos.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Joint {
+    Yaw,
+    Hip,
+    Knee,
+}
+
+impl Joint {
+    /// In the order servos are numbered within each leg.
+    pub const ALL: [Self; 3] = [Self::Yaw, Self::Hip, Self::Knee];
 }
 
+/// Each joint's servo travel (lower, upper) from center, in servo units, before any tuning.
+const YAW_RANGE: (f32, f32) = (-0.5, 0.5);
+const HIP_RANGE: (f32, f32) = (-1.0, 1.0);
+const KNEE_RANGE: (f32, f32) = (-1.0, 0.25);
+
+/// Names accepted by `Leg::parameter` and `Leg::set_parameter`, all in radians.
+pub const PARAMETER_NAMES: [&str; 9] = [
+    "yaw_trim",
+    "hip_trim",
+    "knee_trim",
+    "yaw_min",
+    "yaw_max",
+    "hip_min",
+    "hip_max",
+    "knee_min",
+    "knee_max",
+];
+
+/// What every leg starts with, in the same order as `PARAMETER_NAMES`.
+pub const DEFAULT_PARAMETERS: [f32; 9] = [
+    0.0,
+    0.0,
+    0.0,
+    YAW_RANGE.0 / pwm::RADIANS_TO_SERVO,
+    YAW_RANGE.1 / pwm::RADIANS_TO_SERVO,
+    HIP_RANGE.0 / pwm::RADIANS_TO_SERVO,
+    HIP_RANGE.1 / pwm::RADIANS_TO_SERVO,
+    KNEE_RANGE.0 / pwm::RADIANS_TO_SERVO,
+    KNEE_RANGE.1 / pwm::RADIANS_TO_SERVO,
+];
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum ParameterError {
+    UnknownName,
+    /// In radians, like the parameter itself.
+    OutOfRange(servo::OutOfRange),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntRelax<E = pwm::Error> {
+    Yaw(E),
+    Hip(E),
+    Knee(E),
+}
+
+/// Split e.g. `hip_trim` into its joint and `trim`, `min` or `max`.
 #[inline]
-fn clamp_plus_minus_pi(mut radians: f32) -> f32 {
-    while radians >= PI {
-        radians -= TWO_PI
+fn parameter_name(name: &str) -> Result<(Joint, &str), ParameterError> {
+    let (joint, field) = name.split_once('_').ok_or(ParameterError::UnknownName)?;
+    let joint = match joint {
+        "yaw" => Joint::Yaw,
+        "hip" => Joint::Hip,
+        "knee" => Joint::Knee,
+        _ => return Err(ParameterError::UnknownName),
+    };
+    match field {
+        "trim" | "min" | "max" => Ok((joint, field)),
+        _ => Err(ParameterError::UnknownName),
+    }
+}
+
+/// Where a leg's yaw servo attaches to the body, in the eye-center frame.
+#[derive(Clone, Copy, Debug)]
+pub struct Mount {
+    pub yaw_servo_x: f32,
+    pub yaw_servo_y: f32,
+    /// Direction the leg points when its yaw servo is centered, on [-pi, pi).
+    pub home_yaw_radians: f32,
+}
+
+impl Mount {
+    /// `radial`, in degrees.
+    #[inline]
+    pub fn radial_degrees(home_yaw_degrees: f32) -> Self {
+        Self::radial(home_yaw_degrees.to_radians())
     }
-    while radians < NEGATIVE_PI {
-        radians += TWO_PI
+
+    /// A yaw servo on the rim of the eye, pointing straight outward.
+    #[inline]
+    pub fn radial(home_yaw_radians: f32) -> Self {
+        let home_yaw_radians = math::wrap_angle(home_yaw_radians);
+        let Vec2 { x, y } = Vec2::from_angle(home_yaw_radians, ik::LENGTH_CENTER_TO_YAW);
+        Self {
+            yaw_servo_x: x,
+            yaw_servo_y: y,
+            home_yaw_radians,
+        }
+    }
+
+    #[inline]
+    pub const fn yaw_servo(&self) -> Vec2 {
+        Vec2::new(self.yaw_servo_x, self.yaw_servo_y)
     }
-    radians
+
+    /// From leg-local coordinates (see `ik::CartesianDisplacementFromYawServo`), looking down,
+    /// into the body frame.
+    #[inline]
+    pub fn transform(&self) -> Transform2 {
+        Transform2 {
+            rotation: Rotation2::from_radians(self.home_yaw_radians),
+            translation: self.yaw_servo(),
+        }
+    }
+}
+
+/// How fast and how hard a foot may be driven toward a single far-away target,
+/// so one big jump eases in and out instead of whipping the leg at full servo speed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionLimits {
+    /// Centimeters per second.
+    pub max_speed: f32,
+    /// Centimeters per second per second.
+    pub max_acceleration: f32,
 }
 
-pub struct Leg<'d> {
-    yaw: Servo<'d>,
-    hip: Servo<'d>,
-    knee: Servo<'d>,
-    yaw_servo_x: f32,
-    yaw_servo_y: f32,
-    home_yaw_radians: f32,
+impl MotionLimits {
+    pub const DEFAULT: Self = Self {
+        max_speed: 20.0,
+        max_acceleration: 100.0,
+    };
 }
 
-impl<'d> Leg<'d> {
+/// A trapezoidal speed profile over a straight line: speed up at the limit, cruise,
+/// then slow down at the limit to stop right on the end. Short moves never reach cruising
+/// speed, and just speed up and slow down.
+#[derive(Clone, Copy, Debug)]
+struct Profile {
+    distance: f32,
+    speed: f32,
+    acceleration: f32,
+    /// Seconds spent speeding up (and again slowing down).
+    ramp: f32,
+    /// Seconds end to end.
+    total: f32,
+}
+
+impl Profile {
+    #[inline]
+    fn new(distance: f32, limits: MotionLimits) -> Self {
+        let acceleration = limits.max_acceleration;
+        let ramp = limits.max_speed / acceleration;
+        if acceleration * ramp * ramp >= distance {
+            // Never reaches `max_speed`:
+            let ramp = libm::sqrtf(distance / acceleration);
+            return Self {
+                distance,
+                speed: acceleration * ramp,
+                acceleration,
+                ramp,
+                total: 2.0 * ramp,
+            };
+        }
+        Self {
+            distance,
+            speed: limits.max_speed,
+            acceleration,
+            ramp,
+            total: ramp + distance / limits.max_speed,
+        }
+    }
+
+    /// How far along the line, after `t` seconds.
     #[inline]
-    pub async fn with_home_yaw(
+    fn position(&self, t: f32) -> f32 {
+        if t <= 0.0 {
+            0.0
+        } else if t < self.ramp {
+            0.5 * self.acceleration * t * t
+        } else if t < self.total - self.ramp {
+            0.5 * self.acceleration * self.ramp * self.ramp + self.speed * (t - self.ramp)
+        } else if t < self.total {
+            let left = self.total - t;
+            self.distance - 0.5 * self.acceleration * left * left
+        } else {
+            self.distance
+        }
+    }
+}
+
+/// A `move_limited` under way, for a loop that can't wait for it to finish: `start_move` works
+/// one out, and `continue_move` carries the foot along it a tick at a time.
+#[derive(Clone, Copy, Debug)]
+pub struct Move {
+    from: ik::CartesianDisplacementFromEyeCenterLookingForward,
+    to: ik::CartesianDisplacementFromEyeCenterLookingForward,
+    profile: Profile,
+    /// Time spent moving so far, not counting any spent frozen.
+    elapsed: Duration,
+}
+
+/// The leg as used on the robot, on `pwm::init_slice`'s channels.
+pub type Leg<'d> = AnyLeg<pwm::Output<'d>>;
+
+/// Three servos on any `embedded_hal` PWM channels (see `servo`'s notes on what they need).
+pub struct AnyLeg<P> {
+    yaw: AnyServo<P>,
+    hip: AnyServo<P>,
+    knee: AnyServo<P>,
+    mount: Mount,
+    /// `mount.transform()`, worked out once.
+    transform: Transform2,
+    /// Last joint angles commanded through IK or `set_joint`, in radians.
+    angles: ik::Angles,
+    /// Last foot position commanded through `ik_to`, in the body frame.
+    target: Option<ik::CartesianDisplacementFromEyeCenterLookingForward>,
+    limits: MotionLimits,
+}
+
+impl<P: SetDutyCycle> AnyLeg<P> {
+    #[inline]
+    pub fn with_home_yaw(
         home_yaw_radians: f32,
-        yaw_pwm: PwmOutput<'d>,
-        hip_pwm: PwmOutput<'d>,
-        knee_pwm: PwmOutput<'d>,
+        yaw_pwm: P,
+        hip_pwm: P,
+        knee_pwm: P,
     ) -> Result<Self, CouldntInit> {
-        let home_yaw_radians = clamp_plus_minus_pi(home_yaw_radians);
+        Self::with_mount(Mount::radial(home_yaw_radians), yaw_pwm, hip_pwm, knee_pwm)
+    }
+
+    #[inline]
+    pub fn with_mount(
+        mount: Mount,
+        yaw_pwm: P,
+        hip_pwm: P,
+        knee_pwm: P,
+    ) -> Result<Self, CouldntInit> {
+        let mount = Mount {
+            home_yaw_radians: math::wrap_angle(mount.home_yaw_radians),
+            ..mount
+        };
         Ok(Self {
-            yaw: Servo::with_center_and_ranges(
-                yaw_pwm,
-                0.0,
-                const { -1.0 / 2.0 },
-                const { 1.0 / 2.0 },
-            )
-            .await
-            .map_err(CouldntInit::YawServo)?,
-            hip: Servo::with_center_and_ranges(hip_pwm, 0.0, -1.0, 1.0)
-                .await
+            yaw: AnyServo::with_center_and_ranges(yaw_pwm, 0.0, YAW_RANGE.0, YAW_RANGE.1)
+                .map_err(CouldntInit::YawServo)?,
+            hip: AnyServo::with_center_and_ranges(hip_pwm, 0.0, HIP_RANGE.0, HIP_RANGE.1)
                 .map_err(CouldntInit::HipServo)?,
-            knee: Servo::with_center_and_ranges(knee_pwm, 0.0, -1.0, 0.25)
-                .await
+            knee: AnyServo::with_center_and_ranges(knee_pwm, 0.0, KNEE_RANGE.0, KNEE_RANGE.1)
                 .map_err(CouldntInit::KneeServo)?,
-            yaw_servo_x: libm::cosf(home_yaw_radians) * ik::LENGTH_CENTER_TO_YAW,
-            yaw_servo_y: libm::sinf(home_yaw_radians) * ik::LENGTH_CENTER_TO_YAW,
-            home_yaw_radians,
+            transform: mount.transform(),
+            mount,
+            angles: ik::Angles::default(),
+            target: None,
+            limits: MotionLimits::DEFAULT,
+        })
+    }
+
+    #[inline]
+    pub fn mount(&self) -> &Mount {
+        &self.mount
+    }
+
+    /// Last joint angles commanded through IK or `set_joint`, in radians.
+    /// Raw pulse widths from `set_pulse_width` aren't reflected here.
+    #[inline]
+    pub fn angles(&self) -> ik::Angles {
+        self.angles
+    }
+
+    /// Last foot position commanded through `ik_to`, in the body frame, if any.
+    #[inline]
+    pub fn target(&self) -> Option<ik::CartesianDisplacementFromEyeCenterLookingForward> {
+        self.target
+    }
+
+    /// Where the last commanded joint angles put the foot, in the body frame.
+    /// Unlike `target`, this also follows `set_joint`.
+    #[inline]
+    pub fn forward_kinematics(&self) -> ik::CartesianDisplacementFromEyeCenterLookingForward {
+        let ik::Angles { yaw, hip, knee } = self.angles;
+        let ik::HipToFootDisplacementIn2dPlane { x: reach, y: z } =
+            ik::hip_and_knee_to_foot_2d(ik::HipAndKneeAngles { hip, knee });
+        let local = Vec2::from_angle(yaw, ik::LENGTH_YAW_TO_HIP + reach);
+        // Rotate back by the home yaw and move the origin back to the eye center:
+        self.transform.apply(local).with_z(z).into()
+    }
+
+    /// Power every joint back up at its last commanded angle, e.g. after `relax`.
+    #[inline]
+    pub fn attach(&mut self) -> Result<(), servo::CouldntMove<P::Error>> {
+        self.set_angles(self.angles)
+    }
+
+    /// Let every joint go limp. The next move powers them back up.
+    #[inline]
+    pub fn relax(&mut self) -> Result<(), CouldntRelax<P::Error>> {
+        let () = self.yaw.relax().map_err(CouldntRelax::Yaw)?;
+        let () = self.hip.relax().map_err(CouldntRelax::Hip)?;
+        self.knee.relax().map_err(CouldntRelax::Knee)
+    }
+
+    /// Drive a single joint to `radians` from its center, bypassing IK.
+    #[inline]
+    pub fn set_joint(
+        &mut self,
+        joint: Joint,
+        radians: f32,
+    ) -> Result<(), servo::CouldntMove<P::Error>> {
+        if body::is_frozen() {
+            return Ok(());
+        }
+        let () = self
+            .servo_mut(joint)
+            .go_to(pwm::RADIANS_TO_SERVO * radians)?;
+        *match joint {
+            Joint::Yaw => &mut self.angles.yaw,
+            Joint::Hip => &mut self.angles.hip,
+            Joint::Knee => &mut self.angles.knee,
+        } = radians;
+        Ok(())
+    }
+
+    /// `set_joint`, in degrees.
+    #[inline]
+    pub fn set_joint_degrees(
+        &mut self,
+        joint: Joint,
+        degrees: f32,
+    ) -> Result<(), servo::CouldntMove<P::Error>> {
+        self.set_joint(joint, degrees.to_radians())
+    }
+
+    /// Smallest and largest angles, in radians from center, `joint` may be driven to.
+    #[inline]
+    pub fn joint_limits(&self, joint: Joint) -> (f32, f32) {
+        let (min, max) = self.servo(joint).limits();
+        (min / pwm::RADIANS_TO_SERVO, max / pwm::RADIANS_TO_SERVO)
+    }
+
+    /// `joint_limits`, in degrees.
+    #[inline]
+    pub fn joint_limits_degrees(&self, joint: Joint) -> (f32, f32) {
+        let (min, max) = self.joint_limits(joint);
+        (min.to_degrees(), max.to_degrees())
+    }
+
+    /// Narrow (or widen) the angles, in radians from center, `joint` may be driven to.
+    /// Errors are in radians too.
+    #[inline]
+    pub fn set_joint_limits(
+        &mut self,
+        joint: Joint,
+        min: f32,
+        max: f32,
+    ) -> Result<(), servo::OutOfRange> {
+        self.servo_mut(joint)
+            .set_limits(pwm::RADIANS_TO_SERVO * min, pwm::RADIANS_TO_SERVO * max)
+            .map_err(
+                |servo::OutOfRange { min, max, observed }| servo::OutOfRange {
+                    min: min / pwm::RADIANS_TO_SERVO,
+                    max: max / pwm::RADIANS_TO_SERVO,
+                    observed: observed / pwm::RADIANS_TO_SERVO,
+                },
+            )
+    }
+
+    /// `set_joint_limits`, in degrees. Errors are in radians, as from `set_joint_limits`.
+    #[inline]
+    pub fn set_joint_limits_degrees(
+        &mut self,
+        joint: Joint,
+        min: f32,
+        max: f32,
+    ) -> Result<(), servo::OutOfRange> {
+        self.set_joint_limits(joint, min.to_radians(), max.to_radians())
+    }
+
+    /// Drive all three joints to angles in radians from center, bypassing IK,
+    /// e.g. for calibration or when IK itself is suspect. Stops at the first joint that can't move.
+    #[inline]
+    pub fn set_angles(
+        &mut self,
+        ik::Angles { yaw, hip, knee }: ik::Angles,
+    ) -> Result<(), servo::CouldntMove<P::Error>> {
+        let () = self.set_joint(Joint::Yaw, yaw)?;
+        let () = self.set_joint(Joint::Hip, hip)?;
+        self.set_joint(Joint::Knee, knee)
+    }
+
+    /// Send a raw pulse width to a single joint. See `AnyServo::set_pulse_width`.
+    #[inline]
+    pub fn set_pulse_width(
+        &mut self,
+        joint: Joint,
+        microseconds: u16,
+    ) -> Result<(), servo::CouldntMove<P::Error>> {
+        if body::is_frozen() {
+            return Ok(());
+        }
+        self.servo_mut(joint).set_pulse_width(microseconds)
+    }
+
+    /// Read a trim or joint limit by name, for tuning over a serial link.
+    #[inline]
+    pub fn parameter(&self, name: &str) -> Result<f32, ParameterError> {
+        let (joint, field) = parameter_name(name)?;
+        let servo = self.servo(joint);
+        let (min, max) = servo.limits();
+        Ok(match field {
+            "trim" => servo.trim(),
+            "min" => min,
+            _ => max,
+        } / pwm::RADIANS_TO_SERVO)
+    }
+
+    /// Set a trim or joint limit by name, for tuning over a serial link.
+    /// Takes effect on the next move; out-of-range values (or a minimum above its maximum)
+    /// are rejected.
+    #[inline]
+    pub fn set_parameter(&mut self, name: &str, radians: f32) -> Result<(), ParameterError> {
+        let (joint, field) = parameter_name(name)?;
+        let servo = self.servo_mut(joint);
+        let value = pwm::RADIANS_TO_SERVO * radians;
+        let (min, max) = servo.limits();
+        match field {
+            "trim" => servo.set_trim(value),
+            "min" => servo.set_limits(value, max),
+            _ => servo.set_limits(min, value),
+        }
+        .map_err(|servo::OutOfRange { min, max, observed }| {
+            ParameterError::OutOfRange(servo::OutOfRange {
+                min: min / pwm::RADIANS_TO_SERVO,
+                max: max / pwm::RADIANS_TO_SERVO,
+                observed: observed / pwm::RADIANS_TO_SERVO,
+            })
         })
     }
 
+    #[inline]
+    fn servo(&self, joint: Joint) -> &AnyServo<P> {
+        match joint {
+            Joint::Yaw => &self.yaw,
+            Joint::Hip => &self.hip,
+            Joint::Knee => &self.knee,
+        }
+    }
+
+    #[inline]
+    fn servo_mut(&mut self, joint: Joint) -> &mut AnyServo<P> {
+        match joint {
+            Joint::Yaw => &mut self.yaw,
+            Joint::Hip => &mut self.hip,
+            Joint::Knee => &mut self.knee,
+        }
+    }
+
     #[inline]
     pub fn ik_to(
         &mut self,
@@ -83,41 +499,51 @@ impl<'d> Leg<'d> {
             y: foot_y,
             z: foot_z,
         }: ik::CartesianDisplacementFromEyeCenterLookingForward,
-    ) -> Result<(), IkError> {
+    ) -> Result<(), IkError<P::Error>> {
         // The (x, y) plane is as if you were looking down over the robot.
         // The z plane is up/down, as if it were jumping.
 
-        let mut horizontal_displacement_x = foot_x - self.yaw_servo_x;
-        let mut horizontal_displacement_y = foot_y - self.yaw_servo_y;
-        let global_yaw = libm::atan2f(horizontal_displacement_y, horizontal_displacement_x); // Already guaranteed to be on [-pi, pi).
+        // Move the origin to the yaw servo, then rotate by the negative home yaw
+        // so that the leg's home direction lines up with the local x axis:
+        let local = self.transform.inverse_apply(Vec2::new(foot_x, foot_y));
+        let () = self.ik_to_leg_local(local.with_z(foot_z).into())?;
+        if !body::is_frozen() {
+            self.target = Some(ik::CartesianDisplacementFromEyeCenterLookingForward {
+                x: foot_x,
+                y: foot_y,
+                z: foot_z,
+            });
+        }
+        Ok(())
+    }
 
-        // Update yaw:
-        {
-            let mut local_yaw = global_yaw - self.home_yaw_radians;
-            while local_yaw >= PI {
-                local_yaw -= TWO_PI
-            }
-            while local_yaw < NEGATIVE_PI {
-                local_yaw += TWO_PI
-            }
-            let () = self
-                .yaw
-                .go_to(pwm::RADIANS_TO_SERVO * local_yaw)
-                .map_err(IkError::CouldntMoveYaw)?;
-        };
+    #[inline]
+    pub fn ik_to_leg_local(
+        &mut self,
+        ik::CartesianDisplacementFromYawServo {
+            x: foot_x,
+            y: foot_y,
+            z: foot_z,
+        }: ik::CartesianDisplacementFromYawServo,
+    ) -> Result<(), IkError<P::Error>> {
+        if body::is_frozen() {
+            // Hold the last commanded position:
+            return Ok(());
+        }
 
-        horizontal_displacement_x -= libm::cosf(global_yaw) * ik::LENGTH_YAW_TO_HIP;
-        horizontal_displacement_y -= libm::sinf(global_yaw) * ik::LENGTH_YAW_TO_HIP;
+        let horizontal = Vec2::new(foot_x, foot_y);
+        let local_yaw = horizontal.angle(); // Already guaranteed to be on [-pi, pi).
 
-        let distance_hip_to_foot_projected = {
-            libm::sqrtf(
-                (horizontal_displacement_x * horizontal_displacement_x)
-                    + (horizontal_displacement_y * horizontal_displacement_y),
-            )
-        };
+        // Update yaw:
+        let () = self
+            .yaw
+            .go_to(pwm::RADIANS_TO_SERVO * local_yaw)
+            .map_err(IkError::CouldntMoveYaw)?;
+        self.angles.yaw = local_yaw;
 
+        let hip_to_foot_projected = horizontal - Vec2::from_angle(local_yaw, ik::LENGTH_YAW_TO_HIP);
         let hip_to_foot = ik::HipToFootDisplacementIn2dPlane {
-            x: distance_hip_to_foot_projected,
+            x: hip_to_foot_projected.norm(),
             y: foot_z,
         };
         let ik::HipAndKneeAngles { hip, knee } =
@@ -126,10 +552,158 @@ impl<'d> Leg<'d> {
             .hip
             .go_to(pwm::RADIANS_TO_SERVO * hip)
             .map_err(IkError::CouldntMoveHip)?;
+        self.angles.hip = hip;
         let () = self
             .knee
             .go_to(pwm::RADIANS_TO_SERVO * knee)
             .map_err(IkError::CouldntMoveKnee)?;
+        self.angles.knee = knee;
         Ok(())
     }
+
+    /// Move the foot in a straight line from `from` to `to` over `duration`,
+    /// updating the servos once per PWM period.
+    #[inline]
+    pub async fn move_linearly(
+        &mut self,
+        from: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        to: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        duration: Duration,
+    ) -> Result<(), IkError<P::Error>> {
+        let mut motion = Interpolation::new(from, to, duration, Easing::Linear);
+        let mut last = Instant::now();
+        let mut ticker = Ticker::every(Duration::from_millis(pwm::PULSE_PERIOD_MS as _));
+        loop {
+            let now = Instant::now();
+            // Only count time spent unfrozen, so a freeze pauses rather than skips ahead:
+            let foot = motion.advance(if body::is_frozen() {
+                Duration::from_ticks(0)
+            } else {
+                now - last
+            });
+            last = now;
+            if motion.is_done() {
+                return self.ik_to(foot);
+            }
+            let () = self.ik_to(foot)?;
+            let () = ticker.next().await;
+        }
+    }
+
+    #[inline]
+    pub fn motion_limits(&self) -> MotionLimits {
+        self.limits
+    }
+
+    #[inline]
+    pub fn set_motion_limits(&mut self, limits: MotionLimits) {
+        self.limits = limits
+    }
+
+    /// Drive the foot in a straight line to `to`, within this leg's `MotionLimits`:
+    /// a target within one servo pulse's reach is taken at once, so a stream of nearby
+    /// targets passes straight through, but a far-away one is spread out over as long as
+    /// it takes to get there without exceeding either limit.
+    /// Starts from the last commanded position, or from forward kinematics if there isn't one.
+    #[inline]
+    pub async fn move_limited(
+        &mut self,
+        to: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        limits: MotionLimits,
+    ) -> Result<(), IkError<P::Error>> {
+        let Some(mut motion) = self.start_move(to, limits)? else {
+            return Ok(());
+        };
+        let mut last = Instant::now();
+        let mut ticker = Ticker::every(Duration::from_millis(pwm::PULSE_PERIOD_MS as _));
+        loop {
+            let now = Instant::now();
+            let elapsed = now - last;
+            last = now;
+            if self.continue_move(&mut motion, elapsed)? {
+                return Ok(());
+            }
+            let () = ticker.next().await;
+        }
+    }
+
+    /// `move_limited`, for a loop that can't stop for the whole move: takes a target within
+    /// one servo pulse's reach at once and returns `None`, or returns the `Move` for
+    /// `continue_move` to carry through.
+    #[inline]
+    pub fn start_move(
+        &mut self,
+        to: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        limits: MotionLimits,
+    ) -> Result<Option<Move>, IkError<P::Error>> {
+        let from = self.target().unwrap_or_else(|| self.forward_kinematics());
+        let distance = libm::sqrtf(
+            (to.x - from.x) * (to.x - from.x)
+                + (to.y - from.y) * (to.y - from.y)
+                + (to.z - from.z) * (to.z - from.z),
+        );
+        if !(limits.max_speed > 0.0 && limits.max_acceleration > 0.0) {
+            return self.ik_to(to).map(|()| None);
+        }
+        let profile = Profile::new(distance, limits);
+        if profile.total * 1_000.0 <= pwm::PULSE_PERIOD_MS as f32 {
+            return self.ik_to(to).map(|()| None);
+        }
+        Ok(Some(Move {
+            from,
+            to,
+            profile,
+            elapsed: Duration::from_ticks(0),
+        }))
+    }
+
+    /// Carry a `start_move` on by `elapsed`, returning whether the foot's there. Time spent
+    /// frozen doesn't count, as in `move_linearly`.
+    #[inline]
+    pub fn continue_move(
+        &mut self,
+        motion: &mut Move,
+        elapsed: Duration,
+    ) -> Result<bool, IkError<P::Error>> {
+        if !body::is_frozen() {
+            motion.elapsed += elapsed;
+        }
+        let t = (motion.elapsed.as_micros() as f32) * 1e-6;
+        if t >= motion.profile.total {
+            return self.ik_to(motion.to).map(|()| true);
+        }
+        let along = motion.profile.position(t) / motion.profile.distance;
+        self.ik_to(motion.from.lerp(motion.to, along))
+            .map(|()| false)
+    }
+
+    /// Lift the foot by `lift_height`, carry it over to `to`, then lower it,
+    /// all within `duration`.
+    #[inline]
+    pub async fn step(
+        &mut self,
+        from: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        to: ik::CartesianDisplacementFromEyeCenterLookingForward,
+        lift_height: f32,
+        duration: Duration,
+    ) -> Result<(), IkError<P::Error>> {
+        let lifted_from = ik::CartesianDisplacementFromEyeCenterLookingForward {
+            z: from.z + lift_height,
+            ..from
+        };
+        let lifted_to = ik::CartesianDisplacementFromEyeCenterLookingForward {
+            z: to.z + lift_height,
+            ..to
+        };
+
+        // A quarter of the time going up, half across, and a quarter coming down:
+        let vertical = duration / 4;
+        let horizontal = duration - vertical - vertical;
+
+        let () = self.move_linearly(from, lifted_from, vertical).await?;
+        let () = self
+            .move_linearly(lifted_from, lifted_to, horizontal)
+            .await?;
+        self.move_linearly(lifted_to, to, vertical).await
+    }
 }
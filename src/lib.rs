@@ -1,8 +1,111 @@
 #![no_std]
-#![no_main]
-#![feature(async_trait_bounds, impl_trait_in_assoc_type)]
+#![cfg_attr(not(feature = "sim"), no_main)]
+#![feature(async_trait_bounds)]
+#![cfg_attr(not(feature = "sim"), feature(impl_trait_in_assoc_type))]
 
+// Under the `sim` feature, only the hardware-independent motion stack is built (see `sim`).
+
+#[cfg(not(feature = "sim"))]
+pub mod analog;
+pub mod animation;
+#[cfg(not(feature = "sim"))]
+pub mod battery;
+#[cfg(not(feature = "sim"))]
+pub mod bluetooth;
+pub mod body;
+#[cfg(not(feature = "sim"))]
+pub mod brownout;
+pub mod buzzer;
+#[cfg(not(feature = "sim"))]
+pub mod command;
+#[cfg(not(feature = "sim"))]
+pub mod console;
+#[cfg(not(feature = "sim"))]
+pub mod contact;
+#[cfg(not(feature = "sim"))]
+pub mod crsf;
+#[cfg(not(feature = "sim"))]
+pub mod current;
+#[cfg(not(feature = "sim"))]
+pub mod encoder;
+#[cfg(not(feature = "sim"))]
+pub mod estop;
+#[cfg(not(feature = "sim"))]
+pub mod eye;
+#[cfg(not(feature = "sim"))]
+pub mod eyelid;
+#[cfg(not(feature = "sim"))]
+pub mod failsafe;
+#[cfg(not(feature = "sim"))]
+pub mod fault;
+pub mod fusion;
+pub mod gait;
+#[cfg(not(feature = "sim"))]
+pub mod gcode;
+#[cfg(not(feature = "sim"))]
+pub mod hil;
+#[cfg(not(feature = "sim"))]
+pub mod i2c;
+#[cfg(not(feature = "sim"))]
+pub mod ibus;
 pub mod ik;
+#[cfg(not(feature = "sim"))]
+pub mod imu;
+pub mod interp;
+pub mod journal;
 pub mod leg;
+#[cfg(not(feature = "sim"))]
+pub mod link;
+pub mod logging;
+pub mod math;
+#[cfg(not(feature = "sim"))]
+pub mod mavlink;
+#[cfg(all(feature = "serde", not(feature = "sim")))]
+pub mod message;
+#[cfg(not(feature = "sim"))]
+pub mod multicore;
+#[cfg(not(feature = "sim"))]
+pub mod output;
+pub mod param;
+#[cfg(not(feature = "sim"))]
+pub mod plot;
+#[cfg(not(feature = "sim"))]
+pub mod power;
+#[cfg(not(feature = "sim"))]
+pub mod ppm;
+#[cfg(not(feature = "sim"))]
+pub mod pressure;
 pub mod pwm;
+#[cfg(not(feature = "sim"))]
+pub mod rc;
+#[cfg(not(feature = "sim"))]
+pub mod ros;
+#[cfg(not(feature = "sim"))]
+pub mod sanitize;
+#[cfg(not(feature = "sim"))]
+pub mod sbus;
+#[cfg(not(feature = "sim"))]
+pub mod scheduler;
+pub mod sequence;
 pub mod servo;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(not(feature = "sim"))]
+pub mod spi;
+#[cfg(not(feature = "sim"))]
+pub mod stats;
+pub mod status;
+#[cfg(not(feature = "sim"))]
+pub mod storage;
+pub mod teach;
+#[cfg(not(feature = "sim"))]
+pub mod telemetry;
+#[cfg(not(feature = "sim"))]
+pub mod thermal;
+#[cfg(not(feature = "sim"))]
+pub mod tof;
+pub mod trig;
+#[cfg(not(feature = "sim"))]
+pub mod watchdog;
+#[cfg(not(feature = "sim"))]
+pub mod ws2812;
@@ -0,0 +1,115 @@
+use crate::{
+    command::{self, Command},
+    ros,
+    sanitize::Source,
+    telemetry,
+};
+
+// Incoming frames on any framed transport (SPI, the ROS bridge) go through `handle_frame`.
+// A bare command frame is fire-and-forget. To find out whether it arrived and was accepted,
+// the host wraps it instead:
+//
+//     KIND_SEQUENCED  in   sequence: u16 | kind: u8 | payload ...
+//     KIND_ACK        out  sequence: u16 | code: u8
+//
+// Every sequenced frame gets exactly one ACK (code 0) or NACK (a `Rejection` code) with its
+// sequence number, so a host can tell a dropped frame (no answer) from a rejected one.
+// Frames that fail their CRC can't be answered, since their sequence number can't be trusted.
+// An ACK means the command was queued, not that it has finished: execution errors only
+// show up in telemetry's error counts.
+
+pub const KIND_SEQUENCED: u8 = 0x05;
+pub const KIND_ACK: u8 = 0x06;
+
+/// ACK code for an accepted command.
+pub const ACCEPTED: u8 = 0;
+
+/// Why a command frame was turned away, with its NACK code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Rejection {
+    /// A sequenced frame wrapped inside another.
+    Malformed = 1,
+    /// A text command that didn't parse.
+    Parse = 2,
+    /// A binary message of a kind we don't take, or of the wrong length.
+    Translate = 3,
+    /// The command queue is full; try again.
+    QueueFull = 4,
+    /// A NaN, an infinity, or a value far out of bounds (see `sanitize::check`).
+    Invalid = 5,
+    /// Commands are arriving faster than `sanitize::RATE_HZ`.
+    RateLimited = 6,
+}
+
+impl From<command::Refused> for Rejection {
+    #[inline]
+    fn from(refused: command::Refused) -> Self {
+        match refused {
+            command::Refused::Invalid(_) => Self::Invalid,
+            command::Refused::RateLimited => Self::RateLimited,
+            command::Refused::QueueFull => Self::QueueFull,
+        }
+    }
+}
+
+/// Act on one decoded frame: queue the command it carries and, if it's sequenced, answer it.
+#[inline]
+pub async fn handle_frame(kind: u8, payload: &[u8]) {
+    if kind != KIND_SEQUENCED {
+        match translate(kind, payload) {
+            Ok(command) => {
+                if let Err(e) = command::submit(Source::Link, command).await {
+                    let () = crate::warn!("Refused a frame of kind {kind:#04x}: {e:?}");
+                }
+            }
+            Err(rejection) => {
+                let () = telemetry::count(telemetry::ErrorKind::Command);
+                let () = crate::warn!("Ignoring a frame of kind {kind:#04x}: {rejection:?}");
+            }
+        }
+        return;
+    }
+
+    let &[sequence_low, sequence_high, kind, ref payload @ ..] = payload else {
+        let () = telemetry::count(telemetry::ErrorKind::Command);
+        let () = crate::warn!("Sequenced frame too short to answer");
+        return;
+    };
+    let sequence = u16::from_le_bytes([sequence_low, sequence_high]);
+    let result = translate(kind, payload)
+        .and_then(|command| command::try_submit(Source::Link, command).map_err(Rejection::from));
+    let code = match result {
+        Ok(()) => ACCEPTED,
+        Err(rejection) => {
+            // `command::submit` already counted these:
+            if !matches!(rejection, Rejection::Invalid | Rejection::RateLimited) {
+                let () = telemetry::count(telemetry::ErrorKind::Command);
+            }
+            let () = crate::warn!("Rejected command #{sequence}: {rejection:?}");
+            rejection as u8
+        }
+    };
+    let () = acknowledge(sequence, code);
+}
+
+#[inline]
+fn translate(kind: u8, payload: &[u8]) -> Result<Command, Rejection> {
+    match kind {
+        telemetry::KIND_COMMAND => command::parse_bytes(payload).map_err(|_| Rejection::Parse),
+        KIND_SEQUENCED => Err(Rejection::Malformed),
+        _ => ros::command_from_frame(kind, payload).map_err(|_| Rejection::Translate),
+    }
+}
+
+/// Queue an ACK (or NACK) frame with the rest of telemetry.
+#[inline]
+fn acknowledge(sequence: u16, code: u8) {
+    let [low, high] = sequence.to_le_bytes();
+    let mut frame = [0; 8];
+    match telemetry::encode_frame(KIND_ACK, &[low, high, code], &mut frame) {
+        Ok(n) => telemetry::publish(&frame[..n]),
+        Err(e) => crate::warn!("Couldn't encode an ACK: {e:?}"),
+    }
+}
@@ -0,0 +1,123 @@
+// One set of logging macros for the library and binaries alike, so where messages end up is a
+// build choice rather than something baked into every call site:
+//
+//     log-defmt   over the debug probe, through defmt (and `defmt_rtt`, linked by each binary)
+//     log-usb     into the `log` crate, which `console::run` (or `embassy_usb_logger::run!`)
+//                 forwards over USB serial
+//     log-ring    into `journal`'s ring in RAM, for `dump` to read back after reconnecting
+//
+// All three are on by default; with none, messages compile away. Without `log-usb`, nothing
+// forwards `log` over USB either (see `console::run`), leaving the motion loop more headroom.
+// Either way, the macros take `core::fmt` syntax, like `log`'s: the defmt side formats on the
+// chip, so it's no cheaper than the USB side, just independent of it.
+//
+// The features are checked here rather than where the macros are used, since `cfg` inside an
+// exported macro would look at the calling crate's features instead of this one's.
+
+#[doc(hidden)]
+pub use {defmt, log};
+
+#[cfg(all(feature = "log-defmt", feature = "log-usb"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:ident, $($arg:tt)*) => {{
+        $crate::logging::defmt::$level!(
+            "{}",
+            $crate::logging::defmt::Display2Format(&format_args!($($arg)*))
+        );
+        $crate::logging::log::$level!($($arg)*)
+    }};
+}
+
+#[cfg(all(feature = "log-defmt", not(feature = "log-usb")))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:ident, $($arg:tt)*) => {
+        $crate::logging::defmt::$level!(
+            "{}",
+            $crate::logging::defmt::Display2Format(&format_args!($($arg)*))
+        )
+    };
+}
+
+#[cfg(all(not(feature = "log-defmt"), feature = "log-usb"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:ident, $($arg:tt)*) => {
+        $crate::logging::log::$level!($($arg)*)
+    };
+}
+
+#[cfg(not(any(feature = "log-defmt", feature = "log-usb")))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    // Still type-check (and "use") the arguments:
+    ($level:ident, $($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+#[cfg(feature = "log-ring")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __journal {
+    ($level:ident, $($arg:tt)*) => {
+        $crate::journal::record(
+            $crate::journal::Level::named(stringify!($level)),
+            format_args!($($arg)*),
+        )
+    };
+}
+
+#[cfg(not(feature = "log-ring"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __journal {
+    ($level:ident, $($arg:tt)*) => {
+        ()
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {{
+        $crate::__journal!(trace, $($arg)*);
+        $crate::__log!(trace, $($arg)*)
+    }};
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        $crate::__journal!(debug, $($arg)*);
+        $crate::__log!(debug, $($arg)*)
+    }};
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {{
+        $crate::__journal!(info, $($arg)*);
+        $crate::__log!(info, $($arg)*)
+    }};
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {{
+        $crate::__journal!(warn, $($arg)*);
+        $crate::__log!(warn, $($arg)*)
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {{
+        $crate::__journal!(error, $($arg)*);
+        $crate::__log!(error, $($arg)*)
+    }};
+}
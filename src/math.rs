@@ -0,0 +1,571 @@
+use {
+    crate::trig,
+    core::{
+        f32::consts::{PI, TAU},
+        ops::{Add, Mul, Neg, Sub},
+    },
+};
+
+// The handful of geometry the motion stack shares: points and displacements (`Vec2`, `Vec3`),
+// rotations (`Rotation2` about the vertical axis, `Rotation3` as a matrix, `Quaternion`), and
+// rigid transforms built from them, each mapping a child frame into its parent (`apply`) and
+// back (`inverse_apply`). A leg's mount is a `Transform2` from leg-local coordinates into the
+// body frame; a body pose is a `Transform3` from the body frame into the ground frame.
+//
+// Orientations are kept as unit quaternions wherever they're stored, combined or blended (a
+// body pose, the IMU's attitude): unlike roll, pitch and yaw, they have no angle at which two
+// axes line up and one stops meaning anything, and no wrap-around to trip interpolation. Euler
+// angles are only for people: `body::Rotation` converts either way at the edges. For rotating
+// many points at once, `Quaternion::matrix` is cheaper per point.
+//
+// Everything here is plain `f32` arithmetic over `trig`, with no allocation and no panics.
+// The named types in `ik` convert to and from these at the edges, so that their fields can
+// keep saying which frame they're in.
+
+/// Above this cosine between two quaternions (under two degrees apart), `slerp` blends them
+/// linearly instead.
+const SLERP_LINEAR_ABOVE: f32 = 0.9999;
+
+/// `radians` wrapped onto [-pi, pi).
+#[inline]
+pub fn wrap_angle(mut radians: f32) -> f32 {
+    while radians >= PI {
+        radians -= TAU
+    }
+    while radians < -PI {
+        radians += TAU
+    }
+    radians
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Self = Self::new(0.0, 0.0);
+
+    #[inline]
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    /// `length` out along `radians`, counterclockwise from `x`.
+    #[inline]
+    pub fn from_angle(radians: f32, length: f32) -> Self {
+        Self::new(length * trig::cosf(radians), length * trig::sinf(radians))
+    }
+
+    /// Counterclockwise from `x`, on [-pi, pi].
+    #[inline]
+    pub fn angle(self) -> f32 {
+        trig::atan2f(self.y, self.x)
+    }
+
+    #[inline]
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// Positive if `other` is counterclockwise of `self`.
+    #[inline]
+    pub fn cross(self, other: Self) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    #[inline]
+    pub fn norm(self) -> f32 {
+        libm::hypotf(self.x, self.y)
+    }
+
+    /// Linear interpolation: `t = 0` is `self`, `t = 1` is `other`.
+    #[inline]
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Self::new(
+            self.x + (other.x - self.x) * t,
+            self.y + (other.y - self.y) * t,
+        )
+    }
+
+    /// At height `z`.
+    #[inline]
+    pub const fn with_z(self, z: f32) -> Vec3 {
+        Vec3::new(self.x, self.y, z)
+    }
+}
+
+impl Vec3 {
+    pub const ZERO: Self = Self::new(0.0, 0.0, 0.0);
+
+    #[inline]
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Unit vector toward `azimuth` (counterclockwise from `x`) and `elevation` (up from level).
+    #[inline]
+    pub fn from_heading(azimuth: f32, elevation: f32) -> Self {
+        let level = trig::cosf(elevation);
+        Self::new(
+            level * trig::cosf(azimuth),
+            level * trig::sinf(azimuth),
+            trig::sinf(elevation),
+        )
+    }
+
+    /// `(azimuth, elevation)`, as taken by `from_heading`.
+    #[inline]
+    pub fn heading(self) -> (f32, f32) {
+        (
+            trig::atan2f(self.y, self.x),
+            trig::atan2f(self.z, self.xy().norm()),
+        )
+    }
+
+    /// Looking down, ignoring height.
+    #[inline]
+    pub const fn xy(self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    #[inline]
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    #[inline]
+    pub fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    #[inline]
+    pub fn norm(self) -> f32 {
+        libm::sqrtf(self.dot(self))
+    }
+
+    /// Linear interpolation: `t = 0` is `self`, `t = 1` is `other`.
+    #[inline]
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Self::new(
+            self.x + (other.x - self.x) * t,
+            self.y + (other.y - self.y) * t,
+            self.z + (other.z - self.z) * t,
+        )
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Self;
+    #[inline]
+    fn mul(self, scale: f32) -> Self {
+        Self::new(self.x * scale, self.y * scale)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Self;
+    #[inline]
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y)
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Self;
+    #[inline]
+    fn mul(self, scale: f32) -> Self {
+        Self::new(self.x * scale, self.y * scale, self.z * scale)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Self;
+    #[inline]
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// Counterclockwise about the vertical axis, looking down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotation2 {
+    cos: f32,
+    sin: f32,
+}
+
+impl Default for Rotation2 {
+    #[inline]
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Rotation2 {
+    pub const IDENTITY: Self = Self { cos: 1.0, sin: 0.0 };
+
+    #[inline]
+    pub fn from_radians(radians: f32) -> Self {
+        Self {
+            cos: trig::cosf(radians),
+            sin: trig::sinf(radians),
+        }
+    }
+
+    #[inline]
+    pub fn rotate(&self, v: Vec2) -> Vec2 {
+        Vec2::new(
+            v.x * self.cos - v.y * self.sin,
+            v.x * self.sin + v.y * self.cos,
+        )
+    }
+
+    /// The same angle, clockwise.
+    #[inline]
+    pub fn inverse(&self) -> Self {
+        Self {
+            cos: self.cos,
+            sin: -self.sin,
+        }
+    }
+}
+
+/// A 3D rotation, as a matrix taking child-frame vectors into the parent frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotation3 {
+    rows: [[f32; 3]; 3],
+}
+
+impl Default for Rotation3 {
+    #[inline]
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Rotation3 {
+    pub const IDENTITY: Self = Self {
+        rows: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    /// Yaw-then-pitch-then-roll (intrinsic Z-Y-X): `Rz(yaw) Ry(pitch) Rx(roll)`.
+    #[inline]
+    pub fn from_roll_pitch_yaw(roll: f32, pitch: f32, yaw: f32) -> Self {
+        let (sr, cr) = (trig::sinf(roll), trig::cosf(roll));
+        let (sp, cp) = (trig::sinf(pitch), trig::cosf(pitch));
+        let (sy, cy) = (trig::sinf(yaw), trig::cosf(yaw));
+        Self {
+            rows: [
+                [cy * cp, cy * sp * sr - sy * cr, cy * sp * cr + sy * sr],
+                [sy * cp, sy * sp * sr + cy * cr, sy * sp * cr - cy * sr],
+                [-sp, cp * sr, cp * cr],
+            ],
+        }
+    }
+
+    #[inline]
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        let [a, b, c] = self.rows;
+        Vec3::new(
+            a[0] * v.x + a[1] * v.y + a[2] * v.z,
+            b[0] * v.x + b[1] * v.y + b[2] * v.z,
+            c[0] * v.x + c[1] * v.y + c[2] * v.z,
+        )
+    }
+
+    /// `inverse().rotate(v)`, without building the inverse.
+    #[inline]
+    pub fn inverse_rotate(&self, v: Vec3) -> Vec3 {
+        let [a, b, c] = self.rows;
+        Vec3::new(
+            a[0] * v.x + b[0] * v.y + c[0] * v.z,
+            a[1] * v.x + b[1] * v.y + c[1] * v.z,
+            a[2] * v.x + b[2] * v.y + c[2] * v.z,
+        )
+    }
+
+    /// The transpose, which for a rotation is its inverse.
+    #[inline]
+    pub fn inverse(&self) -> Self {
+        let r = self.rows;
+        Self {
+            rows: core::array::from_fn(|i| core::array::from_fn(|j| r[j][i])),
+        }
+    }
+
+    /// `other`, then `self`: `a.compose(&b).rotate(v)` is `a.rotate(b.rotate(v))`.
+    #[inline]
+    pub fn compose(&self, other: &Self) -> Self {
+        let (a, b) = (self.rows, other.rows);
+        Self {
+            rows: core::array::from_fn(|i| {
+                core::array::from_fn(|j| a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j])
+            }),
+        }
+    }
+}
+
+/// A rotation as `w + xi + yj + zk`, taking child-frame vectors into the parent frame.
+/// Unit length, unless built field by field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Quaternion {
+    #[inline]
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Quaternion {
+    pub const IDENTITY: Self = Self::new(1.0, 0.0, 0.0, 0.0);
+
+    #[inline]
+    pub const fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// Counterclockwise by `radians` about `axis`, which must be unit length.
+    #[inline]
+    pub fn from_axis_angle(axis: Vec3, radians: f32) -> Self {
+        let (sin, cos) = (trig::sinf(0.5 * radians), trig::cosf(0.5 * radians));
+        Self::new(cos, sin * axis.x, sin * axis.y, sin * axis.z)
+    }
+
+    /// Yaw-then-pitch-then-roll (intrinsic Z-Y-X), as `Rotation3::from_roll_pitch_yaw`.
+    #[inline]
+    pub fn from_roll_pitch_yaw(roll: f32, pitch: f32, yaw: f32) -> Self {
+        let (sr, cr) = (trig::sinf(0.5 * roll), trig::cosf(0.5 * roll));
+        let (sp, cp) = (trig::sinf(0.5 * pitch), trig::cosf(0.5 * pitch));
+        let (sy, cy) = (trig::sinf(0.5 * yaw), trig::cosf(0.5 * yaw));
+        Self {
+            w: cr * cp * cy + sr * sp * sy,
+            x: sr * cp * cy - cr * sp * sy,
+            y: cr * sp * cy + sr * cp * sy,
+            z: cr * cp * sy - sr * sp * cy,
+        }
+    }
+
+    /// `(roll, pitch, yaw)`, undoing `from_roll_pitch_yaw`. Pitch is clamped at +/-90 degrees,
+    /// where roll and yaw stop meaning anything separately.
+    #[inline]
+    pub fn roll_pitch_yaw(&self) -> (f32, f32, f32) {
+        let Self { w, x, y, z } = *self;
+        (
+            trig::atan2f(2.0 * (w * x + y * z), 1.0 - 2.0 * (x * x + y * y)),
+            libm::asinf((2.0 * (w * y - z * x)).clamp(-1.0, 1.0)),
+            trig::atan2f(2.0 * (w * z + x * y), 1.0 - 2.0 * (y * y + z * z)),
+        )
+    }
+
+    #[inline]
+    pub fn dot(&self, other: &Self) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    #[inline]
+    pub fn norm(&self) -> f32 {
+        libm::sqrtf(self.dot(self))
+    }
+
+    /// Scaled to unit length, or the identity if it's collapsed to nothing.
+    #[inline]
+    pub fn normalized(self) -> Self {
+        let norm = self.norm();
+        if norm <= f32::EPSILON {
+            return Self::IDENTITY;
+        }
+        self.scaled(1.0 / norm)
+    }
+
+    /// The same angle the other way, for a unit quaternion.
+    #[inline]
+    pub fn inverse(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    #[inline]
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        // v + 2w (u x v) + 2 u x (u x v), with u the vector part:
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+
+    /// `inverse().rotate(v)`.
+    #[inline]
+    pub fn inverse_rotate(&self, v: Vec3) -> Vec3 {
+        self.inverse().rotate(v)
+    }
+
+    /// The same rotation as a matrix.
+    #[inline]
+    pub fn matrix(&self) -> Rotation3 {
+        let Self { w, x, y, z } = *self;
+        Rotation3 {
+            rows: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - w * z),
+                    2.0 * (x * z + w * y),
+                ],
+                [
+                    2.0 * (x * y + w * z),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - w * x),
+                ],
+                [
+                    2.0 * (x * z - w * y),
+                    2.0 * (y * z + w * x),
+                    1.0 - 2.0 * (x * x + y * y),
+                ],
+            ],
+        }
+    }
+
+    /// Spherical linear interpolation, the short way around: `t = 0` is `self`, `t = 1` is
+    /// `other`, turning at a constant rate in between.
+    #[inline]
+    pub fn slerp(self, other: Self, t: f32) -> Self {
+        // `q` and `-q` are the same rotation; pick whichever's nearer:
+        let (other, cos) = match self.dot(&other) {
+            cos if cos < 0.0 => (other.scaled(-1.0), -cos),
+            cos => (other, cos),
+        };
+        let (from, to) = if cos > SLERP_LINEAR_ABOVE {
+            // Too close for `sin(angle)` to divide by; a straight line is as good:
+            (1.0 - t, t)
+        } else {
+            let angle = trig::acosf(cos);
+            let sin = trig::sinf(angle);
+            (
+                trig::sinf((1.0 - t) * angle) / sin,
+                trig::sinf(t * angle) / sin,
+            )
+        };
+        Self::new(
+            self.w * from + other.w * to,
+            self.x * from + other.x * to,
+            self.y * from + other.y * to,
+            self.z * from + other.z * to,
+        )
+        .normalized()
+    }
+
+    #[inline]
+    fn scaled(self, scale: f32) -> Self {
+        Self::new(
+            self.w * scale,
+            self.x * scale,
+            self.y * scale,
+            self.z * scale,
+        )
+    }
+}
+
+/// `self * other` rotates by `other`, then by `self`.
+impl Mul for Quaternion {
+    type Output = Self;
+    #[inline]
+    fn mul(self, other: Self) -> Self {
+        let (a, b) = (self, other);
+        Self::new(
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        )
+    }
+}
+
+/// A child frame placed in its parent, looking down: rotated, then moved by `translation`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform2 {
+    pub rotation: Rotation2,
+    pub translation: Vec2,
+}
+
+impl Transform2 {
+    /// Child-frame point into the parent frame.
+    #[inline]
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        self.translation + self.rotation.rotate(point)
+    }
+
+    /// Parent-frame point into the child frame.
+    #[inline]
+    pub fn inverse_apply(&self, point: Vec2) -> Vec2 {
+        self.rotation.inverse().rotate(point - self.translation)
+    }
+}
+
+/// A child frame placed in its parent: rotated, then moved by `translation`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform3 {
+    pub rotation: Rotation3,
+    pub translation: Vec3,
+}
+
+impl Transform3 {
+    /// Child-frame point into the parent frame.
+    #[inline]
+    pub fn apply(&self, point: Vec3) -> Vec3 {
+        self.translation + self.rotation.rotate(point)
+    }
+
+    /// Parent-frame point into the child frame.
+    #[inline]
+    pub fn inverse_apply(&self, point: Vec3) -> Vec3 {
+        self.rotation.inverse_rotate(point - self.translation)
+    }
+}
@@ -0,0 +1,273 @@
+use {
+    crate::{body, rc},
+    core::cell::RefCell,
+    embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    embassy_time::{Duration, Instant},
+};
+
+// Just enough MAVLink 1 for a ground station to see us and drive us:
+//
+//     0xFE | length | sequence | system | component | message | payload ... | crc: u16
+//
+// with the X.25 CRC covering everything after the 0xFE plus a per-message "extra" byte.
+// We send HEARTBEAT and NAMED_VALUE_FLOAT and accept MANUAL_CONTROL (as virtual RC sticks).
+
+const MAGIC_V1: u8 = 0xFE;
+const MAX_PAYLOAD: usize = 255;
+/// Everything around the payload: magic, five header bytes, and the CRC.
+const OVERHEAD: usize = 1 + 5 + 2;
+
+const MESSAGE_HEARTBEAT: u8 = 0;
+const MESSAGE_MANUAL_CONTROL: u8 = 69;
+const MESSAGE_NAMED_VALUE_FLOAT: u8 = 251;
+
+/// Per-message CRC seeds from the MAVLink definitions.
+const CRC_EXTRA_HEARTBEAT: u8 = 50;
+const CRC_EXTRA_MANUAL_CONTROL: u8 = 243;
+const CRC_EXTRA_NAMED_VALUE_FLOAT: u8 = 170;
+
+const MAV_TYPE_GROUND_ROVER: u8 = 10;
+const MAV_AUTOPILOT_GENERIC: u8 = 0;
+const MAV_STATE_STANDBY: u8 = 3;
+const MAV_STATE_ACTIVE: u8 = 4;
+
+/// Our system and component IDs.
+pub const SYSTEM_ID: u8 = 1;
+pub const COMPONENT_ID: u8 = 1;
+
+/// How often to send a heartbeat and every named value.
+pub const HEARTBEAT_PERIOD: Duration = Duration::from_secs(1);
+
+/// Most named values `report` keeps track of.
+const MAX_NAMED_VALUES: usize = 8;
+/// Bytes in one `Encoder::periodic` burst, at most: a heartbeat and every named value.
+pub const PERIODIC_CAPACITY: usize = (1 + MAX_NAMED_VALUES) * (OVERHEAD + 18);
+
+/// Values to send as NAMED_VALUE_FLOAT with every heartbeat.
+static NAMED_VALUES: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<heapless::Vec<(&'static str, f32), MAX_NAMED_VALUES>>,
+> = Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// Publish (or update) a named value for the ground station. Names over ten bytes are cut short.
+/// Once `MAX_NAMED_VALUES` names are in use, new ones are ignored.
+#[inline]
+pub fn report(name: &'static str, value: f32) {
+    NAMED_VALUES.lock(|values| {
+        let mut values = values.borrow_mut();
+        match values.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, slot)) => *slot = value,
+            None => {
+                let _: Result<(), _> = values.push((name, value));
+            }
+        }
+    })
+}
+
+/// A decoded message we understand.
+#[derive(Clone, Copy, Debug)]
+pub enum Message {
+    /// Sticks on [-1000, 1000] (throttle `z` often on [0, 1000]), plus a button bitmask.
+    ManualControl {
+        x: i16,
+        y: i16,
+        z: i16,
+        r: i16,
+        buttons: u16,
+        target: u8,
+    },
+    /// Some other station is alive.
+    Heartbeat { system: u8 },
+}
+
+impl Message {
+    /// Map a MANUAL_CONTROL onto virtual RC channels in AETR order,
+    /// so it drives the robot under the same `rc::Mapping` as a real transmitter.
+    #[inline]
+    pub fn channels(&self) -> Option<rc::Channels> {
+        let &Self::ManualControl { x, y, z, r, .. } = self else {
+            return None;
+        };
+        let mut values = [0.0; rc::MAX_CHANNELS];
+        // Aileron is roll (y, right positive), elevator pitch (x, forward positive):
+        values[0] = y as f32 / 1_000.0;
+        values[1] = x as f32 / 1_000.0;
+        values[2] = z as f32 / 1_000.0;
+        values[3] = r as f32 / 1_000.0;
+        Some(rc::Channels {
+            values: values.map(|value| value.clamp(-1.0, 1.0)),
+            failsafe: false,
+        })
+    }
+}
+
+/// X.25 CRC, as MAVLink uses.
+#[inline]
+fn crc_accumulate(crc: u16, byte: u8) -> u16 {
+    let tmp = byte ^ (crc as u8);
+    let tmp = tmp ^ (tmp << 4);
+    (crc >> 8) ^ ((tmp as u16) << 8) ^ ((tmp as u16) << 3) ^ ((tmp as u16) >> 4)
+}
+
+#[inline]
+fn crc(bytes: &[u8], extra: u8) -> u16 {
+    crc_accumulate(
+        bytes.iter().fold(0xFFFF, |crc, &b| crc_accumulate(crc, b)),
+        extra,
+    )
+}
+
+/// Builds outgoing packets, numbering them in sequence.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    sequence: u8,
+}
+
+impl Encoder {
+    #[inline]
+    fn encode(
+        &mut self,
+        message: u8,
+        extra: u8,
+        payload: &[u8],
+        out: &mut [u8; OVERHEAD + MAX_PAYLOAD],
+    ) -> usize {
+        let length = payload.len();
+        out[0] = MAGIC_V1;
+        out[1] = length as u8;
+        out[2] = self.sequence;
+        out[3] = SYSTEM_ID;
+        out[4] = COMPONENT_ID;
+        out[5] = message;
+        let () = out[6..6 + length].copy_from_slice(payload);
+        let crc = crc(&out[1..6 + length], extra);
+        let () = out[6 + length..8 + length].copy_from_slice(&crc.to_le_bytes());
+        self.sequence = self.sequence.wrapping_add(1);
+        OVERHEAD + length
+    }
+
+    /// A HEARTBEAT saying we're a ground rover, active unless frozen.
+    #[inline]
+    pub fn heartbeat(&mut self, out: &mut [u8; OVERHEAD + MAX_PAYLOAD]) -> usize {
+        let status = if body::is_frozen() {
+            MAV_STATE_STANDBY
+        } else {
+            MAV_STATE_ACTIVE
+        };
+        // custom_mode: u32 | type | autopilot | base_mode | system_status | mavlink_version
+        let payload = [
+            0,
+            0,
+            0,
+            0,
+            MAV_TYPE_GROUND_ROVER,
+            MAV_AUTOPILOT_GENERIC,
+            0,
+            status,
+            3,
+        ];
+        self.encode(MESSAGE_HEARTBEAT, CRC_EXTRA_HEARTBEAT, &payload, out)
+    }
+
+    /// A NAMED_VALUE_FLOAT, stamped with time since boot.
+    #[inline]
+    pub fn named_value_float(
+        &mut self,
+        name: &str,
+        value: f32,
+        out: &mut [u8; OVERHEAD + MAX_PAYLOAD],
+    ) -> usize {
+        // time_boot_ms: u32 | value: f32 | name: [u8; 10], zero-padded
+        let mut payload = [0; 18];
+        let () = payload[..4].copy_from_slice(&(Instant::now().as_millis() as u32).to_le_bytes());
+        let () = payload[4..8].copy_from_slice(&value.to_le_bytes());
+        let name = &name.as_bytes()[..name.len().min(10)];
+        let () = payload[8..8 + name.len()].copy_from_slice(name);
+        self.encode(
+            MESSAGE_NAMED_VALUE_FLOAT,
+            CRC_EXTRA_NAMED_VALUE_FLOAT,
+            &payload,
+            out,
+        )
+    }
+
+    /// A heartbeat followed by every reported named value, back to back in `out`.
+    #[inline]
+    pub fn periodic(&mut self, out: &mut heapless::Vec<u8, PERIODIC_CAPACITY>) {
+        let () = out.clear();
+        let mut packet = [0; OVERHEAD + MAX_PAYLOAD];
+        let n = self.heartbeat(&mut packet);
+        let _: Result<(), ()> = out.extend_from_slice(&packet[..n]);
+        let values = NAMED_VALUES.lock(|values| values.borrow().clone());
+        for (name, value) in values {
+            let n = self.named_value_float(name, value, &mut packet);
+            let _: Result<(), ()> = out.extend_from_slice(&packet[..n]);
+        }
+    }
+}
+
+/// Reassembles incoming packets from a byte stream, resynchronizing on its own after garbage.
+#[derive(Debug)]
+pub struct Decoder {
+    buffer: [u8; OVERHEAD + MAX_PAYLOAD],
+    length: usize,
+}
+
+impl Default for Decoder {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            buffer: [0; OVERHEAD + MAX_PAYLOAD],
+            length: 0,
+        }
+    }
+}
+
+impl Decoder {
+    /// Feed one byte, getting a message back whenever one we understand completes intact.
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        if self.length == 0 && byte != MAGIC_V1 {
+            return None;
+        }
+        self.buffer[self.length] = byte;
+        self.length += 1;
+        if self.length < 6 || self.length < OVERHEAD + self.buffer[1] as usize {
+            return None;
+        }
+        self.length = 0;
+
+        let length = self.buffer[1] as usize;
+        let system = self.buffer[3];
+        let message = self.buffer[5];
+        let payload = &self.buffer[6..6 + length];
+        let extra = match message {
+            MESSAGE_HEARTBEAT => CRC_EXTRA_HEARTBEAT,
+            MESSAGE_MANUAL_CONTROL => CRC_EXTRA_MANUAL_CONTROL,
+            _ => return None,
+        };
+        let received = u16::from_le_bytes([self.buffer[6 + length], self.buffer[7 + length]]);
+        if crc(&self.buffer[1..6 + length], extra) != received {
+            return None;
+        }
+
+        match message {
+            MESSAGE_HEARTBEAT => Some(Message::Heartbeat { system }),
+            MESSAGE_MANUAL_CONTROL => {
+                // x, y, z, r: i16 | buttons: u16 | target: u8 (MAVLink 1 length: 11)
+                let &[x0, x1, y0, y1, z0, z1, r0, r1, b0, b1, target, ..] = payload else {
+                    return None;
+                };
+                Some(Message::ManualControl {
+                    x: i16::from_le_bytes([x0, x1]),
+                    y: i16::from_le_bytes([y0, y1]),
+                    z: i16::from_le_bytes([z0, z1]),
+                    r: i16::from_le_bytes([r0, r1]),
+                    buttons: u16::from_le_bytes([b0, b1]),
+                    target,
+                })
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use {
+    crate::{
+        command::Command,
+        ik,
+        telemetry::{self, CouldntEncode, MAX_LEGS},
+    },
+    serde::{Deserialize, Serialize},
+};
+
+// The same commands and telemetry as the hand-packed frames, but as `postcard`-encoded
+// structs, so host-side tooling can depend on this crate's types (with the `serde` feature)
+// instead of keeping its own copy of every byte layout. They ride in ordinary `telemetry`
+// frames under their own kinds:
+//
+//     KIND_COMMAND   in   `Command`
+//     KIND_SNAPSHOT  out  `Snapshot`
+
+pub const KIND_COMMAND: u8 = 0x03;
+pub const KIND_SNAPSHOT: u8 = 0x04;
+
+/// Longest serialized message `encode_frame` handles: a `Snapshot` with `MAX_LEGS` legs fits.
+pub const MAX_MESSAGE_LENGTH: usize = 1024;
+
+/// One leg's worth of a `Snapshot`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct LegState {
+    pub angles: ik::Angles,
+    /// In the body frame, as last commanded through IK, if ever.
+    pub commanded: Option<ik::CartesianDisplacementFromEyeCenterLookingForward>,
+    /// In the body frame, from `angles` through forward kinematics.
+    pub measured: ik::CartesianDisplacementFromEyeCenterLookingForward,
+}
+
+/// Everything in `telemetry::Snapshot`, as one serializable value.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub timestamp_us: u32,
+    pub loop_period_us: u32,
+    pub loop_busy_us: u32,
+    pub gait_phase: f32,
+    /// If anything's measuring it.
+    pub battery_volts: Option<f32>,
+    /// If anything's measuring it.
+    pub servo_amps: Option<f32>,
+    /// If anything's measuring it.
+    pub chip_celsius: Option<f32>,
+    /// Indexed by `telemetry::ErrorKind`.
+    pub error_counts: [u32; telemetry::ERROR_KINDS],
+    pub legs: heapless::Vec<LegState, MAX_LEGS>,
+}
+
+/// Serialize `message` and frame it under `kind`, returning how many bytes of `out` it took.
+#[inline]
+pub fn encode_frame<T: Serialize>(
+    kind: u8,
+    message: &T,
+    out: &mut [u8],
+) -> Result<usize, CouldntEncode> {
+    // Not `telemetry::encode_frame`, which stops at `MAX_FRAME_LENGTH` (sized for incoming frames):
+    let mut raw = [0; 1 + MAX_MESSAGE_LENGTH + 2];
+    raw[0] = kind;
+    let length = 1 + postcard::to_slice(message, &mut raw[1..1 + MAX_MESSAGE_LENGTH])
+        .map_err(|_| CouldntEncode::BufferTooSmall)?
+        .len();
+    let crc = telemetry::crc16(&raw[..length]);
+    let () = raw[length..length + 2].copy_from_slice(&crc.to_le_bytes());
+    telemetry::cobs_encode(&raw[..length + 2], out)
+}
+
+/// Largest frame `encode_frame` can produce, including COBS overhead and the trailing zero.
+pub const MAX_ENCODED_LENGTH: usize = {
+    let raw = 1 + MAX_MESSAGE_LENGTH + 2;
+    raw + raw.div_ceil(254) + 1
+};
+
+/// The payload of a `KIND_COMMAND` frame.
+#[inline]
+pub fn decode_command(payload: &[u8]) -> Result<Command, postcard::Error> {
+    postcard::from_bytes(payload)
+}
+
+/// `embassy_time::Duration` as whole milliseconds, for `#[serde(with = ...)]`.
+pub mod milliseconds {
+    use {
+        embassy_time::Duration,
+        serde::{Deserialize, Deserializer, Serializer},
+    };
+
+    #[inline]
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis())
+    }
+
+    #[inline]
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
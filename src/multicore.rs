@@ -0,0 +1,61 @@
+use {
+    crate::command,
+    embassy_executor::{Executor, SpawnError, Spawner},
+    embassy_rp::{
+        multicore::{self, Stack},
+        peripherals::CORE1,
+    },
+    static_cell::StaticCell,
+};
+
+// The RP2350 has two cores, so the servo loop can have one to itself. Core 1 runs only the
+// motion task (IK, gait, servo writes, watchdog); core 0 keeps everything that talks to the
+// outside world (USB, UART, console parsing, telemetry encoding, logging). However much core 0
+// logs or however long a host takes to read, the 20 ms update on core 1 never waits for it.
+//
+// The cores only meet through the crate's existing channels and signals: commands go over
+// `command`'s queue (answers come back through its signals), samples over `telemetry`'s.
+// All of them are `CriticalSectionRawMutex`es, which embassy-rp backs with a hardware spinlock,
+// so they're as safe between cores as between tasks.
+//
+// Flash is the exception. Only core 0 may write it, and while it does, core 1 is paused, so:
+//
+//   - load the config, `fault::recover`, and start the watchdog (which may log a fault)
+//     on core 0, before handing the motion state over with `spawn_motion`
+//   - run `fault::watch` on core 0
+//   - run `command::run_flash_jobs` on core 0 too, which saves and loads for the motion
+//     task (`config ...`, `teach save`, `seq save`, ...) while it keeps ticking. `spawn_motion`
+//     spawns it itself, since without it the motion task would wait on its first save forever
+//
+// Logging from core 1 works (the buffers are behind the same kind of mutex) but isn't free:
+// formatting happens on the core that logs. Keep it out of the motion loop's happy path.
+
+/// Core 1's stack, in bytes. Its executor runs a single task, so this needn't be much.
+pub const CORE1_STACK_SIZE: usize = 16 * 1024;
+
+/// Spawn `command::run_flash_jobs` on core 0's `spawner`, then start core 1 with its own
+/// executor and run `motion` there, which should spawn the motion task (and nothing else).
+/// Returns right away; core 0 goes on with whatever called it. Core 1 is left alone if the
+/// flash task can't be spawned. Only call this once: it claims static buffers.
+#[inline]
+pub fn spawn_motion(
+    spawner: Spawner,
+    core1: CORE1,
+    motion: impl FnOnce(Spawner) + Send + 'static,
+) -> Result<(), SpawnError> {
+    static STACK: StaticCell<Stack<CORE1_STACK_SIZE>> = StaticCell::new();
+    static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+    let () = spawner.spawn(flash_jobs())?;
+    let () = multicore::spawn_core1(core1, STACK.init(Stack::new()), move || {
+        let executor = EXECUTOR.init(Executor::new());
+        executor.run(motion)
+    });
+    Ok(())
+}
+
+/// `command::run_flash_jobs`, on core 0.
+#[embassy_executor::task]
+async fn flash_jobs() {
+    command::run_flash_jobs().await
+}
@@ -0,0 +1,98 @@
+use {
+    crate::pwm,
+    core::sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    embassy_executor::InterruptExecutor,
+    embassy_rp::{
+        interrupt,
+        interrupt::{InterruptExt, Priority},
+        pac,
+    },
+    embassy_time::{Duration, Ticker},
+};
+
+// The last step of every servo update, writing pulse widths into the PWM hardware, runs on an
+// interrupt executor above everything in thread mode. Once `start`ed, a `pwm::Output` only
+// stages what it's given here, and `flush` copies whatever's staged into the compare registers
+// once every PWM period, however busy the thread-mode executor is with USB logging, console
+// parsing or anything else. The motion loop only has to finish working out a pose before the
+// next flush; nothing it shares a core with can hold the write itself back.
+//
+// The compare registers only take effect when the counter wraps, so a flush anywhere within a
+// period never cuts a pulse short; a channel nothing was staged for keeps its last width.
+//
+// Flash writes are the one thing this can't beat: core 0 pauses core 1 outright while it
+// writes (see `multicore`), interrupts included. The hardware keeps repeating the last widths
+// meanwhile, so the servos hold still rather than going limp.
+
+/// Above thread mode, where every executor but this one runs, and below the peripherals' own
+/// (short) interrupt handlers, which are left at P0.
+pub const PRIORITY: Priority = Priority::P1;
+
+/// How often staged widths go out: once per pulse.
+pub const PERIOD: Duration = Duration::from_millis(pwm::PULSE_PERIOD_MS as _);
+
+/// Two channels for each of the RP2350A's 12 PWM slices.
+const SLOTS: usize = 24;
+
+/// Set alongside a staged width, so that zero (fully off) can be staged too.
+const STAGED_FLAG: u32 = 1 << 16;
+
+static EXECUTOR: InterruptExecutor = InterruptExecutor::new();
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+static STAGED: [AtomicU32; SLOTS] = [const { AtomicU32::new(0) }; SLOTS];
+
+#[interrupt]
+unsafe fn SWI_IRQ_1() {
+    unsafe { EXECUTOR.on_interrupt() }
+}
+
+/// Start flushing staged widths from an interrupt executor on this core, and have every
+/// `pwm::Output` stage its writes from now on. Call it once, from the core the motion loop
+/// runs on, before the motion loop starts.
+#[inline]
+pub fn start() {
+    let () = interrupt::SWI_IRQ_1.set_priority(PRIORITY);
+    let spawner = EXECUTOR.start(interrupt::SWI_IRQ_1);
+    let () = RUNNING.store(true, Ordering::Release);
+    let () = match spawner.spawn(flush()) {
+        Ok(()) => crate::info!("Spawned servo output task"),
+        Err(e) => panic!("Error spawning servo output task: {e:?}"),
+    };
+}
+
+/// Whether `start` has been called, so writes should be staged.
+#[inline]
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Acquire)
+}
+
+/// Have the next flush write `duty` to `slot` (see `pwm::Output`), replacing anything staged
+/// there since the last one.
+#[inline]
+pub fn stage(slot: usize, duty: u16) {
+    let () = STAGED[slot].store(STAGED_FLAG | duty as u32, Ordering::Release);
+}
+
+#[embassy_executor::task]
+async fn flush() {
+    let mut ticker = Ticker::every(PERIOD);
+    loop {
+        let () = ticker.next().await;
+        for (slot, staged) in STAGED.iter().enumerate() {
+            let staged = staged.swap(0, Ordering::AcqRel);
+            if staged & STAGED_FLAG == 0 {
+                continue;
+            }
+            let duty = staged as u16;
+            let () = pac::PWM.ch(slot / 2).cc().modify(|w| {
+                if slot % 2 == 0 {
+                    w.set_a(duty)
+                } else {
+                    w.set_b(duty)
+                }
+            });
+        }
+    }
+}
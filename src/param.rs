@@ -0,0 +1,116 @@
+use {
+    crate::{
+        body::Body,
+        gait::{self, Gait},
+        leg,
+    },
+    core::fmt,
+};
+
+// Every tunable value, under one namespace so a serial link can get, set and list them:
+//
+//     gait.<name>        one of `gait::PARAMETER_NAMES`
+//     leg<i>.<name>      one of `leg::PARAMETER_NAMES`, for leg `i`
+//
+// Each owner checks its own types and ranges, so an out-of-range value is refused on-device
+// and nothing changes.
+
+/// One tunable value. Stored as indices into the owners' name lists, so it stays `Copy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Parameter {
+    /// Index into `gait::PARAMETER_NAMES`.
+    Gait(u8),
+    /// Index into `leg::PARAMETER_NAMES`.
+    Leg { leg: usize, name: u8 },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntAccess {
+    NoSuchLeg { index: usize },
+    Gait(gait::ParameterError),
+    Leg(leg::ParameterError),
+}
+
+impl Parameter {
+    /// Look up a `gait.<name>` or `leg<i>.<name>` name.
+    #[inline]
+    pub fn parse(name: &str) -> Option<Self> {
+        let (owner, name) = name.split_once('.')?;
+        if owner == "gait" {
+            let index = gait::PARAMETER_NAMES.iter().position(|&n| n == name)?;
+            return Some(Self::Gait(index as u8));
+        }
+        let leg = owner.strip_prefix("leg")?.parse().ok()?;
+        let index = leg::PARAMETER_NAMES.iter().position(|&n| n == name)?;
+        Some(Self::Leg {
+            leg,
+            name: index as u8,
+        })
+    }
+
+    #[inline]
+    pub fn get<const N: usize>(
+        self,
+        body: &Body<'_, N>,
+        gait: &Gait,
+    ) -> Result<f32, CouldntAccess> {
+        match self {
+            Self::Gait(name) => gait
+                .parameter(gait::PARAMETER_NAMES[name as usize])
+                .map_err(CouldntAccess::Gait),
+            Self::Leg { leg, name } => body
+                .legs()
+                .get(leg)
+                .ok_or(CouldntAccess::NoSuchLeg { index: leg })?
+                .parameter(leg::PARAMETER_NAMES[name as usize])
+                .map_err(CouldntAccess::Leg),
+        }
+    }
+
+    #[inline]
+    pub fn set<const N: usize>(
+        self,
+        value: f32,
+        body: &mut Body<'_, N>,
+        gait: &mut Gait,
+    ) -> Result<(), CouldntAccess> {
+        match self {
+            Self::Gait(name) => gait
+                .set_parameter(gait::PARAMETER_NAMES[name as usize], value)
+                .map_err(CouldntAccess::Gait),
+            Self::Leg { leg, name } => body
+                .legs_mut()
+                .get_mut(leg)
+                .ok_or(CouldntAccess::NoSuchLeg { index: leg })?
+                .set_parameter(leg::PARAMETER_NAMES[name as usize], value)
+                .map_err(CouldntAccess::Leg),
+        }
+    }
+}
+
+impl fmt::Display for Parameter {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Gait(name) => write!(f, "gait.{}", gait::PARAMETER_NAMES[name as usize]),
+            Self::Leg { leg, name } => {
+                write!(f, "leg{leg}.{}", leg::PARAMETER_NAMES[name as usize])
+            }
+        }
+    }
+}
+
+/// Every parameter of a robot with `legs` legs, gait first.
+#[inline]
+pub fn all(legs: usize) -> impl Iterator<Item = Parameter> {
+    (0..gait::PARAMETER_NAMES.len())
+        .map(|name| Parameter::Gait(name as u8))
+        .chain((0..legs).flat_map(|leg| {
+            (0..leg::PARAMETER_NAMES.len()).map(move |name| Parameter::Leg {
+                leg,
+                name: name as u8,
+            })
+        }))
+}
@@ -0,0 +1,241 @@
+use {
+    crate::{
+        ik,
+        leg::Joint,
+        telemetry::{self, Sample},
+    },
+    core::fmt::{self, Write as _},
+    embassy_time::Duration,
+};
+
+// `plot` streams a handful of variables as CSV, one line per `PERIOD`, straight from the newest
+// `telemetry::Sample` (so a `telemetry::Sampler` has to be running). Variable names:
+//
+//     phase, period, busy           gait phase, loop period and busy time (microseconds)
+//     battery, current              filtered battery voltage, average servo-rail current (amps)
+//     temperature                   chip temperature (Celsius)
+//     leg<i>.yaw|hip|knee           joint angles, radians
+//     leg<i>.x|y|z                  foot position from forward kinematics, body frame
+//     leg<i>.tx|ty|tz               commanded foot position, body frame
+//     errors.ik|servo|command|dropped
+//
+// The first line is a header naming the columns, so most plotters label them automatically.
+
+/// Time between CSV lines.
+pub const PERIOD: Duration = Duration::from_millis(20);
+
+/// Most variables one `plot` can stream.
+pub const MAX_VARIABLES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variable {
+    GaitPhase,
+    LoopPeriod,
+    LoopBusy,
+    BatteryVolts,
+    ServoAmps,
+    ChipCelsius,
+    Angle { leg: usize, joint: Joint },
+    Foot { leg: usize, axis: Axis },
+    Target { leg: usize, axis: Axis },
+    Errors(telemetry::ErrorKind),
+}
+
+/// Which variables to stream, in column order. A fixed array rather than a `Vec`
+/// so it fits in a `Command`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Selection {
+    variables: [Variable; MAX_VARIABLES],
+    len: u8,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntSelect {
+    Empty,
+    UnknownVariable,
+    TooManyVariables,
+}
+
+impl Variable {
+    #[inline]
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "phase" => return Some(Self::GaitPhase),
+            "period" => return Some(Self::LoopPeriod),
+            "busy" => return Some(Self::LoopBusy),
+            "battery" => return Some(Self::BatteryVolts),
+            "current" => return Some(Self::ServoAmps),
+            "temperature" => return Some(Self::ChipCelsius),
+            _ => {}
+        }
+        if let Some(kind) = name.strip_prefix("errors.") {
+            return Some(Self::Errors(match kind {
+                "ik" => telemetry::ErrorKind::Ik,
+                "servo" => telemetry::ErrorKind::Servo,
+                "command" => telemetry::ErrorKind::Command,
+                "dropped" => telemetry::ErrorKind::DroppedFrame,
+                _ => return None,
+            }));
+        }
+        let (leg, field) = name.strip_prefix("leg")?.split_once('.')?;
+        let leg = leg.parse().ok()?;
+        Some(match field {
+            "yaw" => Self::Angle {
+                leg,
+                joint: Joint::Yaw,
+            },
+            "hip" => Self::Angle {
+                leg,
+                joint: Joint::Hip,
+            },
+            "knee" => Self::Angle {
+                leg,
+                joint: Joint::Knee,
+            },
+            "x" => Self::Foot { leg, axis: Axis::X },
+            "y" => Self::Foot { leg, axis: Axis::Y },
+            "z" => Self::Foot { leg, axis: Axis::Z },
+            "tx" => Self::Target { leg, axis: Axis::X },
+            "ty" => Self::Target { leg, axis: Axis::Y },
+            "tz" => Self::Target { leg, axis: Axis::Z },
+            _ => return None,
+        })
+    }
+
+    /// This variable's value in `sample`, or NaN for a leg the sample doesn't have.
+    #[inline]
+    pub fn value(&self, sample: &Sample) -> f32 {
+        let axis = |point: Option<&ik::CartesianDisplacementFromEyeCenterLookingForward>,
+                    axis: Axis| {
+            point.map_or(f32::NAN, |point| match axis {
+                Axis::X => point.x,
+                Axis::Y => point.y,
+                Axis::Z => point.z,
+            })
+        };
+        match *self {
+            Self::GaitPhase => sample.gait_phase,
+            Self::LoopPeriod => sample.loop_period.as_micros() as f32,
+            Self::LoopBusy => sample.loop_busy.as_micros() as f32,
+            Self::BatteryVolts => sample.battery_volts,
+            Self::ServoAmps => sample.servo_amps,
+            Self::ChipCelsius => sample.chip_celsius,
+            Self::Angle { leg, joint } => {
+                sample
+                    .joint_angles
+                    .get(leg)
+                    .map_or(f32::NAN, |angles| match joint {
+                        Joint::Yaw => angles.yaw,
+                        Joint::Hip => angles.hip,
+                        Joint::Knee => angles.knee,
+                    })
+            }
+            Self::Foot { leg, axis: a } => axis(sample.measured_foot_positions.get(leg), a),
+            Self::Target { leg, axis: a } => axis(sample.commanded_foot_positions.get(leg), a),
+            Self::Errors(kind) => telemetry::error_counts()[kind as usize] as f32,
+        }
+    }
+}
+
+impl fmt::Display for Variable {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let axis = |axis| match axis {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        };
+        match *self {
+            Self::GaitPhase => write!(f, "phase"),
+            Self::LoopPeriod => write!(f, "period"),
+            Self::LoopBusy => write!(f, "busy"),
+            Self::BatteryVolts => write!(f, "battery"),
+            Self::ServoAmps => write!(f, "current"),
+            Self::ChipCelsius => write!(f, "temperature"),
+            Self::Angle { leg, joint } => write!(
+                f,
+                "leg{leg}.{}",
+                match joint {
+                    Joint::Yaw => "yaw",
+                    Joint::Hip => "hip",
+                    Joint::Knee => "knee",
+                }
+            ),
+            Self::Foot { leg, axis: a } => write!(f, "leg{leg}.{}", axis(a)),
+            Self::Target { leg, axis: a } => write!(f, "leg{leg}.t{}", axis(a)),
+            Self::Errors(kind) => write!(
+                f,
+                "errors.{}",
+                match kind {
+                    telemetry::ErrorKind::Ik => "ik",
+                    telemetry::ErrorKind::Servo => "servo",
+                    telemetry::ErrorKind::Command => "command",
+                    telemetry::ErrorKind::DroppedFrame => "dropped",
+                }
+            ),
+        }
+    }
+}
+
+impl Selection {
+    /// Parse whitespace-separated variable names.
+    #[inline]
+    pub fn parse<'a>(names: impl Iterator<Item = &'a str>) -> Result<Self, CouldntSelect> {
+        let mut selection = Self {
+            variables: [Variable::GaitPhase; MAX_VARIABLES],
+            len: 0,
+        };
+        for name in names {
+            let variable = Variable::parse(name).ok_or(CouldntSelect::UnknownVariable)?;
+            *selection
+                .variables
+                .get_mut(selection.len as usize)
+                .ok_or(CouldntSelect::TooManyVariables)? = variable;
+            selection.len += 1;
+        }
+        if selection.len == 0 {
+            return Err(CouldntSelect::Empty);
+        }
+        Ok(selection)
+    }
+
+    #[inline]
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables[..self.len as usize]
+    }
+
+    /// Column names, comma-separated, ending in `\r\n`.
+    #[inline]
+    pub fn header(&self) -> heapless::String<{ 16 * MAX_VARIABLES }> {
+        let mut line = heapless::String::new();
+        for (i, variable) in self.variables().iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _: fmt::Result = write!(line, "{separator}{variable}");
+        }
+        let _: fmt::Result = write!(line, "\r\n");
+        line
+    }
+
+    /// One line of values from `sample`, comma-separated, ending in `\r\n`.
+    #[inline]
+    pub fn line(&self, sample: &Sample) -> heapless::String<{ 16 * MAX_VARIABLES }> {
+        let mut line = heapless::String::new();
+        for (i, variable) in self.variables().iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _: fmt::Result = write!(line, "{separator}{}", variable.value(sample));
+        }
+        let _: fmt::Result = write!(line, "\r\n");
+        line
+    }
+}
@@ -0,0 +1,95 @@
+use {
+    crate::{body::Body, servo},
+    core::sync::atomic::{AtomicU8, Ordering},
+    embassy_rp::{
+        Peripheral,
+        gpio::{Level, Output, Pin},
+        pac,
+    },
+    embassy_time::{Duration, Timer},
+};
+
+// A MOSFET or load switch gating the servos' 5 V rail, on when its enable pin is high.
+// Give the gate a pull-down: while the chip's in reset or booting, the pin floats, and
+// the rail has to stay off until `Rail::power_up` has every servo's pulse running at a
+// sensible angle, so nothing twitches to some random position on power-up.
+//
+// `cut` works without the `Rail` (or anything else), so it can be called from anywhere a
+// fault's noticed, including the panic handler (see `fault`), which calls it before anything
+// else.
+
+/// Time for the rail to come up and the servos to wake before anything else moves.
+pub const RAIL_SETTLE: Duration = Duration::from_millis(100);
+
+/// The enable pin's GPIO number, for `cut`; `NO_PIN` until a `Rail` exists.
+static PIN: AtomicU8 = AtomicU8::new(NO_PIN);
+
+const NO_PIN: u8 = u8::MAX;
+
+/// Cut the servo rail, right now, by writing the enable pin's output register directly.
+/// Does nothing if there's no `Rail`.
+#[inline]
+pub fn cut() {
+    let pin = PIN.load(Ordering::SeqCst);
+    if pin == NO_PIN {
+        return;
+    }
+    let () = pac::SIO
+        .gpio_out((pin / 32) as usize)
+        .value_clr()
+        .write_value(1 << (pin % 32));
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntPowerUp {
+    Attach {
+        leg: usize,
+        error: servo::CouldntMove,
+    },
+}
+
+pub struct Rail<'d> {
+    enable: Output<'d>,
+}
+
+impl<'d> Rail<'d> {
+    /// Take the enable pin, driving it low (rail off).
+    #[inline]
+    pub fn new(pin: impl Peripheral<P = impl Pin> + 'd) -> Self {
+        let pin = pin.into_ref();
+        let number = pin.pin();
+        let enable = Output::new(pin, Level::Low);
+        let () = PIN.store(number, Ordering::SeqCst);
+        Self { enable }
+    }
+
+    #[inline]
+    pub fn is_on(&self) -> bool {
+        self.enable.is_set_high()
+    }
+
+    /// Start every servo's pulse at its leg's last commanded angles (see `Leg::attach`;
+    /// set them first if the defaults won't do), then switch the rail on and wait `RAIL_SETTLE`.
+    #[inline]
+    pub async fn power_up<const N: usize>(
+        &mut self,
+        body: &mut Body<'_, N>,
+    ) -> Result<(), CouldntPowerUp> {
+        for (index, leg) in body.legs_mut().iter_mut().enumerate() {
+            let () = leg
+                .attach()
+                .map_err(|error| CouldntPowerUp::Attach { leg: index, error })?;
+        }
+        let () = self.enable.set_high();
+        let () = crate::info!("Servo rail on");
+        Timer::after(RAIL_SETTLE).await
+    }
+
+    /// Switch the rail off. Same as `cut`, for whoever owns the rail.
+    #[inline]
+    pub fn power_down(&mut self) {
+        let () = self.enable.set_low();
+        let () = crate::info!("Servo rail off");
+    }
+}
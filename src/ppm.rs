@@ -0,0 +1,72 @@
+use {
+    crate::rc,
+    embassy_rp::gpio::Input,
+    embassy_time::{Duration, Instant},
+};
+
+// A PPM-sum stream carries every channel on one wire: a short pulse marks the start of each
+// channel, and the time between consecutive rising edges is that channel's pulse width
+// (nominally 1000 to 2000 microseconds). A gap longer than any channel ends the frame.
+
+/// Any gap between rising edges at least this long ends a frame.
+pub const SYNC_GAP: Duration = Duration::from_micros(3_000);
+
+/// Most channels a frame may carry; extra ones are ignored.
+const MAX_PPM_CHANNELS: usize = 12;
+
+/// Collects channel widths from rising-edge timestamps.
+#[derive(Debug)]
+pub struct Decoder {
+    widths: [u16; MAX_PPM_CHANNELS],
+    count: usize,
+    last_edge: Option<Instant>,
+    /// Whether we've seen a sync gap yet, i.e. whether `count` lines up with channel numbers.
+    synchronized: bool,
+}
+
+impl Default for Decoder {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            widths: [0; MAX_PPM_CHANNELS],
+            count: 0,
+            last_edge: None,
+            synchronized: false,
+        }
+    }
+}
+
+impl Decoder {
+    /// Feed the time of one rising edge. At the end of a frame,
+    /// returns the pulse widths (in microseconds) of every channel in it.
+    #[inline]
+    pub fn edge(&mut self, at: Instant) -> Option<&[u16]> {
+        let last = self.last_edge.replace(at)?;
+        let width = at - last;
+        if width >= SYNC_GAP {
+            let complete = self.synchronized && self.count > 0;
+            let count = core::mem::replace(&mut self.count, 0);
+            self.synchronized = true;
+            return complete.then_some(&self.widths[..count]);
+        }
+        if self.synchronized && self.count < MAX_PPM_CHANNELS {
+            self.widths[self.count] = width.as_micros().min(u16::MAX as u64) as u16;
+            self.count += 1;
+        }
+        None
+    }
+}
+
+/// Decode PPM-sum from `pin` forever, timestamping rising edges,
+/// and publish each frame through `rc` under `mapping`.
+/// PPM has no failsafe flag of its own; receivers either stop sending or send preset values.
+#[inline]
+pub async fn read(mut pin: Input<'static>, mapping: rc::Mapping) -> ! {
+    let mut decoder = Decoder::default();
+    loop {
+        let () = pin.wait_for_rising_edge().await;
+        if let Some(widths) = decoder.edge(Instant::now()) {
+            let () = rc::publish(&mapping, &rc::normalize_microseconds(widths, false));
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use {
+    crate::gait::{Gait, N_LEGS},
+    core::sync::atomic::{AtomicU8, AtomicU32, Ordering},
+    embassy_rp::adc::{self, Adc, Async, Channel},
+    embassy_time::Duration,
+};
+
+// One force-sensing resistor under each foot, in a divider so the ADC reads higher under more
+// force. FSRs are far from linear and vary part to part, so each gets a two-point calibration:
+// `tare` with the robot held up (feet unloaded), then `scale` with a known load on one foot.
+// That's rough, but it only has to tell "brushing the ground" from "carrying weight".
+
+/// Time between readings of every foot (see `analog::run`).
+pub const SAMPLE_PERIOD: Duration = Duration::from_millis(5);
+
+/// Readings averaged by `Feet::tare`.
+pub const TARE_SAMPLES: u16 = 32;
+
+/// Latest force on each foot, as `f32` bits; NaN for feet without a sensor.
+static NEWTONS: [AtomicU32; N_LEGS] = [const { AtomicU32::new(f32::NAN.to_bits()) }; N_LEGS];
+
+/// Latest `Contact` on each foot, as its discriminant; `NO_SENSOR` for feet without one.
+static CONTACT: [AtomicU8; N_LEGS] = [const { AtomicU8::new(NO_SENSOR) }; N_LEGS];
+
+const NO_SENSOR: u8 = u8::MAX;
+
+/// Latest force on this foot, if it has a sensor.
+#[inline]
+pub fn newtons(leg: usize) -> Option<f32> {
+    NEWTONS
+        .get(leg)
+        .map(|bits| f32::from_bits(bits.load(Ordering::Relaxed)))
+        .filter(|newtons| !newtons.is_nan())
+}
+
+/// Maps one sensor's raw counts to newtons: `(counts - tare) * newtons_per_count`.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Calibration {
+    /// Counts with nothing on the foot.
+    pub tare: f32,
+    pub newtons_per_count: f32,
+}
+
+impl Calibration {
+    /// Uncalibrated: reads raw counts as newtons, so thresholds are way off until `tare`/`scale`.
+    pub const RAW: Self = Self {
+        tare: 0.0,
+        newtons_per_count: 1.0,
+    };
+
+    #[inline]
+    pub fn newtons(&self, counts: u16) -> f32 {
+        ((counts as f32) - self.tare) * self.newtons_per_count
+    }
+}
+
+/// Latest contact level of this foot, if it has a sensor.
+#[inline]
+pub fn contact(leg: usize) -> Option<Contact> {
+    match CONTACT.get(leg)?.load(Ordering::Relaxed) {
+        0 => Some(Contact::Air),
+        1 => Some(Contact::Light),
+        2 => Some(Contact::Loaded),
+        _ => None,
+    }
+}
+
+/// Tell the gait which feet are actually carrying weight. Call once per motion loop
+/// iteration, before `Gait::advance`. Feet without a sensor are left alone.
+#[inline]
+pub fn apply(gait: &mut Gait) {
+    for leg in 0..N_LEGS {
+        if let Some(contact) = contact(leg) {
+            let () = gait.report_load(leg, contact == Contact::Loaded);
+        }
+    }
+}
+
+/// How firmly a foot is on the ground.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Contact {
+    Air = 0,
+    /// Touching, but not carrying enough to count on: the edge of a step, loose gravel, ...
+    Light,
+    /// Carrying its share of the body. Only these count as planted.
+    Loaded,
+}
+
+/// Forces separating the `Contact` levels.
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    /// Above this a foot is touching.
+    pub touch: f32,
+    /// Above this a foot is load-bearing.
+    pub load: f32,
+    /// How far below a threshold a force has to fall before that level clears,
+    /// so a foot on the edge doesn't flicker.
+    pub hysteresis: f32,
+}
+
+impl Thresholds {
+    /// For a robot around half a kilogram, with each foot carrying roughly a newton when planted.
+    pub const DEFAULT: Self = Self {
+        touch: 0.1,
+        load: 0.4,
+        hysteresis: 0.05,
+    };
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntCalibrate {
+    NoSuchLeg {
+        index: usize,
+    },
+    Adc(adc::Error),
+    /// The foot read no more than its tare, so no scale can be worked out.
+    NoLoad,
+}
+
+/// Every foot's sensor and what it last said.
+#[derive(Clone, Copy, Debug)]
+pub struct Feet<const N: usize> {
+    calibrations: [Calibration; N],
+    thresholds: Thresholds,
+    contact: [Contact; N],
+}
+
+impl<const N: usize> Feet<N> {
+    #[inline]
+    pub const fn new(calibrations: [Calibration; N], thresholds: Thresholds) -> Self {
+        Self {
+            calibrations,
+            thresholds,
+            contact: [Contact::Air; N],
+        }
+    }
+
+    #[inline]
+    pub fn calibrations(&self) -> &[Calibration; N] {
+        &self.calibrations
+    }
+
+    #[inline]
+    pub fn contact(&self) -> &[Contact; N] {
+        &self.contact
+    }
+
+    /// Fold in one raw reading from this leg's sensor, returning its (possibly new) contact level.
+    #[inline]
+    pub fn update(&mut self, leg: usize, counts: u16) -> Contact {
+        let newtons = self.calibrations[leg].newtons(counts);
+        let Thresholds {
+            touch,
+            load,
+            hysteresis,
+        } = self.thresholds;
+        // Rising takes the full threshold; falling back takes dropping `hysteresis` below it:
+        let above = |threshold: f32, level: Contact| {
+            let threshold = if self.contact[leg] >= level {
+                threshold - hysteresis
+            } else {
+                threshold
+            };
+            newtons > threshold
+        };
+        let contact = if above(load, Contact::Loaded) {
+            Contact::Loaded
+        } else if above(touch, Contact::Light) {
+            Contact::Light
+        } else {
+            Contact::Air
+        };
+        self.contact[leg] = contact;
+        if let (Some(force), Some(level)) = (NEWTONS.get(leg), CONTACT.get(leg)) {
+            let () = force.store(newtons.to_bits(), Ordering::Relaxed);
+            let () = level.store(contact as u8, Ordering::Relaxed);
+        }
+        contact
+    }
+
+    /// Read every foot once.
+    #[inline]
+    pub async fn sample(
+        &mut self,
+        adc: &mut Adc<'_, Async>,
+        channels: &mut [Channel<'_>; N],
+    ) -> Result<(), adc::Error> {
+        for (leg, channel) in channels.iter_mut().enumerate() {
+            let _: Contact = self.update(leg, adc.read(channel).await?);
+        }
+        Ok(())
+    }
+
+    /// Record this leg's unloaded reading. Hold the robot up (or at least this foot) first.
+    #[inline]
+    pub async fn tare(
+        &mut self,
+        leg: usize,
+        adc: &mut Adc<'_, Async>,
+        channel: &mut Channel<'_>,
+    ) -> Result<(), CouldntCalibrate> {
+        let counts = average(adc, channel).await?;
+        self.calibrations
+            .get_mut(leg)
+            .ok_or(CouldntCalibrate::NoSuchLeg { index: leg })?
+            .tare = counts;
+        Ok(())
+    }
+
+    /// Work out this leg's scale from `known_newtons` resting on it. `tare` it first.
+    #[inline]
+    pub async fn scale(
+        &mut self,
+        leg: usize,
+        known_newtons: f32,
+        adc: &mut Adc<'_, Async>,
+        channel: &mut Channel<'_>,
+    ) -> Result<(), CouldntCalibrate> {
+        let counts = average(adc, channel).await?;
+        let calibration = self
+            .calibrations
+            .get_mut(leg)
+            .ok_or(CouldntCalibrate::NoSuchLeg { index: leg })?;
+        let span = counts - calibration.tare;
+        if span <= 0.0 {
+            return Err(CouldntCalibrate::NoLoad);
+        }
+        calibration.newtons_per_count = known_newtons / span;
+        Ok(())
+    }
+}
+
+#[inline]
+async fn average(
+    adc: &mut Adc<'_, Async>,
+    channel: &mut Channel<'_>,
+) -> Result<f32, CouldntCalibrate> {
+    let mut sum = 0_u32;
+    for _ in 0..TARE_SAMPLES {
+        sum += adc.read(channel).await.map_err(CouldntCalibrate::Adc)? as u32;
+    }
+    Ok((sum as f32) / (TARE_SAMPLES as f32))
+}
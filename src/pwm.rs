@@ -1,8 +1,12 @@
+#[cfg(not(feature = "sim"))]
 use {
     embassy_rp::{
         Peripheral,
         pwm::{self, Config, Pwm, PwmOutput},
     },
+    embedded_hal::pwm::{ErrorType, SetDutyCycle},
+};
+use {
     embassy_sync::once_lock::OnceLock,
     embassy_time::{Duration, Ticker},
     fixed::{FixedU16, FixedU32, traits::LosslessTryFrom, types::extra::U4},
@@ -20,6 +24,47 @@ use {
 // Then, once we have `divider`, we can rearrange the original equation:
 // `top = (clock_hz / (PULSE_FREQ_HZ * 2 * divider)) - 1`.
 
+/// One servo's PWM channel: a real one on the robot, or a stand-in under `sim`.
+/// Writes go straight to the hardware until `output::start`, and are staged for it after.
+#[cfg(not(feature = "sim"))]
+pub struct Output<'d> {
+    channel: PwmOutput<'d>,
+    /// Where `output` stages this channel's writes: twice the slice number, plus one for B.
+    slot: usize,
+}
+#[cfg(feature = "sim")]
+pub type Output<'d> = crate::sim::Output<'d>;
+
+#[cfg(not(feature = "sim"))]
+pub type Error = embassy_rp::pwm::PwmError;
+
+#[cfg(not(feature = "sim"))]
+impl ErrorType for Output<'_> {
+    type Error = Error;
+}
+
+#[cfg(not(feature = "sim"))]
+impl SetDutyCycle for Output<'_> {
+    #[inline]
+    fn max_duty_cycle(&self) -> u16 {
+        self.channel.max_duty_cycle()
+    }
+
+    #[inline]
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        if !crate::output::is_running() {
+            return self.channel.set_duty_cycle(duty);
+        }
+        if duty > self.max_duty_cycle() {
+            return Err(Error::InvalidDutyCycle);
+        }
+        let () = crate::output::stage(self.slot, duty);
+        Ok(())
+    }
+}
+#[cfg(feature = "sim")]
+pub type Error = crate::sim::Error;
+
 pub const PULSE_PERIOD_MS: u16 = 20;
 pub const PULSE_FREQ_HZ: u16 = 1000 / PULSE_PERIOD_MS;
 
@@ -48,8 +93,11 @@ pub async fn clock_frequency() -> u32 {
     static LOCK: OnceLock<u32> = OnceLock::new();
 
     *get_or_init(&LOCK, async || {
+        #[cfg(not(feature = "sim"))]
         let clk_hz: u32 = embassy_rp::clocks::clk_sys_freq();
-        let () = log::info!("Clock frequency: {clk_hz:?} Hz");
+        #[cfg(feature = "sim")]
+        let clk_hz: u32 = crate::sim::CLOCK_HZ;
+        let () = crate::info!("Clock frequency: {clk_hz:?} Hz");
         clk_hz
     })
     .await
@@ -64,7 +112,7 @@ pub async fn clock_frequency_fp() -> FixedU32<U4> {
         let Some(clock_hz) = FixedU32::<U4>::checked_from_num(clock_hz) else {
             let mut ticker = Ticker::every(Duration::from_secs(1));
             loop {
-                let () = log::error!("Clock frequency too large: {clock_hz:#?}");
+                let () = crate::error!("Clock frequency too large: {clock_hz:#?}");
                 let () = ticker.next().await;
             }
         };
@@ -73,25 +121,39 @@ pub async fn clock_frequency_fp() -> FixedU32<U4> {
     .await
 }
 
+/// Smallest clock divider that still fits one pulse period into the 16-bit counter
+/// at `clock_hz` (see the derivation above), or `None` if an intermediate overflows.
+#[inline]
+pub fn divider_for(clock_hz: FixedU32<U4>) -> Option<FixedU32<U4>> {
+    let denominator = FixedU32::<U4>::checked_from_num((PULSE_FREQ_HZ as u32) << 17)?;
+    Some((clock_hz / denominator) + FixedU32::<U4>::from_bits(1))
+}
+
+/// Counter top for one pulse period at `clock_hz` with `divider`,
+/// or `None` if it doesn't fit in 16 bits (or an intermediate overflows).
+#[inline]
+pub fn top_for(clock_hz: FixedU32<U4>, divider: FixedU32<U4>) -> Option<u16> {
+    let denominator = FixedU32::<U4>::checked_from_num((PULSE_FREQ_HZ as u32 * divider) << 1)?;
+    let top = (clock_hz / denominator) - FixedU32::<U4>::ONE;
+    top.floor().checked_to_num()
+}
+
 #[inline]
 pub async fn clock_divider_32b() -> FixedU32<U4> {
     static LOCK: OnceLock<FixedU32<U4>> = OnceLock::new();
 
     *get_or_init(&LOCK, async || {
-        let divider = {
-            let denominator = (PULSE_FREQ_HZ as u32) << 17;
-            let Some(denominator) = FixedU32::<U4>::checked_from_num(denominator) else {
-                let mut ticker = Ticker::every(Duration::from_secs(1));
-                loop {
-                    let () = log::error!(
-                        "Clock divider intermediate computation too large: {denominator:#?}"
-                    );
-                    let () = ticker.next().await;
-                }
-            };
-            (clock_frequency_fp().await / denominator) + FixedU32::<U4>::from_bits(1)
+        let clock_hz = clock_frequency_fp().await;
+        let Some(divider) = divider_for(clock_hz) else {
+            let mut ticker = Ticker::every(Duration::from_secs(1));
+            loop {
+                let () = crate::error!(
+                    "Clock divider intermediate computation too large for {clock_hz:#?} Hz"
+                );
+                let () = ticker.next().await;
+            }
         };
-        let () = log::info!("Clock divider: {divider:?}");
+        let () = crate::info!("Clock divider: {divider:?}");
         divider
     })
     .await
@@ -103,24 +165,14 @@ pub async fn clock_top() -> u16 {
 
     *get_or_init(&LOCK, async || {
         let divider = clock_divider_32b().await;
-        let denominator = (PULSE_FREQ_HZ as u32 * divider) << 1;
-        let Some(denominator) = FixedU32::<U4>::checked_from_num(denominator) else {
-            let mut ticker = Ticker::every(Duration::from_secs(1));
-            loop {
-                let () =
-                    log::error!("Clock top intermediate computation too large: {denominator:#?}");
-                let () = ticker.next().await;
-            }
-        };
-        let top = (clock_frequency_fp().await / denominator) - FixedU32::<U4>::ONE;
-        let () = log::info!("Clock top: {top:?}");
-        let Some(top) = top.floor().checked_to_num() else {
+        let Some(top) = top_for(clock_frequency_fp().await, divider) else {
             let mut ticker = Ticker::every(Duration::from_secs(1));
             loop {
-                let () = log::error!("Clock top too large: {top:#?}");
+                let () = crate::error!("Clock top too large with divider {divider:#?}");
                 let () = ticker.next().await;
             }
         };
+        let () = crate::info!("Clock top: {top:?}");
         top
     })
     .await
@@ -135,7 +187,7 @@ pub async fn clock_divider() -> FixedU16<U4> {
         let Some(divider) = FixedU16::<U4>::lossless_try_from(divider) else {
             let mut ticker = Ticker::every(Duration::from_secs(1));
             loop {
-                let () = log::error!("Clock divider too large: {divider:#?}");
+                let () = crate::error!("Clock divider too large: {divider:#?}");
                 let () = ticker.next().await;
             }
         };
@@ -144,43 +196,20 @@ pub async fn clock_divider() -> FixedU16<U4> {
     .await
 }
 
-#[inline]
-pub async fn pulse_min() -> f32 {
-    static LOCK: OnceLock<f32> = OnceLock::new();
-    *get_or_init(&LOCK, async || (clock_top().await as f32) / 20.0).await
-}
-#[inline]
-pub async fn pulse_max() -> f32 {
-    static LOCK: OnceLock<f32> = OnceLock::new();
-    *get_or_init(&LOCK, async || (clock_top().await as f32) / 10.0).await
-}
-#[inline]
-pub async fn pulse_center() -> f32 {
-    static LOCK: OnceLock<f32> = OnceLock::new();
-    *get_or_init(&LOCK, async || {
-        0.5 * (pulse_min().await + pulse_max().await)
-    })
-    .await
-}
-#[inline]
-pub async fn pulse_range_plus_minus() -> f32 {
-    static LOCK: OnceLock<f32> = OnceLock::new();
-    let if_it_were_a_normal_servo =
-        *get_or_init(&LOCK, async || pulse_center().await - pulse_min().await).await;
-    if_it_were_a_normal_servo * 2.0
-}
-
+#[cfg(not(feature = "sim"))]
 #[inline]
 pub async fn init_slice<'d, Slice: pwm::Slice>(
     slice: impl Peripheral<P = Slice> + 'd,
     a: impl Peripheral<P = impl pwm::ChannelAPin<Slice>> + 'd,
     b: impl Peripheral<P = impl pwm::ChannelBPin<Slice>> + 'd,
-) -> (PwmOutput<'d>, PwmOutput<'d>) {
+) -> (Output<'d>, Output<'d>) {
+    let slice = slice.into_ref();
+    let number = slice.number();
     let (a, b) = Pwm::new_output_ab(slice, a, b, {
         let mut cfg = Config::default();
-        // let pulse_center = pulse_center().await;
-        cfg.compare_a = 0; // pulse_center as _;
-        cfg.compare_b = 0; // pulse_center as _;
+        // No pulses until each servo's first move:
+        cfg.compare_a = 0;
+        cfg.compare_b = 0;
         cfg.divider = clock_divider().await;
         cfg.enable = true;
         cfg.phase_correct = true;
@@ -192,7 +221,7 @@ pub async fn init_slice<'d, Slice: pwm::Slice>(
     let Some(a) = a else {
         let mut ticker = Ticker::every(Duration::from_secs(1));
         loop {
-            let () = log::error!("PWM slice did not allow an A channel");
+            let () = crate::error!("PWM slice did not allow an A channel");
             let () = ticker.next().await;
         }
     };
@@ -200,10 +229,19 @@ pub async fn init_slice<'d, Slice: pwm::Slice>(
     let Some(b) = b else {
         let mut ticker = Ticker::every(Duration::from_secs(1));
         loop {
-            let () = log::error!("PWM slice did not allow a B channel");
+            let () = crate::error!("PWM slice did not allow a B channel");
             let () = ticker.next().await;
         }
     };
 
-    (a, b)
+    (
+        Output {
+            channel: a,
+            slot: 2 * number,
+        },
+        Output {
+            channel: b,
+            slot: 2 * number + 1,
+        },
+    )
 }
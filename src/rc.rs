@@ -0,0 +1,186 @@
+use {
+    crate::{body, failsafe, gait, ik},
+    embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal},
+};
+
+/// Most channels any supported receiver protocol carries.
+pub const MAX_CHANNELS: usize = 16;
+
+/// Raw channel value at center stick, for the 11-bit protocols (SBUS and CRSF).
+const RAW_11_BIT_CENTER: f32 = 992.0;
+/// Raw distance from center to full deflection, for the 11-bit protocols.
+const RAW_11_BIT_HALF_RANGE: f32 = 820.0;
+
+/// Latest stick positions, already mapped, for the motion task to pick up.
+/// A signal rather than a queue: RC frames arrive faster than they're needed,
+/// and only the newest one matters.
+static LATEST: Signal<CriticalSectionRawMutex, Input> = Signal::new();
+
+/// One frame of decoded channels, whatever the receiver protocol.
+#[derive(Clone, Copy, Debug)]
+pub struct Channels {
+    /// Each on [-1, 1], centered at 0. Channels the protocol doesn't carry read 0.
+    pub values: [f32; MAX_CHANNELS],
+    /// The receiver has lost the transmitter and is making up (or holding) values.
+    pub failsafe: bool,
+}
+
+/// Where one control comes from.
+#[derive(Clone, Copy, Debug)]
+pub struct Stick {
+    pub channel: usize,
+    /// Output at full deflection. Negative to reverse.
+    pub scale: f32,
+}
+
+/// Which channel drives what. Unmapped controls stay at zero.
+#[derive(Clone, Copy, Debug)]
+pub struct Mapping {
+    pub forward: Option<Stick>,
+    pub strafe: Option<Stick>,
+    pub yaw_rate: Option<Stick>,
+    pub roll: Option<Stick>,
+    pub pitch: Option<Stick>,
+    /// Raises or lowers the body, added to the pose's translation.
+    pub height: Option<Stick>,
+    /// Stick deflections smaller than this (on [0, 1]) read as centered.
+    pub deadband: f32,
+}
+
+impl Mapping {
+    /// Mode-2 AETR: right stick walks and strafes, left stick turns and sets height.
+    /// Body tilt is left unmapped.
+    pub const DEFAULT: Self = Self {
+        strafe: Some(Stick {
+            channel: 0,
+            scale: -2.0,
+        }),
+        forward: Some(Stick {
+            channel: 1,
+            scale: 2.0,
+        }),
+        height: Some(Stick {
+            channel: 2,
+            scale: 1.0,
+        }),
+        yaw_rate: Some(Stick {
+            channel: 3,
+            scale: -1.0,
+        }),
+        roll: None,
+        pitch: None,
+        deadband: 0.05,
+    };
+
+    /// Turn raw channels into motion commands. In failsafe, stop and level out
+    /// regardless of what the channels say.
+    #[inline]
+    pub fn apply(&self, channels: &Channels) -> Input {
+        if channels.failsafe {
+            return Input {
+                velocity: gait::Velocity::default(),
+                pose: body::Pose::NEUTRAL,
+                failsafe: true,
+            };
+        }
+        let read = |stick: Option<Stick>| {
+            let Some(Stick { channel, scale }) = stick else {
+                return 0.0;
+            };
+            let Some(&value) = channels.values.get(channel) else {
+                return 0.0;
+            };
+            // Rescale what's left outside the deadband, so output still starts at zero:
+            let magnitude = value.abs().clamp(0.0, 1.0);
+            if magnitude <= self.deadband {
+                return 0.0;
+            }
+            let magnitude = (magnitude - self.deadband) / (1.0 - self.deadband);
+            scale * if value < 0.0 { -magnitude } else { magnitude }
+        };
+        Input {
+            velocity: gait::Velocity {
+                x: read(self.forward),
+                y: read(self.strafe),
+                yaw_rate: read(self.yaw_rate),
+            },
+            pose: body::Pose {
+                translation: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                    x: 0.0,
+                    y: 0.0,
+                    z: read(self.height),
+                },
+                rotation: body::Rotation {
+                    roll: read(self.roll),
+                    pitch: read(self.pitch),
+                    yaw: 0.0,
+                }
+                .into(),
+            },
+            failsafe: false,
+        }
+    }
+}
+
+/// What the sticks are asking for.
+#[derive(Clone, Copy, Debug)]
+pub struct Input {
+    pub velocity: gait::Velocity,
+    pub pose: body::Pose,
+    pub failsafe: bool,
+}
+
+/// Sixteen 11-bit channels packed LSB first into 22 bytes, as SBUS and CRSF both send them.
+#[inline]
+pub fn unpack_11_bit(payload: &[u8; 22]) -> [u16; 16] {
+    let mut raw = [0; 16];
+    for (i, channel) in raw.iter_mut().enumerate() {
+        let bit = 11 * i;
+        let byte = bit / 8;
+        let shift = bit % 8;
+        // Eleven bits starting `shift` bits into `byte` span at most three bytes:
+        let mut word = (payload[byte] as u32) | ((payload[byte + 1] as u32) << 8);
+        if let Some(&third) = payload.get(byte + 2) {
+            word |= (third as u32) << 16;
+        }
+        *channel = ((word >> shift) & 0x07FF) as u16;
+    }
+    raw
+}
+
+/// Raw 11-bit channels (SBUS or CRSF) to [-1, 1].
+#[inline]
+pub fn normalize_11_bit(raw: &[u16; 16], failsafe: bool) -> Channels {
+    let mut values = [0.0; MAX_CHANNELS];
+    for (value, &raw) in values.iter_mut().zip(raw) {
+        *value = ((raw as f32 - RAW_11_BIT_CENTER) / RAW_11_BIT_HALF_RANGE).clamp(-1.0, 1.0);
+    }
+    Channels { values, failsafe }
+}
+
+/// Pulse widths (or values in the same units, as iBUS sends) to [-1, 1],
+/// with 1500 microseconds at center and 500 either way to full deflection.
+/// Channels past the end of `microseconds` read 0.
+#[inline]
+pub fn normalize_microseconds(microseconds: &[u16], failsafe: bool) -> Channels {
+    let mut values = [0.0; MAX_CHANNELS];
+    for (value, &width) in values.iter_mut().zip(microseconds) {
+        *value = ((width as f32 - 1_500.0) / 500.0).clamp(-1.0, 1.0);
+    }
+    Channels { values, failsafe }
+}
+
+/// Map a decoded frame and make it the latest input. Called by each receiver backend.
+#[inline]
+pub fn publish(mapping: &Mapping, channels: &Channels) {
+    if !channels.failsafe {
+        let () = failsafe::heard();
+    }
+    LATEST.signal(mapping.apply(channels))
+}
+
+/// The newest input since the last call, if any arrived.
+#[inline]
+pub fn take() -> Option<Input> {
+    LATEST.try_take()
+}
@@ -0,0 +1,158 @@
+use {
+    crate::{body::Body, command::Command, ik, link, telemetry},
+    embassy_futures::join::join,
+    embassy_rp::uart::{self, Async, Uart, UartRx, UartTx},
+    embassy_time::Instant,
+};
+
+// Messages laid out like their ROS 2 counterparts, so a host-side node can copy fields straight
+// across: little-endian, lengths and angles as f64, stamps as `builtin_interfaces/Time`.
+// They travel in ordinary `telemetry` frames, so any transport carrying frames can bridge them.
+//
+//     KIND_JOINT_STATE   out  sensor_msgs/JointState: sec: i32 | nanosec: u32 | n: u32 | n x position: f64
+//     KIND_TWIST         in   geometry_msgs/Twist: linear x, y, z | angular x, y, z
+//     KIND_FOOT_POINT    in   leg: u8 | geometry_msgs/Point x, y, z (body frame)
+//
+// Joint state positions are in the order yaw, hip, knee for leg 0, then leg 1, and so on;
+// `joint_name` gives the matching `name` entries. Lengths are in the same units as `ik`.
+
+pub const KIND_JOINT_STATE: u8 = 0x10;
+pub const KIND_TWIST: u8 = 0x11;
+pub const KIND_FOOT_POINT: u8 = 0x12;
+
+/// Most legs a joint state message can carry.
+pub const MAX_LEGS: usize = 8;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntTranslate {
+    WrongLength,
+    UnknownKind(u8),
+    #[cfg(feature = "serde")]
+    Postcard(postcard::Error),
+}
+
+/// The `name` of joint `index` in a joint state message, e.g. `leg2_hip`.
+#[inline]
+pub fn joint_name(index: usize) -> heapless::String<16> {
+    use core::fmt::Write as _;
+    let mut name = heapless::String::new();
+    let _: core::fmt::Result = write!(
+        name,
+        "leg{}_{}",
+        index / 3,
+        ["yaw", "hip", "knee"][index % 3]
+    );
+    name
+}
+
+/// Encode the body's joint angles as a joint state frame and queue it with the rest of telemetry.
+#[inline]
+pub fn publish_joint_state<const N: usize>(body: &Body<'_, N>) {
+    let micros = Instant::now().as_micros();
+    let angles = body.joint_angles();
+    let legs = N.min(MAX_LEGS);
+
+    let mut payload = heapless::Vec::<u8, { 12 + 3 * 8 * MAX_LEGS }>::new();
+    let _: Result<(), ()> = payload.extend_from_slice(&((micros / 1_000_000) as i32).to_le_bytes());
+    let _: Result<(), ()> =
+        payload.extend_from_slice(&(((micros % 1_000_000) * 1_000) as u32).to_le_bytes());
+    let _: Result<(), ()> = payload.extend_from_slice(&(3 * legs as u32).to_le_bytes());
+    for ik::Angles { yaw, hip, knee } in &angles[..legs] {
+        for angle in [yaw, hip, knee] {
+            let _: Result<(), ()> = payload.extend_from_slice(&(*angle as f64).to_le_bytes());
+        }
+    }
+
+    let mut frame = [0; 2 * (12 + 3 * 8 * MAX_LEGS)];
+    match telemetry::encode_frame(KIND_JOINT_STATE, &payload, &mut frame) {
+        Ok(n) => telemetry::publish(&frame[..n]),
+        Err(e) => crate::warn!("Couldn't encode a joint state: {e:?}"),
+    }
+}
+
+/// Turn an incoming message into a command, if it's one of ours
+/// (including `message::KIND_COMMAND` with the `serde` feature).
+#[inline]
+pub fn command_from_frame(kind: u8, payload: &[u8]) -> Result<Command, CouldntTranslate> {
+    match kind {
+        KIND_TWIST => {
+            let [x, y, _z, _roll_rate, _pitch_rate, yaw_rate] = f64s(payload)?;
+            Ok(Command::GaitVelocity {
+                x: x as f32,
+                y: y as f32,
+                yaw_rate: yaw_rate as f32,
+            })
+        }
+        KIND_FOOT_POINT => {
+            let (&leg, point) = payload.split_first().ok_or(CouldntTranslate::WrongLength)?;
+            let [x, y, z] = f64s(point)?;
+            Ok(Command::FootPosition {
+                leg: leg as usize,
+                position: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                    x: x as f32,
+                    y: y as f32,
+                    z: z as f32,
+                },
+            })
+        }
+        #[cfg(feature = "serde")]
+        crate::message::KIND_COMMAND => {
+            crate::message::decode_command(payload).map_err(CouldntTranslate::Postcard)
+        }
+        other => Err(CouldntTranslate::UnknownKind(other)),
+    }
+}
+
+#[inline]
+fn f64s<const N: usize>(bytes: &[u8]) -> Result<[f64; N], CouldntTranslate> {
+    if bytes.len() != 8 * N {
+        return Err(CouldntTranslate::WrongLength);
+    }
+    Ok(core::array::from_fn(|i| {
+        let mut word = [0; 8];
+        let () = word.copy_from_slice(&bytes[8 * i..8 * (i + 1)]);
+        f64::from_le_bytes(word)
+    }))
+}
+
+/// Bridge a serial link forever: incoming frames become commands,
+/// and everything queued for telemetry (joint states included) goes out.
+#[inline]
+pub async fn bridge<T: uart::Instance>(uart: Uart<'static, T, Async>) -> ! {
+    let (tx, rx) = uart.split();
+    let (never, _) = join(receive(rx), transmit(tx)).await;
+    never
+}
+
+#[inline]
+async fn receive<T: uart::Instance>(mut rx: UartRx<'static, T, Async>) -> ! {
+    let mut decoder = telemetry::FrameDecoder::default();
+    loop {
+        let mut byte = [0];
+        if let Err(e) = rx.read(&mut byte).await {
+            let () = crate::warn!("ROS bridge read error: {e:?}");
+            decoder = telemetry::FrameDecoder::default();
+            continue;
+        }
+        match decoder.push(byte[0]) {
+            None => {}
+            Some(Err(e)) => {
+                let () = telemetry::count(telemetry::ErrorKind::Command);
+                let () = crate::warn!("Bad ROS bridge frame: {e:?}");
+            }
+            Some(Ok((kind, payload))) => link::handle_frame(kind, payload).await,
+        }
+    }
+}
+
+#[inline]
+async fn transmit<T: uart::Instance>(mut tx: UartTx<'static, T, Async>) -> ! {
+    let mut buffer = [0; 64];
+    loop {
+        let n = telemetry::read(&mut buffer).await;
+        if let Err(e) = tx.write(&buffer[..n]).await {
+            let () = crate::warn!("ROS bridge write error: {e:?}");
+        }
+    }
+}
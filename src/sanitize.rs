@@ -0,0 +1,180 @@
+use {
+    crate::{body, command::Command, ik, math::Quaternion},
+    core::{cell::RefCell, f32::consts::PI},
+    embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    embassy_time::{Duration, Instant},
+};
+
+// Everything from outside (a host script, a phone, a flight controller) passes through here
+// before it reaches the motion layer, so a misbehaving sender gets turned away at the door:
+//
+//  - `check` rejects any command carrying a NaN or infinity, or a value so far out of bounds
+//    it can only be garbage. The real limits (joint ranges, reach) are still the owners' to
+//    enforce; these are deliberately loose.
+//  - `Limiter` gives each `Source` its own token bucket, refilled at `RATE_HZ` (the motion
+//    loop's rate) with room for a `BURST`, so no one sender can fill the command queue faster
+//    than the loop empties it, or starve the others.
+
+/// Commands per second each source may send, sustained.
+pub const RATE_HZ: u32 = 50;
+/// Commands a source may send at once after a quiet spell.
+pub const BURST: u32 = 8;
+
+/// Farthest from the eye center a foot could conceivably be asked to go.
+pub const MAX_DISTANCE: f32 = 2.0
+    * (ik::LENGTH_CENTER_TO_YAW
+        + ik::LENGTH_YAW_TO_HIP
+        + ik::LENGTH_HIP_TO_KNEE
+        + ik::LENGTH_KNEE_TO_FOOT);
+/// Fastest linear speed (or foot speed) worth asking for, in length units per second.
+pub const MAX_SPEED: f32 = 100.0;
+/// Fastest turn worth asking for, in radians per second.
+pub const MAX_YAW_RATE: f32 = 4.0 * PI;
+/// Longest `Dwell` worth asking for.
+pub const MAX_DWELL: Duration = Duration::from_secs(60);
+/// Furthest ahead a command may be scheduled (see `command::submit_at`).
+pub const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Somewhere commands come from, each rate-limited separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Uart,
+    Usb,
+    Bluetooth,
+    /// Framed commands over SPI or the ROS bridge (see `link`).
+    Link,
+    I2c,
+    /// Anything on board deciding for itself (a planner, a behaviour), as its own task.
+    Autonomy,
+}
+
+const SOURCES: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum Invalid {
+    /// A NaN or an infinity.
+    NotFinite,
+    /// Finite, but far beyond anything the robot could do.
+    OutOfBounds,
+}
+
+/// Turn away commands carrying numbers no sensible sender would send.
+#[inline]
+pub fn check(command: &Command) -> Result<(), Invalid> {
+    match *command {
+        Command::FootPosition { position, .. } => point(position),
+        Command::JointAngle { radians, .. } => angle(radians),
+        Command::JointAngles { angles, .. } => {
+            let () = angle(angles.yaw)?;
+            let () = angle(angles.hip)?;
+            angle(angles.knee)
+        }
+        Command::GaitVelocity { x, y, yaw_rate } => {
+            let () = within(x, MAX_SPEED)?;
+            let () = within(y, MAX_SPEED)?;
+            within(yaw_rate, MAX_YAW_RATE)
+        }
+        Command::BodyPose(body::Pose {
+            translation,
+            rotation,
+        }) => {
+            let () = point(translation)?;
+            orientation(rotation)
+        }
+        Command::MoveFoot { x, y, z, speed, .. } => {
+            for coordinate in [x, y, z].into_iter().flatten() {
+                let () = within(coordinate, MAX_DISTANCE)?;
+            }
+            match speed {
+                Some(speed) => within(speed, MAX_SPEED),
+                None => Ok(()),
+            }
+        }
+        Command::Dwell(duration) | Command::TeachCapture(duration) if duration > MAX_DWELL => {
+            Err(Invalid::OutOfBounds)
+        }
+        Command::SetParameter(_, value) if !value.is_finite() => Err(Invalid::NotFinite),
+        _ => Ok(()),
+    }
+}
+
+#[inline]
+fn within(value: f32, max: f32) -> Result<(), Invalid> {
+    if !value.is_finite() {
+        return Err(Invalid::NotFinite);
+    }
+    if libm::fabsf(value) > max {
+        return Err(Invalid::OutOfBounds);
+    }
+    Ok(())
+}
+
+#[inline]
+fn angle(radians: f32) -> Result<(), Invalid> {
+    within(radians, PI)
+}
+
+/// Anything close enough to unit length that normalizing it means what the sender meant.
+#[inline]
+fn orientation(rotation: Quaternion) -> Result<(), Invalid> {
+    for component in [rotation.w, rotation.x, rotation.y, rotation.z] {
+        let () = within(component, 2.0)?;
+    }
+    if !(0.5..=2.0).contains(&rotation.norm()) {
+        return Err(Invalid::OutOfBounds);
+    }
+    Ok(())
+}
+
+#[inline]
+fn point(
+    ik::CartesianDisplacementFromEyeCenterLookingForward { x, y, z }: ik::CartesianDisplacementFromEyeCenterLookingForward,
+) -> Result<(), Invalid> {
+    let () = within(x, MAX_DISTANCE)?;
+    let () = within(y, MAX_DISTANCE)?;
+    within(z, MAX_DISTANCE)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: u32,
+    refilled: Instant,
+    /// Whether this source is being turned away, so only the start of a flood is logged.
+    throttled: bool,
+}
+
+static BUCKETS: Mutex<CriticalSectionRawMutex, RefCell<[Option<Bucket>; SOURCES]>> =
+    Mutex::new(RefCell::new([None; SOURCES]));
+
+/// Take one command's worth from `source`'s bucket, or say it's sending too fast.
+#[inline]
+pub fn take_token(source: Source) -> bool {
+    let now = Instant::now();
+    let (allowed, flooding) = BUCKETS.lock(|buckets| {
+        let mut buckets = buckets.borrow_mut();
+        let bucket = buckets[source as usize].get_or_insert(Bucket {
+            tokens: BURST,
+            refilled: now,
+            throttled: false,
+        });
+        let earned = ((now - bucket.refilled).as_micros() * RATE_HZ as u64 / 1_000_000) as u32;
+        if earned > 0 {
+            bucket.tokens = (bucket.tokens + earned).min(BURST);
+            // Advance only by whole tokens, so fractions carry over:
+            bucket.refilled += Duration::from_micros(earned as u64 * 1_000_000 / RATE_HZ as u64);
+        }
+        match bucket.tokens.checked_sub(1) {
+            Some(tokens) => {
+                bucket.tokens = tokens;
+                bucket.throttled = false;
+                (true, false)
+            }
+            None => (false, !core::mem::replace(&mut bucket.throttled, true)),
+        }
+    });
+    if flooding {
+        let () = crate::warn!("{source:?} is sending commands too fast: dropping some");
+    }
+    allowed
+}
@@ -0,0 +1,122 @@
+use {
+    crate::rc,
+    embassy_rp::uart::{self, Async, UartRx},
+};
+
+// SBUS frames are 25 bytes, sent every 7 or 14 ms:
+//
+//     0x0F | 22 bytes: 16 channels x 11 bits, LSB first | flags | 0x00
+//
+// where flags holds digital channels 17 and 18 (bits 0 and 1), "frame lost" (bit 2),
+// and "failsafe" (bit 3).
+
+const FRAME_LENGTH: usize = 25;
+const HEADER: u8 = 0x0F;
+const FOOTER: u8 = 0x00;
+const FLAG_FRAME_LOST: u8 = 1 << 2;
+const FLAG_FAILSAFE: u8 = 1 << 3;
+
+/// 100 000 baud, 8E2, inverted: what every SBUS receiver sends.
+#[inline]
+pub fn config() -> uart::Config {
+    let mut config = uart::Config::default();
+    config.baudrate = 100_000;
+    config.data_bits = uart::DataBits::DataBits8;
+    config.parity = uart::Parity::ParityEven;
+    config.stop_bits = uart::StopBits::STOP2;
+    config.invert_rx = true;
+    config
+}
+
+/// One decoded SBUS frame.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    /// Raw 11-bit values, 0 through 2047.
+    pub raw: [u16; 16],
+    /// The receiver missed this frame from the transmitter (it repeats the last good one).
+    pub frame_lost: bool,
+    pub failsafe: bool,
+}
+
+impl Frame {
+    #[inline]
+    pub fn channels(&self) -> rc::Channels {
+        rc::normalize_11_bit(&self.raw, self.failsafe)
+    }
+}
+
+/// Reassembles frames from a byte stream, resynchronizing on its own after garbage.
+#[derive(Debug)]
+pub struct Decoder {
+    buffer: [u8; FRAME_LENGTH],
+    length: usize,
+}
+
+impl Default for Decoder {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            buffer: [0; FRAME_LENGTH],
+            length: 0,
+        }
+    }
+}
+
+impl Decoder {
+    /// Feed one byte, getting a frame back whenever one completes.
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Option<Frame> {
+        if self.length == 0 && byte != HEADER {
+            return None;
+        }
+        self.buffer[self.length] = byte;
+        self.length += 1;
+        if self.length < FRAME_LENGTH {
+            return None;
+        }
+
+        if self.buffer[FRAME_LENGTH - 1] == FOOTER {
+            self.length = 0;
+            return Some(decode(&self.buffer));
+        }
+        // Out of sync: start over from the next header byte we've already buffered, if any.
+        match self.buffer[1..].iter().position(|&b| b == HEADER) {
+            Some(i) => {
+                let () = self.buffer.copy_within(i + 1.., 0);
+                self.length = FRAME_LENGTH - (i + 1);
+            }
+            None => self.length = 0,
+        }
+        None
+    }
+}
+
+#[inline]
+fn decode(buffer: &[u8; FRAME_LENGTH]) -> Frame {
+    let mut payload = [0; 22];
+    let () = payload.copy_from_slice(&buffer[1..23]);
+    let flags = buffer[23];
+    Frame {
+        raw: rc::unpack_11_bit(&payload),
+        frame_lost: flags & FLAG_FRAME_LOST != 0,
+        failsafe: flags & FLAG_FAILSAFE != 0,
+    }
+}
+
+/// Decode SBUS from a UART configured with `config` forever,
+/// publishing each frame through `rc` under `mapping`.
+#[inline]
+pub async fn read<T: uart::Instance>(mut rx: UartRx<'static, T, Async>, mapping: rc::Mapping) -> ! {
+    let mut decoder = Decoder::default();
+    loop {
+        let mut byte = [0];
+        if let Err(e) = rx.read(&mut byte).await {
+            let () = crate::warn!("SBUS read error: {e:?}");
+            decoder = Decoder::default();
+            continue;
+        }
+        if let Some(frame) = decoder.push(byte[0]) {
+            let () = rc::publish(&mapping, &frame.channels());
+        }
+    }
+}
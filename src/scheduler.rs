@@ -0,0 +1,198 @@
+use {
+    crate::{
+        battery,
+        body::Body,
+        brownout,
+        command::{self, Command, CouldntExecute},
+        contact, current, estop, failsafe,
+        gait::{self, Gait},
+        pressure, pwm, rc, stats,
+        telemetry::{self, Jitter, Sampler},
+        watchdog,
+    },
+    embassy_time::{Duration, Instant, Ticker},
+};
+
+// The fixed-rate motion loop, written once. `Clock` ticks every PWM period but reports how
+// long actually passed, so anything advanced by it (gait phase, telemetry's loop period) keeps
+// real time even when a tick comes late. A tick that's more than a whole period late (a flash
+// erase, a debugger halt) doesn't make the ticker fire again and again to catch up: the missed
+// ticks are dropped, and the next one measures the whole gap, capped at `MAX_STEP` so the gait
+// doesn't leap half a cycle after a long stall.
+//
+// `run` is the whole motion task: each tick, it carries on with whatever command is still going (a
+// dwell, a move eased out under the leg's limits, a playback, or flash work handed to core 0
+// through `command::run_flash_jobs`) and, once nothing is, drains the command queue into a
+// `command::Executor`, which never waits. It then freezes the legs if the command link has gone
+// quiet (see `failsafe`), lets the guards act on what `analog::run` last measured (a flat battery
+// sits the robot down, a tick at a time through `Body::advance`, and dips in the supply slow the
+// gait), and hands the gait what the feet last felt. Then, unless an emergency stop is in force, it
+// takes the newest RC input, advances the gait (unless the body's sitting down or resting, or a
+// command has the legs) and sends every servo its pulse. Last, it checks the rail current for a stall, samples
+// telemetry and feeds the watchdog. It also keeps a `telemetry::Jitter` histogram of how late each
+// tick came, reported every `JITTER_DIVISOR` ticks, to catch anything (USB logging, say) that
+// occasionally stretches the period. Every command it executes, and every tick at least a whole
+// period late, is counted in `stats`.
+
+/// The servos' own period: nothing faster would reach them.
+pub const PERIOD: Duration = Duration::from_millis(pwm::PULSE_PERIOD_MS as _);
+
+/// Longest step the gait takes in one tick, however long it's really been.
+pub const MAX_STEP: Duration = Duration::from_millis(4 * pwm::PULSE_PERIOD_MS as u64);
+
+/// Sample telemetry every this many ticks.
+pub const TELEMETRY_DIVISOR: u32 = 5;
+
+/// Report jitter every this many ticks (about once a second).
+pub const JITTER_DIVISOR: u32 = 50;
+
+/// A ticker that measures rather than assumes.
+#[derive(Debug)]
+pub struct Clock {
+    ticker: Ticker,
+    period: Duration,
+    started: Instant,
+    last: Instant,
+    /// When the next tick should come.
+    deadline: Instant,
+}
+
+/// One tick of a `Clock`.
+#[derive(Clone, Copy, Debug)]
+pub struct Tick {
+    pub at: Instant,
+    /// Since the last tick (or since the clock started, for the first), capped at `MAX_STEP`.
+    pub elapsed: Duration,
+    /// Since the clock started, uncapped.
+    pub since_start: Duration,
+    /// Past when this tick should have come.
+    pub late: Duration,
+}
+
+impl Clock {
+    /// First tick one `period` from now.
+    #[inline]
+    pub fn every(period: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            ticker: Ticker::every(period),
+            period,
+            started: now,
+            last: now,
+            deadline: now + period,
+        }
+    }
+
+    /// Wait for the next tick.
+    #[inline]
+    pub async fn next(&mut self) -> Tick {
+        let () = self.ticker.next().await;
+        let at = Instant::now();
+        let late = at.saturating_duration_since(self.deadline);
+        self.deadline += self.period;
+        if at - self.last > self.period + self.period {
+            // Start counting from here instead of firing every missed tick back to back:
+            let () = self.ticker.reset();
+            self.deadline = at + self.period;
+        }
+        let elapsed = (at - self.last).min(MAX_STEP);
+        self.last = at;
+        Tick {
+            at,
+            elapsed,
+            since_start: at - self.started,
+            late,
+        }
+    }
+}
+
+/// The motion task: own the body and gait forever, ticking every `PERIOD`.
+/// Start the watchdog as late as possible beforehand, since its first deadline is already running.
+#[inline]
+pub async fn run(
+    mut body: Body<'_, { gait::N_LEGS }>,
+    mut gait: Gait,
+    mut watchdog: watchdog::Feeder,
+) -> ! {
+    let mut clock = Clock::every(PERIOD);
+    let mut executor = command::Executor::new();
+    let mut sampler = Sampler::every(TELEMETRY_DIVISOR);
+    let mut jitter = Jitter::new();
+    let mut battery = battery::Guard::default();
+    let mut brownout = brownout::Guard::new(brownout::Thresholds::DEFAULT);
+    let mut stall = current::StallDetector::DEFAULT;
+    let mut estop = estop::Guard::new(estop::Action::Freeze);
+    let mut failsafe = failsafe::Watchdog::new(failsafe::DEFAULT_TIMEOUT, failsafe::Action::Freeze);
+    let mut tick = Tick {
+        at: Instant::now(),
+        elapsed: PERIOD,
+        since_start: Duration::from_ticks(0),
+        late: Duration::from_ticks(0),
+    };
+    loop {
+        // A command that takes longer than a tick carries on here, holding back the rest:
+        if let Some((command, result)) = executor.advance(&mut body, &mut gait, tick.elapsed) {
+            let () = report(command, result);
+        }
+        while !executor.is_busy()
+            && let Some(command) = command::try_receive()
+        {
+            if let Some(result) = executor.execute(command, &mut body, &mut gait) {
+                let () = report(command, result);
+            }
+        }
+
+        let () = failsafe.check(&mut body, &mut gait);
+        let () = battery.check(&mut body, &mut gait);
+        let () = brownout.check(&mut gait);
+        let () = contact::apply(&mut gait);
+        let () = pressure::apply(&mut gait);
+
+        // A stop (see `estop`) holds everything where it is, so nothing moves until it's reset:
+        if !estop.check(&mut body, &mut gait) {
+            // The sticks, if a receiver's running, set the pose outright rather than queueing:
+            let pose = match rc::take() {
+                Some(input) => {
+                    let () = gait.set_velocity(
+                        input.velocity.x,
+                        input.velocity.y,
+                        input.velocity.yaw_rate,
+                    );
+                    input.pose
+                }
+                None => *body.pose(),
+            };
+            if body.is_sitting_down() {
+                if let Some(Err(e)) = body.advance(tick.elapsed) {
+                    let () = crate::error!("Couldn't sit down: {e:?}");
+                }
+            } else if !executor.is_moving()
+                && let Err(e) = gait.step(&mut body, pose, tick.elapsed)
+            {
+                let () = crate::warn!("{e:?}");
+            }
+        }
+        let _: bool = stall.check(&body);
+
+        let () = sampler.tick(&body, &gait, tick.elapsed, Instant::now() - tick.at);
+        let () = watchdog.feed();
+        let last = tick.at;
+        tick = clock.next().await;
+        if tick.late >= PERIOD {
+            let () = stats::count_overrun();
+        }
+        let () = jitter.record(tick.at - last, tick.late);
+        if jitter.ticks.is_multiple_of(JITTER_DIVISOR) {
+            let () = telemetry::report_jitter(&jitter);
+        }
+    }
+}
+
+/// Count a finished command in `stats`, and log it if it failed.
+#[inline]
+fn report(command: Command, result: Result<(), CouldntExecute>) {
+    let () = stats::count_command(result.is_ok());
+    if let Err(e) = result {
+        let () = crate::warn!("Couldn't execute {command:?}: {e:?}");
+    }
+}
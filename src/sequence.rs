@@ -0,0 +1,607 @@
+use {
+    crate::{body, ik},
+    embassy_time::Duration,
+};
+
+#[cfg(not(feature = "sim"))]
+use {
+    crate::{
+        battery,
+        command::{self, Command, Refused},
+        current, imu,
+        sanitize::{self, Source},
+        storage::{self, CouldntLoad, CouldntSave, MAX_PAYLOAD_LENGTH, SEQUENCE_RING, Slot},
+        tof,
+    },
+    core::{
+        cell::RefCell,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+    embassy_futures::select::{Either, select},
+    embassy_sync::{
+        blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+        signal::Signal,
+    },
+    embassy_time::Timer,
+};
+
+// Stored behaviors: a small bytecode uploaded over any command transport (`seq append`), kept
+// in flash (`seq save`), and run on the robot (`seq run`) by its own task, so simple autonomous
+// behaviors don't need new firmware. Each instruction is an opcode byte and its operands, all
+// little-endian, with every number in thousandths of its unit (lengths, radians, length units
+// per second, volts, amps, metres):
+//
+//     op  operands                                    meaning
+//     00                                              end
+//     01  x, y, yaw rate: i16                         walk (all zero to stop)
+//     02  leg: u8, x, y, z: i16                       move one foot, in the body frame
+//     03  x, y, z, roll, pitch, yaw: i16              pose the body over its feet
+//     04  milliseconds: u16                           wait
+//     05  times: u8                                   repeat up to the matching `next`
+//                                                     that many times, or forever for 0
+//     06                                              next
+//     07  sensor: u8, above: u8, threshold: i16,      jump to `target` (an offset into the
+//         target: u16                                 program) if the sensor reads below
+//                                                     `threshold`, or above it if `above` isn't 0
+//     08  target: u16                                 jump
+//     09                                              relax
+//
+// A sensor with nothing to read (not fitted, or its task stopped) never branches. Running off
+// the end is the same as `end`. `check` goes through the whole program before it runs, so a
+// bad opcode or a jump into the middle of an instruction is caught at `seq run`, not halfway
+// through; only loops nested too deep, a `next` without a loop, and a loop that never gets to
+// anything that moves or waits (`MAX_CONTROL_STEPS` in a row) are left for `Machine` to find.
+//
+// `Machine` just decodes and keeps its place, handing back one `Step` at a time, so it runs
+// the same in a host test. On the robot, `run` turns each step into a `Command` submitted as
+// `Source::Autonomy`, through the same checks as every other sender.
+
+/// Longest program there's room for.
+pub const MAX_PROGRAM_LENGTH: usize = 256;
+
+/// Most program bytes one `seq append` line carries.
+pub const MAX_CHUNK_LENGTH: usize = 24;
+
+/// Loops that may be running at once, one inside the next.
+pub const MAX_NESTING: usize = 4;
+
+/// Instructions `Machine::step` gets through without anything to hand back before it gives up.
+pub const MAX_CONTROL_STEPS: usize = 256;
+
+pub type Program = heapless::Vec<u8, MAX_PROGRAM_LENGTH>;
+
+#[cfg(not(feature = "sim"))]
+const _: () = assert!(MAX_PROGRAM_LENGTH <= SEQUENCE_RING.capacity());
+
+/// The program `clear`, `append`, `save` and `start` work on.
+#[cfg(not(feature = "sim"))]
+static PROGRAM: Mutex<CriticalSectionRawMutex, RefCell<Program>> =
+    Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// A checked program for `run` to start on.
+#[cfg(not(feature = "sim"))]
+static START: Signal<CriticalSectionRawMutex, Program> = Signal::new();
+
+/// Set by `stop` for `run` to notice.
+#[cfg(not(feature = "sim"))]
+static STOP: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+#[cfg(not(feature = "sim"))]
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Something a program can branch on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum Sensor {
+    /// `tof::obstacle`, in metres.
+    Obstacle = 0,
+    /// `battery::volts`.
+    Battery = 1,
+    /// The IMU's roll, in radians, left side up positive.
+    Roll = 2,
+    /// The IMU's pitch, in radians, nose down positive.
+    Pitch = 3,
+    /// `current::amps`.
+    Current = 4,
+}
+
+impl Sensor {
+    #[inline]
+    pub const fn from_u8(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0 => Self::Obstacle,
+            1 => Self::Battery,
+            2 => Self::Roll,
+            3 => Self::Pitch,
+            4 => Self::Current,
+            _ => return None,
+        })
+    }
+}
+
+/// One decoded instruction; see the table at the top of this file.
+#[derive(Clone, Copy, Debug)]
+pub enum Instruction {
+    End,
+    Walk {
+        x: f32,
+        y: f32,
+        yaw_rate: f32,
+    },
+    Foot {
+        leg: usize,
+        position: ik::CartesianDisplacementFromEyeCenterLookingForward,
+    },
+    Pose(body::Pose),
+    Wait(Duration),
+    Loop {
+        times: u8,
+    },
+    Next,
+    Branch {
+        sensor: Sensor,
+        above: bool,
+        threshold: f32,
+        target: usize,
+    },
+    Jump {
+        target: usize,
+    },
+    Relax,
+}
+
+/// What a program wants done next.
+#[derive(Clone, Copy, Debug)]
+pub enum Step {
+    Walk {
+        x: f32,
+        y: f32,
+        yaw_rate: f32,
+    },
+    Foot {
+        leg: usize,
+        position: ik::CartesianDisplacementFromEyeCenterLookingForward,
+    },
+    Pose(body::Pose),
+    Wait(Duration),
+    Relax,
+    Done,
+}
+
+/// What's wrong with a program, and the offset of the instruction it's wrong at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum Fault {
+    UnknownOpcode {
+        at: usize,
+        opcode: u8,
+    },
+    UnknownSensor {
+        at: usize,
+        sensor: u8,
+    },
+    /// The program ends partway through this instruction's operands.
+    Truncated {
+        at: usize,
+    },
+    /// A jump or branch to somewhere that isn't the start of an instruction (or the end).
+    BadTarget {
+        at: usize,
+        target: usize,
+    },
+    /// More than `MAX_NESTING` loops inside one another.
+    TooDeep {
+        at: usize,
+    },
+    /// A `next` outside any loop.
+    Unmatched {
+        at: usize,
+    },
+    /// `MAX_CONTROL_STEPS` instructions in a row without moving or waiting.
+    Runaway {
+        at: usize,
+    },
+    /// Longer than `MAX_PROGRAM_LENGTH`.
+    TooLong {
+        length: usize,
+    },
+}
+
+/// Bytes for one `seq append`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chunk {
+    bytes: [u8; MAX_CHUNK_LENGTH],
+    length: u8,
+}
+
+impl Chunk {
+    /// Read hexadecimal digits, two per byte, with nothing between them.
+    #[inline]
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let (pairs, odd) = hex.as_bytes().as_chunks::<2>();
+        if !odd.is_empty() || pairs.len() > MAX_CHUNK_LENGTH {
+            return None;
+        }
+        let mut bytes = [0; MAX_CHUNK_LENGTH];
+        for (byte, pair) in bytes.iter_mut().zip(pairs) {
+            let digit = |c: u8| (c as char).to_digit(16);
+            *byte = (digit(pair[0])? * 16 + digit(pair[1])?) as u8;
+        }
+        Some(Self {
+            bytes,
+            length: pairs.len() as u8,
+        })
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.length as usize]
+    }
+}
+
+/// Decode the instruction starting at `at`, returning it and where the next one starts.
+#[inline]
+pub fn decode(program: &[u8], at: usize) -> Result<(Instruction, usize), Fault> {
+    let Some(&opcode) = program.get(at) else {
+        return Ok((Instruction::End, at));
+    };
+    let length = match opcode {
+        0x00 | 0x06 | 0x09 => 0,
+        0x01 => 6,
+        0x02 => 7,
+        0x03 => 12,
+        0x04 => 2,
+        0x05 => 1,
+        0x07 => 6,
+        0x08 => 2,
+        _ => return Err(Fault::UnknownOpcode { at, opcode }),
+    };
+    let operands = program
+        .get(at + 1..at + 1 + length)
+        .ok_or(Fault::Truncated { at })?;
+    let word = |i: usize| [operands[i], operands[i + 1]];
+    let fixed = |i: usize| f32::from(i16::from_le_bytes(word(i))) / 1_000.0;
+    let instruction = match opcode {
+        0x00 => Instruction::End,
+        0x01 => Instruction::Walk {
+            x: fixed(0),
+            y: fixed(2),
+            yaw_rate: fixed(4),
+        },
+        0x02 => Instruction::Foot {
+            leg: operands[0] as usize,
+            position: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                x: fixed(1),
+                y: fixed(3),
+                z: fixed(5),
+            },
+        },
+        0x03 => Instruction::Pose(body::Pose {
+            translation: ik::CartesianDisplacementFromEyeCenterLookingForward {
+                x: fixed(0),
+                y: fixed(2),
+                z: fixed(4),
+            },
+            rotation: body::Rotation {
+                roll: fixed(6),
+                pitch: fixed(8),
+                yaw: fixed(10),
+            }
+            .into(),
+        }),
+        0x04 => Instruction::Wait(Duration::from_millis(u64::from(u16::from_le_bytes(word(
+            0,
+        ))))),
+        0x05 => Instruction::Loop { times: operands[0] },
+        0x06 => Instruction::Next,
+        0x07 => Instruction::Branch {
+            sensor: Sensor::from_u8(operands[0]).ok_or(Fault::UnknownSensor {
+                at,
+                sensor: operands[0],
+            })?,
+            above: operands[1] != 0,
+            threshold: fixed(2),
+            target: u16::from_le_bytes(word(4)) as usize,
+        },
+        0x08 => Instruction::Jump {
+            target: u16::from_le_bytes(word(0)) as usize,
+        },
+        _ => Instruction::Relax,
+    };
+    Ok((instruction, at + 1 + length))
+}
+
+/// Decode every instruction, and make sure every jump and branch lands on one (or the end).
+#[inline]
+pub fn check(program: &[u8]) -> Result<(), Fault> {
+    if program.len() > MAX_PROGRAM_LENGTH {
+        return Err(Fault::TooLong {
+            length: program.len(),
+        });
+    }
+    let mut starts = [false; MAX_PROGRAM_LENGTH + 1];
+    let mut at = 0;
+    while at < program.len() {
+        let (_, next) = decode(program, at)?;
+        starts[at] = true;
+        at = next;
+    }
+    starts[program.len()] = true;
+    let mut at = 0;
+    while at < program.len() {
+        let (instruction, next) = decode(program, at)?;
+        if let Instruction::Branch { target, .. } | Instruction::Jump { target } = instruction
+            && !starts.get(target).is_some_and(|&start| start)
+        {
+            return Err(Fault::BadTarget { at, target });
+        }
+        at = next;
+    }
+    Ok(())
+}
+
+/// A running loop: where its body starts, and how many more times to go through it.
+#[derive(Clone, Copy, Debug)]
+struct Loop {
+    start: usize,
+    /// `None` for forever.
+    remaining: Option<u8>,
+}
+
+/// Runs a program one `Step` at a time.
+#[derive(Clone, Debug)]
+pub struct Machine<'p> {
+    program: &'p [u8],
+    at: usize,
+    loops: heapless::Vec<Loop, MAX_NESTING>,
+}
+
+impl<'p> Machine<'p> {
+    #[inline]
+    pub fn new(program: &'p [u8]) -> Self {
+        Self {
+            program,
+            at: 0,
+            loops: heapless::Vec::new(),
+        }
+    }
+
+    /// Where the next instruction starts.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.at
+    }
+
+    /// Run up to the next instruction that does something, reading sensors through `read`
+    /// for any branches on the way. Keeps handing back `Step::Done` once the program's over.
+    #[inline]
+    pub fn step(&mut self, mut read: impl FnMut(Sensor) -> Option<f32>) -> Result<Step, Fault> {
+        for _ in 0..MAX_CONTROL_STEPS {
+            let at = self.at;
+            let (instruction, next) = decode(self.program, at)?;
+            self.at = next;
+            match instruction {
+                Instruction::End => {
+                    self.at = at;
+                    return Ok(Step::Done);
+                }
+                Instruction::Walk { x, y, yaw_rate } => return Ok(Step::Walk { x, y, yaw_rate }),
+                Instruction::Foot { leg, position } => return Ok(Step::Foot { leg, position }),
+                Instruction::Pose(pose) => return Ok(Step::Pose(pose)),
+                Instruction::Wait(duration) => return Ok(Step::Wait(duration)),
+                Instruction::Relax => return Ok(Step::Relax),
+                Instruction::Loop { times } => {
+                    let () = self
+                        .loops
+                        .push(Loop {
+                            start: next,
+                            remaining: (times != 0).then_some(times),
+                        })
+                        .map_err(|_| Fault::TooDeep { at })?;
+                }
+                Instruction::Next => {
+                    let current = self.loops.last_mut().ok_or(Fault::Unmatched { at })?;
+                    match &mut current.remaining {
+                        None => self.at = current.start,
+                        Some(remaining) if *remaining > 1 => {
+                            *remaining -= 1;
+                            self.at = current.start;
+                        }
+                        Some(_) => {
+                            let _: Option<Loop> = self.loops.pop();
+                        }
+                    }
+                }
+                Instruction::Branch {
+                    sensor,
+                    above,
+                    threshold,
+                    target,
+                } => {
+                    let taken = read(sensor).is_some_and(|reading| {
+                        if above {
+                            reading > threshold
+                        } else {
+                            reading < threshold
+                        }
+                    });
+                    if taken {
+                        self.at = target;
+                    }
+                }
+                Instruction::Jump { target } => self.at = target,
+            }
+        }
+        Err(Fault::Runaway { at: self.at })
+    }
+}
+
+#[cfg(not(feature = "sim"))]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntSequence {
+    /// Past `MAX_PROGRAM_LENGTH` bytes.
+    TooLong,
+    Save(CouldntSave),
+    Load(CouldntLoad),
+    Fault(Fault),
+}
+
+/// Forget the program in RAM (but not the one saved), to `append` a new one.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn clear() {
+    PROGRAM.lock(|program| program.borrow_mut().clear())
+}
+
+/// Add to the end of the program in RAM.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn append(chunk: &Chunk) -> Result<(), CouldntSequence> {
+    PROGRAM.lock(|program| {
+        program
+            .borrow_mut()
+            .extend_from_slice(chunk.as_bytes())
+            .map_err(|()| CouldntSequence::TooLong)
+    })
+}
+
+/// Check the program in RAM, then write it to flash, replacing the one saved before.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub async fn save() -> Result<(), CouldntSequence> {
+    let program = PROGRAM.lock(|program| program.borrow().clone());
+    let () = check(&program).map_err(CouldntSequence::Fault)?;
+    let mut storage = storage::lock().await;
+    let flash = storage
+        .as_mut()
+        .ok_or(CouldntSequence::Save(CouldntSave::NotInitialized))?;
+    SEQUENCE_RING
+        .append(flash, &program)
+        .map_err(CouldntSequence::Save)
+}
+
+/// Read the newest intact saved program.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub async fn load() -> Result<Program, CouldntSequence> {
+    let mut storage = storage::lock().await;
+    let flash = storage
+        .as_mut()
+        .ok_or(CouldntSequence::Load(CouldntLoad::NotInitialized))?;
+    let scan = SEQUENCE_RING
+        .scan(flash)
+        .map_err(|e| CouldntSequence::Load(CouldntLoad::Flash(e)))?;
+    let newest = scan
+        .newest
+        .ok_or(CouldntSequence::Load(CouldntLoad::Empty))?;
+    let mut payload = [0; MAX_PAYLOAD_LENGTH];
+    let Slot::Intact { length, .. } = SEQUENCE_RING
+        .read(flash, newest.slot, &mut payload)
+        .map_err(|e| CouldntSequence::Load(CouldntLoad::Flash(e)))?
+    else {
+        return Err(CouldntSequence::Load(CouldntLoad::Empty));
+    };
+    Program::from_slice(&payload[..length])
+        .map_err(|()| CouldntSequence::Load(CouldntLoad::WrongLength { length }))
+}
+
+/// Hand `run` the program in RAM, or the one saved if nothing's been appended since boot
+/// (or since `clear`), replacing whatever it was running.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub async fn start() -> Result<(), CouldntSequence> {
+    let program = PROGRAM.lock(|program| program.borrow().clone());
+    let program = if program.is_empty() {
+        load().await?
+    } else {
+        program
+    };
+    let () = check(&program).map_err(CouldntSequence::Fault)?;
+    let () = stop();
+    let () = START.signal(program);
+    Ok(())
+}
+
+/// Stop the running program, if there is one, leaving the robot wherever it got to.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn stop() {
+    if RUNNING.load(Ordering::Relaxed) {
+        let () = STOP.signal(());
+    }
+}
+
+/// The latest reading of `sensor`, in the units the table at the top of this file gives.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn read(sensor: Sensor) -> Option<f32> {
+    match sensor {
+        Sensor::Obstacle => tof::obstacle().map(|mm| f32::from(mm) / 1_000.0),
+        Sensor::Battery => battery::volts(),
+        Sensor::Roll => imu::orientation().map(|orientation| orientation.roll),
+        Sensor::Pitch => imu::orientation().map(|orientation| orientation.pitch),
+        Sensor::Current => current::amps(),
+    }
+}
+
+/// Run each program `start` hands over, one at a time, forever. Spawn once.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub async fn run() -> ! {
+    loop {
+        let program = START.wait().await;
+        let () = STOP.reset();
+        let () = RUNNING.store(true, Ordering::Relaxed);
+        let () = crate::info!("Running a {}-byte sequence", program.len());
+        match select(execute(&program), STOP.wait()).await {
+            Either::First(Ok(())) => crate::info!("Sequence finished"),
+            Either::First(Err(e)) => crate::warn!("Sequence stopped: {e:?}"),
+            Either::Second(()) => crate::info!("Sequence stopped"),
+        }
+        let () = RUNNING.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(not(feature = "sim"))]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+enum Stopped {
+    Fault(Fault),
+    Refused { at: usize, refused: Refused },
+}
+
+#[cfg(not(feature = "sim"))]
+#[inline]
+async fn execute(program: &[u8]) -> Result<(), Stopped> {
+    let mut machine = Machine::new(program);
+    loop {
+        let command = match machine.step(read).map_err(Stopped::Fault)? {
+            Step::Done => return Ok(()),
+            Step::Wait(duration) => {
+                let () = Timer::after(duration).await;
+                continue;
+            }
+            Step::Walk { x, y, yaw_rate } => Command::GaitVelocity { x, y, yaw_rate },
+            Step::Foot { leg, position } => Command::FootPosition { leg, position },
+            Step::Pose(pose) => Command::BodyPose(pose),
+            Step::Relax => Command::Relax,
+        };
+        // A program with no waits can outrun the rate limit, so hold it back rather than stop:
+        loop {
+            match command::submit(Source::Autonomy, command).await {
+                Ok(()) => break,
+                Err(Refused::RateLimited) => {
+                    let () = Timer::after_micros(1_000_000 / u64::from(sanitize::RATE_HZ)).await;
+                }
+                Err(refused) => {
+                    return Err(Stopped::Refused {
+                        at: machine.position(),
+                        refused,
+                    });
+                }
+            }
+        }
+    }
+}
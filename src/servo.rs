@@ -1,18 +1,34 @@
-use {
-    crate::pwm,
-    embassy_rp::pwm::{PwmError, PwmOutput, SetDutyCycle},
-};
+use {crate::pwm, embedded_hal::pwm::SetDutyCycle};
 
-pub struct Servo<'d> {
-    pwm: PwmOutput<'d>,
+// Nothing here is specific to the RP2350: an `AnyServo` drives any `embedded_hal` PWM channel
+// that repeats every `pwm::PULSE_PERIOD_MS` and counts from 0 to its `max_duty_cycle` over that
+// period, which is how `pwm::init_slice` sets up the RP2350's. Pulse widths are worked out from
+// `max_duty_cycle` alone, so another board only has to get its period right.
+
+/// The servo as used on the robot, on one of `pwm::init_slice`'s channels.
+pub type Servo<'d> = AnyServo<pwm::Output<'d>>;
+
+pub struct AnyServo<P> {
+    pwm: P,
     // pulse_center: f32,
     pulse_min: f32,
     pulse_max: f32,
     clkcmp_center: f32,
     clkcmp_range: f32,
+    clkcmp_per_microsecond: f32,
+    /// Added to every `go_to` position, to make up for a horn that isn't quite centered.
+    trim: f32,
+    /// `clkcmp_center` moved by `trim`, so `go_to` is one multiply and one add.
+    clkcmp_trimmed_center: f32,
+    /// The channel's `max_duty_cycle`, which never changes.
+    clkcmp_max: f32,
 }
 
+/// Largest trim either way, in the same units as `go_to`.
+pub const MAX_TRIM: f32 = 0.25;
+
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
 pub enum CouldntInitialize {
     PulseCenterOutOfRange(OutOfRange),
     PulseRangeLowerOutOfRange(OutOfRange),
@@ -20,12 +36,14 @@ pub enum CouldntInitialize {
 }
 
 #[derive(Debug)]
-pub enum CouldntMove {
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntMove<E = pwm::Error> {
     OutOfRange(OutOfRange),
-    PwmError(PwmError),
+    PwmError(E),
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
 pub struct OutOfRange {
     pub min: f32,
     pub max: f32,
@@ -43,10 +61,10 @@ impl OutOfRange {
     }
 }
 
-impl<'d> Servo<'d> {
+impl<P: SetDutyCycle> AnyServo<P> {
     #[inline]
-    pub async fn with_center_and_ranges(
-        pwm: PwmOutput<'d>,
+    pub fn with_center_and_ranges(
+        pwm: P,
         pulse_center: f32,
         pulse_range_lower: f32,
         pulse_range_higher: f32,
@@ -57,24 +75,101 @@ impl<'d> Servo<'d> {
             .map_err(CouldntInitialize::PulseRangeLowerOutOfRange)?;
         let () = OutOfRange::check(0.0, 1.0 - pulse_center, pulse_range_higher)
             .map_err(CouldntInitialize::PulseRangeHigherOutOfRange)?;
-        let clkcmp_range = pwm::pulse_range_plus_minus().await;
+        // A normal servo takes 1 to 2 ms out of every 20 (these take twice that range):
+        let top = pwm.max_duty_cycle() as f32;
+        let (clkcmp_min, clkcmp_max) = (top / 20.0, top / 10.0);
+        let clkcmp_normal_center = 0.5 * (clkcmp_min + clkcmp_max);
+        let clkcmp_range = (clkcmp_normal_center - clkcmp_min) * 2.0;
+        let clkcmp_center = clkcmp_normal_center + clkcmp_range * pulse_center;
         Ok(Self {
             pwm,
             // pulse_center,
             pulse_min: pulse_center + pulse_range_lower,
             pulse_max: pulse_center + pulse_range_higher,
-            clkcmp_center: pwm::pulse_center().await + clkcmp_range * pulse_center,
+            clkcmp_center,
             clkcmp_range,
+            clkcmp_per_microsecond: top / const { 1_000.0 * pwm::PULSE_PERIOD_MS as f32 },
+            trim: 0.0,
+            clkcmp_trimmed_center: clkcmp_center,
+            clkcmp_max: top,
         })
     }
 
     #[inline]
-    pub fn go_to(&mut self, position: f32) -> Result<(), CouldntMove> {
+    pub fn go_to(&mut self, position: f32) -> Result<(), CouldntMove<P::Error>> {
         let () = OutOfRange::check(self.pulse_min, self.pulse_max, position)
             .map_err(CouldntMove::OutOfRange)?;
-        let clkcmp = self.clkcmp_center + self.clkcmp_range * position;
+        let clkcmp = self.clkcmp_trimmed_center + self.clkcmp_range * position;
+        self.pwm
+            .set_duty_cycle(self.duty_cycle(clkcmp))
+            .map_err(CouldntMove::PwmError)
+    }
+
+    /// `go_to`, in radians from center rather than servo units.
+    #[inline]
+    pub fn go_to_radians(&mut self, radians: f32) -> Result<(), CouldntMove<P::Error>> {
+        self.go_to(pwm::RADIANS_TO_SERVO * radians)
+    }
+
+    /// `go_to`, in degrees from center rather than servo units.
+    #[inline]
+    pub fn go_to_degrees(&mut self, degrees: f32) -> Result<(), CouldntMove<P::Error>> {
+        self.go_to_radians(degrees.to_radians())
+    }
+
+    /// The nearest whole duty cycle to `clkcmp`, held to what the channel can count to
+    /// rather than wrapping around.
+    #[inline]
+    fn duty_cycle(&self, clkcmp: f32) -> u16 {
+        (clkcmp.clamp(0.0, self.clkcmp_max) + 0.5) as u16
+    }
+
+    #[inline]
+    pub fn trim(&self) -> f32 {
+        self.trim
+    }
+
+    /// Takes effect on the next `go_to`.
+    #[inline]
+    pub fn set_trim(&mut self, trim: f32) -> Result<(), OutOfRange> {
+        let () = OutOfRange::check(-MAX_TRIM, MAX_TRIM, trim)?;
+        self.trim = trim;
+        self.clkcmp_trimmed_center = self.clkcmp_center + self.clkcmp_range * trim;
+        Ok(())
+    }
+
+    /// Smallest and largest positions `go_to` accepts.
+    #[inline]
+    pub fn limits(&self) -> (f32, f32) {
+        (self.pulse_min, self.pulse_max)
+    }
+
+    /// Narrow (or widen) the positions `go_to` accepts, within what any servo can take.
+    #[inline]
+    pub fn set_limits(&mut self, min: f32, max: f32) -> Result<(), OutOfRange> {
+        let () = OutOfRange::check(-1.0, 1.0, min)?;
+        let () = OutOfRange::check(min, 1.0, max)?;
+        self.pulse_min = min;
+        self.pulse_max = max;
+        Ok(())
+    }
+
+    /// Send a raw pulse width, ignoring this servo's configured range
+    /// (but not the range any servo can take). For calibration and range-finding.
+    #[inline]
+    pub fn set_pulse_width(&mut self, microseconds: u16) -> Result<(), CouldntMove<P::Error>> {
+        let clkcmp = self.clkcmp_per_microsecond * (microseconds as f32);
+        let position = (clkcmp - self.clkcmp_center) / self.clkcmp_range;
+        let () = OutOfRange::check(-1.0, 1.0, position).map_err(CouldntMove::OutOfRange)?;
         self.pwm
-            .set_duty_cycle(clkcmp as _)
+            .set_duty_cycle(self.duty_cycle(clkcmp))
             .map_err(CouldntMove::PwmError)
     }
+
+    /// Stop sending pulses, which lets most hobby servos go limp.
+    /// The next `go_to` powers it back up.
+    #[inline]
+    pub fn relax(&mut self) -> Result<(), P::Error> {
+        self.pwm.set_duty_cycle_fully_off()
+    }
 }
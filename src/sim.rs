@@ -0,0 +1,158 @@
+use {
+    crate::pwm,
+    core::cell::RefCell,
+    embassy_time::Instant,
+    embedded_hal::pwm::{ErrorKind, ErrorType, SetDutyCycle},
+    fixed::{FixedU32, types::extra::U4},
+};
+
+// With the `sim` feature, everything that touches the RP2350 is compiled out and each servo
+// drives an `Output` that just remembers its last pulse instead of a PWM channel. What's left
+// (IK, legs, the body, the gait) runs on the host, and `src/bin/sim.rs` walks it faster than
+// real time, printing every foot's trajectory:
+//
+//     cargo sim [seconds] [vx] [vy] [yaw rate] [tripod|ripple|wave]
+//
+// `cargo sim` is an alias (in `.cargo/config.toml`) that builds for the host instead of the robot.
+//
+// To check exactly what a `Servo` or `Leg` sends, hand its outputs a `Recording` each
+// (`Output::recording_to`), then read back every duty cycle with when it was sent.
+
+/// The RP2350's default system clock, in place of `clocks::clk_sys_freq`,
+/// so pulse widths come out exactly as they would on the robot.
+pub const CLOCK_HZ: u32 = 150_000_000;
+
+/// Most duty cycles a `Recording` keeps before dropping the oldest.
+pub const RECORDING_CAPACITY: usize = 256;
+
+/// A stand-in output never fails.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum Error {}
+
+impl embedded_hal::pwm::Error for Error {
+    #[inline]
+    fn kind(&self) -> ErrorKind {
+        match *self {}
+    }
+}
+
+/// One duty cycle an `Output` was sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sent {
+    pub at: Instant,
+    /// In clock ticks out of `pwm::clock_top`, or `None` for fully off.
+    pub duty_cycle: Option<u16>,
+}
+
+/// Everything sent to one `Output`, oldest first.
+#[derive(Debug, Default)]
+pub struct Recording {
+    sent: RefCell<heapless::Vec<Sent, RECORDING_CAPACITY>>,
+}
+
+impl Recording {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            sent: RefCell::new(heapless::Vec::new()),
+        }
+    }
+
+    #[inline]
+    pub fn sent(&self) -> heapless::Vec<Sent, RECORDING_CAPACITY> {
+        self.sent.borrow().clone()
+    }
+
+    #[inline]
+    pub fn last(&self) -> Option<Sent> {
+        self.sent.borrow().last().copied()
+    }
+
+    #[inline]
+    pub fn clear(&self) {
+        self.sent.borrow_mut().clear()
+    }
+
+    #[inline]
+    fn push(&self, sent: Sent) {
+        let mut recorded = self.sent.borrow_mut();
+        if recorded.is_full() {
+            let _: Sent = recorded.remove(0);
+        }
+        let _: Result<(), Sent> = recorded.push(sent);
+    }
+}
+
+/// Stands in for one PWM channel.
+#[derive(Debug, Default)]
+pub struct Output<'d> {
+    duty_cycle: Option<u16>,
+    recording: Option<&'d Recording>,
+}
+
+impl<'d> Output<'d> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            duty_cycle: None,
+            recording: None,
+        }
+    }
+
+    /// Like `new`, but also keeps everything sent in `recording`.
+    #[inline]
+    pub const fn recording_to(recording: &'d Recording) -> Self {
+        Self {
+            duty_cycle: None,
+            recording: Some(recording),
+        }
+    }
+
+    /// Last compare value sent, in clock ticks out of `pwm::clock_top`, or `None` while off.
+    #[inline]
+    pub fn duty_cycle(&self) -> Option<u16> {
+        self.duty_cycle
+    }
+
+    #[inline]
+    fn send(&mut self, duty_cycle: Option<u16>) {
+        self.duty_cycle = duty_cycle;
+        if let Some(recording) = self.recording {
+            let () = recording.push(Sent {
+                at: Instant::now(),
+                duty_cycle,
+            });
+        }
+    }
+}
+
+impl ErrorType for Output<'_> {
+    type Error = Error;
+}
+
+impl SetDutyCycle for Output<'_> {
+    /// What `pwm::clock_top` works out to at `CLOCK_HZ`, without having to wait for it.
+    #[inline]
+    fn max_duty_cycle(&self) -> u16 {
+        let clock_hz = FixedU32::<U4>::from_num(CLOCK_HZ);
+        let Some(top) =
+            pwm::divider_for(clock_hz).and_then(|divider| pwm::top_for(clock_hz, divider))
+        else {
+            unreachable!()
+        };
+        top
+    }
+
+    #[inline]
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Error> {
+        let () = self.send(Some(duty));
+        Ok(())
+    }
+
+    #[inline]
+    fn set_duty_cycle_fully_off(&mut self) -> Result<(), Error> {
+        let () = self.send(None);
+        Ok(())
+    }
+}
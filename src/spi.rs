@@ -0,0 +1,112 @@
+use {
+    crate::{link, telemetry},
+    embassy_futures::yield_now,
+    embassy_rp::{
+        Peripheral,
+        gpio::Pin,
+        pac,
+        peripherals::SPI0,
+        spi::{ClkPin, CsPin, MisoPin, MosiPin},
+    },
+    embassy_time::{Duration, Timer},
+};
+
+// embassy-rp only drives SPI as a master, so this configures SPI0 as a slave directly through
+// its registers. Frames are the same as everywhere else (`telemetry`'s COBS + CRC framing):
+// the master sends command frames (see `link`) on MOSI and, since SPI is full-duplex,
+// clocks queued telemetry back out on MISO at the same time. When nothing is queued,
+// MISO carries zeros, which are just empty frames.
+//
+// The master has to use SPI mode 3 (CPOL = 1, CPHA = 1): in mode 0 the peripheral needs
+// chip select raised between every byte. Each transfer should be a multiple of eight bytes
+// or so apart from polling, since only the eight-byte transmit FIFO is kept topped up.
+
+/// How long to sleep when neither FIFO has anything to do.
+pub const POLL_PERIOD: Duration = Duration::from_micros(100);
+
+/// The GPIO function that connects a pin to its SPI peripheral.
+const FUNCSEL_SPI: u8 = 1;
+
+/// Serve framed commands and telemetry as an SPI slave on SPI0 forever.
+#[inline]
+pub async fn serve(
+    _spi: impl Peripheral<P = SPI0> + 'static,
+    clk: impl Peripheral<P = impl ClkPin<SPI0>> + 'static,
+    mosi: impl Peripheral<P = impl MosiPin<SPI0>> + 'static,
+    miso: impl Peripheral<P = impl MisoPin<SPI0>> + 'static,
+    cs: impl Peripheral<P = impl CsPin<SPI0>> + 'static,
+) -> ! {
+    let pins = [
+        clk.into_ref().pin(),
+        mosi.into_ref().pin(),
+        miso.into_ref().pin(),
+        cs.into_ref().pin(),
+    ];
+
+    let spi = pac::SPI0;
+    let () = pac::RESETS.reset().modify(|w| w.set_spi0(false));
+    while !pac::RESETS.reset_done().read().spi0() {}
+    for pin in pins {
+        let () = pac::IO_BANK0
+            .gpio(pin as usize)
+            .ctrl()
+            .write(|w| w.set_funcsel(FUNCSEL_SPI));
+        let () = pac::PADS_BANK0.gpio(pin as usize).modify(|w| {
+            w.set_ie(true);
+            w.set_od(false);
+            // The RP2350 powers up with pad isolation on:
+            w.set_iso(false);
+        });
+    }
+    // Eight-bit Motorola frames, mode 3:
+    let () = spi.cr0().write(|w| {
+        w.set_dss(0b0111);
+        w.set_frf(0);
+        w.set_spo(true);
+        w.set_sph(true);
+    });
+    let () = spi.cr1().write(|w| {
+        w.set_ms(true);
+        w.set_sse(true);
+    });
+
+    let mut decoder = telemetry::FrameDecoder::default();
+    let mut outgoing = [0; 8];
+    let mut queued = 0..0;
+    loop {
+        let mut busy = false;
+
+        while spi.sr().read().rne() {
+            busy = true;
+            let byte = spi.dr().read().data() as u8;
+            match decoder.push(byte) {
+                None => {}
+                Some(Err(e)) => {
+                    let () = telemetry::count(telemetry::ErrorKind::Command);
+                    let () = crate::warn!("Bad SPI frame: {e:?}");
+                }
+                Some(Ok((kind, payload))) => link::handle_frame(kind, payload).await,
+            }
+        }
+
+        while spi.sr().read().tnf() {
+            if queued.is_empty() {
+                queued = 0..telemetry::try_read(&mut outgoing);
+                if queued.is_empty() {
+                    break;
+                }
+            }
+            busy = true;
+            let byte = outgoing[queued.start];
+            queued.start += 1;
+            let () = spi.dr().write(|w| w.set_data(byte as u16));
+        }
+
+        if busy {
+            // Still let everything else run between bursts:
+            let () = yield_now().await;
+        } else {
+            let () = Timer::after(POLL_PERIOD).await;
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use {
+    crate::{fault, ik, leg, servo, telemetry},
+    core::{
+        fmt,
+        mem::MaybeUninit,
+        sync::atomic::{AtomicU8, AtomicU32, Ordering, compiler_fence},
+    },
+    embassy_rp::watchdog,
+    embassy_time::{Duration, Instant},
+};
+
+// Running health counters since boot, cheap enough to bump from the motion loop: one relaxed
+// atomic add each. `snapshot` copies them all out at once, for the console's `status` and for
+// a `telemetry::KIND_STATS` frame every `REPORT_PERIOD`, whose payload is:
+//
+//     uptime_s | overruns | unreachable | too_close | knee_lock | would_collide | out_of_range
+//     | pwm_errors | commands | failed_commands | unparsed_commands | resets | reset_reason: u8
+//
+// with every field but the last a little-endian `u32`, and `reset_reason` a `ResetReason`
+// (or 0xFF if nothing's recorded one). Counts wrap rather than saturate.
+//
+// The reset count lives in RAM the reset doesn't clear (like `fault`'s panic stash), so it
+// counts every reset since the chip last powered up, and starts over at the next power-on.
+
+/// Time between `telemetry::KIND_STATS` frames.
+pub const REPORT_PERIOD: Duration = Duration::from_secs(5);
+
+/// Bytes in an encoded `Stats` payload.
+const PAYLOAD_LENGTH: usize = 4 * 12 + 1;
+
+/// Marks a reset count that survived the last reset.
+const RESETS_MAGIC: u32 = u32::from_le_bytes(*b"RSTS");
+
+/// `reset_reason`'s byte before `record_reset`.
+const UNKNOWN_REASON: u8 = 0xFF;
+
+static OVERRUNS: AtomicU32 = AtomicU32::new(0);
+static IK_FAILURES: [AtomicU32; 5] = [const { AtomicU32::new(0) }; 5];
+static PWM_ERRORS: AtomicU32 = AtomicU32::new(0);
+static COMMANDS: AtomicU32 = AtomicU32::new(0);
+static FAILED_COMMANDS: AtomicU32 = AtomicU32::new(0);
+static RESETS: AtomicU32 = AtomicU32::new(0);
+static RESET_REASON: AtomicU8 = AtomicU8::new(UNKNOWN_REASON);
+
+/// Resets since power-on, in RAM the reset doesn't clear.
+#[repr(C)]
+struct Persistent {
+    magic: u32,
+    resets: u32,
+}
+
+#[unsafe(link_section = ".uninit.stats")]
+static mut PERSISTENT: MaybeUninit<Persistent> = MaybeUninit::uninit();
+
+/// Why the chip last came out of reset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum ResetReason {
+    /// Power-on, or anything else the chip doesn't flag.
+    PowerOn = 0,
+    Brownout = 1,
+    /// A panic stashed by `fault::record_panic`.
+    Panic = 2,
+    /// The watchdog ran out: the motion loop stopped feeding it.
+    Watchdog = 3,
+    /// The watchdog was told to reset the chip.
+    Forced = 4,
+}
+
+impl ResetReason {
+    #[inline]
+    fn from_u8(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0 => Self::PowerOn,
+            1 => Self::Brownout,
+            2 => Self::Panic,
+            3 => Self::Watchdog,
+            4 => Self::Forced,
+            _ => return None,
+        })
+    }
+}
+
+/// IK failures, by what went wrong.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IkFailures {
+    /// `ik::HipToFootError::Unreachable`.
+    pub unreachable: u32,
+    /// `ik::HipToFootError::TooClose`.
+    pub too_close: u32,
+    /// `ik::HipToFootError::KneeLock`.
+    pub knee_lock: u32,
+    /// `leg::IkError::WouldCollide`.
+    pub would_collide: u32,
+    /// A solution past a servo's travel (`servo::CouldntMove::OutOfRange`).
+    pub out_of_range: u32,
+}
+
+/// Every counter at one moment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    pub uptime_s: u32,
+    /// Motion loop ticks that came at least a whole period late, i.e. after a missed tick.
+    pub overruns: u32,
+    pub ik_failures: IkFailures,
+    /// Servos the PWM peripheral refused to drive.
+    pub pwm_errors: u32,
+    /// Commands the motion task carried out, or tried to.
+    pub commands: u32,
+    /// Of `commands`, those that failed.
+    pub failed_commands: u32,
+    /// Lines that never made it to a command (`telemetry::ErrorKind::Command`).
+    pub unparsed_commands: u32,
+    /// Since power-on.
+    pub resets: u32,
+    /// `None` until something calls `record_reset`.
+    pub reset_reason: Option<ResetReason>,
+}
+
+impl Stats {
+    /// Write these stats as a complete `telemetry::KIND_STATS` frame (ending in its zero
+    /// delimiter) into `out`, returning how many bytes it took.
+    #[inline]
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, telemetry::CouldntEncode> {
+        let mut payload = heapless::Vec::<u8, PAYLOAD_LENGTH>::new();
+        for value in [
+            self.uptime_s,
+            self.overruns,
+            self.ik_failures.unreachable,
+            self.ik_failures.too_close,
+            self.ik_failures.knee_lock,
+            self.ik_failures.would_collide,
+            self.ik_failures.out_of_range,
+            self.pwm_errors,
+            self.commands,
+            self.failed_commands,
+            self.unparsed_commands,
+            self.resets,
+        ] {
+            let () = payload
+                .extend_from_slice(&value.to_le_bytes())
+                .map_err(|()| telemetry::CouldntEncode::BufferTooSmall)?;
+        }
+        let reason = self
+            .reset_reason
+            .map_or(UNKNOWN_REASON, |reason| reason as u8);
+        let () = payload
+            .push(reason)
+            .map_err(|_| telemetry::CouldntEncode::BufferTooSmall)?;
+        telemetry::encode_frame(telemetry::KIND_STATS, &payload, out)
+    }
+}
+
+impl fmt::Display for Stats {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let IkFailures {
+            unreachable,
+            too_close,
+            knee_lock,
+            would_collide,
+            out_of_range,
+        } = self.ik_failures;
+        write!(
+            f,
+            "up {} s, {} overruns, IK failures {unreachable} unreachable / {too_close} too close \
+             / {knee_lock} knee lock / {would_collide} colliding / {out_of_range} out of range, \
+             {} PWM errors, {} commands ({} failed, {} unparsed), {} resets",
+            self.uptime_s,
+            self.overruns,
+            self.pwm_errors,
+            self.commands,
+            self.failed_commands,
+            self.unparsed_commands,
+            self.resets,
+        )?;
+        match self.reset_reason {
+            Some(reason) => write!(f, " (last {reason:?})"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Every counter, now.
+#[inline]
+pub fn snapshot() -> Stats {
+    let failures = |i: usize| IK_FAILURES[i].load(Ordering::Relaxed);
+    Stats {
+        uptime_s: Instant::now().as_secs() as u32,
+        overruns: OVERRUNS.load(Ordering::Relaxed),
+        ik_failures: IkFailures {
+            unreachable: failures(0),
+            too_close: failures(1),
+            knee_lock: failures(2),
+            would_collide: failures(3),
+            out_of_range: failures(4),
+        },
+        pwm_errors: PWM_ERRORS.load(Ordering::Relaxed),
+        commands: COMMANDS.load(Ordering::Relaxed),
+        failed_commands: FAILED_COMMANDS.load(Ordering::Relaxed),
+        unparsed_commands: telemetry::error_counts()[telemetry::ErrorKind::Command as usize],
+        resets: RESETS.load(Ordering::Relaxed),
+        reset_reason: ResetReason::from_u8(RESET_REASON.load(Ordering::Relaxed)),
+    }
+}
+
+/// Count one motion loop tick that came at least a whole period late.
+#[inline]
+pub fn count_overrun() {
+    let _: u32 = OVERRUNS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count one command the motion task carried out, or tried to.
+#[inline]
+pub fn count_command(succeeded: bool) {
+    let _: u32 = COMMANDS.fetch_add(1, Ordering::Relaxed);
+    if !succeeded {
+        let _: u32 = FAILED_COMMANDS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Count one leg that couldn't reach its target, under IK or PWM by what went wrong.
+#[inline]
+pub fn count_leg_error(error: &leg::IkError) {
+    let counter = match error {
+        leg::IkError::Ik2dError(ik::HipToFootError::Unreachable(_)) => &IK_FAILURES[0],
+        leg::IkError::Ik2dError(ik::HipToFootError::TooClose(_)) => &IK_FAILURES[1],
+        leg::IkError::Ik2dError(ik::HipToFootError::KneeLock(_)) => &IK_FAILURES[2],
+        leg::IkError::WouldCollide { .. } => &IK_FAILURES[3],
+        leg::IkError::CouldntMoveYaw(servo::CouldntMove::OutOfRange(_))
+        | leg::IkError::CouldntMoveHip(servo::CouldntMove::OutOfRange(_))
+        | leg::IkError::CouldntMoveKnee(servo::CouldntMove::OutOfRange(_)) => &IK_FAILURES[4],
+        leg::IkError::CouldntMoveYaw(servo::CouldntMove::PwmError(_))
+        | leg::IkError::CouldntMoveHip(servo::CouldntMove::PwmError(_))
+        | leg::IkError::CouldntMoveKnee(servo::CouldntMove::PwmError(_)) => &PWM_ERRORS,
+    };
+    let _: u32 = counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Work out why the chip last reset, and count the reset if it wasn't a power-on.
+/// Call once, after `fault::recover` (which finds panics and brownouts).
+#[inline]
+pub fn record_reset(watchdog_reason: Option<watchdog::ResetReason>) {
+    let reason = if fault::last_boot_panicked() {
+        ResetReason::Panic
+    } else if fault::last_reset_was_brownout() {
+        ResetReason::Brownout
+    } else {
+        match watchdog_reason {
+            Some(watchdog::ResetReason::TimedOut) => ResetReason::Watchdog,
+            Some(watchdog::ResetReason::Forced) => ResetReason::Forced,
+            None => ResetReason::PowerOn,
+        }
+    };
+    // SAFETY: Only touched here, once per boot. After a power-on it's garbage,
+    // which the magic number rules out (bar a one-in-four-billion coincidence).
+    let persistent = unsafe { (&raw const PERSISTENT).read_volatile().assume_init() };
+    let resets = if persistent.magic == RESETS_MAGIC && reason != ResetReason::PowerOn {
+        persistent.resets.wrapping_add(1)
+    } else {
+        0
+    };
+    // SAFETY: As above.
+    let () = unsafe {
+        (&raw mut PERSISTENT).write_volatile(MaybeUninit::new(Persistent {
+            magic: RESETS_MAGIC,
+            resets,
+        }))
+    };
+    let () = compiler_fence(Ordering::SeqCst);
+    let () = RESETS.store(resets, Ordering::Relaxed);
+    RESET_REASON.store(reason as u8, Ordering::Relaxed)
+}
@@ -0,0 +1,254 @@
+use {crate::trig, core::f32::consts::TAU, embassy_time::Duration};
+
+#[cfg(not(feature = "sim"))]
+use {
+    crate::{battery, body, estop, telemetry, thermal, ws2812::Ws2812},
+    core::sync::atomic::{AtomicBool, Ordering},
+    embassy_rp::pio,
+    embassy_time::{Instant, Ticker},
+};
+
+// What the robot's up to, at a glance, on a WS2812 LED (or a chain of them, all showing the
+// same thing), since without USB plugged in there's no other way to tell. Whatever applies
+// first, top to bottom, is shown:
+//
+//     Fault        red, blinking fast      emergency stop, the chip running hot, or any error
+//                                          telemetry counted in the last `FAULT_HOLD`
+//     LowBattery   orange, blinking        the pack below `battery::Thresholds::DEFAULT.warn`
+//     Calibrating  magenta, breathing      `set_calibrating`
+//     Booting      blue, breathing         no fresh telemetry sample: the motion loop isn't up
+//     Frozen       yellow                  `body::freeze`
+//     Walking      green                   the gait's phase is moving
+//     Idle         green, breathing        standing
+//
+// `State` and `Pattern` are plain values, so they run the same in a host test; `run` is the
+// task that reads every other module's published state and drives the LEDs.
+
+/// How long any new error keeps the fault showing.
+pub const FAULT_HOLD: Duration = Duration::from_secs(5);
+
+/// A telemetry sample older than this means the motion loop's stopped (or not started).
+pub const STALE_AFTER: Duration = Duration::from_secs(1);
+
+/// Time between LED updates: quick enough that breathing looks smooth.
+pub const UPDATE_PERIOD: Duration = Duration::from_millis(20);
+
+/// Set while something's calibrating; see `set_calibrating`.
+#[cfg(not(feature = "sim"))]
+static CALIBRATING: AtomicBool = AtomicBool::new(false);
+
+/// One LED's color, at full brightness (the driver scales it down).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const OFF: Self = Self::new(0, 0, 0);
+    pub const RED: Self = Self::new(255, 0, 0);
+    pub const ORANGE: Self = Self::new(255, 96, 0);
+    pub const YELLOW: Self = Self::new(255, 200, 0);
+    pub const GREEN: Self = Self::new(0, 255, 0);
+    pub const BLUE: Self = Self::new(0, 0, 255);
+    pub const MAGENTA: Self = Self::new(255, 0, 255);
+
+    #[inline]
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// `level` (clamped to [0, 1]) of the way from off to this color.
+    #[inline]
+    pub fn scaled(self, level: f32) -> Self {
+        let level = level.clamp(0.0, 1.0);
+        let scale = |channel: u8| (f32::from(channel) * level + 0.5) as u8;
+        Self::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+}
+
+/// How a color shows over time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    Solid(Rgb),
+    /// On for the first half of each period, off for the second.
+    Blink {
+        color: Rgb,
+        period: Duration,
+    },
+    /// Fading smoothly up from off and back down over each period.
+    Breathe {
+        color: Rgb,
+        period: Duration,
+    },
+}
+
+impl Pattern {
+    /// The color `elapsed` into showing this pattern.
+    #[inline]
+    pub fn color_at(self, elapsed: Duration) -> Rgb {
+        let phase = |period: Duration| {
+            (elapsed.as_micros() % period.as_micros().max(1)) as f32
+                / period.as_micros().max(1) as f32
+        };
+        match self {
+            Self::Solid(color) => color,
+            Self::Blink { color, period } => {
+                if phase(period) < 0.5 {
+                    color
+                } else {
+                    Rgb::OFF
+                }
+            }
+            // Raised cosine, as for the pupil's pulse:
+            Self::Breathe { color, period } => {
+                color.scaled(0.5 * (1.0 - trig::cosf(TAU * phase(period))))
+            }
+        }
+    }
+}
+
+/// What's worth knowing first, most urgent first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum State {
+    Fault,
+    LowBattery,
+    Calibrating,
+    Booting,
+    Frozen,
+    Walking,
+    Idle,
+}
+
+/// Everything `State::from_conditions` decides between.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Conditions {
+    pub fault: bool,
+    pub low_battery: bool,
+    pub calibrating: bool,
+    /// Whether the motion loop's running.
+    pub running: bool,
+    pub frozen: bool,
+    pub walking: bool,
+}
+
+impl State {
+    /// The first that applies, in the order at the top of this file.
+    #[inline]
+    pub const fn from_conditions(conditions: Conditions) -> Self {
+        let Conditions {
+            fault,
+            low_battery,
+            calibrating,
+            running,
+            frozen,
+            walking,
+        } = conditions;
+        if fault {
+            Self::Fault
+        } else if low_battery {
+            Self::LowBattery
+        } else if calibrating {
+            Self::Calibrating
+        } else if !running {
+            Self::Booting
+        } else if frozen {
+            Self::Frozen
+        } else if walking {
+            Self::Walking
+        } else {
+            Self::Idle
+        }
+    }
+
+    #[inline]
+    pub const fn pattern(self) -> Pattern {
+        match self {
+            Self::Fault => Pattern::Blink {
+                color: Rgb::RED,
+                period: Duration::from_millis(250),
+            },
+            Self::LowBattery => Pattern::Blink {
+                color: Rgb::ORANGE,
+                period: Duration::from_secs(1),
+            },
+            Self::Calibrating => Pattern::Breathe {
+                color: Rgb::MAGENTA,
+                period: Duration::from_secs(2),
+            },
+            Self::Booting => Pattern::Breathe {
+                color: Rgb::BLUE,
+                period: Duration::from_secs(1),
+            },
+            Self::Frozen => Pattern::Solid(Rgb::YELLOW),
+            Self::Walking => Pattern::Solid(Rgb::GREEN),
+            Self::Idle => Pattern::Breathe {
+                color: Rgb::GREEN,
+                period: Duration::from_secs(4),
+            },
+        }
+    }
+}
+
+/// Show `State::Calibrating` (over anything but a fault or a low battery) until cleared.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn set_calibrating(calibrating: bool) {
+    CALIBRATING.store(calibrating, Ordering::Relaxed)
+}
+
+/// Keep the LEDs showing the current `State` forever, each pattern starting over whenever
+/// the state changes.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub async fn run<P: pio::Instance, const S: usize, const N: usize>(
+    mut leds: Ws2812<'static, P, S, N>,
+) -> ! {
+    let mut ticker = Ticker::every(UPDATE_PERIOD);
+    let mut counts = telemetry::error_counts();
+    let mut last_error: Option<Instant> = None;
+    let mut last_sample: Option<(Instant, f32)> = None;
+    let mut walking = false;
+    let mut shown: Option<(State, Instant)> = None;
+    loop {
+        let now = Instant::now();
+        let latest = telemetry::error_counts();
+        if latest != counts {
+            counts = latest;
+            last_error = Some(now);
+        }
+        let sample = telemetry::latest()
+            .filter(|sample| now.saturating_duration_since(sample.timestamp) <= STALE_AFTER);
+        // Samples come slower than this loop, so only a new one can say whether the gait moved:
+        if let Some(sample) = &sample
+            && last_sample.is_none_or(|(timestamp, _)| timestamp != sample.timestamp)
+        {
+            walking = last_sample.is_some_and(|(_, phase)| phase != sample.gait_phase);
+            last_sample = Some((sample.timestamp, sample.gait_phase));
+        }
+        let state = State::from_conditions(Conditions {
+            fault: estop::is_latched()
+                || last_error.is_some_and(|at| now - at <= FAULT_HOLD)
+                || thermal::celsius()
+                    .is_some_and(|celsius| celsius >= thermal::DEFAULT_WARNING_CELSIUS),
+            low_battery: battery::volts()
+                .is_some_and(|volts| volts < battery::Thresholds::DEFAULT.warn),
+            calibrating: CALIBRATING.load(Ordering::Relaxed),
+            running: sample.is_some(),
+            frozen: body::is_frozen(),
+            walking,
+        });
+        let since = match shown {
+            Some((shown, since)) if shown == state => since,
+            _ => {
+                shown = Some((state, now));
+                now
+            }
+        };
+        let () = leds.fill(state.pattern().color_at(now - since)).await;
+        let () = ticker.next().await;
+    }
+}
@@ -0,0 +1,802 @@
+use {
+    crate::{
+        body::Body,
+        eye, eyelid,
+        gait::{self, Gait},
+        leg, param, telemetry,
+    },
+    core::{
+        f32::consts::PI,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+    embassy_rp::{
+        Peripheral,
+        flash::{self, Blocking, ERASE_SIZE, Flash},
+        peripherals::FLASH,
+    },
+    embassy_sync::{
+        blocking_mutex::raw::CriticalSectionRawMutex,
+        mutex::{Mutex, MutexGuard},
+    },
+};
+
+// Calibration lives in the last `STORAGE_SIZE` bytes of flash, which `memory.x` keeps
+// the program out of. It's split into `Ring`s (configs, then `fault`'s log) of records
+// appended, never overwritten, as fixed-size slots rotating through a few sectors:
+//
+//     magic: u32 | crc: u16 | length: u16 | sequence: u32 | payload ...
+//
+// with `crc` being `telemetry::crc16` over everything after it. Each append takes the slot
+// after the last one written, numbered one past it, and only erases a sector on first
+// moving into it (by then it holds only the oldest records). So every sector wears evenly,
+// at one erase per sector's worth of appends.
+//
+// Loading takes the valid config with the highest sequence number. Torn or corrupt records
+// fail their CRC and are skipped, falling back to the one before. No valid record at all
+// (erased flash included) reads as "nothing saved", and the compiled-in defaults apply instead.
+//
+// Each config payload starts with its schema version:
+//
+//     schema: u16 | `Config` ...
+//
+// A record from older firmware is brought up to date one version at a time by `MIGRATIONS`
+// as it loads, rather than discarded. Records from before versioning had no schema field,
+// and are told apart (as version 0) by their length alone.
+//
+// On first boot (nothing saved at all), `load_or_init` saves the compiled-in defaults, so
+// there's always a config to load; `reset` (`config reset`) goes back to that state.
+//
+// Load before building anything: leg mounts and eye axes go into constructors,
+// while trims, limits and gait parameters are `apply`d to the finished body and gait
+// before its first move.
+//
+// Tuning (`param set`) only ever changes RAM. Nothing reaches flash until a `commit`
+// (`config save`), and `revert` (`config load`) throws away everything since.
+
+/// Flash chip size, matching `memory.x`.
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+/// Bytes at the end of flash set aside for storage.
+pub const STORAGE_SIZE: usize = 64 * 1024;
+/// Start of the storage region, as an offset from the start of flash.
+const STORAGE_OFFSET: u32 = (FLASH_SIZE - STORAGE_SIZE) as u32;
+
+const MAGIC: u32 = u32::from_le_bytes(*b"EYER");
+const HEADER_LENGTH: usize = 4 + 2 + 2 + 4;
+
+/// Largest slot any `Ring` uses.
+const MAX_SLOT_SIZE: usize = 512;
+/// Largest payload any `Ring` can hold.
+pub(crate) const MAX_PAYLOAD_LENGTH: usize = MAX_SLOT_SIZE - HEADER_LENGTH;
+
+/// A run of sectors that fixed-size record slots rotate through.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Ring {
+    /// Counting from the start of the storage region.
+    first_sector: usize,
+    sectors: usize,
+    /// Bytes each record takes up, header and padding included.
+    slot_size: usize,
+}
+
+const CONFIG_RING: Ring = Ring {
+    first_sector: 0,
+    sectors: 8,
+    slot_size: 512,
+};
+
+/// Where `fault` keeps its log.
+pub(crate) const FAULT_RING: Ring = Ring {
+    first_sector: CONFIG_RING.first_sector + CONFIG_RING.sectors,
+    sectors: 2,
+    slot_size: 64,
+};
+
+/// Where `teach` keeps its recording.
+pub(crate) const TEACH_RING: Ring = Ring {
+    first_sector: FAULT_RING.first_sector + FAULT_RING.sectors,
+    sectors: 2,
+    slot_size: 512,
+};
+
+/// Where `sequence` keeps its program.
+pub(crate) const SEQUENCE_RING: Ring = Ring {
+    first_sector: TEACH_RING.first_sector + TEACH_RING.sectors,
+    sectors: 2,
+    slot_size: 512,
+};
+
+const _: () = CONFIG_RING.check();
+const _: () = FAULT_RING.check();
+const _: () = TEACH_RING.check();
+const _: () = SEQUENCE_RING.check();
+const _: () = assert!(HEADER_LENGTH + 2 + CONFIG_LENGTH <= CONFIG_RING.slot_size);
+
+/// Legs a `Config` has room for.
+pub const MAX_LEGS: usize = gait::N_LEGS;
+
+/// Bytes in an encoded `Config`.
+pub const CONFIG_LENGTH: usize = 4 * gait::PARAMETER_NAMES.len()
+    + MAX_LEGS * 4 * (3 + leg::PARAMETER_NAMES.len())
+    + 2 * (4 * 3 + 1)
+    + 4 * 2
+    + 4 * 2;
+
+/// Version of `Config`'s encoding. Bump it whenever that changes, adding a step to `MIGRATIONS`.
+pub const SCHEMA_VERSION: u16 = 2;
+
+/// Bytes in an unversioned (version 0) config, which had no schema field.
+/// No later version may encode to exactly this many bytes, schema included.
+const UNVERSIONED_LENGTH: usize = 350;
+
+const _: () = assert!(2 + CONFIG_LENGTH != UNVERSIONED_LENGTH);
+
+/// A config payload, minus its schema field, on its way through `MIGRATIONS`.
+type Payload = heapless::Vec<u8, MAX_PAYLOAD_LENGTH>;
+
+#[derive(Debug)]
+struct CouldntMigrate;
+
+/// `MIGRATIONS[v]` rewrites a version-`v` payload, in place, as version `v + 1`.
+/// New fields usually get their compiled-in defaults.
+const MIGRATIONS: [fn(&mut Payload) -> Result<(), CouldntMigrate>; SCHEMA_VERSION as usize] =
+    [from_unversioned, add_swing];
+
+/// Version 1 only added the schema field, which is already gone by now.
+#[inline]
+fn from_unversioned(_: &mut Payload) -> Result<(), CouldntMigrate> {
+    Ok(())
+}
+
+/// Version 2 added the `gait::Swing` parameters after the five gait parameters before them.
+#[inline]
+fn add_swing(payload: &mut Payload) -> Result<(), CouldntMigrate> {
+    const AT: usize = 4 * 5;
+    let tail = Payload::from_slice(payload.get(AT..).ok_or(CouldntMigrate)?)
+        .map_err(|()| CouldntMigrate)?;
+    let () = payload.truncate(AT);
+    let gait::Swing {
+        lift,
+        apex,
+        touchdown_angle,
+    } = gait::Swing::DEFAULT;
+    for value in [lift, apex, touchdown_angle] {
+        let () = payload
+            .extend_from_slice(&value.to_le_bytes())
+            .map_err(|()| CouldntMigrate)?;
+    }
+    payload
+        .extend_from_slice(&tail)
+        .map_err(|()| CouldntMigrate)
+}
+
+pub(crate) type Chip = Flash<'static, FLASH, Blocking, FLASH_SIZE>;
+
+/// The flash, once `init` hands it over.
+static STORAGE: Mutex<CriticalSectionRawMutex, Option<Chip>> = Mutex::new(None);
+
+/// The flash, for other modules' `Ring`s. `None` until `init`.
+pub(crate) type Guard = MutexGuard<'static, CriticalSectionRawMutex, Option<Chip>>;
+
+/// Whether parameters have changed since the last `commit` or `revert`.
+static UNSAVED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntLoad {
+    NotInitialized,
+    Flash(flash::Error),
+    /// Nothing's been saved (or nothing saved is intact).
+    Empty,
+    /// The newest record is intact but isn't a config this firmware can read.
+    WrongLength {
+        length: usize,
+    },
+    /// Saved by newer firmware, which may have changed what fields mean.
+    NewerSchema {
+        version: u16,
+    },
+    Migrate {
+        from: u16,
+    },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntSave {
+    NotInitialized,
+    /// Looking for where the last record ended.
+    Read(flash::Error),
+    /// More than fits in one of the ring's slots.
+    TooLong {
+        length: usize,
+    },
+    Erase(flash::Error),
+    Write(flash::Error),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntReset {
+    NotInitialized,
+    Erase(flash::Error),
+    Save(CouldntSave),
+    Apply(param::CouldntAccess),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntRevert {
+    Load(CouldntLoad),
+    Apply(param::CouldntAccess),
+}
+
+/// One leg's calibration.
+#[derive(Clone, Copy, Debug)]
+pub struct LegConfig {
+    pub mount: leg::Mount,
+    /// In the order of `leg::PARAMETER_NAMES`.
+    pub parameters: [f32; leg::PARAMETER_NAMES.len()],
+}
+
+/// Everything that survives a reboot.
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// In the order of `gait::PARAMETER_NAMES`. NaN leaves the gait's own value alone.
+    pub gait: [f32; gait::PARAMETER_NAMES.len()],
+    pub legs: [LegConfig; MAX_LEGS],
+    pub pan: eye::Axis,
+    pub tilt: eye::Axis,
+    pub iris: eye::IrisCalibration,
+    pub eyelid: eyelid::Calibration,
+}
+
+impl Default for Config {
+    /// Compiled-in defaults: legs evenly spaced around the eye, no trims,
+    /// and the eye free to turn a quarter turn either way.
+    #[inline]
+    fn default() -> Self {
+        let gait::Parameters {
+            max_stride_length,
+            step_height,
+            cycle_period,
+            duty_factor: _,
+            swing,
+        } = gait::Parameters::DEFAULT;
+        Self {
+            // Same order as `gait::PARAMETER_NAMES`; the duty factor stays the pattern's own:
+            gait: [
+                step_height,
+                max_stride_length,
+                cycle_period.as_millis() as f32,
+                f32::NAN,
+                gait::DEFAULT_MIN_STABILITY_MARGIN,
+                swing.lift,
+                swing.apex,
+                swing.touchdown_angle,
+            ],
+            legs: core::array::from_fn(|i| LegConfig {
+                mount: leg::Mount::radial(i as f32 * const { 2.0 * PI / MAX_LEGS as f32 }),
+                parameters: leg::DEFAULT_PARAMETERS,
+            }),
+            pan: eye::Axis::symmetric(const { 0.5 * PI }),
+            tilt: eye::Axis::symmetric(const { 0.5 * PI }),
+            iris: eye::IrisCalibration {
+                constricted_radians: 0.0,
+                dilated_radians: 0.0,
+            },
+            eyelid: eyelid::Calibration {
+                open_radians: 0.0,
+                closed_radians: 0.0,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Record the body's and gait's current parameters (and leg mounts) into this config.
+    /// Eye calibration is left as it was.
+    #[inline]
+    pub fn capture<const N: usize>(&mut self, body: &Body<'_, N>, gait: &Gait) {
+        for (value, parameter) in self.gait.iter_mut().zip(param::all(0)) {
+            if let Ok(live) = parameter.get(body, gait) {
+                *value = live;
+            }
+        }
+        for (i, (config, leg)) in self.legs.iter_mut().zip(body.legs()).enumerate() {
+            config.mount = *leg.mount();
+            for (name, value) in config.parameters.iter_mut().enumerate() {
+                let parameter = param::Parameter::Leg {
+                    leg: i,
+                    name: name as u8,
+                };
+                if let Ok(live) = parameter.get(body, gait) {
+                    *value = live;
+                }
+            }
+        }
+    }
+
+    /// Set every stored parameter on the body and gait. Leg mounts can't change after
+    /// construction, so pass those to `Leg::with_mount` instead. Keeps going past errors,
+    /// returning the first.
+    #[inline]
+    pub fn apply<const N: usize>(
+        &self,
+        body: &mut Body<'_, N>,
+        gait: &mut Gait,
+    ) -> Result<(), param::CouldntAccess> {
+        let mut result = Ok(());
+        for (&value, parameter) in self.gait.iter().zip(param::all(0)) {
+            if !value.is_nan()
+                && let Err(e) = parameter.set(value, body, gait)
+                && result.is_ok()
+            {
+                result = Err(e);
+            }
+        }
+        for (i, config) in self.legs.iter().enumerate().take(N) {
+            // Limits are checked against each other one at a time, so a range that moved
+            // entirely past the old one only fits on the second pass:
+            for pass in 0..2 {
+                for (name, &value) in config.parameters.iter().enumerate() {
+                    let parameter = param::Parameter::Leg {
+                        leg: i,
+                        name: name as u8,
+                    };
+                    if let Err(e) = parameter.set(value, body, gait)
+                        && pass == 1
+                        && result.is_ok()
+                    {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// The stored value of one parameter, or `None` for a leg this config doesn't have.
+    #[inline]
+    pub fn parameter(&self, parameter: param::Parameter) -> Option<f32> {
+        match parameter {
+            param::Parameter::Gait(name) => self.gait.get(name as usize).copied(),
+            param::Parameter::Leg { leg, name } => self
+                .legs
+                .get(leg)
+                .and_then(|config| config.parameters.get(name as usize))
+                .copied(),
+        }
+    }
+
+    #[inline]
+    pub fn encode(&self, out: &mut [u8; CONFIG_LENGTH]) {
+        let mut writer = Writer { out, position: 0 };
+        for &value in &self.gait {
+            let () = writer.f32(value);
+        }
+        for config in &self.legs {
+            let () = writer.f32(config.mount.yaw_servo_x);
+            let () = writer.f32(config.mount.yaw_servo_y);
+            let () = writer.f32(config.mount.home_yaw_radians);
+            for &value in &config.parameters {
+                let () = writer.f32(value);
+            }
+        }
+        for axis in [&self.pan, &self.tilt] {
+            let () = writer.f32(axis.trim_radians);
+            let () = writer.f32(axis.min_radians);
+            let () = writer.f32(axis.max_radians);
+            let () = writer.byte(axis.reversed as u8);
+        }
+        let () = writer.f32(self.iris.constricted_radians);
+        let () = writer.f32(self.iris.dilated_radians);
+        let () = writer.f32(self.eyelid.open_radians);
+        let () = writer.f32(self.eyelid.closed_radians);
+    }
+
+    #[inline]
+    pub fn decode(bytes: &[u8; CONFIG_LENGTH]) -> Self {
+        let mut reader = Reader { bytes, position: 0 };
+        let gait = core::array::from_fn(|_| reader.f32());
+        let legs = core::array::from_fn(|_| LegConfig {
+            mount: leg::Mount {
+                yaw_servo_x: reader.f32(),
+                yaw_servo_y: reader.f32(),
+                home_yaw_radians: reader.f32(),
+            },
+            parameters: core::array::from_fn(|_| reader.f32()),
+        });
+        let [pan, tilt] = core::array::from_fn(|_| eye::Axis {
+            trim_radians: reader.f32(),
+            min_radians: reader.f32(),
+            max_radians: reader.f32(),
+            reversed: reader.byte() != 0,
+        });
+        Self {
+            gait,
+            legs,
+            pan,
+            tilt,
+            iris: eye::IrisCalibration {
+                constricted_radians: reader.f32(),
+                dilated_radians: reader.f32(),
+            },
+            eyelid: eyelid::Calibration {
+                open_radians: reader.f32(),
+                closed_radians: reader.f32(),
+            },
+        }
+    }
+}
+
+struct Writer<'a> {
+    out: &'a mut [u8],
+    position: usize,
+}
+
+impl Writer<'_> {
+    #[inline]
+    fn f32(&mut self, value: f32) {
+        let () = self.out[self.position..self.position + 4].copy_from_slice(&value.to_le_bytes());
+        self.position += 4;
+    }
+
+    #[inline]
+    fn byte(&mut self, value: u8) {
+        self.out[self.position] = value;
+        self.position += 1;
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    #[inline]
+    fn f32(&mut self) -> f32 {
+        let mut word = [0; 4];
+        let () = word.copy_from_slice(&self.bytes[self.position..self.position + 4]);
+        self.position += 4;
+        f32::from_le_bytes(word)
+    }
+
+    #[inline]
+    fn byte(&mut self) -> u8 {
+        let value = self.bytes[self.position];
+        self.position += 1;
+        value
+    }
+}
+
+/// Hand the flash over to storage. Call once, early, before anything loads or saves.
+#[inline]
+pub async fn init(flash: impl Peripheral<P = FLASH> + 'static) {
+    *STORAGE.lock().await = Some(Flash::new_blocking(flash));
+}
+
+/// Read the newest intact saved config, if there is one.
+#[inline]
+pub async fn load() -> Result<Config, CouldntLoad> {
+    let mut storage = lock().await;
+    let flash = storage.as_mut().ok_or(CouldntLoad::NotInitialized)?;
+    let scan = CONFIG_RING.scan(flash).map_err(CouldntLoad::Flash)?;
+    if scan.corrupt > 0 {
+        let () = crate::warn!("Skipped {} corrupt config records", scan.corrupt);
+    }
+    let newest = scan.newest.ok_or(CouldntLoad::Empty)?;
+
+    let mut payload = [0; MAX_PAYLOAD_LENGTH];
+    let Slot::Intact { length, .. } = CONFIG_RING
+        .read(flash, newest.slot, &mut payload)
+        .map_err(CouldntLoad::Flash)?
+    else {
+        return Err(CouldntLoad::Empty);
+    };
+    let (version, bytes) = match payload[..length] {
+        _ if length == UNVERSIONED_LENGTH => (0, &payload[..length]),
+        [v0, v1, ref bytes @ ..] => (u16::from_le_bytes([v0, v1]), bytes),
+        _ => return Err(CouldntLoad::WrongLength { length }),
+    };
+    if version > SCHEMA_VERSION {
+        return Err(CouldntLoad::NewerSchema { version });
+    }
+    // Can't overflow, since it came out of a `MAX_PAYLOAD_LENGTH` array:
+    let mut bytes = Payload::from_slice(bytes).unwrap();
+    for from in version..SCHEMA_VERSION {
+        let () = MIGRATIONS[from as usize](&mut bytes)
+            .map_err(|CouldntMigrate| CouldntLoad::Migrate { from })?;
+    }
+    if version < SCHEMA_VERSION {
+        let () = crate::info!("Migrated a saved config from schema {version} to {SCHEMA_VERSION}");
+    }
+    let bytes = bytes[..]
+        .try_into()
+        .map_err(|_| CouldntLoad::WrongLength { length })?;
+    Ok(Config::decode(bytes))
+}
+
+/// `load`, except that on first boot (or with nothing intact left) the compiled-in defaults
+/// are saved and returned, so the store is never left without a config.
+#[inline]
+pub async fn load_or_init() -> Result<Config, CouldntLoad> {
+    match load().await {
+        Err(CouldntLoad::Empty) => {
+            let () = crate::info!("No saved config; saving the defaults");
+            let config = Config::default();
+            if let Err(e) = save(&config).await {
+                let () = crate::warn!("Couldn't save the default config: {e:?}");
+            }
+            Ok(config)
+        }
+        result => result,
+    }
+}
+
+/// Append a new config record, superseding every one before it. Erasing (once every
+/// sector's worth of saves) stalls everything, interrupts included, for tens of
+/// milliseconds, so don't call this while walking.
+#[inline]
+pub async fn save(config: &Config) -> Result<(), CouldntSave> {
+    let mut storage = lock().await;
+    let flash = storage.as_mut().ok_or(CouldntSave::NotInitialized)?;
+    let mut payload = [0; 2 + CONFIG_LENGTH];
+    let (version, bytes) = payload.split_at_mut(2);
+    let () = version.copy_from_slice(&SCHEMA_VERSION.to_le_bytes());
+    let () = config.encode(bytes.try_into().unwrap());
+    CONFIG_RING.append(flash, &payload)
+}
+
+/// Take the flash, waiting for whoever has it.
+#[inline]
+pub(crate) async fn lock() -> Guard {
+    STORAGE.lock().await
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Found {
+    pub sequence: u32,
+    pub slot: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Scan {
+    /// The intact record with the highest sequence number.
+    pub newest: Option<Found>,
+    /// The record with the highest sequence number, intact or not.
+    pub last_written: Option<Found>,
+    /// Records whose CRC or length was wrong.
+    pub corrupt: usize,
+}
+
+/// What one slot holds.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Slot {
+    /// Erased, or never a record.
+    Empty,
+    Corrupt {
+        sequence: u32,
+    },
+    Intact {
+        sequence: u32,
+        length: usize,
+    },
+}
+
+impl Ring {
+    #[inline]
+    const fn check(&self) {
+        assert!(self.slot_size <= MAX_SLOT_SIZE);
+        assert!(ERASE_SIZE % self.slot_size == 0);
+        // With only one sector, erasing it for a new record would lose the newest old one:
+        assert!(self.sectors >= 2);
+        assert!((self.first_sector + self.sectors) * ERASE_SIZE <= STORAGE_SIZE);
+    }
+
+    #[inline]
+    const fn slots_per_sector(&self) -> usize {
+        ERASE_SIZE / self.slot_size
+    }
+
+    /// Largest payload that fits in one slot.
+    #[inline]
+    pub(crate) const fn capacity(&self) -> usize {
+        self.slot_size - HEADER_LENGTH
+    }
+
+    #[inline]
+    pub(crate) const fn slots(&self) -> usize {
+        self.sectors * self.slots_per_sector()
+    }
+
+    /// Where one slot starts, as an offset from the start of flash.
+    #[inline]
+    fn offset(&self, slot: usize) -> u32 {
+        STORAGE_OFFSET + (self.first_sector * ERASE_SIZE + slot * self.slot_size) as u32
+    }
+
+    /// Read one slot, checking its CRC, with its payload (if intact) at the start of `payload`.
+    #[inline]
+    pub(crate) fn read(
+        &self,
+        flash: &mut Chip,
+        slot: usize,
+        payload: &mut [u8; MAX_PAYLOAD_LENGTH],
+    ) -> Result<Slot, flash::Error> {
+        let mut record = [0; MAX_SLOT_SIZE];
+        let () = flash.blocking_read(self.offset(slot), &mut record[..HEADER_LENGTH])?;
+        let [m0, m1, m2, m3, c0, c1, l0, l1, s0, s1, s2, s3] =
+            record[..HEADER_LENGTH].try_into().unwrap();
+        if u32::from_le_bytes([m0, m1, m2, m3]) != MAGIC {
+            return Ok(Slot::Empty);
+        }
+        let sequence = u32::from_le_bytes([s0, s1, s2, s3]);
+        let length = u16::from_le_bytes([l0, l1]) as usize;
+        if HEADER_LENGTH + length > self.slot_size {
+            return Ok(Slot::Corrupt { sequence });
+        }
+        let end = HEADER_LENGTH + length;
+        let () = flash.blocking_read(
+            self.offset(slot) + HEADER_LENGTH as u32,
+            &mut record[HEADER_LENGTH..end],
+        )?;
+        if telemetry::crc16(&record[6..end]) != u16::from_le_bytes([c0, c1]) {
+            return Ok(Slot::Corrupt { sequence });
+        }
+        let () = payload[..length].copy_from_slice(&record[HEADER_LENGTH..end]);
+        Ok(Slot::Intact { sequence, length })
+    }
+
+    /// Read every slot, finding the newest records.
+    #[inline]
+    pub(crate) fn scan(&self, flash: &mut Chip) -> Result<Scan, flash::Error> {
+        let mut scan = Scan::default();
+        let mut payload = [0; MAX_PAYLOAD_LENGTH];
+        for slot in 0..self.slots() {
+            let (sequence, intact) = match self.read(flash, slot, &mut payload)? {
+                Slot::Empty => continue,
+                Slot::Corrupt { sequence } => {
+                    scan.corrupt += 1;
+                    (sequence, false)
+                }
+                Slot::Intact { sequence, .. } => (sequence, true),
+            };
+            let found = Found { sequence, slot };
+            let newer =
+                |than: Option<Found>| than.is_none_or(|than| found.sequence > than.sequence);
+            if newer(scan.last_written) {
+                scan.last_written = Some(found);
+            }
+            if intact && newer(scan.newest) {
+                scan.newest = Some(found);
+            }
+        }
+        Ok(scan)
+    }
+
+    /// Write `payload` as a new record after the last one written.
+    #[inline]
+    pub(crate) fn append(&self, flash: &mut Chip, payload: &[u8]) -> Result<(), CouldntSave> {
+        if payload.len() > self.capacity() {
+            return Err(CouldntSave::TooLong {
+                length: payload.len(),
+            });
+        }
+        let scan = self.scan(flash).map_err(CouldntSave::Read)?;
+        let (sequence, mut slot) = match scan.last_written {
+            None => (0, 0),
+            Some(last) => (
+                last.sequence.wrapping_add(1),
+                (last.slot + 1) % self.slots(),
+            ),
+        };
+        // A slot that isn't blank mid-sector (say, from a save cut off while erasing)
+        // can't be written until its sector's erased, so skip ahead to the next one:
+        let per_sector = self.slots_per_sector();
+        if slot % per_sector != 0 && !self.is_blank(flash, slot).map_err(CouldntSave::Read)? {
+            slot = (slot / per_sector + 1) * per_sector % self.slots();
+        }
+        if slot % per_sector == 0 {
+            let start = self.offset(slot);
+            let () = flash
+                .blocking_erase(start, start + ERASE_SIZE as u32)
+                .map_err(CouldntSave::Erase)?;
+        }
+
+        let end = HEADER_LENGTH + payload.len();
+        let mut record = [0; MAX_SLOT_SIZE];
+        let () = record[..4].copy_from_slice(&MAGIC.to_le_bytes());
+        let () = record[6..8].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+        let () = record[8..12].copy_from_slice(&sequence.to_le_bytes());
+        let () = record[HEADER_LENGTH..end].copy_from_slice(payload);
+        let crc = telemetry::crc16(&record[6..end]);
+        let () = record[4..6].copy_from_slice(&crc.to_le_bytes());
+        flash
+            .blocking_write(self.offset(slot), &record[..end])
+            .map_err(CouldntSave::Write)
+    }
+
+    /// Erase every sector, forgetting every record.
+    #[inline]
+    pub(crate) fn erase(&self, flash: &mut Chip) -> Result<(), flash::Error> {
+        let start = self.offset(0);
+        flash.blocking_erase(start, start + (self.sectors * ERASE_SIZE) as u32)
+    }
+
+    /// Whether a slot's header is still erased.
+    #[inline]
+    fn is_blank(&self, flash: &mut Chip, slot: usize) -> Result<bool, flash::Error> {
+        let mut header = [0; HEADER_LENGTH];
+        let () = flash.blocking_read(self.offset(slot), &mut header)?;
+        Ok(header.iter().all(|&byte| byte == 0xff))
+    }
+}
+
+/// Factory reset, on flash: erase every saved config and save the compiled-in defaults in
+/// their place. Leaves the fault log alone. Follow up with `apply_defaults` on the body.
+#[inline]
+pub async fn reset() -> Result<(), CouldntReset> {
+    {
+        let mut storage = lock().await;
+        let flash = storage.as_mut().ok_or(CouldntReset::NotInitialized)?;
+        let () = CONFIG_RING.erase(flash).map_err(CouldntReset::Erase)?;
+    }
+    save(&Config::default()).await.map_err(CouldntReset::Save)
+}
+
+/// Factory reset, on the body: apply the compiled-in defaults, as `reset` just saved them.
+/// Leg mounts and eye calibration take effect on the next boot.
+#[inline]
+pub fn apply_defaults<const N: usize>(
+    body: &mut Body<'_, N>,
+    gait: &mut Gait,
+) -> Result<(), CouldntReset> {
+    // Not stored, since NaN means "leave it alone", so clear the override by hand:
+    gait.parameters_mut().duty_factor = None;
+    let () = Config::default()
+        .apply(body, gait)
+        .map_err(CouldntReset::Apply)?;
+    let () = UNSAVED.store(false, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Note a parameter changed in RAM, so `has_unsaved_changes` can say so.
+#[inline]
+pub fn mark_unsaved() {
+    UNSAVED.store(true, Ordering::Relaxed)
+}
+
+#[inline]
+pub fn has_unsaved_changes() -> bool {
+    UNSAVED.load(Ordering::Relaxed)
+}
+
+/// Save the live parameters in `captured` (see `Config::capture`), keeping the eye calibration
+/// already stored (or the defaults, if nothing readable is).
+#[inline]
+pub async fn commit(captured: &Config) -> Result<(), CouldntSave> {
+    let stored = load().await.unwrap_or_default();
+    let () = save(&Config {
+        pan: stored.pan,
+        tilt: stored.tilt,
+        iris: stored.iris,
+        eyelid: stored.eyelid,
+        ..*captured
+    })
+    .await?;
+    let () = UNSAVED.store(false, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Throw away every change since the last `commit`, going back to `stored` (from `load`).
+#[inline]
+pub fn revert<const N: usize>(
+    stored: &Config,
+    body: &mut Body<'_, N>,
+    gait: &mut Gait,
+) -> Result<(), CouldntRevert> {
+    let () = stored.apply(body, gait).map_err(CouldntRevert::Apply)?;
+    let () = UNSAVED.store(false, Ordering::Relaxed);
+    Ok(())
+}
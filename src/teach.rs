@@ -0,0 +1,404 @@
+use {
+    crate::{
+        body::{self, Body},
+        gait, ik,
+        interp::{Easing, Interpolation},
+        pwm, servo,
+    },
+    embassy_time::{Duration, Instant, Ticker},
+};
+
+#[cfg(not(feature = "sim"))]
+use {
+    crate::{
+        encoder,
+        storage::{self, CouldntLoad, CouldntSave, MAX_PAYLOAD_LENGTH, Slot, TEACH_RING},
+    },
+    core::cell::RefCell,
+    embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+};
+
+// Teach mode: relax every servo, pose the robot by hand, and capture each pose as a keyframe,
+// then play the keyframes back through `interp`, easing from each to the next. Only joints
+// with feedback (the yaw encoders, through `measured_angles`) are read where they actually
+// are; every other joint records the angle it was last commanded to, so jog those into place
+// (`leg <index> joint ...`) before capturing. Each keyframe can carry a gaze too, for callers
+// that own the eye.
+//
+// `Recording` and `Playback` know nothing about flash or servos, so they run the same in a host
+// test. On the robot, `start`, `capture`, `save` and `taught` keep one recording for the
+// command path, saved to `storage::TEACH_RING` as one record, all little-endian:
+//
+//     keyframes: u8 | per keyframe: after_ms: u16 | azimuth, elevation: i16 | angles: [i16]
+//
+// with every angle in milliradians, `MAX_LEGS` legs of yaw, hip and knee each, and an azimuth
+// of `i16::MIN` for a keyframe without a gaze.
+
+/// Legs a keyframe has room for.
+pub const MAX_LEGS: usize = gait::N_LEGS;
+
+/// Keyframes a `Recording` has room for.
+pub const MAX_KEYFRAMES: usize = 11;
+
+const KEYFRAME_LENGTH: usize = 2 + 2 * 2 + MAX_LEGS * 3 * 2;
+
+/// Bytes a whole `Recording` takes up, encoded.
+pub const ENCODED_LENGTH: usize = 1 + MAX_KEYFRAMES * KEYFRAME_LENGTH;
+
+/// Stands in for the azimuth of a keyframe without a gaze.
+const NO_GAZE: i16 = i16::MIN;
+
+#[cfg(not(feature = "sim"))]
+const _: () = assert!(ENCODED_LENGTH <= TEACH_RING.capacity());
+
+/// How long a `teach capture` without a time gives the move into its keyframe.
+pub const DEFAULT_TRANSITION: Duration = Duration::from_secs(1);
+
+/// The recording `start`, `capture`, `save` and `taught` work on.
+#[cfg(not(feature = "sim"))]
+static RECORDING: Mutex<CriticalSectionRawMutex, RefCell<Recording>> =
+    Mutex::new(RefCell::new(Recording::new()));
+
+/// Already `MAX_KEYFRAMES` keyframes.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub struct RecordingFull;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub struct CouldntPlay {
+    pub leg: usize,
+    pub error: servo::CouldntMove,
+}
+
+#[cfg(not(feature = "sim"))]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntTeach {
+    Relax(body::RelaxError),
+    Capture(RecordingFull),
+    Save(CouldntSave),
+    Load(CouldntLoad),
+    /// The newest saved recording is intact but doesn't decode.
+    Corrupt,
+    Play(CouldntPlay),
+}
+
+/// One captured pose, and how long to take getting there.
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    /// From the keyframe before, or from wherever the legs are for the first.
+    pub after: Duration,
+    /// Legs past those the body has stay at their defaults.
+    pub angles: [ik::Angles; MAX_LEGS],
+    /// `(azimuth, elevation)`, as for `eye::Eye::look_toward`, if the eye was captured too.
+    pub gaze: Option<(f32, f32)>,
+}
+
+/// Keyframes in the order they were captured.
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    keyframes: heapless::Vec<Keyframe, MAX_KEYFRAMES>,
+}
+
+impl Recording {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            keyframes: heapless::Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.keyframes.clear()
+    }
+
+    /// Add a keyframe at `angles`, reached `after` the one before. Returns how many there are.
+    #[inline]
+    pub fn capture<const N: usize>(
+        &mut self,
+        angles: [ik::Angles; N],
+        gaze: Option<(f32, f32)>,
+        after: Duration,
+    ) -> Result<usize, RecordingFull> {
+        const { assert!(N <= MAX_LEGS) };
+        let mut padded = [ik::Angles::default(); MAX_LEGS];
+        let () = padded[..N].copy_from_slice(&angles);
+        let () = self
+            .keyframes
+            .push(Keyframe {
+                after,
+                angles: padded,
+                gaze,
+            })
+            .map_err(|_| RecordingFull)?;
+        Ok(self.keyframes.len())
+    }
+
+    /// Write the layout described at the top of this file into `out`, returning how much of it
+    /// is used. Times saturate at about a minute, angles at about 33 radians.
+    #[inline]
+    pub fn encode(&self, out: &mut [u8; ENCODED_LENGTH]) -> usize {
+        out[0] = self.keyframes.len() as u8;
+        let (chunks, _) = out[1..].as_chunks_mut::<KEYFRAME_LENGTH>();
+        for (keyframe, chunk) in self.keyframes.iter().zip(chunks) {
+            let after_ms = keyframe.after.as_millis().min(u16::MAX as u64) as u16;
+            let (azimuth, elevation) = match keyframe.gaze {
+                Some((azimuth, elevation)) => (milliradians(azimuth), milliradians(elevation)),
+                None => (NO_GAZE, 0),
+            };
+            let angles = keyframe
+                .angles
+                .iter()
+                .flat_map(|&ik::Angles { yaw, hip, knee }| [yaw, hip, knee])
+                .map(milliradians);
+            let words = [after_ms as i16, azimuth, elevation]
+                .into_iter()
+                .chain(angles);
+            for (word, bytes) in words.zip(chunk.as_chunks_mut::<2>().0) {
+                *bytes = word.to_le_bytes();
+            }
+        }
+        1 + self.keyframes.len() * KEYFRAME_LENGTH
+    }
+
+    /// Read back what `encode` wrote, or `None` if it isn't a whole recording.
+    #[inline]
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let (&count, rest) = bytes.split_first()?;
+        let count = count as usize;
+        if count > MAX_KEYFRAMES || rest.len() != count * KEYFRAME_LENGTH {
+            return None;
+        }
+        let mut recording = Self::new();
+        for chunk in rest.as_chunks::<KEYFRAME_LENGTH>().0 {
+            let word = |i: usize| i16::from_le_bytes([chunk[2 * i], chunk[2 * i + 1]]);
+            let angle = |i: usize| f32::from(word(i)) / 1_000.0;
+            let keyframe = Keyframe {
+                after: Duration::from_millis(u64::from(word(0) as u16)),
+                angles: core::array::from_fn(|leg| ik::Angles {
+                    yaw: angle(3 + 3 * leg),
+                    hip: angle(4 + 3 * leg),
+                    knee: angle(5 + 3 * leg),
+                }),
+                gaze: (word(1) != NO_GAZE).then(|| (angle(1), angle(2))),
+            };
+            // Can't overflow, since `count` is at most `MAX_KEYFRAMES`:
+            let () = recording.keyframes.push(keyframe).unwrap();
+        }
+        Some(recording)
+    }
+}
+
+#[inline]
+fn milliradians(radians: f32) -> i16 {
+    // `as` saturates, and sends NaN to zero:
+    (radians * 1_000.0) as i16
+}
+
+/// Where a `Playback` has the legs (and maybe the eye) on this tick.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame<const N: usize> {
+    pub angles: [ik::Angles; N],
+    /// `None` until the first keyframe with a gaze.
+    pub gaze: Option<(f32, f32)>,
+}
+
+/// A recording being played back, one keyframe after another, each move easing in and out.
+/// Knows nothing about time except what it's told through `advance`, like `Interpolation`.
+#[derive(Clone, Debug)]
+pub struct Playback<const N: usize> {
+    recording: Recording,
+    /// The keyframe after the one being moved toward.
+    next: usize,
+    legs: Interpolation<[ik::Angles; N]>,
+    gaze: Option<Interpolation<(f32, f32)>>,
+    done: bool,
+}
+
+impl<const N: usize> Playback<N> {
+    /// Start toward the first keyframe from `angles`, the joints' angles now.
+    #[inline]
+    pub fn new(recording: Recording, angles: [ik::Angles; N]) -> Self {
+        const { assert!(N <= MAX_LEGS) };
+        let mut playback = Self {
+            done: recording.is_empty(),
+            recording,
+            next: 0,
+            legs: Interpolation::new(angles, angles, Duration::from_ticks(0), Easing::InOut),
+            gaze: None,
+        };
+        let () = playback.start_next();
+        playback
+    }
+
+    /// Move on by `elapsed` and return where that lands, or `None` once the last keyframe's
+    /// been handed out.
+    #[inline]
+    pub fn advance(&mut self, elapsed: Duration) -> Option<Frame<N>> {
+        if self.done {
+            return None;
+        }
+        let frame = Frame {
+            angles: self.legs.advance(elapsed),
+            gaze: self.gaze.as_mut().map(|gaze| gaze.advance(elapsed)),
+        };
+        if self.legs.is_done() {
+            if self.next < self.recording.keyframes().len() {
+                let () = self.start_next();
+            } else {
+                self.done = true;
+            }
+        }
+        Some(frame)
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    #[inline]
+    fn start_next(&mut self) {
+        let Some(&keyframe) = self.recording.keyframes().get(self.next) else {
+            return;
+        };
+        self.next += 1;
+        let to = core::array::from_fn(|i| keyframe.angles[i]);
+        self.legs = Interpolation::new(self.legs.target(), to, keyframe.after, Easing::InOut);
+        if let Some(to) = keyframe.gaze {
+            // Without a gaze to start from, look straight there:
+            let (from, duration) = match self.gaze {
+                Some(gaze) => (gaze.target(), keyframe.after),
+                None => (to, Duration::from_ticks(0)),
+            };
+            self.gaze = Some(Interpolation::new(from, to, duration, Easing::InOut));
+        }
+    }
+}
+
+/// Play `recording` on the legs, starting from wherever they were last commanded, updating
+/// the servos once per PWM period. Time spent frozen (`body::freeze`) doesn't count, so a
+/// freeze pauses the playback. Gazes are left to callers that own the eye, through `Playback`.
+#[inline]
+pub async fn play<const N: usize>(
+    body: &mut Body<'_, N>,
+    recording: Recording,
+) -> Result<(), CouldntPlay> {
+    let mut playback = Playback::new(recording, body.joint_angles());
+    let mut last = Instant::now();
+    let mut ticker = Ticker::every(Duration::from_millis(pwm::PULSE_PERIOD_MS as _));
+    loop {
+        let now = Instant::now();
+        let elapsed = if body::is_frozen() {
+            Duration::from_ticks(0)
+        } else {
+            now - last
+        };
+        last = now;
+        let Some(frame) = playback.advance(elapsed) else {
+            return Ok(());
+        };
+        for (leg, (servos, angles)) in body.legs_mut().iter_mut().zip(frame.angles).enumerate() {
+            let () = servos
+                .set_angles(angles)
+                .map_err(|error| CouldntPlay { leg, error })?;
+        }
+        let () = ticker.next().await;
+    }
+}
+
+/// Every joint's last commanded angle, except yaws read off homed encoders.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn measured_angles<const N: usize>(
+    body: &Body<'_, N>,
+    feedback: &encoder::Feedback<N>,
+) -> [ik::Angles; N] {
+    let mut angles = body.joint_angles();
+    for (leg, angles) in angles.iter_mut().enumerate() {
+        if let Some(yaw) = feedback.measured(leg) {
+            angles.yaw = yaw;
+        }
+    }
+    angles
+}
+
+/// Let every servo go limp to be posed by hand, and forget the recording in RAM
+/// (but not the one saved).
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn start<const N: usize>(body: &mut Body<'_, N>) -> Result<(), CouldntTeach> {
+    let () = RECORDING.lock(|recording| recording.borrow_mut().clear());
+    body.relax().map_err(CouldntTeach::Relax)
+}
+
+/// Capture the legs' angles as the next keyframe, reached `after` the one before.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn capture<const N: usize>(body: &Body<'_, N>, after: Duration) -> Result<(), CouldntTeach> {
+    let count = RECORDING
+        .lock(|recording| {
+            recording
+                .borrow_mut()
+                .capture(body.joint_angles(), None, after)
+        })
+        .map_err(CouldntTeach::Capture)?;
+    let () = crate::info!("Captured keyframe {count} of {MAX_KEYFRAMES}");
+    Ok(())
+}
+
+/// Write the recording in RAM to flash, replacing the one saved before.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub async fn save() -> Result<(), CouldntTeach> {
+    let mut payload = [0; ENCODED_LENGTH];
+    let length = RECORDING.lock(|recording| recording.borrow().encode(&mut payload));
+    let mut storage = storage::lock().await;
+    let flash = storage
+        .as_mut()
+        .ok_or(CouldntTeach::Save(CouldntSave::NotInitialized))?;
+    TEACH_RING
+        .append(flash, &payload[..length])
+        .map_err(CouldntTeach::Save)
+}
+
+/// Read the newest intact saved recording.
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub async fn load() -> Result<Recording, CouldntTeach> {
+    let mut storage = storage::lock().await;
+    let flash = storage
+        .as_mut()
+        .ok_or(CouldntTeach::Load(CouldntLoad::NotInitialized))?;
+    let scan = TEACH_RING
+        .scan(flash)
+        .map_err(|e| CouldntTeach::Load(CouldntLoad::Flash(e)))?;
+    let newest = scan.newest.ok_or(CouldntTeach::Load(CouldntLoad::Empty))?;
+    let mut payload = [0; MAX_PAYLOAD_LENGTH];
+    let Slot::Intact { length, .. } = TEACH_RING
+        .read(flash, newest.slot, &mut payload)
+        .map_err(|e| CouldntTeach::Load(CouldntLoad::Flash(e)))?
+    else {
+        return Err(CouldntTeach::Load(CouldntLoad::Empty));
+    };
+    Recording::decode(&payload[..length]).ok_or(CouldntTeach::Corrupt)
+}
+
+/// The recording in RAM: everything captured since boot (or since `start`).
+#[cfg(not(feature = "sim"))]
+#[inline]
+pub fn taught() -> Recording {
+    RECORDING.lock(|recording| recording.borrow().clone())
+}
@@ -0,0 +1,658 @@
+#[cfg(feature = "serde")]
+use crate::message;
+use {
+    crate::{battery, body::Body, current, gait::Gait, ik, stats, thermal},
+    core::{
+        cell::RefCell,
+        sync::atomic::{AtomicU32, Ordering},
+    },
+    embassy_sync::{
+        blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+        pipe::Pipe,
+        signal::Signal,
+    },
+    embassy_time::{Duration, Instant},
+};
+
+// Frames are COBS-encoded, so a zero byte always means "end of frame"
+// and a PC-side reader can resynchronize after dropped bytes by skipping to the next one.
+// Before encoding, each frame is:
+//
+//     kind: u8 | payload ... | crc: u16
+//
+// with everything little-endian, and `crc` being CRC-16/CCITT-FALSE over `kind` and `payload`.
+//
+// A `KIND_JITTER` frame's payload is a `Jitter`, all `u32`s:
+//
+//     ticks | worst_late_us | shortest_interval_us | longest_interval_us | buckets ...
+//
+// where `buckets` has `JITTER_BUCKETS` counts, split at `JITTER_BUCKET_LIMITS_US`. The motion
+// loop reports one every `scheduler::JITTER_DIVISOR` ticks, sent after the next snapshot.
+//
+// A `KIND_STATS` frame's payload is a `stats::Stats` (laid out at the top of `stats`), sent
+// after a snapshot every `stats::REPORT_PERIOD`.
+
+/// Frame kind for a `Snapshot`.
+pub const KIND_SNAPSHOT: u8 = 0x01;
+/// Frame kind for a command from the host: the payload is one line of `command::parse` text.
+pub const KIND_COMMAND: u8 = 0x02;
+/// Frame kind for a `Jitter` report.
+pub const KIND_JITTER: u8 = 0x07;
+/// Frame kind for a `stats::Stats` report.
+pub const KIND_STATS: u8 = 0x08;
+
+/// Longest frame, before encoding, that `FrameDecoder` accepts.
+pub const MAX_FRAME_LENGTH: usize = 256;
+
+/// Bytes in an encoded `Snapshot` header, before the per-leg records.
+const SNAPSHOT_HEADER_LENGTH: usize = 1 + 4 + 4 + 4 + 4 + 4 + 4 + 4 + 4 * ERROR_KINDS + 1;
+/// Bytes per leg in an encoded `Snapshot`: three joint angles, then the commanded
+/// and forward-kinematic foot positions.
+const SNAPSHOT_LEG_LENGTH: usize = 9 * 4;
+/// Most legs a `Snapshot` can carry.
+pub const MAX_LEGS: usize = 16;
+
+/// Encoded frames waiting for a transport (e.g. `console::run`) to send them.
+static FRAMES: Pipe<CriticalSectionRawMutex, 1024> = Pipe::new();
+
+/// Kinds of error worth watching from the host, each with its own running count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    /// Unreachable or colliding targets.
+    Ik,
+    /// A servo refused a position or the PWM peripheral complained.
+    Servo,
+    /// A command that didn't parse.
+    Command,
+    /// A telemetry frame that didn't fit in the outgoing queue.
+    DroppedFrame,
+}
+
+/// How many `ErrorKind`s there are, i.e. the length of `error_counts`.
+pub const ERROR_KINDS: usize = 4;
+
+static ERROR_COUNTS: [AtomicU32; ERROR_KINDS] = [const { AtomicU32::new(0) }; ERROR_KINDS];
+
+/// Bump the running count for `kind`. Wraps rather than saturates.
+#[inline]
+pub fn count(kind: ErrorKind) {
+    let _: u32 = ERROR_COUNTS[kind as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn error_counts() -> [u32; ERROR_KINDS] {
+    core::array::from_fn(|i| ERROR_COUNTS[i].load(Ordering::Relaxed))
+}
+
+/// How many buckets a `Jitter` histogram has.
+pub const JITTER_BUCKETS: usize = 9;
+
+/// Latest tick (in microseconds past its deadline) each `Jitter` bucket counts, but the last,
+/// which counts everything later.
+pub const JITTER_BUCKET_LIMITS_US: [u32; JITTER_BUCKETS - 1] =
+    [50, 100, 250, 500, 1_000, 2_000, 5_000, 10_000];
+
+/// Bytes in an encoded `Jitter` payload.
+const JITTER_PAYLOAD_LENGTH: usize = 4 * (4 + JITTER_BUCKETS);
+
+/// How late, and how far apart, the control loop's ticks have come since boot. Sent as its own
+/// frame (see the top of this file), whatever the `serde` feature.
+/// Counts wrap rather than saturate.
+#[derive(Clone, Copy, Debug)]
+pub struct Jitter {
+    pub ticks: u32,
+    pub worst_late: Duration,
+    pub shortest_interval: Duration,
+    pub longest_interval: Duration,
+    /// Ticks by lateness, split at `JITTER_BUCKET_LIMITS_US`.
+    pub buckets: [u32; JITTER_BUCKETS],
+}
+
+impl Default for Jitter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Jitter {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            ticks: 0,
+            worst_late: Duration::from_ticks(0),
+            shortest_interval: Duration::MAX,
+            longest_interval: Duration::from_ticks(0),
+            buckets: [0; JITTER_BUCKETS],
+        }
+    }
+
+    /// Count one tick that came `late` past its deadline, `interval` after the one before.
+    #[inline]
+    pub fn record(&mut self, interval: Duration, late: Duration) {
+        self.ticks = self.ticks.wrapping_add(1);
+        self.worst_late = self.worst_late.max(late);
+        self.shortest_interval = self.shortest_interval.min(interval);
+        self.longest_interval = self.longest_interval.max(interval);
+        let late_us = late.as_micros();
+        let bucket = JITTER_BUCKET_LIMITS_US
+            .iter()
+            .position(|&limit| late_us <= limit as u64)
+            .unwrap_or(JITTER_BUCKETS - 1);
+        self.buckets[bucket] = self.buckets[bucket].wrapping_add(1);
+    }
+
+    /// Write this report as a complete frame (ending in its zero delimiter) into `out`,
+    /// returning how many bytes it took.
+    #[inline]
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, CouldntEncode> {
+        let mut payload = heapless::Vec::<u8, JITTER_PAYLOAD_LENGTH>::new();
+        for value in [
+            self.ticks,
+            self.worst_late.as_micros() as u32,
+            self.shortest_interval.as_micros().min(u32::MAX as u64) as u32,
+            self.longest_interval.as_micros() as u32,
+        ]
+        .into_iter()
+        .chain(self.buckets)
+        {
+            let () = payload
+                .extend_from_slice(&value.to_le_bytes())
+                .map_err(|()| CouldntEncode::BufferTooSmall)?;
+        }
+        encode_frame(KIND_JITTER, &payload, out)
+    }
+}
+
+/// Everything in one telemetry frame. Slices rather than arrays so any number of legs fits.
+#[derive(Clone, Copy, Debug)]
+pub struct Snapshot<'a> {
+    pub timestamp: Instant,
+    /// Time between the starts of the last two control loop iterations.
+    pub loop_period: Duration,
+    /// Time the last control loop iteration spent working rather than waiting.
+    pub loop_busy: Duration,
+    /// Fraction of the way through the current gait cycle, on [0, 1).
+    pub gait_phase: f32,
+    /// Filtered battery voltage (see `battery`), or NaN if nothing's measuring it.
+    pub battery_volts: f32,
+    /// Servo-rail current averaged over a window (see `current`), or NaN if nothing's measuring it.
+    pub servo_amps: f32,
+    /// The chip's own temperature in degrees Celsius (see `thermal`), or NaN if nothing's measuring it.
+    pub chip_celsius: f32,
+    pub joint_angles: &'a [ik::Angles],
+    /// In the body frame, as last commanded through IK; NaN for legs never commanded that way.
+    pub commanded_foot_positions: &'a [ik::CartesianDisplacementFromEyeCenterLookingForward],
+    /// In the body frame, from `joint_angles` through forward kinematics.
+    pub measured_foot_positions: &'a [ik::CartesianDisplacementFromEyeCenterLookingForward],
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntEncode {
+    BufferTooSmall,
+    /// `joint_angles` and the foot positions have different lengths.
+    MismatchedLegs,
+    TooManyLegs,
+}
+
+impl Snapshot<'_> {
+    /// Largest encoded frame with `legs` legs, including COBS overhead and the trailing zero.
+    #[inline]
+    pub const fn max_encoded_length(legs: usize) -> usize {
+        let raw = SNAPSHOT_HEADER_LENGTH + legs * SNAPSHOT_LEG_LENGTH + 2;
+        raw + raw.div_ceil(254) + 1
+    }
+
+    /// Write this snapshot as a complete frame (ending in its zero delimiter) into `out`,
+    /// returning how many bytes it took.
+    #[inline]
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, CouldntEncode> {
+        if self.joint_angles.len() != self.commanded_foot_positions.len()
+            || self.joint_angles.len() != self.measured_foot_positions.len()
+        {
+            return Err(CouldntEncode::MismatchedLegs);
+        }
+        if self.joint_angles.len() > MAX_LEGS {
+            return Err(CouldntEncode::TooManyLegs);
+        }
+        let legs = self.joint_angles.len() as u8;
+
+        let mut raw = heapless::Vec::<
+            u8,
+            { SNAPSHOT_HEADER_LENGTH + MAX_LEGS * SNAPSHOT_LEG_LENGTH + 2 },
+        >::new();
+        let mut put = |bytes: &[u8]| {
+            raw.extend_from_slice(bytes)
+                .map_err(|()| CouldntEncode::TooManyLegs)
+        };
+        let () = put(&[KIND_SNAPSHOT])?;
+        let () = put(&(self.timestamp.as_micros() as u32).to_le_bytes())?;
+        let () = put(&(self.loop_period.as_micros() as u32).to_le_bytes())?;
+        let () = put(&(self.loop_busy.as_micros() as u32).to_le_bytes())?;
+        let () = put(&self.gait_phase.to_le_bytes())?;
+        let () = put(&self.battery_volts.to_le_bytes())?;
+        let () = put(&self.servo_amps.to_le_bytes())?;
+        let () = put(&self.chip_celsius.to_le_bytes())?;
+        for count in error_counts() {
+            let () = put(&count.to_le_bytes())?;
+        }
+        let () = put(&[legs])?;
+        for ((angles, commanded), measured) in self
+            .joint_angles
+            .iter()
+            .zip(self.commanded_foot_positions)
+            .zip(self.measured_foot_positions)
+        {
+            for value in [
+                angles.yaw,
+                angles.hip,
+                angles.knee,
+                commanded.x,
+                commanded.y,
+                commanded.z,
+                measured.x,
+                measured.y,
+                measured.z,
+            ] {
+                let () = put(&value.to_le_bytes())?;
+            }
+        }
+        let crc = crc16(&raw);
+        let () = raw
+            .extend_from_slice(&crc.to_le_bytes())
+            .map_err(|()| CouldntEncode::TooManyLegs)?;
+
+        cobs_encode(&raw, out)
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum CouldntDecode {
+    /// A zero byte inside COBS data, or a code pointing past the end.
+    Cobs,
+    TooShort,
+    BadCrc,
+    TooLong,
+}
+
+/// Reassembles incoming frames from a byte stream, checking their CRCs.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: heapless::Vec<u8, { MAX_FRAME_LENGTH + MAX_FRAME_LENGTH / 254 + 1 }>,
+    overflowed: bool,
+    /// The last `push` handed out `buffer`, so the next one starts a new frame.
+    complete: bool,
+}
+
+impl FrameDecoder {
+    /// Feed one byte, getting `(kind, payload)` back whenever a frame ends.
+    /// Empty frames (back-to-back delimiters, e.g. idle filler) are skipped.
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Option<Result<(u8, &[u8]), CouldntDecode>> {
+        if core::mem::take(&mut self.complete) {
+            let () = self.buffer.clear();
+        }
+        if byte != 0 {
+            if self.buffer.push(byte).is_err() {
+                self.overflowed = true;
+            }
+            return None;
+        }
+        if core::mem::take(&mut self.overflowed) {
+            let () = self.buffer.clear();
+            return Some(Err(CouldntDecode::TooLong));
+        }
+        if self.buffer.is_empty() {
+            return None;
+        }
+        self.complete = true;
+        Some(decode_frame(&mut self.buffer))
+    }
+}
+
+/// Undo COBS in place (without the delimiter), then check and strip the CRC.
+#[inline]
+fn decode_frame(buffer: &mut [u8]) -> Result<(u8, &[u8]), CouldntDecode> {
+    let length = cobs_decode_in_place(buffer)?;
+    if length < 3 {
+        return Err(CouldntDecode::TooShort);
+    }
+    let (body, crc) = buffer[..length].split_at(length - 2);
+    if crc16(body) != u16::from_le_bytes([crc[0], crc[1]]) {
+        return Err(CouldntDecode::BadCrc);
+    }
+    let (&kind, payload) = body.split_first().ok_or(CouldntDecode::TooShort)?;
+    Ok((kind, payload))
+}
+
+/// COBS-decode `buffer` (without its trailing zero) in place, returning the decoded length.
+/// Decoding never grows data, so the output always fits.
+#[inline]
+pub fn cobs_decode_in_place(buffer: &mut [u8]) -> Result<usize, CouldntDecode> {
+    let mut read = 0;
+    let mut write = 0;
+    while read < buffer.len() {
+        let code = buffer[read] as usize;
+        if code == 0 || read + code > buffer.len() {
+            return Err(CouldntDecode::Cobs);
+        }
+        read += 1;
+        for _ in 1..code {
+            if buffer[read] == 0 {
+                return Err(CouldntDecode::Cobs);
+            }
+            buffer[write] = buffer[read];
+            write += 1;
+            read += 1;
+        }
+        // Every block but a maximal one stands for a zero, except the implied one at the end:
+        if code != 0xFF && read < buffer.len() {
+            buffer[write] = 0;
+            write += 1;
+        }
+    }
+    Ok(write)
+}
+
+/// Frame an arbitrary payload: kind byte, payload, CRC, COBS, delimiter.
+#[inline]
+pub fn encode_frame(kind: u8, payload: &[u8], out: &mut [u8]) -> Result<usize, CouldntEncode> {
+    let mut raw = heapless::Vec::<u8, MAX_FRAME_LENGTH>::new();
+    let () = raw.push(kind).map_err(|_| CouldntEncode::BufferTooSmall)?;
+    let () = raw
+        .extend_from_slice(payload)
+        .map_err(|()| CouldntEncode::BufferTooSmall)?;
+    let crc = crc16(&raw);
+    let () = raw
+        .extend_from_slice(&crc.to_le_bytes())
+        .map_err(|()| CouldntEncode::BufferTooSmall)?;
+    cobs_encode(&raw, out)
+}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, no reflection.
+#[inline]
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Consistent-overhead byte stuffing, followed by the zero delimiter.
+#[inline]
+pub fn cobs_encode(input: &[u8], out: &mut [u8]) -> Result<usize, CouldntEncode> {
+    let mut code_index = 0;
+    let mut write_index = 1;
+    let mut code: u8 = 1;
+    for &byte in input {
+        if byte != 0 {
+            *out.get_mut(write_index)
+                .ok_or(CouldntEncode::BufferTooSmall)? = byte;
+            write_index += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            *out.get_mut(code_index)
+                .ok_or(CouldntEncode::BufferTooSmall)? = code;
+            code = 1;
+            code_index = write_index;
+            write_index += 1;
+        }
+    }
+    *out.get_mut(code_index)
+        .ok_or(CouldntEncode::BufferTooSmall)? = code;
+    *out.get_mut(write_index)
+        .ok_or(CouldntEncode::BufferTooSmall)? = 0;
+    Ok(write_index + 1)
+}
+
+/// Decides when the next frame is due, so the control loop can publish
+/// at a configurable rate no matter how fast it runs itself.
+#[derive(Clone, Copy, Debug)]
+pub struct Rate {
+    period: Duration,
+    last: Option<Instant>,
+}
+
+impl Rate {
+    #[inline]
+    pub const fn every(period: Duration) -> Self {
+        Self { period, last: None }
+    }
+
+    #[inline]
+    pub fn set_period(&mut self, period: Duration) {
+        self.period = period
+    }
+
+    /// Whether a frame is due at `now`. If so, the next one is due a period later.
+    #[inline]
+    pub fn due(&mut self, now: Instant) -> bool {
+        if let Some(last) = self.last
+            && now - last < self.period
+        {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+/// Queue a complete frame for whichever transport is draining telemetry.
+/// Never waits: if the queue can't take the whole frame, it's dropped (and counted)
+/// rather than sent in part, which would corrupt the stream.
+#[inline]
+pub fn publish(frame: &[u8]) {
+    if FRAMES.capacity() - FRAMES.len() < frame.len() {
+        let () = count(ErrorKind::DroppedFrame);
+        return;
+    }
+    let mut rest = frame;
+    // The queue is a ring buffer, so one write may stop at the wraparound:
+    while let Ok(n) = FRAMES.try_write(rest)
+        && n < rest.len()
+    {
+        rest = &rest[n..];
+    }
+}
+
+/// Take whatever queued frame bytes are ready without waiting, for transports that can't block.
+#[inline]
+pub fn try_read(buffer: &mut [u8]) -> usize {
+    FRAMES.try_read(buffer).unwrap_or(0)
+}
+
+/// Wait for queued frame bytes, for a transport to send. May return part of a frame;
+/// just send bytes in order and the delimiters take care of themselves.
+#[inline]
+pub async fn read(buffer: &mut [u8]) -> usize {
+    FRAMES.read(buffer).await
+}
+
+/// The newest `Jitter` report, waiting for `stream` to send it.
+static JITTER: Signal<CriticalSectionRawMutex, Jitter> = Signal::new();
+
+/// Have `stream` send `jitter` after its next snapshot. Cheap enough for the control loop:
+/// the report's only copied here, not encoded.
+#[inline]
+pub fn report_jitter(jitter: &Jitter) {
+    let () = JITTER.signal(*jitter);
+}
+
+/// Everything a `Snapshot` needs, copied out of the control loop so it can be encoded elsewhere.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub timestamp: Instant,
+    pub loop_period: Duration,
+    pub loop_busy: Duration,
+    pub gait_phase: f32,
+    /// NaN if nothing's measuring it.
+    pub battery_volts: f32,
+    /// NaN if nothing's measuring it.
+    pub servo_amps: f32,
+    /// NaN if nothing's measuring it.
+    pub chip_celsius: f32,
+    pub joint_angles: heapless::Vec<ik::Angles, MAX_LEGS>,
+    /// NaN for legs never commanded through IK.
+    pub commanded_foot_positions:
+        heapless::Vec<ik::CartesianDisplacementFromEyeCenterLookingForward, MAX_LEGS>,
+    pub measured_foot_positions:
+        heapless::Vec<ik::CartesianDisplacementFromEyeCenterLookingForward, MAX_LEGS>,
+}
+
+/// The newest sample from `Sampler`, waiting for `stream` to encode it.
+static LATEST: Signal<CriticalSectionRawMutex, Sample> = Signal::new();
+
+/// The newest sample from `Sampler` again, for anyone peeking without waiting (see `latest`).
+static NEWEST: Mutex<CriticalSectionRawMutex, RefCell<Option<Sample>>> =
+    Mutex::new(RefCell::new(None));
+
+/// The newest sample a `Sampler` has taken, if any, without waiting for the next one.
+#[inline]
+pub fn latest() -> Option<Sample> {
+    NEWEST.lock(|newest| newest.borrow().clone())
+}
+
+/// Samples the robot's state every `divisor`-th control loop iteration for `stream`,
+/// so telemetry runs at a fixed fraction of the control rate without the loop encoding anything.
+#[derive(Clone, Copy, Debug)]
+pub struct Sampler {
+    divisor: u32,
+    countdown: u32,
+}
+
+impl Sampler {
+    /// Sample every `divisor`-th tick (zero counts as one).
+    #[inline]
+    pub const fn every(divisor: u32) -> Self {
+        Self {
+            divisor,
+            countdown: 0,
+        }
+    }
+
+    #[inline]
+    pub fn set_divisor(&mut self, divisor: u32) {
+        self.divisor = divisor;
+        self.countdown = self.countdown.min(divisor.saturating_sub(1));
+    }
+
+    /// Call once per control loop iteration. Legs past `MAX_LEGS` are left out.
+    #[inline]
+    pub fn tick<const N: usize>(
+        &mut self,
+        body: &Body<'_, N>,
+        gait: &Gait,
+        loop_period: Duration,
+        loop_busy: Duration,
+    ) {
+        if let Some(countdown) = self.countdown.checked_sub(1) {
+            self.countdown = countdown;
+            return;
+        }
+        self.countdown = self.divisor.saturating_sub(1);
+
+        let legs = &body.legs()[..N.min(MAX_LEGS)];
+        let unknown = ik::CartesianDisplacementFromEyeCenterLookingForward {
+            x: f32::NAN,
+            y: f32::NAN,
+            z: f32::NAN,
+        };
+        let sample = Sample {
+            timestamp: Instant::now(),
+            loop_period,
+            loop_busy,
+            gait_phase: gait.phase(),
+            battery_volts: battery::volts().unwrap_or(f32::NAN),
+            servo_amps: current::amps().unwrap_or(f32::NAN),
+            chip_celsius: thermal::celsius().unwrap_or(f32::NAN),
+            joint_angles: legs.iter().map(|leg| leg.angles()).collect(),
+            commanded_foot_positions: legs
+                .iter()
+                .map(|leg| leg.target().unwrap_or(unknown))
+                .collect(),
+            measured_foot_positions: legs.iter().map(|leg| leg.forward_kinematics()).collect(),
+        };
+        let () = NEWEST.lock(|newest| *newest.borrow_mut() = Some(sample.clone()));
+        let () = LATEST.signal(sample);
+    }
+}
+
+/// Encode and queue every sample a `Sampler` takes, forever. Run it as its own task;
+/// if it falls behind, older samples are skipped rather than queued.
+/// With the `serde` feature, samples go out as `message::Snapshot`s instead.
+#[inline]
+pub async fn stream() -> ! {
+    #[cfg(not(feature = "serde"))]
+    let mut frame = [0; Snapshot::max_encoded_length(MAX_LEGS)];
+    #[cfg(feature = "serde")]
+    let mut frame = [0; message::MAX_ENCODED_LENGTH];
+    let mut stats_rate = Rate::every(stats::REPORT_PERIOD);
+    loop {
+        let sample = LATEST.wait().await;
+        #[cfg(not(feature = "serde"))]
+        let encoded = Snapshot {
+            timestamp: sample.timestamp,
+            loop_period: sample.loop_period,
+            loop_busy: sample.loop_busy,
+            gait_phase: sample.gait_phase,
+            battery_volts: sample.battery_volts,
+            servo_amps: sample.servo_amps,
+            chip_celsius: sample.chip_celsius,
+            joint_angles: &sample.joint_angles,
+            commanded_foot_positions: &sample.commanded_foot_positions,
+            measured_foot_positions: &sample.measured_foot_positions,
+        }
+        .encode(&mut frame);
+        #[cfg(feature = "serde")]
+        let encoded = message::encode_frame(
+            message::KIND_SNAPSHOT,
+            &message::Snapshot {
+                timestamp_us: sample.timestamp.as_micros() as u32,
+                loop_period_us: sample.loop_period.as_micros() as u32,
+                loop_busy_us: sample.loop_busy.as_micros() as u32,
+                gait_phase: sample.gait_phase,
+                battery_volts: Some(sample.battery_volts).filter(|volts| !volts.is_nan()),
+                servo_amps: Some(sample.servo_amps).filter(|amps| !amps.is_nan()),
+                chip_celsius: Some(sample.chip_celsius).filter(|celsius| !celsius.is_nan()),
+                error_counts: error_counts(),
+                legs: (0..sample.joint_angles.len())
+                    .map(|i| message::LegState {
+                        angles: sample.joint_angles[i],
+                        commanded: Some(sample.commanded_foot_positions[i])
+                            .filter(|foot| !foot.x.is_nan()),
+                        measured: sample.measured_foot_positions[i],
+                    })
+                    .collect(),
+            },
+            &mut frame,
+        );
+        match encoded {
+            Ok(n) => publish(&frame[..n]),
+            Err(e) => crate::warn!("Couldn't encode a telemetry snapshot: {e:?}"),
+        }
+        if let Some(jitter) = JITTER.try_take() {
+            match jitter.encode(&mut frame) {
+                Ok(n) => publish(&frame[..n]),
+                Err(e) => crate::warn!("Couldn't encode a jitter report: {e:?}"),
+            }
+        }
+        if stats_rate.due(Instant::now()) {
+            match stats::snapshot().encode(&mut frame) {
+                Ok(n) => publish(&frame[..n]),
+                Err(e) => crate::warn!("Couldn't encode a stats report: {e:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use {
+    crate::{analog, fault},
+    core::{
+        fmt::{self, Write as _},
+        sync::atomic::{AtomicU32, Ordering},
+    },
+    embassy_rp::adc::{self, Adc, Async, Channel},
+    embassy_time::Duration,
+};
+
+// The chip's own temperature sensor, on the ADC's internal channel
+// (`Channel::new_temp_sensor`). It's a diode whose forward voltage falls about 1.721 mV
+// per degree from 0.706 V at 27 C, which the datasheet calls good to a few degrees:
+// plenty to see an enclosure cooking. Crossing the warning threshold is also logged to
+// flash (`fault::Kind::Overheat`), so a reset can be lined up against it afterward.
+
+/// Time between ADC readings (see `analog::run`).
+pub const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Default temperature worth a warning. The chip's rated to 85 C.
+pub const DEFAULT_WARNING_CELSIUS: f32 = 70.0;
+/// How far below the warning the temperature has to fall before it clears.
+pub const HYSTERESIS_CELSIUS: f32 = 5.0;
+
+/// Latest filtered chip temperature, as `f32` bits; NaN until the first reading.
+static CELSIUS: AtomicU32 = AtomicU32::new(f32::NAN.to_bits());
+
+/// Latest filtered chip temperature in degrees Celsius, if anything's measuring it.
+#[inline]
+pub fn celsius() -> Option<f32> {
+    Some(f32::from_bits(CELSIUS.load(Ordering::Relaxed))).filter(|celsius| !celsius.is_nan())
+}
+
+/// What a reading changed about the warning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Heated,
+    Cooled,
+}
+
+/// Turns raw ADC counts into a filtered temperature, published through `celsius`,
+/// and watches it against a warning threshold.
+#[derive(Clone, Copy, Debug)]
+pub struct Monitor {
+    warning_celsius: f32,
+    /// Weight of each new reading in the exponential moving average, on (0, 1].
+    smoothing: f32,
+    filtered: Option<f32>,
+    hot: bool,
+}
+
+impl Monitor {
+    #[inline]
+    pub const fn new(warning_celsius: f32, smoothing: f32) -> Self {
+        Self {
+            warning_celsius,
+            smoothing,
+            filtered: None,
+            hot: false,
+        }
+    }
+
+    #[inline]
+    pub fn is_hot(&self) -> bool {
+        self.hot
+    }
+
+    /// Fold in one raw reading, returning whether it crossed the warning threshold.
+    #[inline]
+    pub fn update(&mut self, counts: u16) -> Option<Change> {
+        let volts = analog::volts(counts);
+        let celsius = 27.0 - (volts - 0.706) / 0.001721;
+        let filtered = self
+            .filtered
+            .map_or(celsius, |old| old + self.smoothing * (celsius - old));
+        self.filtered = Some(filtered);
+        let () = CELSIUS.store(filtered.to_bits(), Ordering::Relaxed);
+
+        let hot = if self.hot {
+            filtered > self.warning_celsius - HYSTERESIS_CELSIUS
+        } else {
+            filtered > self.warning_celsius
+        };
+        if hot == core::mem::replace(&mut self.hot, hot) {
+            return None;
+        }
+        Some(if hot { Change::Heated } else { Change::Cooled })
+    }
+
+    /// Take one reading from `channel`, `update` with it, and log any change
+    /// (to flash, too, when it first gets hot).
+    #[inline]
+    pub async fn sample(
+        &mut self,
+        adc: &mut Adc<'_, Async>,
+        channel: &mut Channel<'_>,
+    ) -> Result<(), adc::Error> {
+        let change = self.update(adc.read(channel).await?);
+        let celsius = self.filtered.unwrap_or(f32::NAN);
+        match change {
+            None => {}
+            Some(Change::Heated) => {
+                let () = crate::warn!("Chip at {celsius:.1} C: running hot");
+                let mut message = heapless::String::<16>::new();
+                let _: fmt::Result = write!(message, "{celsius:.1} C");
+                let () = fault::record(fault::Kind::Overheat, 0, &message).await;
+            }
+            Some(Change::Cooled) => crate::info!("Chip back down to {celsius:.1} C"),
+        }
+        Ok(())
+    }
+}
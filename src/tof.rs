@@ -0,0 +1,538 @@
+use {
+    crate::gait::{self, Gait},
+    core::sync::atomic::{AtomicU32, Ordering},
+    embassy_rp::i2c::{self, Async, I2c},
+    embassy_time::{Duration, Instant, Timer},
+};
+
+// A time-of-flight rangefinder looking straight ahead along the eye's x axis.
+// Both supported chips run back-to-back continuous ranging; `run` keeps `obstacle` current,
+// and everything else (the gait's `Guard`, the eye's `notice`) just reads that.
+//
+//     VL53L0X  8-bit registers, up to about 1.2 m; set up the way ST's API does it
+//              (SPAD selection, tuning table, VHV and phase calibration)
+//     VL53L1X  16-bit registers, up to about 4 m; set up from ST's ULD default configuration
+//
+// Readings past the sensor's range, or ones it flags as invalid, clear `obstacle`.
+
+/// Default I2C address of both chips.
+pub const DEFAULT_ADDRESS: u8 = 0x29;
+
+/// Longest a reading may take before the sensor counts as hung.
+const READING_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Nearest obstacle ahead in millimetres, or `NOTHING` if there's none in range.
+static OBSTACLE_MM: AtomicU32 = AtomicU32::new(NOTHING);
+/// When `OBSTACLE_MM` was last written, in milliseconds since boot (wrapping).
+static UPDATED_MS: AtomicU32 = AtomicU32::new(0);
+
+const NOTHING: u32 = u32::MAX;
+
+/// A reading older than this is stale: the sensor (or its task) has stopped.
+pub const STALE_AFTER: Duration = Duration::from_millis(500);
+
+/// Distance to the nearest thing straight ahead, in millimetres,
+/// if the sensor's running and sees anything in range.
+#[inline]
+pub fn obstacle() -> Option<u16> {
+    let age = (Instant::now().as_millis() as u32).wrapping_sub(UPDATED_MS.load(Ordering::Relaxed));
+    if age as u64 > STALE_AFTER.as_millis() {
+        return None;
+    }
+    match OBSTACLE_MM.load(Ordering::Relaxed) {
+        NOTHING => None,
+        mm => Some(mm as u16),
+    }
+}
+
+#[inline]
+fn publish(mm: Option<u16>) {
+    let () = OBSTACLE_MM.store(mm.map_or(NOTHING, u32::from), Ordering::Relaxed);
+    let () = UPDATED_MS.store(Instant::now().as_millis() as u32, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Model {
+    Vl53l0x,
+    Vl53l1x,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-format", derive(defmt::Format))]
+pub enum TofError {
+    I2c(i2c::Error),
+    /// Something answered, but not the expected chip.
+    WrongChip {
+        id: u16,
+    },
+    /// The sensor didn't boot, calibrate or finish a reading in time.
+    Timeout,
+}
+
+const VL53L0X_SYSRANGE_START: u8 = 0x00;
+const VL53L0X_SYSTEM_SEQUENCE_CONFIG: u8 = 0x01;
+const VL53L0X_SYSTEM_INTERRUPT_CONFIG_GPIO: u8 = 0x0A;
+const VL53L0X_SYSTEM_INTERRUPT_CLEAR: u8 = 0x0B;
+const VL53L0X_RESULT_INTERRUPT_STATUS: u8 = 0x13;
+const VL53L0X_RESULT_RANGE: u8 = 0x14 + 10;
+const VL53L0X_FINAL_RANGE_MIN_COUNT_RATE_RTN_LIMIT: u8 = 0x44;
+const VL53L0X_MSRC_CONFIG_CONTROL: u8 = 0x60;
+const VL53L0X_GPIO_HV_MUX_ACTIVE_HIGH: u8 = 0x84;
+const VL53L0X_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV: u8 = 0x89;
+const VL53L0X_GLOBAL_CONFIG_SPAD_ENABLES_REF_0: u8 = 0xB0;
+const VL53L0X_GLOBAL_CONFIG_REF_EN_START_SELECT: u8 = 0xB6;
+const VL53L0X_DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD: u8 = 0x4E;
+const VL53L0X_DYNAMIC_SPAD_REF_EN_START_OFFSET: u8 = 0x4F;
+const VL53L0X_IDENTIFICATION_MODEL_ID: u8 = 0xC0;
+const VL53L0X_MODEL_ID: u8 = 0xEE;
+/// Anything at or beyond this means "nothing in range".
+const VL53L0X_OUT_OF_RANGE: u16 = 8190;
+
+/// ST's default tuning settings, written as-is.
+#[rustfmt::skip]
+const VL53L0X_TUNING: &[(u8, u8)] = &[
+    (0xFF, 0x01), (0x00, 0x00), (0xFF, 0x00), (0x09, 0x00), (0x10, 0x00), (0x11, 0x00),
+    (0x24, 0x01), (0x25, 0xFF), (0x75, 0x00), (0xFF, 0x01), (0x4E, 0x2C), (0x48, 0x00),
+    (0x30, 0x20), (0xFF, 0x00), (0x30, 0x09), (0x54, 0x00), (0x31, 0x04), (0x32, 0x03),
+    (0x40, 0x83), (0x46, 0x25), (0x60, 0x00), (0x27, 0x00), (0x50, 0x06), (0x51, 0x00),
+    (0x52, 0x96), (0x56, 0x08), (0x57, 0x30), (0x61, 0x00), (0x62, 0x00), (0x64, 0x00),
+    (0x65, 0x00), (0x66, 0xA0), (0xFF, 0x01), (0x22, 0x32), (0x47, 0x14), (0x49, 0xFF),
+    (0x4A, 0x00), (0xFF, 0x00), (0x7A, 0x0A), (0x7B, 0x00), (0x78, 0x21), (0xFF, 0x01),
+    (0x23, 0x34), (0x42, 0x00), (0x44, 0xFF), (0x45, 0x26), (0x46, 0x05), (0x40, 0x40),
+    (0x0E, 0x06), (0x20, 0x1A), (0x43, 0x40), (0xFF, 0x00), (0x34, 0x03), (0x35, 0x44),
+    (0xFF, 0x01), (0x31, 0x04), (0x4B, 0x09), (0x4C, 0x05), (0x4D, 0x04), (0xFF, 0x00),
+    (0x44, 0x00), (0x45, 0x20), (0x47, 0x08), (0x48, 0x28), (0x67, 0x00), (0x70, 0x04),
+    (0x71, 0x01), (0x72, 0xFE), (0x76, 0x00), (0x77, 0x00), (0xFF, 0x01), (0x0D, 0x01),
+    (0xFF, 0x00), (0x80, 0x01), (0x01, 0xF8), (0xFF, 0x01), (0x8E, 0x01), (0x00, 0x01),
+    (0xFF, 0x00), (0x80, 0x00),
+];
+
+const VL53L1X_VHV_CONFIG_TIMEOUT_MACROP_LOOP_BOUND: u16 = 0x0008;
+const VL53L1X_VHV_CONFIG_INIT: u16 = 0x000B;
+const VL53L1X_DEFAULT_CONFIG_START: u16 = 0x002D;
+const VL53L1X_GPIO_TIO_HV_STATUS: u16 = 0x0031;
+const VL53L1X_SYSTEM_INTERRUPT_CLEAR: u16 = 0x0086;
+const VL53L1X_SYSTEM_MODE_START: u16 = 0x0087;
+const VL53L1X_RESULT_RANGE_STATUS: u16 = 0x0089;
+const VL53L1X_RESULT_RANGE_MM: u16 = 0x0096;
+const VL53L1X_FIRMWARE_SYSTEM_STATUS: u16 = 0x00E5;
+const VL53L1X_IDENTIFICATION_MODEL_ID: u16 = 0x010F;
+const VL53L1X_MODEL_ID: u16 = 0xEACC;
+/// Raw range status of a good reading.
+const VL53L1X_RANGE_VALID: u8 = 9;
+
+/// ST's ULD default configuration, for registers 0x2D through 0x87. It leaves the
+/// interrupt active high and ranging stopped.
+#[rustfmt::skip]
+const VL53L1X_DEFAULT_CONFIG: [u8; 91] = [
+    0x00, 0x00, 0x00, 0x01, 0x02, 0x00, 0x02, 0x08, 0x00, 0x08, 0x10, 0x01, 0x01, 0x00, 0x00,
+    0x00, 0x00, 0xFF, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x0B, 0x00, 0x00, 0x02,
+    0x0A, 0x21, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0xC8, 0x00, 0x00, 0x38, 0xFF, 0x01,
+    0x00, 0x08, 0x00, 0x00, 0x01, 0xCC, 0x0F, 0x01, 0xF1, 0x0D, 0x01, 0x68, 0x00, 0x80, 0x08,
+    0xB8, 0x00, 0x00, 0x00, 0x00, 0x0F, 0x89, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    0x0F, 0x0D, 0x0E, 0x0E, 0x00, 0x00, 0x02, 0xC7, 0xFF, 0x9B, 0x00, 0x00, 0x00, 0x01, 0x00,
+    0x00,
+];
+
+pub struct Tof<'d, T: i2c::Instance> {
+    i2c: I2c<'d, T, Async>,
+    model: Model,
+    address: u8,
+    /// VL53L0X only: a value read at init and written back before each start.
+    stop_variable: u8,
+}
+
+impl<'d, T: i2c::Instance> Tof<'d, T> {
+    /// Check the chip is there and what it should be, set it up, and start it ranging.
+    #[inline]
+    pub async fn new(i2c: I2c<'d, T, Async>, model: Model, address: u8) -> Result<Self, TofError> {
+        let mut tof = Self {
+            i2c,
+            model,
+            address,
+            stop_variable: 0,
+        };
+        match model {
+            Model::Vl53l0x => {
+                let id = tof.read_u8(VL53L0X_IDENTIFICATION_MODEL_ID).await?;
+                if id != VL53L0X_MODEL_ID {
+                    return Err(TofError::WrongChip { id: id as u16 });
+                }
+                let () = tof.init_vl53l0x().await?;
+            }
+            Model::Vl53l1x => {
+                let () = tof
+                    .until(async |tof: &mut Self| {
+                        Ok(tof.read_u8_at(VL53L1X_FIRMWARE_SYSTEM_STATUS).await? & 0x01 != 0)
+                    })
+                    .await?;
+                let id = tof.read_u16_at(VL53L1X_IDENTIFICATION_MODEL_ID).await?;
+                if id != VL53L1X_MODEL_ID {
+                    return Err(TofError::WrongChip { id });
+                }
+                let () = tof.init_vl53l1x().await?;
+            }
+        }
+        Ok(tof)
+    }
+
+    #[inline]
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Wait for the next reading: millimetres to whatever's ahead, or `None` if nothing's in range.
+    #[inline]
+    pub async fn read(&mut self) -> Result<Option<u16>, TofError> {
+        match self.model {
+            Model::Vl53l0x => {
+                let () = self
+                    .until(async |tof: &mut Self| {
+                        Ok(tof.read_u8(VL53L0X_RESULT_INTERRUPT_STATUS).await? & 0x07 != 0)
+                    })
+                    .await?;
+                let mm = self.read_u16(VL53L0X_RESULT_RANGE).await?;
+                let () = self.write_u8(VL53L0X_SYSTEM_INTERRUPT_CLEAR, 0x01).await?;
+                Ok(Some(mm).filter(|&mm| mm < VL53L0X_OUT_OF_RANGE))
+            }
+            Model::Vl53l1x => {
+                let () = self
+                    .until(async |tof: &mut Self| tof.vl53l1x_ready().await)
+                    .await?;
+                let status = self.read_u8_at(VL53L1X_RESULT_RANGE_STATUS).await? & 0x1F;
+                let mm = self.read_u16_at(VL53L1X_RESULT_RANGE_MM).await?;
+                let () = self
+                    .write_u8_at(VL53L1X_SYSTEM_INTERRUPT_CLEAR, 0x01)
+                    .await?;
+                Ok(Some(mm).filter(|_| status == VL53L1X_RANGE_VALID))
+            }
+        }
+    }
+
+    #[inline]
+    async fn init_vl53l0x(&mut self) -> Result<(), TofError> {
+        // 2.8 V I/O rather than the default 1.8 V:
+        let pad = self
+            .read_u8(VL53L0X_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV)
+            .await?;
+        let () = self
+            .write_u8(VL53L0X_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV, pad | 0x01)
+            .await?;
+        // Standard-mode I2C, and fetch the stop variable from behind the private page:
+        let () = self
+            .write_all(&[(0x88, 0x00), (0x80, 0x01), (0xFF, 0x01), (0x00, 0x00)])
+            .await?;
+        self.stop_variable = self.read_u8(0x91).await?;
+        let () = self
+            .write_all(&[(0x00, 0x01), (0xFF, 0x00), (0x80, 0x00)])
+            .await?;
+
+        // No signal-rate limit checks on MSRC or pre-range, and 0.25 MCPS (9.7 fixed point)
+        // on the final range:
+        let msrc = self.read_u8(VL53L0X_MSRC_CONFIG_CONTROL).await?;
+        let () = self
+            .write_u8(VL53L0X_MSRC_CONFIG_CONTROL, msrc | 0x12)
+            .await?;
+        let () = self
+            .write_u16(VL53L0X_FINAL_RANGE_MIN_COUNT_RATE_RTN_LIMIT, 1 << 5)
+            .await?;
+        let () = self.write_u8(VL53L0X_SYSTEM_SEQUENCE_CONFIG, 0xFF).await?;
+
+        // Reference SPADs: how many to use and of which type lives in NVM,
+        // and the map of good ones in the enable registers:
+        let () = self
+            .write_all(&[(0x80, 0x01), (0xFF, 0x01), (0x00, 0x00), (0xFF, 0x06)])
+            .await?;
+        let page = self.read_u8(0x83).await?;
+        let () = self.write_u8(0x83, page | 0x04).await?;
+        let () = self
+            .write_all(&[
+                (0xFF, 0x07),
+                (0x81, 0x01),
+                (0x80, 0x01),
+                (0x94, 0x6B),
+                (0x83, 0x00),
+            ])
+            .await?;
+        let () = self
+            .until(async |tof: &mut Self| Ok(tof.read_u8(0x83).await? != 0x00))
+            .await?;
+        let () = self.write_u8(0x83, 0x01).await?;
+        let spad_info = self.read_u8(0x92).await?;
+        let () = self.write_all(&[(0x81, 0x00), (0xFF, 0x06)]).await?;
+        let page = self.read_u8(0x83).await?;
+        let () = self.write_u8(0x83, page & !0x04).await?;
+        let () = self
+            .write_all(&[(0xFF, 0x01), (0x00, 0x01), (0xFF, 0x00), (0x80, 0x00)])
+            .await?;
+        let spad_count = spad_info & 0x7F;
+        let aperture = spad_info & 0x80 != 0;
+
+        let mut spads = [0; 6];
+        let () = self
+            .i2c
+            .write_read_async(
+                self.address,
+                [VL53L0X_GLOBAL_CONFIG_SPAD_ENABLES_REF_0],
+                &mut spads,
+            )
+            .await
+            .map_err(TofError::I2c)?;
+        let () = self
+            .write_all(&[
+                (0xFF, 0x01),
+                (VL53L0X_DYNAMIC_SPAD_REF_EN_START_OFFSET, 0x00),
+                (VL53L0X_DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD, 0x2C),
+                (0xFF, 0x00),
+                (VL53L0X_GLOBAL_CONFIG_REF_EN_START_SELECT, 0xB4),
+            ])
+            .await?;
+        // Aperture SPADs start at 12; keep only the first `spad_count` good ones from there:
+        let first = if aperture { 12 } else { 0 };
+        let mut enabled = 0;
+        for i in 0..48 {
+            let bit = 1 << (i % 8);
+            if i < first || enabled == spad_count {
+                spads[i / 8] &= !bit;
+            } else if spads[i / 8] & bit != 0 {
+                enabled += 1;
+            }
+        }
+        let () = self
+            .i2c
+            .write_async(
+                self.address,
+                core::iter::once(VL53L0X_GLOBAL_CONFIG_SPAD_ENABLES_REF_0).chain(spads),
+            )
+            .await
+            .map_err(TofError::I2c)?;
+
+        let () = self.write_all(VL53L0X_TUNING).await?;
+
+        // Interrupt (polled, not wired) on each new sample:
+        let () = self
+            .write_u8(VL53L0X_SYSTEM_INTERRUPT_CONFIG_GPIO, 0x04)
+            .await?;
+        let mux = self.read_u8(VL53L0X_GPIO_HV_MUX_ACTIVE_HIGH).await?;
+        let () = self
+            .write_u8(VL53L0X_GPIO_HV_MUX_ACTIVE_HIGH, mux & !0x10)
+            .await?;
+        let () = self.write_u8(VL53L0X_SYSTEM_INTERRUPT_CLEAR, 0x01).await?;
+
+        // VHV then phase calibration, each with only its own step enabled,
+        // then the default sequence (no MSRC or TCC):
+        for (sequence, start) in [(0x01, 0x40), (0x02, 0x00)] {
+            let () = self
+                .write_u8(VL53L0X_SYSTEM_SEQUENCE_CONFIG, sequence)
+                .await?;
+            let () = self.write_u8(VL53L0X_SYSRANGE_START, 0x01 | start).await?;
+            let () = self
+                .until(async |tof: &mut Self| {
+                    Ok(tof.read_u8(VL53L0X_RESULT_INTERRUPT_STATUS).await? & 0x07 != 0)
+                })
+                .await?;
+            let () = self.write_u8(VL53L0X_SYSTEM_INTERRUPT_CLEAR, 0x01).await?;
+            let () = self.write_u8(VL53L0X_SYSRANGE_START, 0x00).await?;
+        }
+        let () = self.write_u8(VL53L0X_SYSTEM_SEQUENCE_CONFIG, 0xE8).await?;
+
+        // Back-to-back continuous ranging:
+        self.write_all(&[
+            (0x80, 0x01),
+            (0xFF, 0x01),
+            (0x00, 0x00),
+            (0x91, self.stop_variable),
+            (0x00, 0x01),
+            (0xFF, 0x00),
+            (0x80, 0x00),
+            (VL53L0X_SYSRANGE_START, 0x02),
+        ])
+        .await
+    }
+
+    #[inline]
+    async fn init_vl53l1x(&mut self) -> Result<(), TofError> {
+        let [high, low] = VL53L1X_DEFAULT_CONFIG_START.to_be_bytes();
+        let () = self
+            .i2c
+            .write_async(
+                self.address,
+                [high, low].into_iter().chain(VL53L1X_DEFAULT_CONFIG),
+            )
+            .await
+            .map_err(TofError::I2c)?;
+        // One throwaway reading, then VHV settings as ST's ULD leaves them, then start for real:
+        let () = self.write_u8_at(VL53L1X_SYSTEM_MODE_START, 0x40).await?;
+        let () = self
+            .until(async |tof: &mut Self| tof.vl53l1x_ready().await)
+            .await?;
+        let () = self
+            .write_u8_at(VL53L1X_SYSTEM_INTERRUPT_CLEAR, 0x01)
+            .await?;
+        let () = self.write_u8_at(VL53L1X_SYSTEM_MODE_START, 0x00).await?;
+        let () = self
+            .write_u8_at(VL53L1X_VHV_CONFIG_TIMEOUT_MACROP_LOOP_BOUND, 0x09)
+            .await?;
+        let () = self.write_u8_at(VL53L1X_VHV_CONFIG_INIT, 0x00).await?;
+        self.write_u8_at(VL53L1X_SYSTEM_MODE_START, 0x40).await
+    }
+
+    /// With the default configuration's active-high interrupt, bit 0 set means data's ready.
+    #[inline]
+    async fn vl53l1x_ready(&mut self) -> Result<bool, TofError> {
+        Ok(self.read_u8_at(VL53L1X_GPIO_TIO_HV_STATUS).await? & 0x01 != 0)
+    }
+
+    /// Poll `done` every millisecond until it's true, for up to `READING_TIMEOUT`.
+    #[inline]
+    async fn until(
+        &mut self,
+        mut done: impl AsyncFnMut(&mut Self) -> Result<bool, TofError>,
+    ) -> Result<(), TofError> {
+        let deadline = Instant::now() + READING_TIMEOUT;
+        while !done(self).await? {
+            if Instant::now() > deadline {
+                return Err(TofError::Timeout);
+            }
+            let () = Timer::after_millis(1).await;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    async fn write_all(&mut self, writes: &[(u8, u8)]) -> Result<(), TofError> {
+        for &(register, value) in writes {
+            let () = self.write_u8(register, value).await?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    async fn read_u8(&mut self, register: u8) -> Result<u8, TofError> {
+        let mut value = [0];
+        let () = self
+            .i2c
+            .write_read_async(self.address, [register], &mut value)
+            .await
+            .map_err(TofError::I2c)?;
+        Ok(value[0])
+    }
+
+    #[inline]
+    async fn read_u16(&mut self, register: u8) -> Result<u16, TofError> {
+        let mut value = [0; 2];
+        let () = self
+            .i2c
+            .write_read_async(self.address, [register], &mut value)
+            .await
+            .map_err(TofError::I2c)?;
+        Ok(u16::from_be_bytes(value))
+    }
+
+    #[inline]
+    async fn write_u8(&mut self, register: u8, value: u8) -> Result<(), TofError> {
+        self.i2c
+            .write_async(self.address, [register, value])
+            .await
+            .map_err(TofError::I2c)
+    }
+
+    #[inline]
+    async fn write_u16(&mut self, register: u8, value: u16) -> Result<(), TofError> {
+        let [high, low] = value.to_be_bytes();
+        self.i2c
+            .write_async(self.address, [register, high, low])
+            .await
+            .map_err(TofError::I2c)
+    }
+
+    #[inline]
+    async fn read_u8_at(&mut self, register: u16) -> Result<u8, TofError> {
+        let mut value = [0];
+        let () = self
+            .i2c
+            .write_read_async(self.address, register.to_be_bytes(), &mut value)
+            .await
+            .map_err(TofError::I2c)?;
+        Ok(value[0])
+    }
+
+    #[inline]
+    async fn read_u16_at(&mut self, register: u16) -> Result<u16, TofError> {
+        let mut value = [0; 2];
+        let () = self
+            .i2c
+            .write_read_async(self.address, register.to_be_bytes(), &mut value)
+            .await
+            .map_err(TofError::I2c)?;
+        Ok(u16::from_be_bytes(value))
+    }
+
+    #[inline]
+    async fn write_u8_at(&mut self, register: u16, value: u8) -> Result<(), TofError> {
+        let [high, low] = register.to_be_bytes();
+        self.i2c
+            .write_async(self.address, [high, low, value])
+            .await
+            .map_err(TofError::I2c)
+    }
+}
+
+/// Range forever, keeping `obstacle` up to date. The sensors pace themselves, so no ticker.
+#[inline]
+pub async fn run<T: i2c::Instance>(mut tof: Tof<'static, T>) -> ! {
+    loop {
+        match tof.read().await {
+            Ok(mm) => publish(mm),
+            Err(e) => {
+                let () = crate::warn!("Couldn't read the rangefinder: {e:?}");
+                let () = Timer::after_millis(100).await;
+            }
+        }
+    }
+}
+
+/// Slows the gait as something gets close ahead, and stops it short of walking into it.
+/// Only held back while heading forward, so the robot can still back away or turn in place.
+#[derive(Clone, Copy, Debug)]
+pub struct Guard {
+    /// Start slowing down at this distance, in millimetres...
+    slow_mm: u16,
+    /// ...scaling linearly down to a stop here.
+    stop_mm: u16,
+    stopped: bool,
+}
+
+impl Guard {
+    #[inline]
+    pub const fn new(slow_mm: u16, stop_mm: u16) -> Self {
+        Self {
+            slow_mm,
+            stop_mm,
+            stopped: false,
+        }
+    }
+
+    /// Call once per motion loop iteration.
+    #[inline]
+    pub fn check(&mut self, gait: &mut Gait) {
+        let forward = gait.commanded_velocity().x > 0.0;
+        let scale = match obstacle() {
+            Some(mm) if forward => {
+                let span = self.slow_mm.saturating_sub(self.stop_mm).max(1) as f32;
+                ((mm.saturating_sub(self.stop_mm) as f32) / span).clamp(0.0, 1.0)
+            }
+            _ => 1.0,
+        };
+        let stopped = scale == 0.0;
+        if stopped != self.stopped {
+            self.stopped = stopped;
+            if stopped {
+                let () = crate::warn!("Obstacle ahead: stopping");
+            } else {
+                let () = crate::info!("Path ahead clear");
+            }
+        }
+        let () = gait.set_speed_scale(gait::Limiter::Obstacle, scale);
+    }
+}
@@ -0,0 +1,191 @@
+// The trigonometry under IK, gait, body and eye math, with the backend picked per build:
+//
+//     (default)         `libm`, correct to within an ulp or so
+//     `trig-lut`        tables with linear interpolation, built at compile time (2 KB of flash)
+//     `trig-micromath`  `micromath`'s polynomial approximations
+//
+// Full precision is more than a servo can use: the best of these reads about a degree
+// (0.017 rad) of its command, and the loosest backend here stays within a third of that.
+// `MAX_ERROR` is the bound for whichever one's built, checked against `libm` by `tests/trig`
+// for every function here (for `acosf`, over its whole domain; for the rest, over a few turns
+// either side of zero, which is everywhere this crate calls them).
+//
+// Only these four are swapped: `sqrtf` and `hypotf` are already cheap next to them.
+
+#[cfg(all(feature = "trig-lut", feature = "trig-micromath"))]
+compile_error!("Pick at most one of `trig-lut` and `trig-micromath`.");
+
+/// Largest difference from `libm` any function here makes, in radians (for `acosf` and
+/// `atan2f`) or in units of the result (for `sinf` and `cosf`).
+#[cfg(not(any(feature = "trig-lut", feature = "trig-micromath")))]
+pub const MAX_ERROR: f32 = 0.0;
+#[cfg(feature = "trig-lut")]
+pub const MAX_ERROR: f32 = 1e-5;
+#[cfg(feature = "trig-micromath")]
+pub const MAX_ERROR: f32 = 5e-3; // `micromath` documents 2e-3 for each, before rounding.
+
+#[cfg(not(any(feature = "trig-lut", feature = "trig-micromath")))]
+pub use libm::{acosf, atan2f, cosf, sinf};
+
+#[cfg(feature = "trig-lut")]
+pub use lut::{acosf, atan2f, cosf, sinf};
+
+#[cfg(feature = "trig-micromath")]
+pub use fast::{acosf, atan2f, cosf, sinf};
+
+#[cfg(feature = "trig-micromath")]
+mod fast {
+    // Called through the trait rather than as methods, which `std` (under `sim`) would shadow:
+    use micromath::F32Ext;
+
+    #[inline]
+    pub fn sinf(x: f32) -> f32 {
+        F32Ext::sin(x)
+    }
+
+    #[inline]
+    pub fn cosf(x: f32) -> f32 {
+        F32Ext::cos(x)
+    }
+
+    #[inline]
+    pub fn acosf(x: f32) -> f32 {
+        F32Ext::acos(x)
+    }
+
+    #[inline]
+    pub fn atan2f(y: f32, x: f32) -> f32 {
+        F32Ext::atan2(y, x)
+    }
+}
+
+#[cfg(feature = "trig-lut")]
+mod lut {
+    use core::f32::consts::{FRAC_PI_2, PI, TAU};
+
+    // Sine over a quarter turn and arctangent over [0, 1], each at `STEPS + 1` evenly spaced
+    // points, worked out in `f64` by `const fn`s below. Linear interpolation between samples
+    // `h` apart is off by at most `h^2 / 8` times the function's largest second derivative:
+    // about 5e-6 for sine (h = pi/512) and 1e-6 for arctangent (h = 1/256), leaving `MAX_ERROR`
+    // room for `f32` rounding.
+
+    const STEPS: usize = 256;
+
+    static SINE: [f32; STEPS + 1] = {
+        let mut table = [0.0; STEPS + 1];
+        let mut i = 0;
+        while i <= STEPS {
+            table[i] = sine(core::f64::consts::FRAC_PI_2 * (i as f64) / (STEPS as f64)) as f32;
+            i += 1;
+        }
+        table
+    };
+
+    static ARCTANGENT: [f32; STEPS + 1] = {
+        let mut table = [0.0; STEPS + 1];
+        let mut i = 0;
+        while i <= STEPS {
+            table[i] = arctangent((i as f64) / (STEPS as f64)) as f32;
+            i += 1;
+        }
+        table
+    };
+
+    /// Taylor series, for `x` on [0, pi/2]: the last term kept is under 1e-12.
+    const fn sine(x: f64) -> f64 {
+        let mut sum = 0.0;
+        let mut term = x;
+        let mut n = 1;
+        while n < 20 {
+            sum += term;
+            term *= -x * x / (((n + 1) * (n + 2)) as f64);
+            n += 2;
+        }
+        sum
+    }
+
+    /// Newton's method, for `x` on [1, 2].
+    const fn square_root(x: f64) -> f64 {
+        let mut root = x;
+        let mut i = 0;
+        while i < 8 {
+            root = 0.5 * (root + x / root);
+            i += 1;
+        }
+        root
+    }
+
+    /// Halve the angle once (bringing `x` under tan(pi/8)), then a Taylor series.
+    const fn arctangent(x: f64) -> f64 {
+        let x = x / (1.0 + square_root(1.0 + x * x));
+        let mut sum = 0.0;
+        let mut power = x;
+        let mut n = 1;
+        while n < 60 {
+            sum += power / (n as f64);
+            power *= -x * x;
+            n += 2;
+        }
+        2.0 * sum
+    }
+
+    #[inline]
+    fn interpolate(table: &[f32; STEPS + 1], i: usize, fraction: f32) -> f32 {
+        table[i] + (table[i + 1] - table[i]) * fraction
+    }
+
+    #[inline]
+    pub fn sinf(x: f32) -> f32 {
+        let turns = x * (1.0 / TAU);
+        let position = (turns - libm::floorf(turns)) * const { 4.0 * STEPS as f32 };
+        let whole = position as usize;
+        let fraction = position - whole as f32;
+        // A turn's worth of rounding up lands back on zero:
+        let whole = whole % (4 * STEPS);
+        let (quadrant, i) = (whole / STEPS, whole % STEPS);
+        match quadrant {
+            0 => interpolate(&SINE, i, fraction),
+            1 => interpolate(&SINE, STEPS - i - 1, 1.0 - fraction),
+            2 => -interpolate(&SINE, i, fraction),
+            _ => -interpolate(&SINE, STEPS - i - 1, 1.0 - fraction),
+        }
+    }
+
+    #[inline]
+    pub fn cosf(x: f32) -> f32 {
+        sinf(x + FRAC_PI_2)
+    }
+
+    #[inline]
+    pub fn atan2f(y: f32, x: f32) -> f32 {
+        if x.is_nan() || y.is_nan() {
+            return f32::NAN;
+        }
+        let (ax, ay) = (libm::fabsf(x), libm::fabsf(y));
+        if ax == 0.0 && ay == 0.0 {
+            // As `libm` does: the sign of `y`, and pi if `x` is negative zero.
+            return libm::copysignf(if x.is_sign_negative() { PI } else { 0.0 }, y);
+        }
+        let (steep, ratio) = if ay > ax {
+            (true, ax / ay)
+        } else {
+            (false, ay / ax)
+        };
+        let position = ratio * STEPS as f32;
+        let i = (position as usize).min(STEPS - 1);
+        let mut angle = interpolate(&ARCTANGENT, i, position - i as f32);
+        if steep {
+            angle = FRAC_PI_2 - angle;
+        }
+        if x.is_sign_negative() {
+            angle = PI - angle;
+        }
+        libm::copysignf(angle, y)
+    }
+
+    /// `NaN` outside [-1, 1], as `libm`'s is.
+    #[inline]
+    pub fn acosf(x: f32) -> f32 {
+        atan2f(libm::sqrtf((1.0 - x) * (1.0 + x)), x)
+    }
+}
@@ -0,0 +1,43 @@
+use {
+    crate::{fault, stats},
+    embassy_rp::watchdog::{ResetReason, Watchdog},
+    embassy_time::Duration,
+};
+
+// The chip's hardware watchdog, fed once per motion loop iteration. If the loop stops coming
+// round (a task that never yields, a deadlock, a loop stuck logging the same error forever),
+// the chip resets. A reset drops every PWM output, so the servos go limp rather than holding
+// their last position indefinitely; the next boot logs the timeout to flash.
+
+/// Default time the motion loop may go without feeding before the chip resets:
+/// many missed ticks, but short enough that nothing's left stuck for long.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A running watchdog. Dropping it doesn't stop it, so don't.
+pub struct Feeder {
+    watchdog: Watchdog,
+}
+
+impl Feeder {
+    /// Log (and record to flash) a watchdog reset last boot, if there was one, and tell `stats`
+    /// why the chip reset, then start the countdown. Call after `fault::recover`, and as close
+    /// to the motion loop as possible, since the first deadline starts now. The watchdog pauses
+    /// while a debugger has the chip halted.
+    #[inline]
+    pub async fn start(mut watchdog: Watchdog, timeout: Duration) -> Self {
+        if watchdog.reset_reason() == Some(ResetReason::TimedOut) {
+            let () = crate::error!("Last reset was the watchdog's: something hung");
+            let () = fault::record(fault::Kind::Watchdog, 0, "").await;
+        }
+        let () = stats::record_reset(watchdog.reset_reason());
+        let () = watchdog.pause_on_debug(true);
+        let () = watchdog.start(timeout);
+        Self { watchdog }
+    }
+
+    /// Call once per motion loop iteration.
+    #[inline]
+    pub fn feed(&mut self) {
+        self.watchdog.feed()
+    }
+}
@@ -0,0 +1,64 @@
+use {
+    crate::status::Rgb,
+    embassy_rp::{
+        Peripheral, dma,
+        pio::{self, Common, PioPin, StateMachine},
+        pio_programs::ws2812::{PioWs2812, PioWs2812Program},
+    },
+    smart_leds::RGB8,
+};
+
+// A chain of WS2812 ("NeoPixel") LEDs on one pin, clocked out by a PIO state machine
+// (`pio_programs::ws2812`) fed by DMA, so the bit timing holds however busy the core is.
+// Every color is scaled by one brightness on the way out: full white is blinding up close,
+// and draws about 60 mA an LED from the 5 V rail.
+
+/// Out of 255.
+pub const DEFAULT_BRIGHTNESS: u8 = 32;
+
+pub struct Ws2812<'d, P: pio::Instance, const S: usize, const N: usize> {
+    leds: PioWs2812<'d, P, S, N>,
+    brightness: u8,
+}
+
+impl<'d, P: pio::Instance, const S: usize, const N: usize> Ws2812<'d, P, S, N> {
+    /// Loads its own copy of the PIO program, so give each chain its own state machine.
+    #[inline]
+    pub fn new(
+        common: &mut Common<'d, P>,
+        state_machine: StateMachine<'d, P, S>,
+        dma: impl Peripheral<P = impl dma::Channel> + 'd,
+        pin: impl PioPin,
+    ) -> Self {
+        let program = PioWs2812Program::new(common);
+        Self {
+            leds: PioWs2812::new(common, state_machine, dma, pin, &program),
+            brightness: DEFAULT_BRIGHTNESS,
+        }
+    }
+
+    #[inline]
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Takes effect on the next `write` or `fill`.
+    #[inline]
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness
+    }
+
+    /// Light every LED, first (nearest the pin) to last.
+    #[inline]
+    pub async fn write(&mut self, colors: &[Rgb; N]) {
+        let scale = |channel: u8| (u16::from(channel) * u16::from(self.brightness) / 255) as u8;
+        let colors = colors.map(|Rgb { r, g, b }| RGB8::new(scale(r), scale(g), scale(b)));
+        self.leds.write(&colors).await
+    }
+
+    /// Light every LED the same.
+    #[inline]
+    pub async fn fill(&mut self, color: Rgb) {
+        self.write(&[color; N]).await
+    }
+}
@@ -0,0 +1,44 @@
+// Host tests for `buzzer`'s pitch settings:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test buzzer
+
+use eye_bot_inverse_kinematics::buzzer::{self, Melody};
+
+const CLOCK_HZ: u32 = 150_000_000;
+
+/// The pitch `settings_for` actually plays.
+fn played(clock_hz: u32, divider: u8, top: u16) -> f32 {
+    clock_hz as f32 / (f32::from(divider) * (f32::from(top) + 1.0))
+}
+
+#[test]
+fn pitches_land_close() {
+    for hz in [20, 440, 587, 1_047, 2_700, 10_000, u16::MAX] {
+        let (divider, top) = buzzer::settings_for(CLOCK_HZ, hz).unwrap();
+        let error = (played(CLOCK_HZ, divider, top) - f32::from(hz)).abs() / f32::from(hz);
+        assert!(error < 1e-3, "{hz} Hz came out {error} off");
+    }
+    // Audible pitches need no divider at all:
+    assert_eq!(buzzer::settings_for(CLOCK_HZ, 2_700).unwrap().0, 1);
+    // Silence, and a pitch below what the largest divider reaches:
+    assert!(buzzer::settings_for(CLOCK_HZ, 0).is_none());
+    assert!(buzzer::settings_for(CLOCK_HZ, 1).is_none());
+}
+
+#[test]
+fn every_melody_plays() {
+    let melodies: [Melody; 6] = [
+        buzzer::STARTUP,
+        buzzer::IK_FAULT,
+        buzzer::SERVO_FAULT,
+        buzzer::COMMAND_FAULT,
+        buzzer::ESTOP,
+        buzzer::LOW_BATTERY,
+    ];
+    for melody in melodies {
+        assert!(!melody.is_empty());
+        for note in melody {
+            assert!(note.hz == 0 || buzzer::settings_for(CLOCK_HZ, note.hz).is_some());
+        }
+    }
+}
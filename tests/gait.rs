@@ -0,0 +1,174 @@
+// Host tests for `gait`'s swing profile and stepping patterns:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test gait
+
+use {
+    core::f32::consts::{FRAC_PI_4, TAU},
+    embassy_time::Duration,
+    eye_bot_inverse_kinematics::{
+        gait::{self, Gait, Pattern, Swing},
+        ik,
+    },
+};
+
+const TOLERANCE: f32 = 1e-5;
+
+/// Which way the foot's heading (progress, height) just before `s` of the way through.
+fn direction(swing: &Swing, s: f32) -> (f32, f32) {
+    let (x0, y0) = swing.at(s - 1e-3);
+    let (x1, y1) = swing.at(s);
+    let norm = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+    ((x1 - x0) / norm, (y1 - y0) / norm)
+}
+
+#[test]
+fn swings_run_from_liftoff_over_the_apex_to_touchdown() {
+    for swing in [
+        Swing::DEFAULT,
+        Swing {
+            lift: 0.2,
+            apex: 0.3,
+            touchdown_angle: 0.4,
+        },
+    ] {
+        let (x, y) = swing.at(0.0);
+        assert!(x.abs() < TOLERANCE && y.abs() < TOLERANCE);
+        let (x, y) = swing.at(0.5);
+        assert!((x - swing.apex).abs() < TOLERANCE && (y - 1.0).abs() < TOLERANCE);
+        let (x, y) = swing.at(1.0);
+        assert!((x - 1.0).abs() < TOLERANCE && y.abs() < TOLERANCE);
+        // Never below the ground, never above the step, never backward:
+        let mut last = 0.0;
+        for i in 0..=100 {
+            let (x, y) = swing.at(i as f32 / 100.0);
+            assert!(
+                (-TOLERANCE..=1.0 + TOLERANCE).contains(&y),
+                "{swing:?} at {i}%"
+            );
+            assert!(x >= last - TOLERANCE, "{swing:?} at {i}%");
+            last = x;
+        }
+    }
+}
+
+#[test]
+fn feet_land_at_the_touchdown_angle() {
+    // Straight down, so the toe doesn't scuff:
+    let (x, y) = direction(&Swing::DEFAULT, 1.0);
+    assert!(x.abs() < 1e-2 && y < 0.0);
+    let (x, y) = direction(
+        &Swing {
+            touchdown_angle: FRAC_PI_4,
+            ..Swing::DEFAULT
+        },
+        1.0,
+    );
+    assert!((x - -y).abs() < 1e-2 && x > 0.0);
+    // And straight up off the ground:
+    let (x, y) = direction(&Swing::DEFAULT, 1e-3);
+    assert!(x.abs() < 1e-2 && y > 0.0);
+}
+
+#[test]
+fn swing_parameters_are_checked() {
+    let neutral = ik::CartesianDisplacementFromEyeCenterLookingForward::new(5.0, 0.0, -3.0);
+    let mut gait = Gait::new(
+        gait::Pattern::Tripod,
+        [neutral; gait::N_LEGS],
+        gait::Parameters::DEFAULT,
+    );
+    assert!(gait.set_parameter("swing_apex", 0.7).is_ok());
+    assert_eq!(gait.parameter("swing_apex").ok(), Some(0.7));
+    assert!(gait.set_parameter("swing_apex", 1.0).is_err());
+    assert!(gait.set_parameter("swing_lift", -0.1).is_err());
+    assert!(gait.set_parameter("swing_touchdown_angle", 2.0).is_err());
+}
+
+#[test]
+fn neighbours_never_swing_together() {
+    for pattern in [Pattern::Tripod, Pattern::Ripple, Pattern::Wave] {
+        let swing = 1.0 - pattern.duty_factor();
+        let swinging = |leg: usize, phase: f32| {
+            let phase = phase - pattern.phase_offset(leg);
+            phase - phase.floor() < swing
+        };
+        for step in 0..600 {
+            // Halfway between steps, clear of the instants one leg lands as the next lifts off:
+            let phase = (step as f32 + 0.5) / 600.0;
+            for leg in 0..gait::N_LEGS {
+                let next = (leg + 1) % gait::N_LEGS;
+                assert!(
+                    !(swinging(leg, phase) && swinging(next, phase)),
+                    "{pattern:?}: legs {leg} and {next} both up at {phase}",
+                );
+            }
+            let up = (0..gait::N_LEGS)
+                .filter(|&leg| swinging(leg, phase))
+                .count();
+            let expected = (swing * gait::N_LEGS as f32).round() as usize;
+            assert_eq!(up, expected, "{pattern:?} at {phase}");
+        }
+    }
+}
+
+#[test]
+fn stalls_hold_the_blend_and_the_velocity_ramp_too() {
+    let neutral = core::array::from_fn(|i| {
+        let heading = TAU * (i as f32) / (gait::N_LEGS as f32);
+        ik::CartesianDisplacementFromEyeCenterLookingForward::from_heading(heading, 5.0, -3.0)
+    });
+    let mut gait = Gait::new(Pattern::Tripod, neutral, gait::Parameters::DEFAULT);
+    // Nothing's ever stable enough, so the first liftoff stalls, mid-blend and mid-ramp:
+    gait.set_parameter("min_stability_margin", f32::MAX)
+        .unwrap();
+    gait.set_velocity(5.0, 0.0, 0.0);
+    gait.set_pattern(Pattern::Wave);
+    let tick = Duration::from_millis(20);
+    let mut ticks = 0;
+    while !gait.is_stalled() {
+        let _ = gait.advance(tick);
+        ticks += 1;
+        assert!(ticks < 50, "never stalled");
+    }
+
+    let (velocity, feet) = (gait.velocity(), gait.foot_targets());
+    assert!(velocity.x < 5.0);
+    for _ in 0..50 {
+        let held = gait.advance(tick);
+        assert!(gait.is_stalled());
+        assert_eq!(gait.velocity().x, velocity.x);
+        for (held, foot) in held.iter().zip(&feet) {
+            assert_eq!((held.x, held.y, held.z), (foot.x, foot.y, foot.z));
+        }
+    }
+}
+
+#[test]
+fn stopping_clears_a_stall() {
+    let neutral = core::array::from_fn(|i| {
+        let heading = TAU * (i as f32) / (gait::N_LEGS as f32);
+        ik::CartesianDisplacementFromEyeCenterLookingForward::from_heading(heading, 5.0, -3.0)
+    });
+    let mut gait = Gait::new(Pattern::Tripod, neutral, gait::Parameters::DEFAULT);
+    // Standing still, the planted tripod leaves 2.5 each side of the center of mass,
+    // and a long stride shifts it back far enough to fall short of this:
+    gait.set_parameter("min_stability_margin", 2.4).unwrap();
+    gait.set_velocity(50.0, 0.0, 0.0);
+    let tick = Duration::from_millis(20);
+    let mut ticks = 0;
+    while !gait.is_stalled() {
+        let _ = gait.advance(tick);
+        ticks += 1;
+        assert!(ticks < 500, "never stalled");
+    }
+
+    gait.set_velocity(0.0, 0.0, 0.0);
+    let phase = gait.phase();
+    let mut ticks = 0;
+    while gait.is_stalled() || gait.phase() == phase {
+        let _ = gait.advance(tick);
+        ticks += 1;
+        assert!(ticks < 500, "still stalled");
+    }
+    assert!(gait.velocity().x < 50.0);
+}
@@ -0,0 +1,204 @@
+// Golden-trajectory regression tests: canonical inputs go through the real IK and gait on
+// stand-in servos, and the joint angles that come out are compared, step by step, against
+// the CSVs in `tests/golden/`:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test golden
+//
+// A change that moves any angle by more than `TOLERANCE` fails with the first step and column
+// that differ. If the change is meant to, rerun with `UPDATE_GOLDEN=1` to rewrite the files,
+// and review the diff before checking them in.
+
+use {
+    core::f32::consts::PI,
+    embassy_time::Duration,
+    eye_bot_inverse_kinematics::{
+        body::Body,
+        gait::{self, Gait, Pattern},
+        ik,
+        leg::Leg,
+        pwm, sim,
+    },
+    std::{env, fmt::Write as _, fs, path::PathBuf},
+};
+
+/// In the same units as `ik::Angles`. Loose enough for a different `libm`, tight enough
+/// that any real change to the math shows up.
+const TOLERANCE: f32 = 1e-4;
+
+const PERIOD: Duration = Duration::from_millis(pwm::PULSE_PERIOD_MS as _);
+
+/// One row per servo period: `t` in seconds, then yaw, hip and knee for each leg.
+struct Trajectory {
+    header: String,
+    rows: Vec<Vec<f32>>,
+}
+
+impl Trajectory {
+    fn new(legs: usize) -> Self {
+        let mut header = String::from("t");
+        for i in 0..legs {
+            let _: std::fmt::Result = write!(header, ",leg{i}.yaw,leg{i}.hip,leg{i}.knee");
+        }
+        Self {
+            header,
+            rows: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, step: u32, angles: &[ik::Angles]) {
+        let mut row = vec![(step as f32) * (pwm::PULSE_PERIOD_MS as f32) * 1e-3];
+        for angles in angles {
+            row.extend([angles.yaw, angles.hip, angles.knee]);
+        }
+        self.rows.push(row)
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = self.header.clone();
+        for row in &self.rows {
+            csv.push('\n');
+            for (i, value) in row.iter().enumerate() {
+                let _: std::fmt::Result = write!(csv, "{}{value}", if i == 0 { "" } else { "," });
+            }
+        }
+        csv.push('\n');
+        csv
+    }
+
+    fn from_csv(csv: &str) -> Self {
+        let mut lines = csv.lines();
+        let header = lines.next().unwrap_or_default().to_owned();
+        let rows = lines
+            .map(|line| {
+                line.split(',')
+                    .map(|value| value.parse().expect("a number in a golden file"))
+                    .collect()
+            })
+            .collect();
+        Self { header, rows }
+    }
+}
+
+/// Compare against `tests/golden/<name>.csv`, or rewrite it with `UPDATE_GOLDEN` set.
+fn check(name: &str, actual: &Trajectory) {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "golden",
+        &format!("{name}.csv"),
+    ]
+    .iter()
+    .collect();
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual.to_csv()).unwrap();
+        return;
+    }
+    let Ok(golden) = fs::read_to_string(&path) else {
+        panic!(
+            "No golden file at {}; run with UPDATE_GOLDEN=1 to record one",
+            path.display()
+        )
+    };
+    let golden = Trajectory::from_csv(&golden);
+
+    assert_eq!(actual.header, golden.header, "columns changed");
+    assert_eq!(
+        actual.rows.len(),
+        golden.rows.len(),
+        "number of steps changed"
+    );
+    let columns: Vec<&str> = golden.header.split(',').collect();
+    for (step, (actual, golden)) in actual.rows.iter().zip(&golden.rows).enumerate() {
+        for (column, (&actual, &golden)) in actual.iter().zip(golden).enumerate() {
+            let same = if golden.is_nan() {
+                actual.is_nan()
+            } else {
+                (actual - golden).abs() <= TOLERANCE
+            };
+            assert!(
+                same,
+                "{name}: step {step}, {}: got {actual}, golden {golden}",
+                columns[column],
+            );
+        }
+    }
+}
+
+fn leg(home_yaw_radians: f32) -> Leg<'static> {
+    Leg::with_home_yaw(
+        home_yaw_radians,
+        sim::Output::new(),
+        sim::Output::new(),
+        sim::Output::new(),
+    )
+    .unwrap()
+}
+
+fn body() -> Body<'static, { gait::N_LEGS }> {
+    Body::new(core::array::from_fn(|i| {
+        leg(2.0 * PI * (i as f32) / (gait::N_LEGS as f32))
+    }))
+}
+
+/// Walk for `seconds`, calling `steer` with the step number before each step.
+fn walk(pattern: Pattern, seconds: f32, mut steer: impl FnMut(u32, &mut Gait)) -> Trajectory {
+    let mut body = body();
+    let neutral = body.standing_foot_positions();
+    body.set_foot_positions(neutral).unwrap();
+    let mut gait = Gait::new(pattern, neutral, gait::Parameters::DEFAULT);
+
+    let mut trajectory = Trajectory::new(gait::N_LEGS);
+    let steps = (seconds * 1e3 / (pwm::PULSE_PERIOD_MS as f32)) as u32;
+    for step in 0..steps {
+        let () = steer(step, &mut gait);
+        // Errors leave the angles where they were, which the golden file catches just the same:
+        let _ = body.set_foot_positions(gait.advance(PERIOD));
+        trajectory.record(step, &body.joint_angles());
+    }
+    trajectory
+}
+
+/// The sweep in `src/bin/ik_test.rs`: a circle in the horizontal plane, slowly bobbing up and down.
+#[test]
+fn circle() {
+    let mut leg = leg(0.0);
+    let mut trajectory = Trajectory::new(1);
+    for step in 0..1_000 {
+        let counter = (step * pwm::PULSE_PERIOD_MS as u32) as f32;
+        let _ = leg.ik_to(ik::CartesianDisplacementFromEyeCenterLookingForward {
+            x: 2.0 * libm::sinf(counter / 100.0)
+                + 2.0
+                + ik::LENGTH_CENTER_TO_YAW
+                + ik::LENGTH_YAW_TO_HIP
+                + ik::LENGTH_HIP_TO_KNEE,
+            y: 2.0 * libm::cosf(counter / 100.0),
+            z: 1.0 * libm::sinf(counter / 1_000.0) + 2.0 - ik::LENGTH_KNEE_TO_FOOT,
+        });
+        trajectory.record(step, &[leg.angles()]);
+    }
+    check("circle", &trajectory);
+}
+
+#[test]
+fn tripod_forward() {
+    let trajectory = walk(Pattern::Tripod, 4.0, |step, gait| {
+        if step == 0 {
+            gait.set_velocity(5.0, 0.0, 0.0)
+        }
+    });
+    check("tripod_forward", &trajectory);
+}
+
+/// Crabbing and turning at once, then blending from one pattern into another mid-stride.
+#[test]
+fn ripple_turn_into_wave() {
+    // Two seconds in:
+    let trajectory = walk(Pattern::Ripple, 4.0, |step, gait| {
+        if step == 0 {
+            gait.set_velocity(2.0, 2.0, 0.5)
+        } else if step == 100 {
+            gait.set_pattern(Pattern::Wave)
+        }
+    });
+    check("ripple_turn_into_wave", &trajectory);
+}
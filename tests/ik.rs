@@ -0,0 +1,76 @@
+// Host tests for `ik` and `Leg`'s kinematics, against joint angles worked out from the leg's
+// shape rather than from the IK itself:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test ik
+
+use eye_bot_inverse_kinematics::{
+    ik::{self, HipAndKneeAngles, HipToFootDisplacementIn2dPlane},
+    leg::Leg,
+    sim::Output,
+};
+
+const TOLERANCE: f32 = 1e-4;
+
+/// Where the foot ends up, relative to the hip, with the thigh `hip` radians above level and the
+/// shin `shin` radians out from straight down: the knee's servo angle is then `-shin`.
+fn foot(hip: f32, shin: f32) -> HipToFootDisplacementIn2dPlane {
+    HipToFootDisplacementIn2dPlane {
+        x: ik::LENGTH_HIP_TO_KNEE * libm::cosf(hip) + ik::LENGTH_KNEE_TO_FOOT * libm::sinf(shin),
+        y: ik::LENGTH_HIP_TO_KNEE * libm::sinf(hip) - ik::LENGTH_KNEE_TO_FOOT * libm::cosf(shin),
+    }
+}
+
+/// Thigh and shin angles across the leg's travel, neither of them zero.
+const POSES: [(f32, f32); 4] = [(0.48, 0.3), (-0.4, 0.6), (0.9, -0.2), (0.2, 1.1)];
+
+fn assert_close(actual: f32, expected: f32, what: &str) {
+    assert!(
+        (actual - expected).abs() <= TOLERANCE,
+        "{what}: got {actual}, expected {expected}",
+    );
+}
+
+#[test]
+fn planar_ik_finds_the_angles_that_put_the_foot_there() {
+    for (hip, shin) in POSES {
+        let angles = ik::hip_to_foot_2d(foot(hip, shin)).unwrap();
+        assert_close(angles.hip, hip, "hip");
+        assert_close(angles.knee, -shin, "knee");
+    }
+}
+
+#[test]
+fn planar_fk_takes_radians() {
+    for (hip, shin) in POSES {
+        let expected = foot(hip, shin);
+        let actual = ik::hip_and_knee_to_foot_2d(HipAndKneeAngles { hip, knee: -shin });
+        assert_close(actual.x, expected.x, "x");
+        assert_close(actual.y, expected.y, "y");
+    }
+}
+
+#[test]
+fn legs_keep_the_angles_they_command_in_radians() {
+    let mut leg = Leg::with_home_yaw(0.0, Output::new(), Output::new(), Output::new()).unwrap();
+    for (hip, shin) in POSES {
+        let HipToFootDisplacementIn2dPlane { x: reach, y: z } = foot(hip, shin);
+        let yaw: f32 = 0.25;
+        let radius = ik::LENGTH_YAW_TO_HIP + reach;
+        let target = ik::CartesianDisplacementFromEyeCenterLookingForward {
+            x: ik::LENGTH_CENTER_TO_YAW + radius * libm::cosf(yaw),
+            y: radius * libm::sinf(yaw),
+            z,
+        };
+        leg.ik_to(target).unwrap();
+
+        let angles = leg.angles();
+        assert_close(angles.yaw, yaw, "yaw");
+        assert_close(angles.hip, hip, "hip");
+        assert_close(angles.knee, -shin, "knee");
+
+        let foot = leg.forward_kinematics();
+        assert_close(foot.x, target.x, "x");
+        assert_close(foot.y, target.y, "y");
+        assert_close(foot.z, target.z, "z");
+    }
+}
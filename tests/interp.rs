@@ -0,0 +1,149 @@
+// Host tests for `interp`, the one implementation of smooth motion everything else uses:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test interp
+
+use {
+    embassy_futures::block_on,
+    embassy_time::Duration,
+    eye_bot_inverse_kinematics::{
+        body::Pose,
+        ik,
+        interp::{self, Channel, CouldntStart, Easing, Interpolation, Interpolator},
+    },
+};
+
+const EASINGS: [Easing; 4] = [Easing::Linear, Easing::In, Easing::Out, Easing::InOut];
+
+fn point(x: f32, y: f32, z: f32) -> ik::CartesianDisplacementFromEyeCenterLookingForward {
+    ik::CartesianDisplacementFromEyeCenterLookingForward { x, y, z }
+}
+
+#[test]
+fn easings_start_at_zero_end_at_one_and_never_go_back() {
+    for easing in EASINGS {
+        assert_eq!(easing.apply(0.0), 0.0, "{easing:?}");
+        assert_eq!(easing.apply(1.0), 1.0, "{easing:?}");
+        assert_eq!(easing.apply(-1.0), 0.0, "{easing:?}");
+        assert_eq!(easing.apply(2.0), 1.0, "{easing:?}");
+        let mut last = 0.0;
+        for i in 1..=100 {
+            let eased = easing.apply(i as f32 / 100.0);
+            assert!(eased >= last, "{easing:?} went back at {i}%");
+            last = eased;
+        }
+    }
+    assert_eq!(Easing::Linear.apply(0.25), 0.25);
+    assert!(Easing::In.apply(0.5) < 0.5);
+    assert!(Easing::Out.apply(0.5) > 0.5);
+    assert_eq!(Easing::InOut.apply(0.5), 0.5);
+}
+
+#[test]
+fn interpolation_lands_exactly_on_its_target_and_stays() {
+    let (from, to) = (point(1.0, -2.0, 3.0), point(1.1, 7.3, -0.7));
+    let mut motion = Interpolation::new(from, to, Duration::from_millis(100), Easing::InOut);
+    for _ in 0..4 {
+        let _ = motion.advance(Duration::from_millis(20));
+        assert!(!motion.is_done());
+    }
+    let landed = motion.advance(Duration::from_millis(20));
+    assert!(motion.is_done());
+    assert_eq!((landed.x, landed.y, landed.z), (to.x, to.y, to.z));
+    let after = motion.advance(Duration::from_millis(20));
+    assert_eq!((after.x, after.y, after.z), (to.x, to.y, to.z));
+}
+
+#[test]
+fn linear_interpolation_is_halfway_at_half_time() {
+    let mut motion = Interpolation::new(0.0_f32, 10.0, Duration::from_millis(40), Easing::Linear);
+    assert_eq!(motion.advance(Duration::from_millis(20)), 5.0);
+    assert_eq!(motion.value(), 5.0);
+    assert_eq!(motion.target(), 10.0);
+}
+
+#[test]
+fn zero_duration_jumps_straight_there() {
+    let motion = Interpolation::new(0.0_f32, 3.0, Duration::from_ticks(0), Easing::In);
+    assert!(motion.is_done());
+    assert_eq!(motion.value(), 3.0);
+}
+
+#[test]
+fn channels_move_independently_and_finish_their_tickets() {
+    let mut interpolator = Interpolator::<2>::new();
+    assert!(interpolator.is_idle());
+
+    let to = Pose {
+        translation: point(0.0, 0.0, 1.0),
+        ..Pose::NEUTRAL
+    };
+    let body = interpolator.move_body(Pose::NEUTRAL, to, Duration::from_millis(40), Easing::Linear);
+    let foot = interpolator
+        .move_foot(
+            1,
+            point(0.0, 0.0, 0.0),
+            point(2.0, 0.0, 0.0),
+            Duration::from_millis(80),
+            Easing::Linear,
+        )
+        .unwrap();
+    assert_eq!(body.channel(), Channel::Body);
+    assert_eq!(foot.channel(), Channel::Leg(1));
+
+    let frame = interpolator.advance(Duration::from_millis(20));
+    assert_eq!(frame.pose.unwrap().translation.z, 0.5);
+    assert!(frame.feet[0].is_none());
+    assert_eq!(frame.feet[1].unwrap().x, 0.5);
+    assert!(frame.gaze.is_none());
+
+    let frame = interpolator.advance(Duration::from_millis(20));
+    assert_eq!(frame.pose.unwrap().translation.z, 1.0);
+    assert!(interp::is_finished(body));
+    assert!(!interp::is_finished(foot));
+
+    let frame = interpolator.advance(Duration::from_millis(40));
+    assert!(frame.pose.is_none());
+    assert_eq!(frame.feet[1].unwrap().x, 2.0);
+    assert!(interp::is_finished(foot));
+    assert!(interpolator.is_idle());
+    block_on(interp::finished(foot));
+}
+
+#[test]
+fn a_new_move_replaces_the_last_one_on_its_channel() {
+    let mut interpolator = Interpolator::<1>::new();
+    let first = interpolator.move_eye(
+        (0.0, 0.0),
+        (1.0, 0.0),
+        Duration::from_secs(1),
+        Easing::Linear,
+    );
+    let _ = interpolator.advance(Duration::from_millis(500));
+    let second = interpolator.move_eye(
+        (0.5, 0.0),
+        (0.5, 1.0),
+        Duration::from_secs(1),
+        Easing::Linear,
+    );
+    assert!(interp::is_finished(first));
+    assert!(!interp::is_finished(second));
+    let (azimuth, elevation) = interpolator
+        .advance(Duration::from_millis(500))
+        .gaze
+        .unwrap();
+    assert_eq!((azimuth, elevation), (0.5, 0.5));
+
+    interpolator.cancel(Channel::Eye);
+    assert!(interp::is_finished(second));
+    assert!(interpolator.is_idle());
+}
+
+#[test]
+fn feet_past_the_body_are_refused() {
+    let mut interpolator = Interpolator::<2>::new();
+    let origin = point(0.0, 0.0, 0.0);
+    assert!(matches!(
+        interpolator.move_foot(2, origin, origin, Duration::from_secs(1), Easing::Linear),
+        Err(CouldntStart::NoSuchLeg { index: 2 }),
+    ));
+}
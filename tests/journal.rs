@@ -0,0 +1,51 @@
+// Host tests for `journal`, the ring of recent log lines:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test journal
+
+use eye_bot_inverse_kinematics::journal::{self, Level, Ring};
+
+/// Every line's sequence number, reading the ring the way `dump` does.
+fn sequences<const N: usize>(ring: &Ring<N>) -> Vec<u32> {
+    let mut after = None;
+    let mut sequences = Vec::new();
+    while let Some(line) = ring.read_after(after) {
+        sequences.push(line.sequence);
+        after = Some(line.sequence);
+    }
+    sequences
+}
+
+#[test]
+fn oldest_lines_go_first() {
+    let mut ring = Ring::<3>::new();
+    assert!(ring.read_after(None).is_none());
+    for i in 0..5 {
+        let () = ring.push(i * 10, Level::Info, &format!("line {i}"));
+    }
+    assert_eq!(ring.len(), 3);
+    assert_eq!(sequences(&ring), [2, 3, 4]);
+    let oldest = ring.read_after(None).unwrap();
+    assert_eq!((oldest.uptime_ms, oldest.text.as_str()), (20, "line 2"));
+    assert_eq!(oldest.to_string(), "#2 at 20 ms: Info line 2");
+    // Asking from before the oldest skips straight to it:
+    assert_eq!(ring.read_after(Some(0)).unwrap().sequence, 2);
+    assert!(ring.read_after(Some(4)).is_none());
+}
+
+#[test]
+fn long_lines_are_cut() {
+    let mut ring = Ring::<1>::new();
+    let long = "é".repeat(journal::MAX_LINE_LENGTH);
+    let () = ring.push(0, Level::Error, &long);
+    let text = ring.read_after(None).unwrap().text;
+    // Whole characters only, two bytes each:
+    assert_eq!(text.len(), journal::MAX_LINE_LENGTH);
+    assert!(text.chars().all(|c| c == 'é'));
+}
+
+#[test]
+fn levels_come_from_macro_names() {
+    assert_eq!(Level::named("warn"), Some(Level::Warn));
+    assert_eq!(Level::named("shout"), None);
+    assert!(Level::Trace < journal::MIN_LEVEL && journal::MIN_LEVEL <= Level::Error);
+}
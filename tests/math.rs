@@ -0,0 +1,133 @@
+// Host tests for `math`, the geometry shared by IK, legs, the body and the eye:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test math
+
+use {
+    core::f32::consts::{FRAC_PI_2, PI, TAU},
+    eye_bot_inverse_kinematics::math::{
+        self, Quaternion, Rotation2, Rotation3, Transform2, Transform3, Vec2, Vec3,
+    },
+};
+
+const TOLERANCE: f32 = 1e-5;
+
+fn assert_close(expected: Vec3, observed: Vec3) {
+    assert!(
+        (expected - observed).norm() <= TOLERANCE,
+        "expected {expected:?}, got {observed:?}",
+    );
+}
+
+#[test]
+fn angles_wrap_onto_plus_or_minus_pi() {
+    assert_eq!(math::wrap_angle(0.5), 0.5);
+    assert_eq!(math::wrap_angle(PI), -PI);
+    assert_eq!(math::wrap_angle(-PI), -PI);
+    assert!((math::wrap_angle(0.5 + 3.0 * TAU) - 0.5).abs() <= TOLERANCE);
+    assert!((math::wrap_angle(0.5 - 3.0 * TAU) - 0.5).abs() <= TOLERANCE);
+}
+
+#[test]
+fn a_quarter_turn_about_each_axis() {
+    let x = Vec3::new(1.0, 0.0, 0.0);
+    let y = Vec3::new(0.0, 1.0, 0.0);
+    let z = Vec3::new(0.0, 0.0, 1.0);
+    // Yaw swings forward to the left, pitch tips forward down, roll tips left up:
+    let yaw = Rotation3::from_roll_pitch_yaw(0.0, 0.0, FRAC_PI_2);
+    assert_close(y, yaw.rotate(x));
+    let pitch = Rotation3::from_roll_pitch_yaw(0.0, FRAC_PI_2, 0.0);
+    assert_close(-z, pitch.rotate(x));
+    let roll = Rotation3::from_roll_pitch_yaw(FRAC_PI_2, 0.0, 0.0);
+    assert_close(z, roll.rotate(y));
+
+    let r2 = Rotation2::from_radians(FRAC_PI_2).rotate(Vec2::new(1.0, 0.0));
+    assert_close(y, r2.with_z(0.0));
+}
+
+#[test]
+fn yaw_pitch_roll_is_yaw_after_pitch_after_roll() {
+    let (roll, pitch, yaw) = (0.3, -0.7, 2.1);
+    let composed = Rotation3::from_roll_pitch_yaw(0.0, 0.0, yaw).compose(
+        &Rotation3::from_roll_pitch_yaw(0.0, pitch, 0.0)
+            .compose(&Rotation3::from_roll_pitch_yaw(roll, 0.0, 0.0)),
+    );
+    let v = Vec3::new(0.4, -1.3, 2.2);
+    assert_close(
+        Rotation3::from_roll_pitch_yaw(roll, pitch, yaw).rotate(v),
+        composed.rotate(v),
+    );
+}
+
+#[test]
+fn transforms_undo_themselves() {
+    let planar = Transform2 {
+        rotation: Rotation2::from_radians(-2.4),
+        translation: Vec2::new(0.9, -0.2),
+    };
+    let spatial = Transform3 {
+        rotation: Rotation3::from_roll_pitch_yaw(0.1, 0.2, -3.0),
+        translation: Vec3::new(-1.0, 0.5, 2.0),
+    };
+    for i in 0..100 {
+        let angle = TAU * (i as f32) / 100.0;
+        let p = Vec3::new(3.0 * libm::cosf(angle), libm::sinf(2.0 * angle), angle);
+        assert_close(p, spatial.inverse_apply(spatial.apply(p)));
+        assert_close(p, spatial.apply(spatial.inverse_apply(p)));
+        let q = p.xy();
+        assert_close(p, planar.inverse_apply(planar.apply(q)).with_z(p.z));
+        assert!(((planar.apply(q) - planar.translation).norm() - q.norm()).abs() <= TOLERANCE);
+    }
+}
+
+#[test]
+fn headings_round_trip() {
+    for (azimuth, elevation) in [(0.0, 0.0), (1.0, 0.5), (-2.5, -1.2), (3.0, 1.5)] {
+        let v = Vec3::from_heading(azimuth, elevation);
+        assert!((v.norm() - 1.0).abs() <= TOLERANCE);
+        let (a, e) = (v * 4.0).heading();
+        assert!((a - azimuth).abs() <= TOLERANCE && (e - elevation).abs() <= TOLERANCE);
+    }
+}
+
+#[test]
+fn quaternions_agree_with_matrices_and_euler_angles() {
+    let (roll, pitch, yaw) = (0.3, -0.7, 2.1);
+    let q = Quaternion::from_roll_pitch_yaw(roll, pitch, yaw);
+    let (r, p, y) = q.roll_pitch_yaw();
+    assert!((r - roll).abs() + (p - pitch).abs() + (y - yaw).abs() <= TOLERANCE);
+
+    let matrix = Rotation3::from_roll_pitch_yaw(roll, pitch, yaw);
+    let v = Vec3::new(0.4, -1.3, 2.2);
+    assert_close(matrix.rotate(v), q.rotate(v));
+    assert_close(matrix.rotate(v), q.matrix().rotate(v));
+    assert_close(v, q.inverse_rotate(q.rotate(v)));
+
+    // Yaw after pitch after roll, as with the matrices:
+    let z = Vec3::new(0.0, 0.0, 1.0);
+    let composed = Quaternion::from_axis_angle(z, yaw)
+        * Quaternion::from_roll_pitch_yaw(0.0, pitch, 0.0)
+        * Quaternion::from_roll_pitch_yaw(roll, 0.0, 0.0);
+    assert_close(q.rotate(v), composed.rotate(v));
+}
+
+#[test]
+fn slerp_turns_steadily_the_short_way() {
+    let z = Vec3::new(0.0, 0.0, 1.0);
+    let from = Quaternion::from_axis_angle(z, 0.2);
+    let to = Quaternion::from_axis_angle(z, 1.4);
+    assert_eq!(from.slerp(to, 0.0), from);
+    let x = Vec3::new(1.0, 0.0, 0.0);
+    for (t, expected) in [(0.25, 0.5), (0.5, 0.8), (1.0, 1.4)] {
+        let angle = from.slerp(to, t).rotate(x).xy().angle();
+        assert!((angle - expected).abs() <= TOLERANCE, "{t}: {angle}");
+    }
+    // The same rotation written the other way round still turns the short way:
+    let flipped = Quaternion::new(-to.w, -to.x, -to.y, -to.z);
+    let angle = from.slerp(flipped, 0.5).rotate(x).xy().angle();
+    assert!((angle - 0.8).abs() <= TOLERANCE);
+    // Even straight through pitching past vertical, where Euler angles fall apart:
+    let up = Quaternion::from_roll_pitch_yaw(0.0, -1.5, 0.0);
+    let over = Quaternion::from_roll_pitch_yaw(PI, -1.5, PI);
+    let halfway = up.slerp(over, 0.5).rotate(x);
+    assert!((halfway.norm() - 1.0).abs() <= TOLERANCE && halfway.z > 0.99);
+}
@@ -0,0 +1,211 @@
+#![no_std]
+#![no_main]
+
+// Runs on a bare Pico 2 over a debug probe, with nothing attached to the servo pins:
+//
+//     cargo test-target
+//
+// (an alias in `.cargo/config.toml` that swaps `picotool` for `probe-rs` as the runner).
+// Servo tests drive PWM slice 5 (pins 10 and 11) and read back what it was told to send.
+
+use defmt_rtt as _;
+
+#[defmt_test::tests]
+mod tests {
+    use {
+        defmt::assert,
+        embassy_futures::block_on,
+        embassy_rp::pac,
+        eye_bot_inverse_kinematics::{
+            ik::{self, HipToFootDisplacementIn2dPlane, HipToFootError},
+            pwm,
+            servo::{self, Servo},
+        },
+        fixed::{FixedU16, FixedU32, traits::LosslessTryFrom, types::extra::U4},
+    };
+
+    const REACH: f32 = ik::LENGTH_HIP_TO_KNEE + ik::LENGTH_KNEE_TO_FOOT;
+    const MIN_REACH: f32 = ik::LENGTH_KNEE_TO_FOOT - ik::LENGTH_HIP_TO_KNEE;
+
+    /// The slice `State::servo` drives, on channel A.
+    const SLICE: usize = 5;
+
+    struct State {
+        servo: Servo<'static>,
+    }
+
+    #[inline]
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    /// What the servo's PWM channel was last told to send, in counts out of `pwm::clock_top`.
+    #[inline]
+    fn compare() -> f32 {
+        pac::PWM.ch(SLICE).cc().read().a() as f32
+    }
+
+    #[init]
+    fn init() -> State {
+        let p = embassy_rp::init(Default::default());
+        let (a, _b) = block_on(pwm::init_slice(p.PWM_SLICE5, p.PIN_10, p.PIN_11));
+        let servo = match Servo::with_center_and_ranges(a, 0.0, -0.5, 0.5) {
+            Ok(ok) => ok,
+            Err(e) => defmt::panic!("Couldn't set up a servo: {}", defmt::Debug2Format(&e)),
+        };
+        State { servo }
+    }
+
+    #[test]
+    fn divider_and_top_fit_one_period_at_known_clocks() {
+        for clock_hz in [48_000_000_u32, 125_000_000, 150_000_000, 200_000_000] {
+            let clock = FixedU32::<U4>::from_num(clock_hz);
+            let Some(divider) = pwm::divider_for(clock) else {
+                defmt::panic!("No divider at {} Hz", clock_hz)
+            };
+            let Some(top) = pwm::top_for(clock, divider) else {
+                defmt::panic!("No top at {} Hz", clock_hz)
+            };
+            // The divider register is only 8.4 bits wide:
+            assert!(FixedU16::<U4>::lossless_try_from(divider).is_some());
+            // The smallest divider that fits should leave at least 15 bits of resolution:
+            assert!(top >= 32_768, "top {} at {} Hz", top, clock_hz);
+            // Phase-correct, so the counter goes up and back down every period:
+            let period_ms =
+                1e3 * (top as f32 + 1.0) * 2.0 * divider.to_num::<f32>() / clock_hz as f32;
+            assert!(
+                close(period_ms, pwm::PULSE_PERIOD_MS as f32, 0.01),
+                "{} ms at {} Hz",
+                period_ms,
+                clock_hz,
+            );
+        }
+    }
+
+    #[test]
+    fn this_chip_uses_the_same_math() {
+        let clock = block_on(pwm::clock_frequency_fp());
+        let divider = block_on(pwm::clock_divider_32b());
+        assert!(pwm::divider_for(clock) == Some(divider));
+        assert!(pwm::top_for(clock, divider) == Some(block_on(pwm::clock_top())));
+    }
+
+    #[test]
+    fn servo_maps_positions_to_pulses(state: &mut State) {
+        // 1.5 ms out of 20 at center, and 1 ms more or less for each whole unit:
+        let top = block_on(pwm::clock_top()) as f32;
+        let (center, range) = (top * 0.075, top / 20.0);
+
+        assert!(state.servo.go_to(0.0).is_ok());
+        assert!(close(compare(), center, 1.0));
+        assert!(state.servo.go_to(0.5).is_ok());
+        assert!(close(compare(), center + 0.5 * range, 1.0));
+        assert!(state.servo.go_to(-0.5).is_ok());
+        assert!(close(compare(), center - 0.5 * range, 1.0));
+
+        // Trim shifts every position:
+        assert!(state.servo.set_trim(0.1).is_ok());
+        assert!(state.servo.go_to(0.0).is_ok());
+        assert!(close(compare(), center + 0.1 * range, 1.0));
+        assert!(state.servo.set_trim(0.0).is_ok());
+    }
+
+    #[test]
+    fn servo_refuses_positions_outside_its_range(state: &mut State) {
+        let before = compare();
+        for position in [0.51, -0.51, 2.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                state.servo.go_to(position),
+                Err(servo::CouldntMove::OutOfRange(_))
+            ));
+        }
+        // And didn't send anything:
+        assert!(compare() == before);
+    }
+
+    #[test]
+    fn servo_checks_its_settings(state: &mut State) {
+        assert!(state.servo.set_limits(0.5, 0.2).is_err());
+        assert!(state.servo.set_limits(-1.1, 0.0).is_err());
+        assert!(state.servo.set_limits(0.0, 1.1).is_err());
+        assert!(state.servo.limits() == (-0.5, 0.5));
+
+        assert!(state.servo.set_trim(servo::MAX_TRIM).is_ok());
+        assert!(state.servo.set_trim(servo::MAX_TRIM + 0.01).is_err());
+        assert!(state.servo.trim() == servo::MAX_TRIM);
+        assert!(state.servo.set_trim(0.0).is_ok());
+    }
+
+    #[test]
+    fn servo_maps_pulse_widths(state: &mut State) {
+        let top = block_on(pwm::clock_top()) as f32;
+        for microseconds in [600, 1_000, 1_500, 2_000, 2_400] {
+            assert!(state.servo.set_pulse_width(microseconds).is_ok());
+            let expected = top * (microseconds as f32) / (1e3 * pwm::PULSE_PERIOD_MS as f32);
+            assert!(close(compare(), expected, 1.0), "{} us", microseconds);
+        }
+        // Past what any servo can take, even ignoring this one's range:
+        assert!(state.servo.set_pulse_width(400).is_err());
+        assert!(state.servo.set_pulse_width(2_600).is_err());
+
+        assert!(state.servo.relax().is_ok());
+        assert!(compare() == 0.0);
+    }
+
+    #[test]
+    fn ik_reaches_all_the_way_out() {
+        for (x, y) in [(REACH, 0.0), (0.0, -REACH), (-REACH, 0.0)] {
+            let Ok(angles) = ik::hip_to_foot_2d(HipToFootDisplacementIn2dPlane { x, y }) else {
+                defmt::panic!("Couldn't reach ({}, {})", x, y)
+            };
+            assert!(angles.hip.is_finite() && angles.knee.is_finite());
+            let foot = ik::hip_and_knee_to_foot_2d(angles);
+            assert!(close(foot.x, x, 1e-3) && close(foot.y, y, 1e-3));
+        }
+    }
+
+    #[test]
+    fn ik_refuses_points_out_of_reach() {
+        for (x, y) in [(REACH * 1.001, 0.0), (0.0, -REACH * 1.001), (1e6, 1e6)] {
+            assert!(matches!(
+                ik::hip_to_foot_2d(HipToFootDisplacementIn2dPlane { x, y }),
+                Err(HipToFootError::Unreachable(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn ik_refuses_points_too_close_to_the_hip() {
+        for (x, y) in [(0.0, 0.0), (MIN_REACH * 0.999, 0.0), (0.0, -1.0)] {
+            assert!(matches!(
+                ik::hip_to_foot_2d(HipToFootDisplacementIn2dPlane { x, y }),
+                Err(HipToFootError::TooClose(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn ik_round_trips_across_its_workspace() {
+        for i in 0..=10 {
+            // Just inside either edge, so rounding doesn't push a point out of reach:
+            let distance =
+                1.001 * MIN_REACH + (0.999 * REACH - 1.001 * MIN_REACH) * (i as f32) / 10.0;
+            for j in 0..16 {
+                let angle = core::f32::consts::TAU * (j as f32) / 16.0;
+                let (x, y) = (distance * libm::cosf(angle), distance * libm::sinf(angle));
+                let Ok(angles) = ik::hip_to_foot_2d(HipToFootDisplacementIn2dPlane { x, y }) else {
+                    defmt::panic!("Couldn't reach ({}, {})", x, y)
+                };
+                let foot = ik::hip_and_knee_to_foot_2d(angles);
+                assert!(
+                    close(foot.x, x, 1e-3) && close(foot.y, y, 1e-3),
+                    "({}, {}) came back as ({}, {})",
+                    x,
+                    y,
+                    foot.x,
+                    foot.y,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Host tests for what `scheduler::run` does with the legs each tick, through `Gait::step`:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test scheduler
+
+use {
+    core::f32::consts::TAU,
+    embassy_time::Duration,
+    eye_bot_inverse_kinematics::{
+        body::{Body, Pose},
+        gait::{self, Gait, Pattern},
+        leg::Leg,
+        pwm,
+        sim::{Output, Recording},
+    },
+};
+
+/// `scheduler::PERIOD`, which isn't built for the host.
+const PERIOD: Duration = Duration::from_millis(pwm::PULSE_PERIOD_MS as _);
+
+fn standing_body(recordings: &[Recording; 3 * gait::N_LEGS]) -> Body<'_, { gait::N_LEGS }> {
+    let legs = core::array::from_fn(|i| {
+        Leg::with_home_yaw(
+            TAU * (i as f32) / (gait::N_LEGS as f32),
+            Output::recording_to(&recordings[3 * i]),
+            Output::recording_to(&recordings[3 * i + 1]),
+            Output::recording_to(&recordings[3 * i + 2]),
+        )
+        .unwrap()
+    });
+    let mut body = Body::new(legs);
+    body.set_foot_positions(body.standing_foot_positions())
+        .unwrap();
+    body
+}
+
+fn walking_gait(body: &Body<'_, { gait::N_LEGS }>) -> Gait {
+    let mut gait = Gait::new(
+        Pattern::Tripod,
+        body.standing_foot_positions(),
+        gait::Parameters::DEFAULT,
+    );
+    gait.set_velocity(5.0, 0.0, 0.0);
+    gait
+}
+
+fn nothing_sent(recordings: &[Recording]) -> bool {
+    recordings
+        .iter()
+        .all(|recording| recording.sent().is_empty())
+}
+
+#[test]
+fn a_relaxed_body_stays_relaxed_until_attached() {
+    let recordings = [const { Recording::new() }; 3 * gait::N_LEGS];
+    let mut body = standing_body(&recordings);
+    let mut gait = walking_gait(&body);
+    for _ in 0..10 {
+        gait.step(&mut body, Pose::NEUTRAL, PERIOD).unwrap();
+    }
+    assert!(!body.is_resting());
+
+    body.relax().unwrap();
+    recordings.iter().for_each(Recording::clear);
+    let phase = gait.phase();
+    gait.step(&mut body, Pose::NEUTRAL, PERIOD).unwrap();
+    assert!(nothing_sent(&recordings));
+    assert!(body.foot_positions().is_none());
+    assert_eq!(gait.phase(), phase);
+
+    // Posing a foot by hand doesn't hand the legs back to the gait either:
+    body.set_foot_positions(body.standing_foot_positions())
+        .unwrap();
+    recordings.iter().for_each(Recording::clear);
+    gait.step(&mut body, Pose::NEUTRAL, PERIOD).unwrap();
+    assert!(nothing_sent(&recordings));
+    assert_eq!(gait.phase(), phase);
+
+    body.attach().unwrap();
+    assert!(!body.is_resting());
+    gait.step(&mut body, Pose::NEUTRAL, PERIOD).unwrap();
+    assert!(gait.phase() > phase);
+}
+
+#[test]
+fn a_sat_down_body_stays_down() {
+    let recordings = [const { Recording::new() }; 3 * gait::N_LEGS];
+    let mut body = standing_body(&recordings);
+    let mut gait = walking_gait(&body);
+    body.start_sitting_down();
+    let mut ticks = 0;
+    while body.advance(Duration::from_millis(100)).is_none() {
+        ticks += 1;
+        assert!(ticks < 100, "never sat down");
+    }
+    assert!(body.is_resting());
+
+    recordings.iter().for_each(Recording::clear);
+    for _ in 0..10 {
+        gait.step(&mut body, Pose::NEUTRAL, PERIOD).unwrap();
+    }
+    assert!(nothing_sent(&recordings));
+    assert_eq!(gait.phase(), 0.0);
+}
@@ -0,0 +1,139 @@
+// Host tests for `sequence`, the bytecode for stored behaviors:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test sequence
+
+use eye_bot_inverse_kinematics::sequence::{self, Chunk, Fault, Machine, Sensor, Step};
+
+/// Every step up to `Done`, as short labels, reading `obstacle` for every branch.
+fn run(program: &[u8], mut obstacle: impl FnMut() -> Option<f32>) -> Vec<String> {
+    let () = sequence::check(program).unwrap();
+    let mut machine = Machine::new(program);
+    let mut steps = Vec::new();
+    loop {
+        let step = machine
+            .step(|sensor| {
+                assert_eq!(sensor, Sensor::Obstacle);
+                obstacle()
+            })
+            .unwrap();
+        steps.push(match step {
+            Step::Done => return steps,
+            Step::Walk { x, y, yaw_rate } => format!("walk {x} {y} {yaw_rate}"),
+            Step::Foot { leg, position } => {
+                format!("foot {leg} {} {} {}", position.x, position.y, position.z)
+            }
+            Step::Pose(pose) => format!("pose {}", pose.translation.z),
+            Step::Wait(duration) => format!("wait {}", duration.as_millis()),
+            Step::Relax => "relax".into(),
+        });
+    }
+}
+
+#[test]
+fn loops_repeat_their_bodies() {
+    #[rustfmt::skip]
+    let program = [
+        0x05, 2,             // loop twice:
+        0x05, 3,             //     loop three times:
+        0x04, 0x0A, 0x00,    //         wait 10 ms
+        0x06,                //     next
+        0x09,                //     relax
+        0x06,                // next
+        0x02, 1, 0xF4, 0x01, 0x0C, 0xFE, 0x00, 0x00, // foot 1 to (0.5, -0.5, 0)
+    ];
+    let steps = run(&program, || None);
+    let expected = [
+        "wait 10",
+        "wait 10",
+        "wait 10",
+        "relax",
+        "wait 10",
+        "wait 10",
+        "wait 10",
+        "relax",
+        "foot 1 0.5 -0.5 0",
+    ];
+    assert_eq!(steps, expected);
+}
+
+#[test]
+fn branches_follow_the_sensor() {
+    #[rustfmt::skip]
+    let program = [
+        0x01, 0xE8, 0x03, 0x00, 0x00, 0x00, 0x00, // 0: walk forward at 1
+        0x07, 0, 0, 0x2C, 0x01, 24, 0x00,         // 7: if obstacle < 0.3, to 24
+        0x04, 0x64, 0x00,                         // 14: wait 100 ms
+        0x08, 7, 0x00,                            // 17: back to 7
+        0x00,                                     // 20: (never reached)
+        0x00, 0x00, 0x00,                         // 21: padding, ends
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 24: stop
+    ];
+    let mut distances = [None, Some(1.0), Some(0.5), Some(0.2)].into_iter();
+    let steps = run(&program, || distances.next().unwrap());
+    // Nothing to read never branches:
+    assert_eq!(
+        steps,
+        [
+            "walk 1 0 0",
+            "wait 100",
+            "wait 100",
+            "wait 100",
+            "walk 0 0 0"
+        ]
+    );
+}
+
+#[test]
+fn bad_programs_are_caught() {
+    // Unknown opcode, cut short, and a jump into the middle of an instruction:
+    assert_eq!(
+        sequence::check(&[0x09, 0x42]),
+        Err(Fault::UnknownOpcode {
+            at: 1,
+            opcode: 0x42
+        })
+    );
+    assert_eq!(
+        sequence::check(&[0x04, 0x10]),
+        Err(Fault::Truncated { at: 0 })
+    );
+    assert_eq!(
+        sequence::check(&[0x04, 0x10, 0x00, 0x08, 1, 0]),
+        Err(Fault::BadTarget { at: 3, target: 1 })
+    );
+    assert_eq!(
+        sequence::check(&[0x07, 9, 0, 0, 0, 0, 0]),
+        Err(Fault::UnknownSensor { at: 0, sensor: 9 })
+    );
+    // Jumping to the very end is fine, and so is an empty program:
+    assert_eq!(sequence::check(&[0x08, 3, 0]), Ok(()));
+    assert_eq!(sequence::check(&[]), Ok(()));
+
+    // A loop that never moves or waits:
+    let spin = [0x05, 0, 0x06];
+    assert_eq!(sequence::check(&spin), Ok(()));
+    assert!(matches!(
+        Machine::new(&spin).step(|_| None),
+        Err(Fault::Runaway { .. })
+    ));
+    // A `next` with no loop, and loops nested too deep:
+    assert_eq!(
+        Machine::new(&[0x06]).step(|_| None).unwrap_err(),
+        Fault::Unmatched { at: 0 }
+    );
+    let deep = [0x05, 1].repeat(sequence::MAX_NESTING + 1);
+    assert!(matches!(
+        Machine::new(&deep).step(|_| None),
+        Err(Fault::TooDeep { .. })
+    ));
+}
+
+#[test]
+fn chunks_read_hex() {
+    let chunk = Chunk::from_hex("0409aBFf").unwrap();
+    assert_eq!(chunk.as_bytes(), [0x04, 0x09, 0xAB, 0xFF]);
+    assert!(Chunk::from_hex("040").is_none());
+    assert!(Chunk::from_hex("0g").is_none());
+    assert!(Chunk::from_hex(&"00".repeat(sequence::MAX_CHUNK_LENGTH + 1)).is_none());
+    assert!(Chunk::from_hex("").unwrap().as_bytes().is_empty());
+}
@@ -0,0 +1,198 @@
+// Host tests against `sim::Output`, checking the exact pulses `Servo` and `Leg` send:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test servo_output
+
+use {
+    core::f32::consts::{FRAC_PI_2, PI},
+    embassy_futures::block_on,
+    eye_bot_inverse_kinematics::{
+        ik,
+        leg::{Joint, Leg},
+        pwm,
+        servo::Servo,
+        sim::{Output, Recording},
+    },
+};
+
+/// Duty cycles are rounded to the nearest whole clock tick.
+const TOLERANCE: f32 = 0.5;
+
+/// The duty cycle `Servo::go_to(position)` should send, with no trim: these servos take
+/// 0.5 to 2.5 ms pulses for -1 to 1, out of every `pwm::PULSE_PERIOD_MS`.
+fn expected(position: f32) -> f32 {
+    let microseconds = 1_500.0 + 1_000.0 * position;
+    (block_on(pwm::clock_top()) as f32) * microseconds / (1e3 * pwm::PULSE_PERIOD_MS as f32)
+}
+
+/// The `go_to` position for `radians` from center, over 180 degrees of travel.
+fn position(radians: f32) -> f32 {
+    radians / FRAC_PI_2
+}
+
+fn assert_sent(recording: &Recording, positions: &[Option<f32>]) {
+    let sent = recording.sent();
+    assert_eq!(sent.len(), positions.len(), "{sent:?}");
+    for (sent, position) in sent.iter().zip(positions) {
+        match (sent.duty_cycle, position) {
+            (Some(duty_cycle), Some(position)) => assert!(
+                (duty_cycle as f32 - expected(*position)).abs() <= TOLERANCE,
+                "sent {duty_cycle}, expected {} for {position}",
+                expected(*position),
+            ),
+            (None, None) => {}
+            _ => panic!("sent {:?}, expected {position:?}", sent.duty_cycle),
+        }
+    }
+    assert!(sent.windows(2).all(|pair| pair[0].at <= pair[1].at));
+}
+
+/// Where the foot rests with the yaw servo `yaw_radians` away from `home_yaw_radians`,
+/// the thigh `thigh_radians` above level, and the shin `shin_radians` out from vertical.
+fn foot(
+    home_yaw_radians: f32,
+    yaw_radians: f32,
+    thigh_radians: f32,
+    shin_radians: f32,
+) -> ik::CartesianDisplacementFromEyeCenterLookingForward {
+    let reach = ik::LENGTH_YAW_TO_HIP
+        + ik::LENGTH_HIP_TO_KNEE * libm::cosf(thigh_radians)
+        + ik::LENGTH_KNEE_TO_FOOT * libm::sinf(shin_radians);
+    let direction = home_yaw_radians + yaw_radians;
+    ik::CartesianDisplacementFromEyeCenterLookingForward {
+        x: ik::LENGTH_CENTER_TO_YAW * libm::cosf(home_yaw_radians) + reach * libm::cosf(direction),
+        y: ik::LENGTH_CENTER_TO_YAW * libm::sinf(home_yaw_radians) + reach * libm::sinf(direction),
+        z: ik::LENGTH_HIP_TO_KNEE * libm::sinf(thigh_radians)
+            - ik::LENGTH_KNEE_TO_FOOT * libm::cosf(shin_radians),
+    }
+}
+
+#[test]
+fn servo_sends_positions_within_its_range() {
+    let recording = Recording::new();
+    let mut servo =
+        Servo::with_center_and_ranges(Output::recording_to(&recording), 0.0, -0.5, 0.5).unwrap();
+
+    servo.go_to(0.0).unwrap();
+    servo.go_to(0.5).unwrap();
+    servo.go_to(-0.25).unwrap();
+    assert!(servo.go_to(0.75).is_err());
+    servo.set_trim(0.1).unwrap();
+    servo.go_to(0.0).unwrap();
+    servo.relax().unwrap();
+
+    assert_sent(
+        &recording,
+        &[Some(0.0), Some(0.5), Some(-0.25), Some(0.1), None],
+    );
+}
+
+#[test]
+fn servo_and_leg_take_degrees() {
+    let recording = Recording::new();
+    let mut servo =
+        Servo::with_center_and_ranges(Output::recording_to(&recording), 0.0, -1.0, 1.0).unwrap();
+    servo.go_to_degrees(45.0).unwrap();
+    servo.go_to_radians(-PI / 4.0).unwrap();
+    assert_sent(&recording, &[Some(0.5), Some(-0.5)]);
+
+    let [yaw, hip, knee] = [Recording::new(), Recording::new(), Recording::new()];
+    let mut leg = Leg::with_home_yaw(
+        0.0,
+        Output::recording_to(&yaw),
+        Output::recording_to(&hip),
+        Output::recording_to(&knee),
+    )
+    .unwrap();
+    leg.set_joint_limits_degrees(Joint::Hip, -30.0, 60.0)
+        .unwrap();
+    let (min, max) = leg.joint_limits(Joint::Hip);
+    assert!((min - -PI / 6.0).abs() < 1e-6 && (max - PI / 3.0).abs() < 1e-6);
+    assert!(leg.set_joint_degrees(Joint::Hip, 45.0).is_ok());
+    assert!(leg.set_joint_degrees(Joint::Hip, 75.0).is_err());
+    assert_sent(&hip, &[Some(0.5)]);
+    assert!((leg.angles().to_degrees().hip - 45.0).abs() < 1e-4);
+}
+
+/// Thigh and shin angles (as for `foot`) that bend both the hip and the knee.
+const BENT: [(f32, f32); 3] = [
+    (PI / 6.0, PI / 8.0),
+    (-PI / 8.0, PI / 4.0),
+    (PI / 4.0, -PI / 10.0),
+];
+
+#[test]
+fn leg_sends_canonical_poses() {
+    let [yaw, hip, knee] = [Recording::new(), Recording::new(), Recording::new()];
+    for home_yaw in [0.0, 0.5 * PI, -2.0 * PI / 3.0] {
+        for recording in [&yaw, &hip, &knee] {
+            recording.clear();
+        }
+        let mut leg = Leg::with_home_yaw(
+            home_yaw,
+            Output::recording_to(&yaw),
+            Output::recording_to(&hip),
+            Output::recording_to(&knee),
+        )
+        .unwrap();
+
+        // Standing, then swung 30 degrees either way, always with the thigh level and the shin
+        // vertical, so only the yaw servo moves:
+        for yaw_radians in [0.0, PI / 6.0, -PI / 6.0] {
+            leg.ik_to(foot(home_yaw, yaw_radians, 0.0, 0.0)).unwrap();
+        }
+        // Then crouched and stretched: the hip turns with the thigh, and the knee against the
+        // shin swinging out:
+        for (thigh, shin) in BENT {
+            leg.ik_to(foot(home_yaw, 0.0, thigh, shin)).unwrap();
+        }
+        let [a, b, c] = BENT;
+        assert_sent(
+            &yaw,
+            &[
+                Some(0.0),
+                Some(1.0 / 3.0),
+                Some(-1.0 / 3.0),
+                Some(0.0),
+                Some(0.0),
+                Some(0.0),
+            ],
+        );
+        assert_sent(
+            &hip,
+            &[Some(0.0), Some(0.0), Some(0.0)]
+                .into_iter()
+                .chain([a, b, c].map(|(thigh, _)| Some(position(thigh))))
+                .collect::<Vec<_>>(),
+        );
+        assert_sent(
+            &knee,
+            &[Some(0.0), Some(0.0), Some(0.0)]
+                .into_iter()
+                .chain([a, b, c].map(|(_, shin)| Some(position(-shin))))
+                .collect::<Vec<_>>(),
+        );
+    }
+}
+
+#[test]
+fn leg_sends_nothing_for_unreachable_targets() {
+    let [yaw, hip, knee] = [Recording::new(), Recording::new(), Recording::new()];
+    let mut leg = Leg::with_home_yaw(
+        0.0,
+        Output::recording_to(&yaw),
+        Output::recording_to(&hip),
+        Output::recording_to(&knee),
+    )
+    .unwrap();
+
+    let far = ik::CartesianDisplacementFromEyeCenterLookingForward {
+        x: 100.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    assert!(leg.ik_to(far).is_err());
+    // The yaw servo still turns toward it, but nothing past the hip moves:
+    assert_sent(&yaw, &[Some(0.0)]);
+    assert_sent(&hip, &[]);
+    assert_sent(&knee, &[]);
+}
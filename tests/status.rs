@@ -0,0 +1,86 @@
+// Host tests for `status`, what the status LED shows:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test status
+
+use {
+    embassy_time::Duration,
+    eye_bot_inverse_kinematics::status::{Conditions, Pattern, Rgb, State},
+};
+
+#[test]
+fn the_most_urgent_condition_wins() {
+    let all = Conditions {
+        fault: true,
+        low_battery: true,
+        calibrating: true,
+        running: false,
+        frozen: true,
+        walking: true,
+    };
+    assert_eq!(State::from_conditions(all), State::Fault);
+    let all = Conditions {
+        fault: false,
+        ..all
+    };
+    assert_eq!(State::from_conditions(all), State::LowBattery);
+    let all = Conditions {
+        low_battery: false,
+        ..all
+    };
+    assert_eq!(State::from_conditions(all), State::Calibrating);
+    let all = Conditions {
+        calibrating: false,
+        ..all
+    };
+    assert_eq!(State::from_conditions(all), State::Booting);
+    let all = Conditions {
+        running: true,
+        ..all
+    };
+    assert_eq!(State::from_conditions(all), State::Frozen);
+    let all = Conditions {
+        frozen: false,
+        ..all
+    };
+    assert_eq!(State::from_conditions(all), State::Walking);
+    let all = Conditions {
+        walking: false,
+        ..all
+    };
+    assert_eq!(State::from_conditions(all), State::Idle);
+    // Nothing at all yet means the motion loop hasn't started:
+    assert_eq!(
+        State::from_conditions(Conditions::default()),
+        State::Booting
+    );
+}
+
+#[test]
+fn patterns_blink_and_breathe_on_time() {
+    let period = Duration::from_millis(1_000);
+    let blink = Pattern::Blink {
+        color: Rgb::RED,
+        period,
+    };
+    assert_eq!(blink.color_at(Duration::from_millis(0)), Rgb::RED);
+    assert_eq!(blink.color_at(Duration::from_millis(499)), Rgb::RED);
+    assert_eq!(blink.color_at(Duration::from_millis(500)), Rgb::OFF);
+    assert_eq!(blink.color_at(Duration::from_millis(1_250)), Rgb::RED);
+
+    let breathe = Pattern::Breathe {
+        color: Rgb::new(200, 100, 0),
+        period,
+    };
+    assert_eq!(breathe.color_at(Duration::from_millis(0)), Rgb::OFF);
+    assert_eq!(
+        breathe.color_at(Duration::from_millis(500)),
+        Rgb::new(200, 100, 0)
+    );
+    let quarter = breathe.color_at(Duration::from_millis(250));
+    assert!(quarter.r.abs_diff(100) <= 1 && quarter.g.abs_diff(50) <= 1);
+
+    assert_eq!(
+        Pattern::Solid(Rgb::BLUE).color_at(Duration::from_secs(7)),
+        Rgb::BLUE
+    );
+}
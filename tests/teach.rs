@@ -0,0 +1,135 @@
+// Host tests for `teach`, recording hand-posed keyframes and playing them back:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test teach
+
+use {
+    embassy_time::Duration,
+    eye_bot_inverse_kinematics::{
+        ik,
+        teach::{self, Playback, Recording},
+    },
+};
+
+const TOLERANCE: f32 = 1e-3;
+
+fn angles(yaw: f32, hip: f32, knee: f32) -> ik::Angles {
+    ik::Angles { yaw, hip, knee }
+}
+
+fn assert_close(expected: ik::Angles, observed: ik::Angles) {
+    assert!(
+        (expected.yaw - observed.yaw).abs() <= TOLERANCE
+            && (expected.hip - observed.hip).abs() <= TOLERANCE
+            && (expected.knee - observed.knee).abs() <= TOLERANCE,
+        "expected {expected:?}, got {observed:?}",
+    );
+}
+
+#[test]
+fn recordings_round_trip_through_their_encoding() {
+    let mut recording = Recording::new();
+    let first = [angles(0.1, -0.5, 1.2), angles(-0.3, 0.25, -1.0)];
+    let second = [angles(1.5, 0.0, -2.9), angles(0.0, -0.001, 3.1)];
+    assert_eq!(
+        recording
+            .capture(first, None, Duration::from_millis(750))
+            .ok(),
+        Some(1)
+    );
+    assert_eq!(
+        recording
+            .capture(second, Some((0.4, -0.2)), Duration::from_secs(2))
+            .ok(),
+        Some(2)
+    );
+
+    let mut bytes = [0; teach::ENCODED_LENGTH];
+    let length = recording.encode(&mut bytes);
+    let decoded = Recording::decode(&bytes[..length]).unwrap();
+    assert_eq!(decoded.keyframes().len(), 2);
+    for (original, decoded) in recording.keyframes().iter().zip(decoded.keyframes()) {
+        assert_eq!(original.after, decoded.after);
+        for (&original, &decoded) in original.angles.iter().zip(&decoded.angles) {
+            assert_close(original, decoded);
+        }
+        match (original.gaze, decoded.gaze) {
+            (None, None) => {}
+            (Some((a0, e0)), Some((a1, e1))) => {
+                assert!((a0 - a1).abs() <= TOLERANCE && (e0 - e1).abs() <= TOLERANCE);
+            }
+            (original, decoded) => panic!("expected gaze {original:?}, got {decoded:?}"),
+        }
+    }
+
+    // Anything cut short, or with extra on the end, isn't a recording:
+    assert!(Recording::decode(&bytes[..length - 1]).is_none());
+    assert!(Recording::decode(&bytes[..length + 1]).is_none());
+    assert!(Recording::decode(&[]).is_none());
+
+    while recording.keyframes().len() < teach::MAX_KEYFRAMES {
+        let _ = recording
+            .capture(first, None, Duration::from_millis(1))
+            .unwrap();
+    }
+    assert!(
+        recording
+            .capture(first, None, Duration::from_millis(1))
+            .is_err()
+    );
+    let length = recording.encode(&mut bytes);
+    assert_eq!(length, teach::ENCODED_LENGTH);
+    assert_eq!(
+        Recording::decode(&bytes).unwrap().keyframes().len(),
+        teach::MAX_KEYFRAMES
+    );
+}
+
+#[test]
+fn playback_eases_through_every_keyframe_in_turn() {
+    let start = [angles(0.0, 0.0, 0.0)];
+    let first = [angles(1.0, -1.0, 0.5)];
+    let second = [angles(-1.0, 0.0, 2.0)];
+    let mut recording = Recording::new();
+    let _ = recording
+        .capture(first, None, Duration::from_millis(100))
+        .unwrap();
+    let _ = recording
+        .capture(second, Some((0.5, 0.0)), Duration::from_millis(200))
+        .unwrap();
+
+    let tick = Duration::from_millis(10);
+    let mut playback = Playback::new(recording, start);
+    let mut frames = 0;
+    let mut reached = [false; 2];
+    while let Some(frame) = playback.advance(tick) {
+        frames += 1;
+        if frames == 5 {
+            // Halfway to the first keyframe, where easing in and out is halfway too:
+            assert_close(angles(0.5, -0.5, 0.25), frame.angles[0]);
+            assert!(frame.gaze.is_none());
+        }
+        if frames == 10 {
+            assert_close(first[0], frame.angles[0]);
+            reached[0] = true;
+        }
+        if frames > 10 {
+            // Nothing to ease from, so the eye looks straight at the first gaze:
+            assert_eq!(frame.gaze, Some((0.5, 0.0)));
+        }
+        if frames == 30 {
+            assert_close(second[0], frame.angles[0]);
+            reached[1] = true;
+        }
+    }
+    assert_eq!(frames, 30);
+    assert_eq!(reached, [true; 2]);
+    assert!(playback.is_done());
+    assert!(playback.advance(tick).is_none());
+
+    // Nothing recorded, nothing played:
+    assert!(
+        Playback::new(Recording::new(), start)
+            .advance(tick)
+            .is_none()
+    );
+}
@@ -0,0 +1,49 @@
+// Checks whichever `trig` backend is built against `libm`, to within its `MAX_ERROR`:
+//
+//     cargo test --no-default-features --features sim --target host-tuple -Zbuild-std --test trig
+//
+// Add `,trig-lut` or `,trig-micromath` to the features to check that backend instead.
+
+use {core::f32::consts::TAU, eye_bot_inverse_kinematics::trig};
+
+/// Evenly spaced points from `from` to `to`, both included.
+fn sweep(from: f32, to: f32, steps: u32) -> impl Iterator<Item = f32> {
+    (0..=steps).map(move |i| from + (to - from) * (i as f32) / (steps as f32))
+}
+
+fn assert_close(name: &str, input: (f32, f32), expected: f32, observed: f32) {
+    assert!(
+        (expected - observed).abs() <= trig::MAX_ERROR,
+        "{name}{input:?}: expected {expected}, got {observed} (allowed {})",
+        trig::MAX_ERROR,
+    );
+}
+
+#[test]
+fn sine_and_cosine() {
+    for x in sweep(-2.0 * TAU, 2.0 * TAU, 100_000) {
+        assert_close("sinf", (x, 0.0), libm::sinf(x), trig::sinf(x));
+        assert_close("cosf", (x, 0.0), libm::cosf(x), trig::cosf(x));
+    }
+}
+
+#[test]
+fn arccosine_over_its_whole_domain() {
+    for x in sweep(-1.0, 1.0, 100_000) {
+        assert_close("acosf", (x, 0.0), libm::acosf(x), trig::acosf(x));
+    }
+    assert!(trig::acosf(1.5).is_nan());
+}
+
+#[test]
+fn arctangent_all_the_way_around() {
+    for angle in sweep(-TAU, TAU, 20_000) {
+        for radius in [1e-3, 1.0, 7.5] {
+            let (y, x) = (radius * libm::sinf(angle), radius * libm::cosf(angle));
+            assert_close("atan2f", (y, x), libm::atan2f(y, x), trig::atan2f(y, x));
+        }
+    }
+    for (y, x) in [(0.0, 1.0), (1.0, 0.0), (0.0, -1.0), (-1.0, 0.0), (0.0, 0.0)] {
+        assert_close("atan2f", (y, x), libm::atan2f(y, x), trig::atan2f(y, x));
+    }
+}